@@ -0,0 +1,164 @@
+//! Support for circle-valued scalar functions, such as angles and phases.
+//!
+//! The Morse machinery sweeps through the values in a linear order, which isn't well defined for
+//! values on a circle: two nodes at 0.01 and 6.27 radians are nearly equal as angles but as far
+//! apart as possible as numbers. This module handles such functions by cutting the circle at the
+//! angle that the fewest graph edges cross, then unrolling the values from that cut into an
+//! ordinary real-valued function. Away from the cut, extrema and persistence match their
+//! circular counterparts exactly.
+use std::cmp::Ordering;
+use std::f64;
+use petgraph::graph::UnGraph;
+
+use super::LabeledPoint;
+use super::morse::{MorseSmaleComplex, MorseError};
+
+/// The location at which a circle of values was cut in order to unroll it.
+#[derive(Debug, Clone, Copy)]
+pub struct CircularCut {
+    /// The angle (in the same units as the period) at which the circle was cut.
+    pub cut: f64,
+
+    /// The number of edges whose endpoints lie on opposite sides of the cut. The values along
+    /// these edges jump by nearly a full period after unrolling, so extrema at their endpoints may
+    /// be artifacts of the cut.
+    pub crossing_edges: usize
+}
+
+/// Finds the cut of the circle of values that the fewest edges cross.
+///
+/// Each edge is treated as covering the shorter arc between the values of its endpoints. Among
+/// cuts that are crossed by equally few edges, the one in the middle of the widest gap between
+/// node values is chosen.
+pub fn find_cut<T>(graph: &UnGraph<LabeledPoint<T>, f64>, period: f64) -> CircularCut {
+    let mut values: Vec<f64> = graph.raw_nodes().iter()
+        .map(|node| normalize(node.weight.value, period))
+        .collect();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Less));
+    values.dedup();
+    if values.is_empty() {
+        return CircularCut{cut: 0., crossing_edges: 0};
+    }
+
+    // candidate cuts are the midpoints of the gaps between consecutive values, with the gap
+    // between the largest and smallest value wrapping around the circle
+    let candidates: Vec<(f64, f64)> = (0..values.len())
+        .map(|i| {
+            let low = values[i];
+            let high = if i + 1 == values.len() { values[0] + period } else { values[i + 1] };
+            (((low + high) / 2.) % period, high - low)
+        })
+        .collect();
+
+    // Every arc starts in [0, period) and has length at most period / 2, so laying them out on
+    // [0, 2 * period) means a cut c is crossed by an arc iff c or c + period lies inside it
+    let mut events: Vec<(f64, i64)> = Vec::with_capacity(2 * graph.edge_count());
+    for edge in graph.raw_edges() {
+        let a = normalize(graph[edge.source()].value, period);
+        let b = normalize(graph[edge.target()].value, period);
+        let (low, high) = if a < b { (a, b) } else { (b, a) };
+        let (start, end) = if high - low <= period / 2. { (low, high) } else { (high, low + period) };
+        if start < end {
+            events.push((start, 1));
+            events.push((end, -1));
+        }
+    }
+    // ends sort before starts so that arcs touching at a point don't overlap
+    events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Less).then(a.1.cmp(&b.1)));
+
+    let mut queries: Vec<(f64, usize)> = candidates.iter().enumerate()
+        .flat_map(|(i, &(cut, _))| vec![(cut, i), (cut + period, i)])
+        .collect();
+    queries.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Less));
+
+    let mut crossings = vec![0; candidates.len()];
+    let mut coverage = 0;
+    let mut event_iter = events.iter().peekable();
+    for &(position, candidate) in &queries {
+        while let Some(&&(event_position, delta)) = event_iter.peek() {
+            if event_position > position {
+                break;
+            }
+            coverage += delta;
+            event_iter.next();
+        }
+        crossings[candidate] += coverage as usize;
+    }
+
+    let best = (0..candidates.len())
+        .min_by(|&i, &j| {
+            crossings[i].cmp(&crossings[j])
+                .then(candidates[j].1.partial_cmp(&candidates[i].1).unwrap_or(Ordering::Equal))
+        })
+        .unwrap_or(0);
+    CircularCut{cut: candidates[best].0, crossing_edges: crossings[best]}
+}
+
+/// Unrolls the circle-valued function on `graph` into a real-valued one by cutting it at `cut`.
+///
+/// Every value is replaced by its angle past the cut, in `[0, period)`. Node and edge indices are
+/// unchanged.
+pub fn unroll<T: Clone>(graph: &UnGraph<LabeledPoint<T>, f64>, period: f64, cut: f64) -> UnGraph<LabeledPoint<T>, f64> {
+    graph.map(
+        |_, point| {
+            let mut point = point.clone();
+            point.value = normalize(point.value - cut, period);
+            point
+        },
+        |_, &weight| weight)
+}
+
+fn normalize(value: f64, period: f64) -> f64 {
+    value.rem_euclid(period)
+}
+
+impl MorseSmaleComplex {
+    /// Constructs a MorseSmaleComplex from a graph whose values are angles with the given
+    /// `period` (e.g. `2π` for radians).
+    ///
+    /// The circle of values is cut where the fewest edges cross it (see
+    /// [find_cut](../circular/fn.find_cut.html)) and the complex is computed over the unrolled
+    /// values. The cut is returned alongside the complex so callers can check how many edges it
+    /// disturbed.
+    pub fn from_circular_graph<T: Clone>(graph: &UnGraph<LabeledPoint<T>, f64>, period: f64)
+        -> Result<(MorseSmaleComplex, CircularCut), MorseError> {
+        let cut = find_cut(graph, period);
+        let unrolled = unroll(graph, period, cut.cut);
+        let complex = MorseSmaleComplex::from_graph(&unrolled)?;
+        Ok((complex, cut))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_find_cut() {
+        let period = 2. * f64::consts::PI;
+        let graph = path_graph(&[6.0, 6.2, 0.1, 0.3, 0.2, 6.1]);
+        let cut = find_cut(&graph, period);
+        assert_eq!(cut.crossing_edges, 0);
+        assert!(cut.cut > 0.3 && cut.cut < 6.0);
+    }
+
+    #[test]
+    fn test_circular_complex() {
+        let period = 2. * f64::consts::PI;
+        let graph = path_graph(&[6.0, 6.2, 0.1, 0.3, 0.2, 6.1]);
+
+        // read linearly, the jump from 6.2 to 0.1 makes spurious maxima at 6.2 and 6.1
+        let linear = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let linear_maxima = linear.descending_complex.get_persistence().values()
+            .filter(|&&l| l > 0.)
+            .count();
+        assert_eq!(linear_maxima, 3);
+
+        let (complex, _) = MorseSmaleComplex::from_circular_graph(&graph, period).unwrap();
+        let lifetimes = complex.descending_complex.get_persistence();
+        let maxima: Vec<_> = lifetimes.iter().filter(|(_, &l)| l > 0.).collect();
+        assert_eq!(maxima.len(), 1);
+        assert_eq!(graph[*maxima[0].0].id, 3);
+    }
+}
@@ -0,0 +1,244 @@
+//! Contour trees, which record how the components of the level sets of a function are born, merge,
+//! split and die.
+//!
+//! The [merge trees](../merge_tree/index.html) each follow the level sets from only one side: the
+//! join tree sees the sublevel sets merge but not the superlevel sets. The contour tree combines
+//! the two into a single tree with a leaf at every extremum, using the algorithm of Carr,
+//! Snoeyink and Axen: both trees are built over every node of the graph (rather than only their
+//! critical nodes), and leaves are then repeatedly peeled off of one tree and spliced out of the
+//! other.
+use std::collections::{HashMap, VecDeque};
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use crate::ScalarValued;
+use crate::morse::{MorseComplex, MorseKind, MorseError};
+use crate::util::PointedUnionFind;
+
+/// An arc of a contour tree, running from a lower to a higher critical node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContourArc {
+    pub lower: NodeIndex,
+    pub upper: NodeIndex,
+
+    /// The difference between the values of the endpoints. For an arc ending at a leaf, this is
+    /// how far the extremum rises above (or sinks below) the rest of the function.
+    pub persistence: f64
+}
+
+/// The contour tree of a function over a graph.
+///
+/// The nodes of the tree are the critical nodes of the function: its extrema, which are the
+/// leaves, and the nodes at which contours merge or split. Nodes whose contour just passes
+/// through are left out, so each arc stands for a chain of them.
+#[derive(Debug, Clone)]
+pub struct ContourTree {
+    // from lowest to highest
+    nodes: Vec<NodeIndex>,
+    arcs: Vec<ContourArc>
+}
+
+impl ContourTree {
+    /// Computes the contour tree of the function on `graph`.
+    ///
+    /// The graph should be like the triangulation of a simply connected domain, so that its level
+    /// sets can't wrap around a hole. Otherwise, contours that split and then join up again make
+    /// a graph with loops rather than a tree, and the result is not meaningful. If the graph isn't
+    /// connected, this is a forest with one tree per connected component.
+    pub fn from_graph<N: ScalarValued>(graph: &UnGraph<N, f64>) -> Result<ContourTree, MorseError> {
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        // both trees need the same strict order of the nodes, ties included
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, graph)?;
+        let mut order: Vec<NodeIndex> = graph.node_indices().collect();
+        order.sort_by_key(|&node| complex.sweep_position(node));
+        let position: HashMap<NodeIndex, usize> = order.iter().enumerate().map(|(p, &node)| (node, p)).collect();
+
+        // Each tree is kept as the parent and the children of every node
+        let (mut join_parents, mut join_children) = augmented_merge_tree(graph, &order, &position);
+        let reversed: Vec<NodeIndex> = order.iter().rev().cloned().collect();
+        let reversed_position = order.iter().enumerate().map(|(p, &node)| (node, order.len() - 1 - p)).collect();
+        let (mut split_parents, mut split_children) = augmented_merge_tree(graph, &reversed, &reversed_position);
+
+        let degree = |node: &NodeIndex, children: &HashMap<NodeIndex, Vec<NodeIndex>>| children.get(node).map_or(0, |c| c.len());
+        let is_leaf = |node: &NodeIndex, join_children: &HashMap<NodeIndex, Vec<NodeIndex>>, split_children: &HashMap<NodeIndex, Vec<NodeIndex>>| {
+            degree(node, join_children) + degree(node, split_children) == 1
+        };
+        let mut queue: VecDeque<NodeIndex> = order.iter().filter(|node| is_leaf(node, &join_children, &split_children)).cloned().collect();
+        let mut neighbors: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::new();
+        while let Some(node) = queue.pop_front() {
+            // a minimum of what remains is a leaf of the join tree, and a maximum a leaf of the
+            // split tree; either way it is peeled off of one and spliced out of the other
+            let (peeled, spliced) = if degree(&node, &join_children) == 0 {
+                ((&mut join_parents, &mut join_children), (&mut split_parents, &mut split_children))
+            } else {
+                ((&mut split_parents, &mut split_children), (&mut join_parents, &mut join_children))
+            };
+            let next = match peeled.0.remove(&node) {
+                None => continue,
+                Some(next) => next
+            };
+            if let Some(siblings) = peeled.1.get_mut(&next) {
+                siblings.retain(|&sibling| sibling != node);
+            }
+            splice(node, spliced.0, spliced.1);
+            neighbors.entry(node).or_default().push(next);
+            neighbors.entry(next).or_default().push(node);
+            if is_leaf(&next, &join_children, &split_children) {
+                queue.push_back(next);
+            }
+        }
+
+        // Contract the chains of regular nodes, which have one neighbor on each side
+        let is_regular = |node: &NodeIndex| match neighbors.get(node) {
+            Some(adjacent) if adjacent.len() == 2 => (position[&adjacent[0]] < position[node]) != (position[&adjacent[1]] < position[node]),
+            _ => false
+        };
+        let nodes: Vec<NodeIndex> = order.iter().filter(|node| !is_regular(node)).cloned().collect();
+        let mut arcs = vec![];
+        for &node in nodes.iter() {
+            for &start in neighbors.get(&node).map(|n| n.as_slice()).unwrap_or(&[]) {
+                // follow each chain upward only, so that every arc is found once
+                if position[&start] < position[&node] {
+                    continue;
+                }
+                let (mut previous, mut current) = (node, start);
+                while is_regular(&current) {
+                    let adjacent = &neighbors[&current];
+                    let following = if adjacent[0] == previous { adjacent[1] } else { adjacent[0] };
+                    previous = current;
+                    current = following;
+                }
+                arcs.push(ContourArc{lower: node, upper: current, persistence: (value(current)? - value(node)?).abs()});
+            }
+        }
+        Ok(ContourTree{nodes, arcs})
+    }
+
+    /// The critical nodes, from the lowest to the highest.
+    pub fn nodes(&self) -> &[NodeIndex] {
+        &self.nodes
+    }
+
+    /// The arcs of the tree, in order of their lower ends.
+    pub fn arcs(&self) -> &[ContourArc] {
+        &self.arcs
+    }
+
+    /// The leaves of the tree, i.e. the extrema, from the lowest to the highest.
+    pub fn leaves(&self) -> Vec<NodeIndex> {
+        let mut degrees: HashMap<NodeIndex, usize> = HashMap::new();
+        for arc in self.arcs.iter() {
+            *degrees.entry(arc.lower).or_insert(0) += 1;
+            *degrees.entry(arc.upper).or_insert(0) += 1;
+        }
+        self.nodes.iter().filter(|node| degrees.get(node).cloned().unwrap_or(0) <= 1).cloned().collect()
+    }
+}
+
+// Sweeps through the nodes in `order`, connecting the most recent node of each component of the
+// level set to the node that next grows or joins it. Returns the parent and the children of
+// every node.
+fn augmented_merge_tree<N: ScalarValued>(graph: &UnGraph<N, f64>, order: &[NodeIndex], position: &HashMap<NodeIndex, usize>)
+    -> (HashMap<NodeIndex, NodeIndex>, HashMap<NodeIndex, Vec<NodeIndex>>) {
+    let mut components = PointedUnionFind::new(order.len());
+    // the most recent node of each component, at its representative
+    let mut latest: Vec<usize> = (0..order.len()).collect();
+    let mut parents = HashMap::with_capacity(order.len());
+    let mut children: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::with_capacity(order.len());
+    for (p, &node) in order.iter().enumerate() {
+        let mut earlier: Vec<usize> = graph.neighbors(node)
+            .map(|neighbor| position[&neighbor])
+            .filter(|&q| q < p)
+            .map(|q| components.find_mut(q))
+            .collect();
+        earlier.sort_unstable();
+        earlier.dedup();
+        for component in earlier {
+            parents.insert(order[latest[component]], node);
+            children.entry(node).or_default().push(order[latest[component]]);
+            components.union(p, component);
+        }
+        latest[components.find_mut(p)] = p;
+    }
+    (parents, children)
+}
+
+// Removes `node`, which has at most one child, from a tree, connecting its child to its parent
+fn splice(node: NodeIndex, parents: &mut HashMap<NodeIndex, NodeIndex>, children: &mut HashMap<NodeIndex, Vec<NodeIndex>>) {
+    let parent = parents.remove(&node);
+    let child = children.remove(&node).and_then(|c| c.first().cloned());
+    if let Some(parent) = parent {
+        let siblings = children.entry(parent).or_default();
+        siblings.retain(|&sibling| sibling != node);
+        siblings.extend(child);
+    }
+    if let Some(child) = child {
+        match parent {
+            Some(parent) => parents.insert(child, parent),
+            None => parents.remove(&child)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+    use crate::merge_tree::MergeTree;
+
+    #[test]
+    fn test_contour_tree() {
+        // a path with minima at 0 and 4 and a maximum at 2, and a branch from 2 up to a second
+        // maximum at 5, through the regular node 6
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [0., 3., 5., 2., 1., 9., 7.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: ()}))
+            .collect();
+        for i in 0..4 {
+            graph.add_edge(nodes[i], nodes[i + 1], 1.);
+        }
+        graph.add_edge(nodes[2], nodes[6], 1.);
+        graph.add_edge(nodes[6], nodes[5], 1.);
+
+        let tree = ContourTree::from_graph(&graph).unwrap();
+        assert_eq!(tree.leaves(), vec![nodes[0], nodes[4], nodes[5]]);
+        assert!(!tree.nodes().contains(&nodes[6]));
+        assert!(!tree.nodes().contains(&nodes[1]));
+        assert_eq!(tree.arcs().len(), 3);
+        assert!(tree.arcs().contains(&ContourArc{lower: nodes[0], upper: nodes[2], persistence: 5.}));
+        assert!(tree.arcs().contains(&ContourArc{lower: nodes[4], upper: nodes[2], persistence: 4.}));
+        assert!(tree.arcs().contains(&ContourArc{lower: nodes[2], upper: nodes[5], persistence: 4.}));
+    }
+
+    #[test]
+    fn test_contour_tree_grid() {
+        // a triangulated grid, whose contour tree has the leaves of both merge trees
+        let mut graph = UnGraph::new_undirected();
+        let values = [3., 7., 2., 8., 1., 6., 0., 5., 9., 4., 11., 10.];
+        let nodes: Vec<_> = values.iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: ()}))
+            .collect();
+        for row in 0..3 {
+            for col in 0..4 {
+                let i = 4 * row + col;
+                if col < 3 {
+                    graph.add_edge(nodes[i], nodes[i + 1], 1.);
+                }
+                if row < 2 {
+                    graph.add_edge(nodes[i], nodes[i + 4], 1.);
+                }
+                if row < 2 && col < 3 {
+                    graph.add_edge(nodes[i], nodes[i + 5], 1.);
+                }
+            }
+        }
+        let tree = ContourTree::from_graph(&graph).unwrap();
+        let mut extrema = MergeTree::join_tree(&graph).unwrap().leaves();
+        extrema.extend(MergeTree::split_tree(&graph).unwrap().leaves());
+        extrema.sort_by(|a, b| values[a.index()].partial_cmp(&values[b.index()]).unwrap());
+        assert_eq!(tree.leaves(), extrema);
+        assert_eq!(tree.arcs().len(), tree.nodes().len() - 1);
+        for arc in tree.arcs() {
+            assert_eq!(arc.persistence, values[arc.upper.index()] - values[arc.lower.index()]);
+        }
+    }
+}
@@ -0,0 +1,1067 @@
+//! Distances between persistence diagrams, and batch computation of those distances over
+//! collections of diagrams.
+//!
+//! Diagrams are given as slices of `(birth, death)` pairs.
+use std::f64;
+use std::collections::VecDeque;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DistanceError {
+    #[error("Could not compute distance due to a NaN in a diagram")]
+    NanInDiagram {},
+
+    #[error("Distance computation failed to converge")]
+    ConvergenceFailure {},
+
+    #[error("The regularization strength must be positive, got {epsilon:?}")]
+    InvalidEpsilon {
+        epsilon: f64
+    },
+
+    #[error("The exponent p must be at least 1, got {p:?}")]
+    InvalidExponent {
+        p: f64
+    },
+
+    #[error("The stopping precision must be positive, got {precision:?}")]
+    InvalidPrecision {
+        precision: f64
+    },
+
+    #[error("The norm q must be at least 1, got {q:?}")]
+    InvalidNorm {
+        q: f64
+    },
+
+    #[error("The kernel bandwidth must be positive and finite, got {sigma:?}")]
+    InvalidBandwidth {
+        sigma: f64
+    },
+
+    #[error("At least one direction is needed to slice a diagram")]
+    NoDirections {}
+}
+
+/// A symmetric matrix of pairwise distances between the items of a collection.
+///
+/// Only the upper triangle is stored (the diagonal is always 0), so each distance is computed
+/// exactly once. The matrix also acts as a cache: growing the collection with
+/// [extend](struct.DistanceMatrix.html#method.extend) only computes the distances that involve
+/// the new items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceMatrix {
+    size: usize,
+    upper: Vec<f64>
+}
+
+impl DistanceMatrix {
+    /// Creates an empty matrix, for use with [extend](struct.DistanceMatrix.html#method.extend).
+    pub fn new() -> Self {
+        DistanceMatrix{size: 0, upper: vec![]}
+    }
+
+    /// The number of items the matrix covers.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the distance between items `i` and `j`.
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        assert!(i < self.size && j < self.size, "Index ({}, {}) out of bounds for {} items", i, j, self.size);
+        match i.cmp(&j) {
+            std::cmp::Ordering::Equal => 0.,
+            std::cmp::Ordering::Less => self.upper[upper_index(i, j)],
+            std::cmp::Ordering::Greater => self.upper[upper_index(j, i)]
+        }
+    }
+
+    /// Returns the full matrix as a vector of rows.
+    pub fn to_dense(&self) -> Vec<Vec<f64>> {
+        (0..self.size)
+            .map(|i| (0..self.size).map(|j| self.get(i, j)).collect())
+            .collect()
+    }
+
+    /// Grows the matrix to cover all of `items`, computing only the distances that involve items
+    /// not already covered.
+    ///
+    /// `items` must start with the same items (in the same order) that this matrix was built from;
+    /// the existing distances are reused as-is.
+    pub fn extend<D, F>(&mut self, items: &[D], distance: F)
+        where D: Sync, F: Fn(&D, &D) -> f64 + Sync {
+        if items.len() <= self.size {
+            return;
+        }
+        // The upper triangle is stored column by column, so all of the new entries are contiguous
+        // at the end of the storage
+        let old_size = self.size;
+        let pairs: Vec<(usize, usize)> = (old_size..items.len())
+            .flat_map(|j| (0..j).map(move |i| (i, j)))
+            .collect();
+        let new_entries = map_indices(pairs.len(), |k| distance(&items[pairs[k].0], &items[pairs[k].1]));
+        self.upper.extend(new_entries);
+        self.size = items.len();
+    }
+}
+
+impl Default for DistanceMatrix {
+    fn default() -> Self {
+        DistanceMatrix::new()
+    }
+}
+
+// Maps over the indices below `n` independently of each other, so with the parallel feature
+// they're spread across threads
+#[cfg(feature = "parallel")]
+fn map_indices<R: Send, F: Fn(usize) -> R + Sync + Send>(n: usize, f: F) -> Vec<R> {
+    use rayon::prelude::*;
+    (0..n).into_par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_indices<R, F: Fn(usize) -> R>(n: usize, f: F) -> Vec<R> {
+    (0..n).map(f).collect()
+}
+
+fn upper_index(i: usize, j: usize) -> usize {
+    // column-major upper triangle, i < j
+    j * (j - 1) / 2 + i
+}
+
+/// Computes the distance between every pair of `items`, in parallel with the `parallel` feature.
+///
+/// `distance` is assumed to be symmetric and to be 0 between an item and itself, so only
+/// `m(m-1)/2` distances are actually evaluated for `m` items. This is intended for diagram
+/// distances, which are expensive enough that computing an `O(m²)` matrix serially is slow.
+pub fn pairwise_distances<D, F>(items: &[D], distance: F) -> DistanceMatrix
+    where D: Sync, F: Fn(&D, &D) -> f64 + Sync {
+    let mut matrix = DistanceMatrix::new();
+    matrix.extend(items, distance);
+    matrix
+}
+
+fn diagonal_distance(point: (f64, f64)) -> f64 {
+    // L-infinity distance to the closest point on the diagonal
+    (point.1 - point.0).abs() / 2.
+}
+
+fn point_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+fn log_sum_exp(values: impl Iterator<Item = f64>) -> f64 {
+    // a single pass, rescaling the sum whenever the maximum grows, so that the values don't have
+    // to be collected first
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.;
+    for value in values.filter(|&v| v != f64::NEG_INFINITY) {
+        if value <= max {
+            sum += (value - max).exp();
+        } else {
+            sum = sum * (max - value).exp() + 1.;
+            max = value;
+        }
+    }
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + sum.ln()
+}
+
+/// Computes the bottleneck distance between two diagrams.
+///
+/// This is the smallest `δ` such that the points of the diagrams can be matched to each other or
+/// to the diagonal, with every point moved by at most `δ` in the L-infinity norm. It is found
+/// exactly, by searching the distances between pairs of points (and from each point to the
+/// diagonal) for the smallest at which a perfect matching exists.
+///
+/// Points with an infinite death can only be matched to each other, so if the diagrams have
+/// different numbers of them (dying at `+∞` or at `-∞`), the distance is infinite. Otherwise they
+/// are matched in order of birth, and contribute the largest difference between matched births.
+pub fn bottleneck_distance(first: &[(f64, f64)], second: &[(f64, f64)]) -> Result<f64, DistanceError> {
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let mut essential = 0f64;
+    for &end in &[f64::INFINITY, f64::NEG_INFINITY] {
+        let births = |diagram: &[(f64, f64)]| {
+            let mut births: Vec<f64> = diagram.iter().filter(|&&(_, d)| d == end).map(|&(b, _)| b).collect();
+            births.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            births
+        };
+        let (first_births, second_births) = (births(first), births(second));
+        if first_births.len() != second_births.len() {
+            return Ok(f64::INFINITY);
+        }
+        for (a, b) in first_births.iter().zip(second_births.iter()) {
+            essential = essential.max((a - b).abs());
+        }
+    }
+
+    let first: Vec<(f64, f64)> = first.iter().copied().filter(|(_, d)| d.is_finite()).collect();
+    let second: Vec<(f64, f64)> = second.iter().copied().filter(|(_, d)| d.is_finite()).collect();
+    Ok(essential.max(finite_bottleneck(&first, &second)))
+}
+
+/// Finds a matching between the points of two diagrams that is optimal for the bottleneck
+/// distance, as in [bottleneck_distance](fn.bottleneck_distance.html): no point is moved further
+/// than the distance.
+///
+/// Returns the `(i, j)` pairs of indices of the points of `first` and `second` that are matched to
+/// each other, in order of `i`, as with [wasserstein_matching](fn.wasserstein_matching.html). The
+/// points that aren't in any pair are matched to the diagonal. Points with an infinite death are
+/// matched in order of birth, and if the diagrams have different numbers of them, the latest born
+/// of the larger group are left unmatched.
+pub fn bottleneck_matching(first: &[(f64, f64)], second: &[(f64, f64)]) -> Result<Vec<(usize, usize)>, DistanceError> {
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let mut matching = essential_matching(first, second);
+
+    let first_finite: Vec<usize> = (0..first.len()).filter(|&i| first[i].1.is_finite()).collect();
+    let second_finite: Vec<usize> = (0..second.len()).filter(|&j| second[j].1.is_finite()).collect();
+    let first_points: Vec<(f64, f64)> = first_finite.iter().map(|&i| first[i]).collect();
+    let second_points: Vec<(f64, f64)> = second_finite.iter().map(|&j| second[j]).collect();
+    let delta = finite_bottleneck(&first_points, &second_points);
+    // the search only stops at a distance with a perfect matching
+    let matched = perfect_matching(&first_points, &second_points, delta).unwrap();
+    let (n, m) = (first_finite.len(), second_finite.len());
+    matching.extend(matched.into_iter().enumerate()
+        .filter_map(|(j, i)| i.map(|i| (i, j)))
+        .filter(|&(i, j)| i < n && j < m)
+        .map(|(i, j)| (first_finite[i], second_finite[j])));
+    matching.sort_unstable();
+    Ok(matching)
+}
+
+// The bottleneck distance between two diagrams with only finite points
+fn finite_bottleneck(first: &[(f64, f64)], second: &[(f64, f64)]) -> f64 {
+    let mut candidates: Vec<f64> = first.iter().chain(second.iter()).map(|&point| diagonal_distance(point)).collect();
+    for &a in first.iter() {
+        candidates.extend(second.iter().map(|&b| point_distance(a, b)));
+    }
+    candidates.push(0.);
+    candidates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    candidates.dedup();
+    // the largest candidate always allows a matching, e.g. of every point to the diagonal
+    let (mut low, mut high) = (0, candidates.len() - 1);
+    while low < high {
+        let middle = (low + high) / 2;
+        if perfect_matching(first, second, candidates[middle]).is_some() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    candidates[low]
+}
+
+// The points with an infinite death of both diagrams matched in order of birth, among those dying
+// at `+∞` and among those dying at `-∞`
+fn essential_matching(first: &[(f64, f64)], second: &[(f64, f64)]) -> Vec<(usize, usize)> {
+    let mut matching = vec![];
+    for &end in &[f64::INFINITY, f64::NEG_INFINITY] {
+        let births = |diagram: &[(f64, f64)]| {
+            let mut indices: Vec<usize> = (0..diagram.len()).filter(|&i| diagram[i].1 == end).collect();
+            indices.sort_by(|&a, &b| diagram[a].0.partial_cmp(&diagram[b].0).unwrap());
+            indices
+        };
+        matching.extend(births(first).into_iter().zip(births(second)));
+    }
+    matching
+}
+
+// Matches the points of both diagrams with each other or the diagonal, moving none of them by
+// more than `delta`, if that's possible. Each side gets a copy of the diagonal for every point of
+// the other, so the matching is between two sets of `n + m` nodes, and is returned as the node of
+// the first side matched to each node of the second.
+fn perfect_matching(first: &[(f64, f64)], second: &[(f64, f64)], delta: f64) -> Option<Vec<Option<usize>>> {
+    let (n, m) = (first.len(), second.len());
+    let neighbors: Vec<Vec<usize>> = (0..n + m)
+        .map(|i| {
+            if i < n {
+                let mut adjacent: Vec<usize> = (0..m).filter(|&j| point_distance(first[i], second[j]) <= delta).collect();
+                if diagonal_distance(first[i]) <= delta {
+                    adjacent.push(m + i);
+                }
+                adjacent
+            } else {
+                // the copy of the diagonal for the point of `second` at `i - n`, which can also take
+                // any of the diagonal's copies for the points of `first`
+                let mut adjacent: Vec<usize> = (m..m + n).collect();
+                if diagonal_distance(second[i - n]) <= delta {
+                    adjacent.push(i - n);
+                }
+                adjacent
+            }
+        })
+        .collect();
+
+    let matched = maximum_matching(&neighbors, n + m);
+    if matched.iter().all(Option::is_some) {
+        Some(matched)
+    } else {
+        None
+    }
+}
+
+// A maximum matching of the bipartite graph in which the left node `i` is adjacent to the right
+// nodes in `neighbors[i]`, with the Hopcroft-Karp algorithm, as the left node matched to each of
+// the `right` right nodes. The augmenting paths can be as long as the graph is big, so they're
+// followed with a stack of our own rather than by recursion.
+fn maximum_matching(neighbors: &[Vec<usize>], right: usize) -> Vec<Option<usize>> {
+    let unreached = usize::MAX;
+    let mut matched: Vec<Option<usize>> = vec![None; right];
+    let mut partner: Vec<Option<usize>> = vec![None; neighbors.len()];
+    let mut layer = vec![unreached; neighbors.len()];
+    let mut next = vec![0; neighbors.len()];
+    let mut queue = VecDeque::new();
+    let mut stack = vec![];
+    loop {
+        // layer the left nodes by the length of the shortest alternating path to them from a free
+        // one, stopping once there's an augmenting path
+        for (i, depth) in layer.iter_mut().enumerate() {
+            *depth = if partner[i].is_none() { 0 } else { unreached };
+            if *depth == 0 {
+                queue.push_back(i);
+            }
+        }
+        let mut augmentable = false;
+        while let Some(i) = queue.pop_front() {
+            for &j in neighbors[i].iter() {
+                match matched[j] {
+                    None => augmentable = true,
+                    Some(k) if layer[k] == unreached => {
+                        layer[k] = layer[i] + 1;
+                        queue.push_back(k);
+                    },
+                    Some(_) => ()
+                }
+            }
+        }
+        if !augmentable {
+            return matched;
+        }
+
+        // then augment along disjoint paths that go down the layers, from each free left node.
+        // `next[i]` is the next neighbor of `i` to try, so the edge into the stack's top is always
+        // the one just before it
+        for position in next.iter_mut() {
+            *position = 0;
+        }
+        for start in 0..neighbors.len() {
+            if partner[start].is_some() {
+                continue;
+            }
+            stack.clear();
+            stack.push(start);
+            while let Some(&i) = stack.last() {
+                if next[i] == neighbors[i].len() {
+                    // no augmenting path goes through `i` in this phase
+                    layer[i] = unreached;
+                    stack.pop();
+                    continue;
+                }
+                let j = neighbors[i][next[i]];
+                next[i] += 1;
+                match matched[j] {
+                    None => {
+                        for &k in stack.iter() {
+                            let j = neighbors[k][next[k] - 1];
+                            matched[j] = Some(k);
+                            partner[k] = Some(j);
+                        }
+                        break;
+                    },
+                    Some(k) if layer[k] == layer[i] + 1 => stack.push(k),
+                    Some(_) => ()
+                }
+            }
+        }
+    }
+}
+
+/// Computes the `p`-Wasserstein distance between two diagrams exactly.
+///
+/// This is the smallest `(Σ ‖x - y‖ᵖ)^(1/p)` over the ways of matching the points of the
+/// diagrams to each other or to the diagonal, where each `‖x - y‖` is the distance between a pair
+/// of matched points (or from a point to the closest point on the diagonal) in the `q`-norm, so
+/// `q = f64::INFINITY` gives the L-infinity norm used by
+/// [sinkhorn_wasserstein](fn.sinkhorn_wasserstein.html). It is an error for `p` or `q` to be less
+/// than 1.
+///
+/// The optimal matching is found with the Hungarian algorithm, which takes `O((n + m)³)` time, so
+/// for large diagrams the approximation of [sinkhorn_wasserstein](fn.sinkhorn_wasserstein.html)
+/// is much faster. Points with an infinite death are matched among themselves as in
+/// [bottleneck_distance](fn.bottleneck_distance.html), making the distance infinite if the
+/// diagrams have different numbers of them.
+pub fn wasserstein_distance(first: &[(f64, f64)], second: &[(f64, f64)], p: f64, q: f64) -> Result<f64, DistanceError> {
+    check_exponents(p, q)?;
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let mut total = 0.;
+    for &end in &[f64::INFINITY, f64::NEG_INFINITY] {
+        let births = |diagram: &[(f64, f64)]| {
+            let mut births: Vec<f64> = diagram.iter().filter(|&&(_, d)| d == end).map(|&(b, _)| b).collect();
+            births.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            births
+        };
+        let (first_births, second_births) = (births(first), births(second));
+        if first_births.len() != second_births.len() {
+            return Ok(f64::INFINITY);
+        }
+        total += first_births.iter().zip(second_births.iter()).map(|(a, b)| (a - b).abs().powf(p)).sum::<f64>();
+    }
+
+    let first: Vec<(f64, f64)> = first.iter().copied().filter(|(_, d)| d.is_finite()).collect();
+    let second: Vec<(f64, f64)> = second.iter().copied().filter(|(_, d)| d.is_finite()).collect();
+    let (n, m) = (first.len(), second.len());
+    // As for the bottleneck distance, each side gets a copy of the diagonal for every point of the
+    // other. The copies are interchangeable, so any point can go to any copy.
+    let cost = |i: usize, j: usize| -> f64 {
+        match (i < n, j < m) {
+            (true, true) => norm_distance(first[i], second[j], q).powf(p),
+            (true, false) => diagonal_norm_distance(first[i], q).powf(p),
+            (false, true) => diagonal_norm_distance(second[j], q).powf(p),
+            (false, false) => 0.
+        }
+    };
+    total += minimum_assignment(n + m, cost);
+    Ok(total.powf(1. / p))
+}
+
+/// Finds a matching between the points of two diagrams that is optimal for the `p`-Wasserstein
+/// distance, with points compared in the `q`-norm, as in
+/// [wasserstein_distance](fn.wasserstein_distance.html).
+///
+/// Returns the `(i, j)` pairs of indices of the points of `first` and `second` that are matched to
+/// each other, in order of `i`. The points that aren't in any pair are matched to the diagonal.
+/// Points with an infinite death are matched in order of birth, and if the diagrams have
+/// different numbers of them, the latest born of the larger group are left unmatched. As for the
+/// distance, it is an error for `p` or `q` to be less than 1.
+pub fn wasserstein_matching(first: &[(f64, f64)], second: &[(f64, f64)], p: f64, q: f64) -> Result<Vec<(usize, usize)>, DistanceError> {
+    check_exponents(p, q)?;
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let mut matching = essential_matching(first, second);
+
+    let first_finite: Vec<usize> = (0..first.len()).filter(|&i| first[i].1.is_finite()).collect();
+    let second_finite: Vec<usize> = (0..second.len()).filter(|&j| second[j].1.is_finite()).collect();
+    let (n, m) = (first_finite.len(), second_finite.len());
+    let cost = |i: usize, j: usize| -> f64 {
+        match (i < n, j < m) {
+            (true, true) => norm_distance(first[first_finite[i]], second[second_finite[j]], q).powf(p),
+            (true, false) => diagonal_norm_distance(first[first_finite[i]], q).powf(p),
+            (false, true) => diagonal_norm_distance(second[second_finite[j]], q).powf(p),
+            (false, false) => 0.
+        }
+    };
+    let assignment = optimal_assignment(n + m, cost);
+    matching.extend(assignment.into_iter().enumerate()
+        .filter(|&(i, j)| i < n && j < m)
+        .map(|(i, j)| (first_finite[i], second_finite[j])));
+    matching.sort_unstable();
+    Ok(matching)
+}
+
+// The p-Wasserstein distance in the q-norm is only a distance for p, q >= 1
+fn check_exponents(p: f64, q: f64) -> Result<(), DistanceError> {
+    if p.is_nan() || p < 1. {
+        return Err(DistanceError::InvalidExponent{p});
+    }
+    if q.is_nan() || q < 1. {
+        return Err(DistanceError::InvalidNorm{q});
+    }
+    Ok(())
+}
+
+// A Gaussian-style kernel needs a positive, finite bandwidth to be a kernel at all
+fn check_bandwidth(sigma: f64) -> Result<(), DistanceError> {
+    if !sigma.is_finite() || sigma <= 0. {
+        return Err(DistanceError::InvalidBandwidth{sigma});
+    }
+    Ok(())
+}
+
+fn norm_distance(a: (f64, f64), b: (f64, f64), q: f64) -> f64 {
+    if q.is_infinite() {
+        point_distance(a, b)
+    } else {
+        ((a.0 - b.0).abs().powf(q) + (a.1 - b.1).abs().powf(q)).powf(1. / q)
+    }
+}
+
+fn diagonal_norm_distance(point: (f64, f64), q: f64) -> f64 {
+    // the closest point on the diagonal is the same in every q-norm
+    if q.is_infinite() {
+        diagonal_distance(point)
+    } else {
+        diagonal_distance(point) * 2f64.powf(1. / q)
+    }
+}
+
+// The total cost of the cheapest perfect matching between two sets of `size` items
+fn minimum_assignment<F>(size: usize, cost: F) -> f64
+    where F: Fn(usize, usize) -> f64 {
+    optimal_assignment(size, &cost).into_iter().enumerate().map(|(i, j)| cost(i, j)).sum()
+}
+
+// The cheapest perfect matching between two sets of `size` items, as the item of the second set
+// matched to each item of the first, with the Hungarian algorithm (in its shortest augmenting
+// path form, with potentials on both sides)
+fn optimal_assignment<F>(size: usize, cost: F) -> Vec<usize>
+    where F: Fn(usize, usize) -> f64 {
+    // 1-based, with 0 standing for "unmatched" in `matched` and `previous`
+    let mut row_potential = vec![0.; size + 1];
+    let mut column_potential = vec![0.; size + 1];
+    let mut matched = vec![0; size + 1];
+    let mut previous = vec![0; size + 1];
+    for row in 1..=size {
+        matched[0] = row;
+        let mut column = 0;
+        let mut slack = vec![f64::INFINITY; size + 1];
+        let mut used = vec![false; size + 1];
+        loop {
+            used[column] = true;
+            let current = matched[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=size {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(current - 1, j - 1) - row_potential[current] - column_potential[j];
+                if reduced < slack[j] {
+                    slack[j] = reduced;
+                    previous[j] = column;
+                }
+                if slack[j] < delta {
+                    delta = slack[j];
+                    next = j;
+                }
+            }
+            for j in 0..=size {
+                if used[j] {
+                    row_potential[matched[j]] += delta;
+                    column_potential[j] -= delta;
+                } else {
+                    slack[j] -= delta;
+                }
+            }
+            column = next;
+            if matched[column] == 0 {
+                break;
+            }
+        }
+        while column != 0 {
+            let before = previous[column];
+            matched[column] = matched[before];
+            column = before;
+        }
+    }
+    let mut assignment = vec![0; size];
+    for j in 1..=size {
+        assignment[matched[j] - 1] = j - 1;
+    }
+    assignment
+}
+
+/// The most Sinkhorn iterations [sinkhorn_wasserstein](fn.sinkhorn_wasserstein.html) runs before
+/// giving up on reaching the requested precision.
+pub const MAX_SINKHORN_ITERATIONS: usize = 10_000;
+
+/// Approximates the `p`-Wasserstein distance between two diagrams with entropic regularization.
+///
+/// This solves the optimal transport problem between the diagrams with Sinkhorn iterations,
+/// where all of the diagonal is collapsed into a single point that can absorb any amount of mass.
+/// Each iteration costs `O(nm)` time but only `O(n + m)` memory, as the transport costs are
+/// recomputed as needed rather than stored, so this scales to diagrams far too large for an exact
+/// matching. Points are compared with the L-infinity norm.
+///
+/// `epsilon` is the strength of the regularization, in the same units as the diagram's values
+/// raised to the `p`. Smaller values approximate the true distance more closely (the error
+/// vanishes as `epsilon` goes to 0) but need more iterations to converge. It is an error for
+/// `epsilon` not to be positive, or for `p` to be less than 1.
+///
+/// `precision` controls early stopping: iteration stops once the transported mass is within
+/// `precision` (relative) of the required mass. Lower values increase runtime but increase
+/// accuracy. It is an error for `precision` not to be positive, and if it still hasn't been
+/// reached after [MAX_SINKHORN_ITERATIONS](constant.MAX_SINKHORN_ITERATIONS.html) iterations, the
+/// result is a `ConvergenceFailure`.
+///
+/// Points with an infinite death never cancel out against finite points, so they are ignored
+/// here and should be compared separately.
+pub fn sinkhorn_wasserstein(first: &[(f64, f64)], second: &[(f64, f64)], p: f64, epsilon: f64, precision: f64)
+    -> Result<f64, DistanceError> {
+    if epsilon.is_nan() || epsilon <= 0. {
+        return Err(DistanceError::InvalidEpsilon{epsilon});
+    }
+    if p.is_nan() || p < 1. {
+        return Err(DistanceError::InvalidExponent{p});
+    }
+    if precision.is_nan() || precision <= 0. {
+        return Err(DistanceError::InvalidPrecision{precision});
+    }
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let first: Vec<(f64, f64)> = first.iter().copied().filter(|(b, d)| b.is_finite() && d.is_finite()).collect();
+    let second: Vec<(f64, f64)> = second.iter().copied().filter(|(b, d)| b.is_finite() && d.is_finite()).collect();
+    if first.is_empty() && second.is_empty() {
+        return Ok(0.);
+    }
+
+    // The last entry on each side is the diagonal, which takes the mass of every point on the
+    // other side. Moving mass from the diagonal to the diagonal is free.
+    let n = first.len();
+    let m = second.len();
+    let cost = |i: usize, j: usize| -> f64 {
+        match (i == n, j == m) {
+            (false, false) => point_distance(first[i], second[j]).powf(p),
+            (false, true) => diagonal_distance(first[i]).powf(p),
+            (true, false) => diagonal_distance(second[j]).powf(p),
+            (true, true) => 0.
+        }
+    };
+    let mass = |count: usize, other_count: usize, i: usize| if i == count {other_count as f64} else {1.};
+    let log_a: Vec<f64> = (0..=n).map(|i| mass(n, m, i).ln()).collect();
+    let log_b: Vec<f64> = (0..=m).map(|j| mass(m, n, j).ln()).collect();
+    let total_mass = (n + m) as f64;
+
+    let mut f = vec![0.; n + 1];
+    let mut g = vec![0.; m + 1];
+    let mut iters = 0;
+    loop {
+        iters += 1;
+        f = map_indices(n + 1, |i| epsilon * (log_a[i] - log_sum_exp((0..=m).map(|j| (g[j] - cost(i, j)) / epsilon))));
+        g = map_indices(m + 1, |j| epsilon * (log_b[j] - log_sum_exp((0..=n).map(|i| (f[i] - cost(i, j)) / epsilon))));
+
+        // g is exact for the columns after its update, so only the rows can be off
+        let error: f64 = map_indices(n + 1, |i| {
+                let row_mass = log_sum_exp((0..=m).map(|j| (f[i] + g[j] - cost(i, j)) / epsilon)).exp();
+                (row_mass - log_a[i].exp()).abs()
+            })
+            .into_iter()
+            .sum();
+        if error <= precision * total_mass {
+            break;
+        }
+        if iters >= MAX_SINKHORN_ITERATIONS {
+            return Err(DistanceError::ConvergenceFailure{});
+        }
+    }
+
+    let transport_cost: f64 = map_indices(n + 1, |i| {
+            (0..=m)
+                .map(|j| {
+                    let c = cost(i, j);
+                    ((f[i] + g[j] - c) / epsilon).exp() * c
+                })
+                .sum::<f64>()
+        })
+        .into_iter()
+        .sum();
+    Ok(transport_cost.powf(1. / p))
+}
+
+/// Approximates the sliced Wasserstein distance between two diagrams, as defined in [Sliced
+/// Wasserstein kernel for persistence diagrams](https://arxiv.org/abs/1706.03358).
+///
+/// Each diagram is completed with the projections onto the diagonal of the points of the other,
+/// and both are projected onto `directions` lines through the origin, evenly spaced in angle. On
+/// each line, the 1-Wasserstein distance between the projections is found exactly by sorting
+/// them, and the result is the average over the lines. This takes `O(k (n + m) log(n + m))` time
+/// for `k` directions, and unlike the other diagram distances, it is conditionally negative
+/// definite, so it makes a valid kernel (see
+/// [sliced_wasserstein_kernel](fn.sliced_wasserstein_kernel.html)).
+///
+/// Points with an infinite death are ignored, as in
+/// [sinkhorn_wasserstein](fn.sinkhorn_wasserstein.html). It is an error for `directions` to be 0.
+pub fn sliced_wasserstein_distance(first: &[(f64, f64)], second: &[(f64, f64)], directions: usize) -> Result<f64, DistanceError> {
+    if directions == 0 {
+        return Err(DistanceError::NoDirections{});
+    }
+    let nan_present = first.iter().chain(second.iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let finite = |diagram: &[(f64, f64)]| -> Vec<(f64, f64)> {
+        diagram.iter().copied().filter(|(b, d)| b.is_finite() && d.is_finite()).collect()
+    };
+    let (first, second) = (finite(first), finite(second));
+    let diagonal = |&(b, d): &(f64, f64)| ((b + d) / 2., (b + d) / 2.);
+    let completed_first: Vec<(f64, f64)> = first.iter().cloned().chain(second.iter().map(diagonal)).collect();
+    let completed_second: Vec<(f64, f64)> = second.iter().cloned().chain(first.iter().map(diagonal)).collect();
+    if completed_first.is_empty() {
+        return Ok(0.);
+    }
+
+    let total: f64 = (0..directions)
+        .map(|k| {
+            let angle = -std::f64::consts::FRAC_PI_2 + std::f64::consts::PI * k as f64 / directions as f64;
+            let (cos, sin) = (angle.cos(), angle.sin());
+            let project = |points: &[(f64, f64)]| {
+                let mut projected: Vec<f64> = points.iter().map(|&(b, d)| b * cos + d * sin).collect();
+                projected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                projected
+            };
+            project(&completed_first).iter().zip(project(&completed_second).iter()).map(|(a, b)| (a - b).abs()).sum::<f64>()
+        })
+        .sum();
+    Ok(total / directions as f64)
+}
+
+/// Computes the sliced Wasserstein kernel between two diagrams, `exp(-SW / (2σ²))` for the
+/// [sliced Wasserstein distance](fn.sliced_wasserstein_distance.html) `SW` approximated with
+/// `directions` lines, and bandwidth `sigma`.
+///
+/// The kernel is positive definite, so it can be used directly in kernel methods like SVMs and
+/// Gaussian processes. It is an error for `sigma` not to be positive and finite, or for
+/// `directions` to be 0.
+pub fn sliced_wasserstein_kernel(first: &[(f64, f64)], second: &[(f64, f64)], directions: usize, sigma: f64) -> Result<f64, DistanceError> {
+    check_bandwidth(sigma)?;
+    Ok((-sliced_wasserstein_distance(first, second, directions)? / (2. * sigma * sigma)).exp())
+}
+
+/// Computes the Gram matrix of the [sliced Wasserstein
+/// kernel](fn.sliced_wasserstein_kernel.html) between every pair of `diagrams`, in parallel with
+/// the `parallel` feature.
+///
+/// Each distance is only computed once, as with [pairwise_distances](fn.pairwise_distances.html),
+/// and the diagonal is 1. `sigma` and `directions` are checked as in
+/// [sliced_wasserstein_kernel](fn.sliced_wasserstein_kernel.html).
+pub fn sliced_wasserstein_gram<D: AsRef<[(f64, f64)]> + Sync>(diagrams: &[D], directions: usize, sigma: f64)
+    -> Result<Vec<Vec<f64>>, DistanceError> {
+    check_bandwidth(sigma)?;
+    if directions == 0 {
+        return Err(DistanceError::NoDirections{});
+    }
+    let nan_present = diagrams.iter().flat_map(|diagram| diagram.as_ref().iter()).any(|(b, d)| b.is_nan() || d.is_nan());
+    if nan_present {
+        return Err(DistanceError::NanInDiagram{});
+    }
+    let distances = pairwise_distances(diagrams, |a, b| {
+        // no NaNs and some directions, so this can't fail
+        sliced_wasserstein_distance(a.as_ref(), b.as_ref(), directions).unwrap_or(f64::NAN)
+    });
+    Ok(distances.to_dense().into_iter()
+        .map(|row| row.into_iter().map(|distance| (-distance / (2. * sigma * sigma)).exp()).collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_pairwise_distances() {
+        let items = [0., 1., 3., 7.];
+        let calls = AtomicUsize::new(0);
+        let matrix = pairwise_distances(&items, |a: &f64, b: &f64| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            (a - b).abs()
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        assert_eq!(matrix.len(), 4);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(matrix.get(i, j), (items[i] - items[j]).abs());
+            }
+        }
+    }
+
+    #[test]
+    fn test_extend_reuses_distances() {
+        let items = [0., 1., 3., 7., 15.];
+        let calls = AtomicUsize::new(0);
+        let distance = |a: &f64, b: &f64| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            (a - b).abs()
+        };
+        let mut matrix = pairwise_distances(&items[..3], distance);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        matrix.extend(&items, distance);
+        // only the pairs involving the two new items get computed
+        assert_eq!(calls.load(Ordering::SeqCst), 3 + 3 + 4);
+        assert_eq!(matrix.get(4, 1), 14.);
+        assert_eq!(matrix.to_dense()[2][3], 4.);
+    }
+
+    #[test]
+    fn test_bottleneck_distance() {
+        let first = [(0., 4.), (1., 2.)];
+        let second = [(0., 4.5)];
+        // (0, 4) moves to (0, 4.5) and (1, 2) to the diagonal, each by 0.5
+        assert_eq!(bottleneck_distance(&first, &second).unwrap(), 0.5);
+        assert_eq!(bottleneck_distance(&second, &first).unwrap(), 0.5);
+        assert_eq!(bottleneck_distance(&first, &first).unwrap(), 0.);
+        assert_eq!(bottleneck_distance(&[], &[]).unwrap(), 0.);
+        // sending both points to the diagonal is cheaper than matching them to each other
+        assert_eq!(bottleneck_distance(&[(0., 10.)], &[(0., 2.)]).unwrap(), 5.);
+        assert_eq!(bottleneck_distance(&[(0., 10.)], &[(1., 9.5)]).unwrap(), 1.);
+        // one point can't be matched to two
+        assert_eq!(bottleneck_distance(&[(0., 10.)], &[(0., 10.), (0., 10.)]).unwrap(), 5.);
+
+        let with_infinite = [(0., 4.), (1., 2.), (-1., f64::INFINITY)];
+        assert_eq!(bottleneck_distance(&with_infinite, &second).unwrap(), f64::INFINITY);
+        let other_infinite = [(0., 4.5), (2., f64::INFINITY)];
+        assert_eq!(bottleneck_distance(&with_infinite, &other_infinite).unwrap(), 3.);
+        let maxima = [(5., f64::NEG_INFINITY)];
+        assert_eq!(bottleneck_distance(&maxima, &[(5., f64::INFINITY)]).unwrap(), f64::INFINITY);
+
+        assert!(bottleneck_distance(&[(0., f64::NAN)], &second).is_err());
+    }
+
+    fn permutations<F: FnMut(&[usize])>(items: &mut Vec<usize>, start: usize, visit: &mut F) {
+        if start == items.len() {
+            visit(items);
+        }
+        for i in start..items.len() {
+            items.swap(start, i);
+            permutations(items, start + 1, visit);
+            items.swap(start, i);
+        }
+    }
+
+    #[test]
+    fn test_wasserstein_distance() {
+        let first = [(0., 4.), (1., 2.)];
+        let second = [(0., 4.5)];
+        let distance = |a: &[(f64, f64)], b: &[(f64, f64)], p, q| wasserstein_distance(a, b, p, q).unwrap();
+        // (0, 4) moves to (0, 4.5) and (1, 2) to the diagonal, each by 0.5
+        assert_eq!(distance(&first, &second, 1., f64::INFINITY), 1.);
+        assert!((distance(&first, &second, 2., f64::INFINITY) - 0.5f64.sqrt()).abs() < 1e-12);
+        // in the 2-norm, (1, 2) is 1/√2 from the diagonal at (1.5, 1.5)
+        assert!((distance(&first, &second, 1., 2.) - (0.5 + 0.5f64.sqrt())).abs() < 1e-12);
+        assert_eq!(distance(&first, &first, 1., 2.), 0.);
+        assert_eq!(distance(&[], &[], 1., 1.), 0.);
+        assert_eq!(distance(&[(0., 10.)], &[(0., 2.)], 1., f64::INFINITY), 6.);
+
+        // the assignment agrees with trying every matching, and is symmetric
+        let third = [(0., 3.), (2., 6.), (1., 1.5), (4., 5.)];
+        let exact = distance(&first, &third, 2., 1.);
+        assert!((exact - distance(&third, &first, 2., 1.)).abs() < 1e-12);
+        let cost = |i: usize, j: usize| match (i < 2, j < 4) {
+            (true, true) => norm_distance(first[i], third[j], 1.).powi(2),
+            (true, false) => diagonal_norm_distance(first[i], 1.).powi(2),
+            (false, true) => diagonal_norm_distance(third[j], 1.).powi(2),
+            (false, false) => 0.
+        };
+        let mut best = f64::INFINITY;
+        let mut permutation: Vec<usize> = (0..6).collect();
+        permutations(&mut permutation, 0, &mut |p| best = best.min((0..6).map(|i| cost(i, p[i])).sum()));
+        assert!((exact - best.sqrt()).abs() < 1e-12);
+
+        let with_infinite = [(0., 4.), (1., 2.), (-1., f64::INFINITY)];
+        assert_eq!(distance(&with_infinite, &second, 1., 2.), f64::INFINITY);
+        assert_eq!(distance(&with_infinite, &[(0., 4.5), (2., f64::INFINITY)], 1., f64::INFINITY), 4.);
+        assert!(wasserstein_distance(&[(0., f64::NAN)], &second, 1., 1.).is_err());
+
+        for &p in [0., 0.5, -1., f64::NAN].iter() {
+            match wasserstein_distance(&first, &second, p, 2.) {
+                Err(DistanceError::InvalidExponent{..}) => (),
+                other => panic!("expected an invalid exponent error, got {:?}", other)
+            }
+        }
+        match wasserstein_distance(&first, &second, 1., 0.5) {
+            Err(DistanceError::InvalidNorm{q}) => assert_eq!(q, 0.5),
+            other => panic!("expected an invalid norm error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_wasserstein_matching() {
+        let first = [(0., 4.), (1., 2.), (5., f64::INFINITY)];
+        let second = [(3., 3.2), (0., 4.5), (6., f64::INFINITY), (2., f64::INFINITY)];
+        // (1, 2) and (3, 3.2) are closer to the diagonal than to each other
+        let matching = wasserstein_matching(&first, &second, 1., 1.).unwrap();
+        assert_eq!(matching, vec![(0, 1), (2, 3)]);
+        // the matched points account for the whole distance
+        let first_points = [(0., 4.), (1., 2.)];
+        let second_points = [(3., 3.2), (0., 4.5)];
+        let matched: f64 = matching.iter().filter(|&&(i, _)| i < 2)
+            .map(|&(i, j)| norm_distance(first[i], second[j], 1.))
+            .sum::<f64>()
+            + diagonal_norm_distance(first[1], 1.) + diagonal_norm_distance(second[0], 1.);
+        assert!((matched - wasserstein_distance(&first_points, &second_points, 1., 1.).unwrap()).abs() < 1e-12);
+        assert!(wasserstein_matching(&[], &[], 1., 1.).unwrap().is_empty());
+        assert!(wasserstein_matching(&[(f64::NAN, 1.)], &[], 1., 1.).is_err());
+        // the matching accepts exactly the exponents the distance does
+        for &(p, q) in [(0.5, 2.), (1., 0.), (f64::NAN, 1.), (1., f64::INFINITY)].iter() {
+            assert_eq!(wasserstein_matching(&first, &second, p, q).is_ok(), wasserstein_distance(&first, &second, p, q).is_ok());
+        }
+        assert!(wasserstein_matching(&first, &second, 1., 0.).is_err());
+    }
+
+    #[test]
+    fn test_maximum_matching() {
+        // left `i` can take right `i` or `i + 1`, except the last, which can only take right 0, so
+        // matching it means moving every other left node along by one
+        let size = 200_000;
+        let mut neighbors: Vec<Vec<usize>> = (0..size - 1).map(|i| vec![i, i + 1]).collect();
+        neighbors.push(vec![0]);
+        let matched = maximum_matching(&neighbors, size);
+        assert_eq!(matched[0], Some(size - 1));
+        assert!((1..size).all(|j| matched[j] == Some(j - 1)));
+
+        // left 0 and 1 both need right 0
+        assert_eq!(maximum_matching(&[vec![0], vec![0], vec![1, 2]], 3), vec![Some(0), Some(2), None]);
+        assert!(maximum_matching(&[], 2).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_bottleneck_matching() {
+        use rand::Rng;
+        // (0, 10) can only go to (0, 9), and (3, 4) is closer to the diagonal than to (5, 6.5)
+        let first = [(0., 10.), (3., 4.), (1., f64::INFINITY)];
+        let second = [(2., f64::INFINITY), (5., 6.5), (0., 9.)];
+        assert_eq!(bottleneck_matching(&first, &second).unwrap(), vec![(0, 2), (2, 0)]);
+        assert_eq!(bottleneck_distance(&first, &second).unwrap(), 1.);
+        assert_eq!(bottleneck_matching(&first, &[]).unwrap(), vec![]);
+        assert!(bottleneck_matching(&[(f64::NAN, 1.)], &[]).is_err());
+
+        // no point moves further than the distance
+        let mut rng = rand::thread_rng();
+        let mut random_diagram = |size: usize| -> Vec<(f64, f64)> {
+            (0..size).map(|_| {
+                let birth: f64 = rng.gen::<f64>();
+                (birth, birth + rng.gen::<f64>())
+            }).collect()
+        };
+        for _ in 0..20 {
+            let (first, second) = (random_diagram(6), random_diagram(4));
+            let distance = bottleneck_distance(&first, &second).unwrap();
+            let matching = bottleneck_matching(&first, &second).unwrap();
+            let mut matched_first = vec![false; first.len()];
+            let mut matched_second = vec![false; second.len()];
+            for &(i, j) in &matching {
+                assert!(point_distance(first[i], second[j]) <= distance);
+                matched_first[i] = true;
+                matched_second[j] = true;
+            }
+            for (i, _) in matched_first.iter().enumerate().filter(|&(_, &matched)| !matched) {
+                assert!(diagonal_distance(first[i]) <= distance);
+            }
+            for (j, _) in matched_second.iter().enumerate().filter(|&(_, &matched)| !matched) {
+                assert!(diagonal_distance(second[j]) <= distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sliced_wasserstein_distance() {
+        // a single point against an empty diagram moves to the diagonal along every direction
+        let distance = sliced_wasserstein_distance(&[(0., 2.)], &[], 1000).unwrap();
+        // it moves by |sin θ - cos θ| = √2 |sin(θ - π/4)|, which averages to 2√2/π over the angles
+        assert!((distance - 2. * 2f64.sqrt() / std::f64::consts::PI).abs() < 1e-3);
+        assert_eq!(sliced_wasserstein_distance(&[(0., 2.), (1., 3.)], &[(1., 3.), (0., 2.)], 50).unwrap(), 0.);
+        assert_eq!(sliced_wasserstein_distance(&[(0., f64::INFINITY)], &[], 50).unwrap(), 0.);
+        assert!(sliced_wasserstein_distance(&[(0., f64::NAN)], &[], 50).is_err());
+        match sliced_wasserstein_distance(&[(0., 2.)], &[], 0) {
+            Err(DistanceError::NoDirections{}) => (),
+            other => panic!("expected a no directions error, got {:?}", other)
+        }
+
+        // it is symmetric, and like the other distances, grows as the diagrams move apart
+        let first = [(0., 4.), (1., 2.)];
+        let near = [(0., 4.5), (1., 2.)];
+        let far = [(0., 8.), (1., 2.)];
+        let d = |a: &[(f64, f64)], b: &[(f64, f64)]| sliced_wasserstein_distance(a, b, 100).unwrap();
+        assert!((d(&first, &near) - d(&near, &first)).abs() < 1e-12);
+        assert!(d(&first, &near) < d(&first, &far));
+        assert!(d(&first, &far) <= 2f64.sqrt() * wasserstein_distance(&first, &far, 1., 2.).unwrap());
+    }
+
+    #[test]
+    fn test_sliced_wasserstein_gram() {
+        let diagrams = vec![vec![(0., 4.), (1., 2.)], vec![(0., 4.5)], vec![]];
+        let gram = sliced_wasserstein_gram(&diagrams, 50, 1.).unwrap();
+        assert_eq!(gram.len(), 3);
+        for i in 0..3 {
+            assert_eq!(gram[i][i], 1.);
+            for j in 0..3 {
+                assert_eq!(gram[i][j], gram[j][i]);
+                assert!((gram[i][j] - sliced_wasserstein_kernel(&diagrams[i], &diagrams[j], 50, 1.).unwrap()).abs() < 1e-12);
+            }
+        }
+        assert!(gram[0][1] > gram[0][2]);
+        assert!(sliced_wasserstein_gram(&[vec![(f64::NAN, 1.)]], 50, 1.).is_err());
+
+        for &sigma in &[0., -1., f64::NAN, f64::INFINITY] {
+            match sliced_wasserstein_kernel(&diagrams[0], &diagrams[1], 50, sigma) {
+                Err(DistanceError::InvalidBandwidth{..}) => (),
+                other => panic!("expected an invalid bandwidth error, got {:?}", other)
+            }
+            match sliced_wasserstein_gram(&diagrams, 50, sigma) {
+                Err(DistanceError::InvalidBandwidth{..}) => (),
+                other => panic!("expected an invalid bandwidth error, got {:?}", other)
+            }
+        }
+        match sliced_wasserstein_gram(&diagrams, 0, 1.) {
+            Err(DistanceError::NoDirections{}) => (),
+            other => panic!("expected a no directions error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_sinkhorn_wasserstein() {
+        let first = [(0., 4.), (1., 2.)];
+        let second = [(0., 4.5)];
+        // exact 1-Wasserstein: (0, 4) matches (0, 4.5) for 0.5, (1, 2) goes to the diagonal for 0.5
+        let distance = sinkhorn_wasserstein(&first, &second, 1., 0.01, 1e-6).unwrap();
+        assert!((distance - 1.).abs() < 0.05);
+
+        let distance = sinkhorn_wasserstein(&first, &first, 2., 0.001, 1e-6).unwrap();
+        assert!(distance < 0.1);
+
+        let with_infinite = [(0., 4.), (1., 2.), (-1., f64::INFINITY)];
+        let distance = sinkhorn_wasserstein(&with_infinite, &second, 1., 0.01, 1e-6).unwrap();
+        assert!((distance - 1.).abs() < 0.05);
+
+        assert!(sinkhorn_wasserstein(&[(0., f64::NAN)], &second, 1., 0.01, 1e-6).is_err());
+        assert_eq!(sinkhorn_wasserstein(&[], &[], 1., 0.01, 1e-6).unwrap(), 0.);
+
+        for &epsilon in [0., -0.01, f64::NAN].iter() {
+            match sinkhorn_wasserstein(&first, &second, 1., epsilon, 1e-6) {
+                Err(DistanceError::InvalidEpsilon{..}) => (),
+                other => panic!("expected an invalid epsilon error, got {:?}", other)
+            }
+        }
+        match sinkhorn_wasserstein(&first, &second, 0.5, 0.01, 1e-6) {
+            Err(DistanceError::InvalidExponent{p}) => assert_eq!(p, 0.5),
+            other => panic!("expected an invalid exponent error, got {:?}", other)
+        }
+        for &precision in [0., -1e-6, f64::NAN].iter() {
+            match sinkhorn_wasserstein(&first, &second, 1., 0.01, precision) {
+                Err(DistanceError::InvalidPrecision{..}) => (),
+                other => panic!("expected an invalid precision error, got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_log_sum_exp() {
+        let values = [-1000., 3., f64::NEG_INFINITY, 1., 2.];
+        let expected = 3. + ((-1003f64).exp() + 1. + (-2f64).exp() + (-1f64).exp()).ln();
+        assert!((log_sum_exp(values.iter().copied()) - expected).abs() < 1e-12);
+        // large values don't overflow, whichever order they come in
+        assert!((log_sum_exp([1000., 1000.].iter().copied()) - (1000. + 2f64.ln())).abs() < 1e-9);
+        assert_eq!(log_sum_exp([f64::NEG_INFINITY].iter().copied()), f64::NEG_INFINITY);
+        assert_eq!(log_sum_exp(std::iter::empty()), f64::NEG_INFINITY);
+    }
+}
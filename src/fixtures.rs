@@ -0,0 +1,31 @@
+//! Graphs shared by the tests of several modules.
+use petgraph::graph::UnGraph;
+
+use crate::LabeledPoint;
+
+/// A path through the given points, in order, with edges of unit weight.
+pub fn path_graph_of<T>(points: impl IntoIterator<Item = LabeledPoint<T>>) -> UnGraph<LabeledPoint<T>, f64> {
+    let mut graph = UnGraph::new_undirected();
+    let nodes: Vec<_> = points.into_iter().map(|point| graph.add_node(point)).collect();
+    for pair in nodes.windows(2) {
+        graph.add_edge(pair[0], pair[1], 1.);
+    }
+    graph
+}
+
+/// A path with one node per value, with edges of unit weight. The `i`th node has id `i` and sits at
+/// `vec![i]`, so the node indices, ids and positions all follow the path.
+pub fn path_graph(values: &[f64]) -> UnGraph<LabeledPoint<Vec<f64>>, f64> {
+    path_graph_of(values.iter().enumerate().map(|(i, &value)| LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+}
+
+/// Like [path_graph](fn.path_graph.html), with the `i`th edge (from node `i` to node `i + 1`)
+/// weighted by `weights[i]`.
+pub fn weighted_path_graph(values: &[f64], weights: &[f64]) -> UnGraph<LabeledPoint<Vec<f64>>, f64> {
+    assert_eq!(weights.len() + 1, values.len());
+    let mut graph = path_graph(values);
+    for (weight, &w) in graph.edge_weights_mut().zip(weights.iter()) {
+        *weight = w;
+    }
+    graph
+}
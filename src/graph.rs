@@ -1,16 +1,44 @@
 //! Algorithms for constructing graphs from sets of points
 use std::hash::{Hash, Hasher};
 use petgraph::graph::UnGraph;
-use kdtree;
-use kdtree::distance::squared_euclidean;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::f64;
 use rand::prelude::*;
 use thiserror::Error;
 
 use super::{PreMetric, LabeledPoint};
+use super::metric::{Metric, Euclidean};
+
+mod index;
+use index::SpatialIndex;
+mod delaunay;
+pub use delaunay::{build_delaunay_2d, build_delaunay_3d};
+mod matrix;
+pub use matrix::{NeighborStrategy, from_distance_matrix, from_sparse_distances};
+mod grid;
+pub use grid::{GridConnectivity, VoxelConnectivity, build_grid_2d, build_grid_3d};
+mod mesh;
+pub use mesh::from_triangle_mesh;
+mod connectivity;
+pub use connectivity::{ConnectivityReport, component_labels, stitch_components, add_spanning_tree};
+mod kernel;
+pub use kernel::self_tuning_weights;
+mod prune;
+pub use prune::prune_long_edges;
+mod skeleton;
+pub use skeleton::build_beta_skeleton;
+mod snn;
+pub use snn::{SimilarityWeighting, build_snn};
+mod incremental;
+pub use incremental::IncrementalKnn;
+
+#[cfg(feature = "hnsw")]
+mod hnsw;
+#[cfg(feature = "hnsw")]
+pub use hnsw::{build_knn_hnsw, build_knn_hnsw_with};
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum GraphError {
     #[error("Could not construct graph due to a KdTree error")]
     GraphConstructionFailure (#[from] kdtree::ErrorKind),
@@ -19,6 +47,12 @@ pub enum GraphError {
     // TODO: If NaNs are bad, why does LabeledPoint allow them?
     NanInPoints {},
 
+    #[error("The distance or edge weight between points {first:?} and {second:?} is NaN")]
+    NanWeight {
+        first: usize,
+        second: usize
+    },
+
     #[error("Requested {k:?} neighbors but only {num_points:?} exist")]
     KTooLarge {
         k: usize,
@@ -28,7 +62,73 @@ pub enum GraphError {
     // TODO: This should have the existing graph in it
     // (only not doing it right now because the graph is generic in T
     #[error("Graph construction failed to converge")]
-    ConvergenceFailure {}
+    ConvergenceFailure {},
+
+    #[error("Expected {expected:?}-dimensional points but found one with {found:?} dimensions")]
+    WrongDimension {
+        expected: usize,
+        found: usize
+    },
+
+    #[error("Point {index:?} has a coordinate too close to 0 or too large to triangulate exactly")]
+    TriangulationFailure {
+        index: usize
+    },
+
+    #[error("Expected a square matrix of distances between {points:?} points, found a {rows:?}x{columns:?} one")]
+    MatrixShape {
+        points: usize,
+        rows: usize,
+        columns: usize
+    },
+
+    #[error("Point index {index:?} is out of bounds for {num_points:?} points")]
+    IndexOutOfBounds {
+        index: usize,
+        num_points: usize
+    },
+
+    #[error("The grid has {found:?} entries at {index:?}, but {expected:?} were expected")]
+    RaggedGrid {
+        index: Vec<usize>,
+        expected: usize,
+        found: usize
+    },
+
+    #[error("Got {values:?} values for {vertices:?} vertices")]
+    ValueCount {
+        vertices: usize,
+        values: usize
+    },
+
+    #[error("The percentile {percentile:?} is not between 0 and 100")]
+    InvalidPercentile {
+        percentile: f64
+    },
+
+    #[error("Beta must be non-negative, got {beta:?}")]
+    InvalidBeta {
+        beta: f64
+    },
+
+    #[error("Point {index:?} has a kernel bandwidth of 0, since its scale neighbor is at the same location")]
+    ZeroBandwidth {
+        index: usize
+    },
+
+    #[error("The bandwidth scale neighbor must be at least the first nearest neighbor, got 0")]
+    ZeroScale {},
+
+    #[error("Point {index:?} is at the same location as point {original:?}")]
+    DuplicatePoint {
+        index: usize,
+        original: usize
+    },
+
+    #[error("Point {index:?} has a coordinate that is infinite or NaN")]
+    NonFiniteCoordinate {
+        index: usize
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -68,7 +168,7 @@ impl PartialEq for NeighborData {
 impl Eq for NeighborData {}
 
 
-fn sample_neighbors(neighbors: &mut Vec<NeighborData>, sample_rate: f64) -> HashSet<NeighborData> {
+fn sample_neighbors(neighbors: &mut [NeighborData], sample_rate: f64) -> HashSet<NeighborData> {
     let mut rng = rand::thread_rng();
     let mut targets = HashSet::with_capacity(neighbors.len());
     for neighbor in neighbors.iter_mut() {
@@ -87,7 +187,7 @@ fn sample_neighbors(neighbors: &mut Vec<NeighborData>, sample_rate: f64) -> Hash
     targets
 }
 
-fn add_reversed_targets(targets: &mut Vec<HashSet<NeighborData>>) {
+fn add_reversed_targets(targets: &mut [HashSet<NeighborData>]) {
     // TODO: i don't love this
     let acc: Vec<(usize, NeighborData)> = targets.iter().enumerate()
         .flat_map(|(i, neighbors)| {
@@ -156,6 +256,16 @@ fn rejection_sample(count: usize, range: usize, rng: &mut ThreadRng) -> Result<V
 /// Note that this _does not_ require the similarity function to be a distance metric.
 pub fn build_knn_approximate<T: PreMetric + Clone>(points: &[LabeledPoint<T>], k: usize, sample_rate: f64, precision: f64) 
     -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
+    build_knn_approximate_with(points, k, sample_rate, precision, &PointPreMetric, KnnOptions::default())
+}
+
+/// Constructs an approximate `k`-NN graph like
+/// [build_knn_approximate](fn.build_knn_approximate.html), with distances measured by `metric`
+/// instead of the points' own [PreMetric](../trait.PreMetric.html), and the neighbor lists turned
+/// into edges according to `options`.
+pub fn build_knn_approximate_with<T: Clone, M: Metric<T>>(points: &[LabeledPoint<T>], k: usize, sample_rate: f64,
+                                                          precision: f64, metric: &M, options: KnnOptions)
+    -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
     // https://www.cs.princeton.edu/cass/papers/www11.pdf
     let nans_present = points.iter().any(|p| p.value.is_nan());
     if nans_present {
@@ -163,7 +273,7 @@ pub fn build_knn_approximate<T: PreMetric + Clone>(points: &[LabeledPoint<T>], k
     }
 
     let mut rng = rand::thread_rng();
-    let mut approximate_neighbors = Vec::with_capacity(points.len());
+    let mut approximate_neighbors: Vec<Vec<NeighborData>> = Vec::with_capacity(points.len());
     for _ in 0..(points.len()) {
         let points = rejection_sample(k, points.len(), &mut rng)?;
         approximate_neighbors.push(points.iter()
@@ -190,7 +300,7 @@ pub fn build_knn_approximate<T: PreMetric + Clone>(points: &[LabeledPoint<T>], k
                 if let NeighborState::New = target.state {
                     for (j, other) in targ.iter().enumerate() {
                         if j < i || !other.state.is_new() {
-                            let distance = points[target.idx].point.predistance(&points[other.idx].point);
+                            let distance = metric.distance(&points[target.idx].point, &points[other.idx].point);
                             let changed = update_neighbors(&mut approximate_neighbors[target.idx], target.idx, other.idx, distance, k);
                             if changed {counter += 1};
                             let changed = update_neighbors(&mut approximate_neighbors[other.idx], other.idx, target.idx, distance, k);
@@ -209,62 +319,251 @@ pub fn build_knn_approximate<T: PreMetric + Clone>(points: &[LabeledPoint<T>], k
         }
     }
 
-    Ok(graph_from_neighbordata(points, approximate_neighbors))
+    Ok(graph_from_neighbordata(points, approximate_neighbors, metric, options))
+}
+
+fn graph_from_neighbordata<T: Clone, M: Metric<T>>(points: &[LabeledPoint<T>], neighbors: Vec<Vec<NeighborData>>,
+                                                    metric: &M, options: KnnOptions)
+    -> UnGraph<LabeledPoint<T>, f64> {
+    let neighbors: Vec<Vec<(f64, usize)>> = neighbors.iter()
+        .map(|data| data.iter().map(|neighbor| (neighbor.distance, neighbor.idx)).collect())
+        .collect();
+    symmetrized_graph(points, &neighbors, options.symmetrization, |i, j| metric.distance(&points[i].point, &points[j].point),
+                      |distance| options.weighting.weight(distance))
+}
+
+/// How the directed neighbor lists of a `k`-NN graph are turned into an undirected graph.
+///
+/// Being among the `k` nearest neighbors isn't symmetric, so this decides which points are
+/// connected, and with it which points become extrema.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetrization {
+    /// Connect two points when either is among the other's nearest neighbors.
+    Union,
+
+    /// Connect two points only when each is among the other's nearest neighbors (a mutual `k`-NN
+    /// graph). This drops the edges that isolated points reach out with, so it can leave points
+    /// without any neighbors.
+    Intersection,
+
+    /// Connect two points when either is among the other's nearest neighbors, weighting the edge
+    /// by the mean of the distances measured from each of them. This only differs from the union
+    /// for distances that aren't symmetric.
+    Mean
 }
 
-fn graph_from_neighbordata<T: PreMetric + Clone>(points: &[LabeledPoint<T>], neighbors: Vec<Vec<NeighborData>>)
+// Builds the graph of the directed neighbor lists of the points, as (distance, index) pairs.
+// distance(i, j) is only used for the distances from i to j that aren't in the lists
+fn symmetrized_graph<T: Clone, D: Fn(usize, usize) -> f64, W: Fn(f64) -> f64>(points: &[LabeledPoint<T>],
+                                                                                neighbors: &[Vec<(f64, usize)>],
+                                                                                symmetrization: Symmetrization,
+                                                                                distance: D, weight: W)
     -> UnGraph<LabeledPoint<T>, f64> {
     let mut neighbor_graph = UnGraph::new_undirected();
-    let mut node_lookup = Vec::with_capacity(points.len());
-    for point in points {
-        let node = neighbor_graph.add_node(point.clone());
-        node_lookup.push(node);
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| neighbor_graph.add_node(point.clone()))
+        .collect();
+    let directed: HashMap<(usize, usize), f64> = neighbors.iter().enumerate()
+        .flat_map(|(i, data)| data.iter().map(move |&(distance, j)| ((i, j), distance)))
+        .collect();
+    for (i, data) in neighbors.iter().enumerate() {
+        for &(forward, j) in data {
+            let backward = directed.get(&(j, i));
+            let distance = match symmetrization {
+                Symmetrization::Union => forward,
+                Symmetrization::Intersection if backward.is_some() => forward,
+                Symmetrization::Intersection => continue,
+                Symmetrization::Mean => (forward + backward.cloned().unwrap_or_else(|| distance(j, i))) / 2.
+            };
+            neighbor_graph.update_edge(node_lookup[i], node_lookup[j], weight(distance));
+        }
     }
+    neighbor_graph
+}
 
-    for (i, data) in neighbors.iter().enumerate() {
-        for neighbor in data{
-            neighbor_graph.update_edge(node_lookup[i], node_lookup[neighbor.idx], neighbor.distance);
+/// Determines the weight given to an edge between two points.
+///
+/// The Morse complexes follow each point's steepest neighbor, with the steepness along an edge
+/// being the value of the neighbor divided by the weight of the edge. So the weighting doesn't just
+/// rescale the results: distances favor nearby neighbors, while similarities (the inverse distance
+/// and the Gaussian kernel) favor distant ones, and unit weights ignore the geometry entirely.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeWeighting {
+    /// The distance between the points.
+    Distance,
+
+    /// The squared distance between the points, which is how [build_knn](fn.build_knn.html)
+    /// weights its edges.
+    SquaredDistance,
+
+    /// Every edge has weight 1, so only the structure of the graph matters.
+    Unit,
+
+    /// The reciprocal of the distance between the points, which is infinite for coincident points.
+    InverseDistance,
+
+    /// A Gaussian kernel `exp(-d² / 2σ²)` of the distance `d` between the points, with the given
+    /// bandwidth `σ`.
+    Gaussian(f64)
+}
+
+// Lets the builders that take a Metric fall back on the points' own PreMetric
+struct PointPreMetric;
+
+impl<T: PreMetric> Metric<T> for PointPreMetric {
+    fn distance(&self, a: &T, b: &T) -> f64 {
+        a.predistance(b)
+    }
+}
+
+impl EdgeWeighting {
+    fn weight(self, distance: f64) -> f64 {
+        match self {
+            EdgeWeighting::Distance => distance,
+            EdgeWeighting::SquaredDistance => distance * distance,
+            EdgeWeighting::Unit => 1.,
+            EdgeWeighting::InverseDistance => 1. / distance,
+            EdgeWeighting::Gaussian(sigma) => (-distance * distance / (2. * sigma * sigma)).exp()
         }
     }
+}
 
-    neighbor_graph
+/// How the neighbor lists found by the `build_knn_*_with` builders are turned into a graph.
+///
+/// The default takes the [union](enum.Symmetrization.html#variant.Union) of the neighbor lists
+/// and weights edges by distance, as [build_knn_approximate](fn.build_knn_approximate.html) and
+/// [build_knn_hnsw](fn.build_knn_hnsw.html) do. Start from it and change only what's needed:
+///
+/// ```
+/// use talus::graph::{KnnOptions, Symmetrization, EdgeWeighting};
+///
+/// let options = KnnOptions::default().symmetrization(Symmetrization::Intersection).weighting(EdgeWeighting::Unit);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KnnOptions {
+    symmetrization: Symmetrization,
+    weighting: EdgeWeighting
 }
 
-/// Constructs an exact `k`-NN graph from a set of `points`.
+impl KnnOptions {
+    /// Symmetrizes the neighbor lists according to `symmetrization`.
+    pub fn symmetrization(self, symmetrization: Symmetrization) -> KnnOptions {
+        KnnOptions{symmetrization, ..self}
+    }
+
+    /// Weights edges according to `weighting`.
+    pub fn weighting(self, weighting: EdgeWeighting) -> KnnOptions {
+        KnnOptions{weighting, ..self}
+    }
+}
+
+impl Default for KnnOptions {
+    fn default() -> KnnOptions {
+        KnnOptions{symmetrization: Symmetrization::Union, weighting: EdgeWeighting::Distance}
+    }
+}
+
+/// The spatial index used to find neighbors when constructing exact neighbor graphs.
 ///
-/// This implementation uses a KD-tree for efficient nearest neighbor querying. This means that it
-/// only works for vectors of real numbers, and can only use the Euclidean metric.
-pub fn build_knn(points: &[LabeledPoint<Vec<f64>>], k: usize) -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+/// All of them give identical graphs (up to ties in distance); they only differ in speed. The
+/// trees only work with some metrics (see [Metric](../metric/trait.Metric.html)); when the chosen
+/// one can't be used, every pair of points is compared instead.
+///
+/// With the `parallel` feature, the neighbors of different points are found on different threads,
+/// whichever index is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborIndex {
+    /// A KD-tree, which is the fastest choice for low dimensional points (up to about 10
+    /// dimensions). Only works with Minkowski distances.
+    KdTree,
+
+    /// A ball tree, which degrades more gracefully than a KD-tree as the dimension grows, so it
+    /// is the better choice for high dimensional points. Works with any true metric, including
+    /// periodic ones.
+    BallTree,
+
+    /// Compares every pair of points, taking `O(n²)` distance computations.
+    BruteForce
+}
+
+/// Constructs an `epsilon`-neighborhood graph from a set of `points`.
+///
+/// Every pair of points within `epsilon` of each other is connected, with the edge weighted
+/// according to `weighting`. Unlike a `k`-NN graph, every point gets the same sized neighborhood,
+/// which makes this the better choice for samples of roughly uniform density.
+///
+/// Like [build_knn](fn.build_knn.html), this uses a KD-tree to find the neighborhoods with the
+/// Euclidean metric. See [build_epsilon_with](fn.build_epsilon_with.html) for other choices.
+pub fn build_epsilon(points: &[LabeledPoint<Vec<f64>>], epsilon: f64, weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    build_epsilon_with(points, epsilon, weighting, &Euclidean, NeighborIndex::KdTree)
+}
+
+/// Constructs an `epsilon`-neighborhood graph like [build_epsilon](fn.build_epsilon.html), with
+/// distances measured by `metric` and neighborhoods found with the given kind of spatial index.
+pub fn build_epsilon_with<M: Metric<[f64]>>(points: &[LabeledPoint<Vec<f64>>], epsilon: f64, weighting: EdgeWeighting,
+                                            metric: &M, index: NeighborIndex)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
     let nans_present = points.iter().any(|p| p.value.is_nan());
     if nans_present {
         return Err(GraphError::NanInPoints{})
     }
-    let dim = points[0].point.len();
-    let mut tree = kdtree::KdTree::new(dim);
-    for (i, point) in points.iter().enumerate() {
-        tree.add(&point.point, i)?;
-    }
+    let index = SpatialIndex::new(points, index, metric)?;
     let mut neighbor_graph = UnGraph::new_undirected();
-    let mut node_lookup = Vec::with_capacity(points.len());
-    for point in points {
-        let node = neighbor_graph.add_node(point.to_owned());
-        node_lookup.push(node);
-    }
-    for (i, point) in points.iter().enumerate() {
-        tree.iter_nearest(&point.point, &squared_euclidean)?
-            .skip(1)  // always returns itself as the first one
-            .take(k)
-            .for_each(|(dist, &j)| {
-                neighbor_graph.update_edge(node_lookup[i], node_lookup[j], dist);
-            })
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| neighbor_graph.add_node(point.clone()))
+        .collect();
+    for (i, neighbors) in index.all_within(epsilon)?.into_iter().enumerate() {
+        for (distance, j) in neighbors {
+            if i < j {
+                neighbor_graph.add_edge(node_lookup[i], node_lookup[j], weighting.weight(distance));
+            }
+        }
     }
     Ok(neighbor_graph)
 }
 
+/// Constructs an exact `k`-NN graph from a set of `points`.
+///
+/// This implementation uses a KD-tree for efficient nearest neighbor querying, so it takes
+/// `O(n log n)` time for low dimensional points. This means that it only works for vectors of
+/// real numbers, and uses the Euclidean metric. Edges are weighted by the squared distance
+/// between their endpoints, as with [SquaredDistance](enum.EdgeWeighting.html#variant.SquaredDistance).
+///
+/// For other metrics, or a ball tree for high dimensional points, see
+/// [build_knn_with](fn.build_knn_with.html).
+pub fn build_knn(points: &[LabeledPoint<Vec<f64>>], k: usize) -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    knn_graph(points, k, &Euclidean, NeighborIndex::KdTree, Symmetrization::Union,
+              |distance| EdgeWeighting::SquaredDistance.weight(distance))
+}
+
+/// Constructs an exact `k`-NN graph like [build_knn](fn.build_knn.html), with distances measured
+/// by `metric`, neighbors found with the given kind of spatial index, and the neighbor lists
+/// turned into edges according to `options`.
+pub fn build_knn_with<M: Metric<[f64]>>(points: &[LabeledPoint<Vec<f64>>], k: usize, metric: &M, index: NeighborIndex,
+                                        options: KnnOptions)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    knn_graph(points, k, metric, index, options.symmetrization, |distance| options.weighting.weight(distance))
+}
+
+fn knn_graph<M: Metric<[f64]>, W: Fn(f64) -> f64>(points: &[LabeledPoint<Vec<f64>>], k: usize, metric: &M,
+                                                 index: NeighborIndex, symmetrization: Symmetrization, weight: W)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    let nans_present = points.iter().any(|p| p.value.is_nan());
+    if nans_present {
+        return Err(GraphError::NanInPoints{})
+    }
+    let index = SpatialIndex::new(points, index, metric)?;
+    let neighbors = index.all_nearest(k)?;
+    Ok(symmetrized_graph(points, &neighbors, symmetrization, |i, j| metric.distance(&points[i].point, &points[j].point),
+                         weight))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::{HashMap, HashSet};
+    use petgraph::graph::NodeIndex;
+    use crate::metric::Cosine;
 
     #[test]
     fn test_knn() {
@@ -301,6 +600,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_neighbor_indices_agree() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let points: Vec<LabeledPoint<Vec<f64>>> = (0..300)
+            .map(|i| LabeledPoint{id: i, value: 0., point: (0..12).map(|_| rng.gen_range(0., 1.)).collect()})
+            .collect();
+        let kd = build_knn_with(&points, 4, &Euclidean, NeighborIndex::KdTree, KnnOptions::default()).unwrap();
+        let ball = build_knn_with(&points, 4, &Euclidean, NeighborIndex::BallTree, KnnOptions::default()).unwrap();
+        assert_eq!(kd.edge_count(), ball.edge_count());
+        assert!(kd.raw_edges().iter().all(|e| ball.find_edge(e.source(), e.target()).is_some()));
+
+        let kd = build_epsilon_with(&points, 0.9, EdgeWeighting::Distance, &Euclidean, NeighborIndex::KdTree).unwrap();
+        let ball = build_epsilon_with(&points, 0.9, EdgeWeighting::Distance, &Euclidean, NeighborIndex::BallTree).unwrap();
+        assert_eq!(kd.edge_count(), ball.edge_count());
+    }
+
+    #[test]
+    fn test_knn_cosine() {
+        // by direction, 0 and 1 are neighbors and so are 2 and 3, even though 0 is much closer
+        // to 2 in Euclidean terms
+        let points = [
+            LabeledPoint{id: 0, value: 0., point: vec![1., 0.1]},
+            LabeledPoint{id: 1, value: 0., point: vec![10., 1.1]},
+            LabeledPoint{id: 2, value: 0., point: vec![0.1, 1.]},
+            LabeledPoint{id: 3, value: 0., point: vec![1., 9.]}
+        ];
+        let g = build_knn_with(&points, 1, &Cosine, NeighborIndex::KdTree, KnnOptions::default()).unwrap();
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).is_some());
+        assert!(g.find_edge(NodeIndex::new(2), NodeIndex::new(3)).is_some());
+
+        // with every other point as a neighbor, the approximate search always finds all of them,
+        // and the distances it measures are cosine distances
+        let g = build_knn_approximate_with(&points, 3, 1., 0., &Cosine, KnnOptions::default()).unwrap();
+        assert!(g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).is_some());
+        assert!(g.find_edge(NodeIndex::new(2), NodeIndex::new(3)).is_some());
+        for edge in g.raw_edges().iter().filter(|edge| edge.weight.is_finite()) {
+            let (a, b) = (&points[edge.source().index()].point, &points[edge.target().index()].point);
+            assert_eq!(edge.weight, Cosine.distance(a, b));
+        }
+    }
+
     #[test]
     fn test_knn_approximate() {
         // FIXME: this stochastically fails. Which is expected! So fix the assertions to account
@@ -337,4 +678,85 @@ mod tests {
 
         }
     }
+
+    #[test]
+    fn test_epsilon() {
+        let points = [
+            LabeledPoint{id: 0, value: 6., point: vec![0., 0.]},
+            LabeledPoint{id: 1, value: 2., point: vec![1., 0.]},
+            LabeledPoint{id: 2, value: 3., point: vec![1.5, 0.]},
+            LabeledPoint{id: 3, value: 5., point: vec![0., 0.7]},
+            LabeledPoint{id: 4, value: 4., point: vec![1., 1.]},
+            LabeledPoint{id: 5, value: -5., point: vec![0., 2.]},
+            LabeledPoint{id: 6, value: 0., point: vec![2., 3.]}
+        ];
+        let mut expected_adjacencies = HashMap::with_capacity(7);
+        expected_adjacencies.insert(0, vec![1, 3]);
+        expected_adjacencies.insert(1, vec![0, 2, 4]);
+        expected_adjacencies.insert(2, vec![1]);
+        expected_adjacencies.insert(3, vec![0]);
+        expected_adjacencies.insert(4, vec![1]);
+        expected_adjacencies.insert(5, vec![]);
+        expected_adjacencies.insert(6, vec![]);
+
+        let g = build_epsilon(&points, 1., EdgeWeighting::Distance).unwrap();
+        for node in g.node_indices() {
+            let id = g.node_weight(node).unwrap().id;
+            let adj_ids: HashSet<i64> = g.neighbors(node)
+                .map(|n| g.node_weight(n).unwrap().id)
+                .collect();
+            let expected = expected_adjacencies.get(&id).unwrap();
+            assert_eq!(expected.len(), adj_ids.len());
+            for exp in expected {
+                assert!(adj_ids.contains(exp));
+            }
+        }
+        let edge = g.find_edge(NodeIndex::new(1), NodeIndex::new(2)).unwrap();
+        assert_eq!(g[edge], 0.5);
+
+        let g = build_epsilon(&points, 1., EdgeWeighting::Unit).unwrap();
+        assert!(g.raw_edges().iter().all(|e| e.weight == 1.));
+    }
+
+    #[test]
+    fn test_symmetrization() {
+        let points: Vec<_> = [0., 1., 3., 7.].iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect();
+        // going right costs one more than going left
+        let lopsided = crate::metric::FnMetric(|a: &[f64], b: &[f64]| {
+            (a[0] - b[0]).abs() + if b[0] > a[0] { 1. } else { 0. }
+        });
+        let build = |symmetrization| {
+            build_knn_with(&points, 1, &lopsided, NeighborIndex::BruteForce, KnnOptions::default().symmetrization(symmetrization)).unwrap()
+        };
+        assert_eq!(build(Symmetrization::Union).edge_count(), 3);
+
+        let mutual = build(Symmetrization::Intersection);
+        assert_eq!(mutual.edge_count(), 1);
+        assert!(mutual.find_edge(NodeIndex::new(0), NodeIndex::new(1)).is_some());
+
+        let mean = build(Symmetrization::Mean);
+        assert_eq!(mean.edge_count(), 3);
+        assert_eq!(mean[mean.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap()], 1.5);
+        assert_eq!(mean[mean.find_edge(NodeIndex::new(2), NodeIndex::new(3)).unwrap()], 4.5);
+    }
+
+    #[test]
+    fn test_edge_weighting() {
+        assert_eq!(EdgeWeighting::Distance.weight(2.), 2.);
+        assert_eq!(EdgeWeighting::SquaredDistance.weight(2.), 4.);
+        assert_eq!(EdgeWeighting::Unit.weight(2.), 1.);
+        assert_eq!(EdgeWeighting::InverseDistance.weight(2.), 0.5);
+        assert_eq!(EdgeWeighting::InverseDistance.weight(0.), f64::INFINITY);
+        assert_eq!(EdgeWeighting::Gaussian(1.).weight(0.), 1.);
+        assert!((EdgeWeighting::Gaussian(2.).weight(2.) - (-0.5f64).exp()).abs() < 1e-12);
+
+        let points: Vec<_> = (0..4)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![i as f64 * i as f64]})
+            .collect();
+        let g = build_knn_with(&points, 1, &Euclidean, NeighborIndex::KdTree, KnnOptions::default().weighting(EdgeWeighting::InverseDistance)).unwrap();
+        let edge = g.find_edge(NodeIndex::new(2), NodeIndex::new(3)).unwrap();
+        assert!((g[edge] - 0.2).abs() < 1e-12);
+    }
 }
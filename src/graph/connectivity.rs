@@ -0,0 +1,220 @@
+//! Detecting and repairing disconnected neighbor graphs.
+//!
+//! Neighbor graphs often come out disconnected, e.g. when the points form well separated
+//! clusters, and the Morse complex of a disconnected graph is computed separately on each
+//! component, so each one has its own extrema of infinite persistence. Stitching the components
+//! together first gives a single complex over all the points.
+use std::cmp::Ordering;
+use std::f64;
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::EdgeWeighting;
+use crate::LabeledPoint;
+use crate::metric::Metric;
+use crate::util::PointedUnionFind;
+
+/// What [stitch_components](fn.stitch_components.html) or
+/// [add_spanning_tree](fn.add_spanning_tree.html) did to a graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectivityReport {
+    /// The number of connected components the graph had beforehand.
+    pub num_components: usize,
+
+    /// The edges that were added, as `(source, target, distance)`.
+    pub added_edges: Vec<(NodeIndex, NodeIndex, f64)>
+}
+
+/// Labels each node of `graph` with the connected component it belongs to.
+///
+/// Components are numbered from 0 in order of their lowest node index, so the number of components
+/// is one more than the largest label.
+pub fn component_labels<N, E>(graph: &UnGraph<N, E>) -> Vec<usize> {
+    let mut sets = PointedUnionFind::new(graph.node_count());
+    for edge in graph.raw_edges() {
+        sets.union(edge.source().index(), edge.target().index());
+    }
+    let mut labels = vec![usize::MAX; graph.node_count()];
+    let mut num_components = 0;
+    for i in 0..graph.node_count() {
+        let root = sets.find_mut(i);
+        if labels[root] == usize::MAX {
+            labels[root] = num_components;
+            num_components += 1;
+        }
+        labels[i] = labels[root];
+    }
+    labels
+}
+
+/// Connects the components of `graph` by adding the shortest edges between them.
+///
+/// The added edges form a minimum spanning tree of the components, with the distance between two
+/// components being the distance between their closest pair of points under `metric`, so the
+/// components are joined as cheaply as possible. New edges are weighted according to `weighting`,
+/// which should match how the graph was built, e.g.
+/// [SquaredDistance](enum.EdgeWeighting.html#variant.SquaredDistance) for
+/// [build_knn](fn.build_knn.html).
+///
+/// Every pair of points may be compared, so this takes `O(n^2)` time.
+pub fn stitch_components<T, M: Metric<T>>(graph: &mut UnGraph<LabeledPoint<T>, f64>, metric: &M, weighting: EdgeWeighting)
+    -> ConnectivityReport {
+    let labels = component_labels(graph);
+    let num_components = labels.iter().max().map(|&l| l + 1).unwrap_or(0);
+    let added_edges = spanning_edges(graph, &labels, num_components, metric);
+    for &(source, target, distance) in &added_edges {
+        graph.add_edge(source, target, weighting.weight(distance));
+    }
+    ConnectivityReport{num_components, added_edges}
+}
+
+/// Adds the edges of the minimum spanning tree of the points of `graph` under `metric` that aren't
+/// already in it.
+///
+/// Taking the union of a sparse `k`-NN graph with the minimum spanning tree guarantees that the
+/// graph is connected, without raising `k` (or `epsilon`) until the sparsest regions are covered.
+/// Since the tree only uses the shortest edges possible, it adds little besides the bridges between
+/// clusters. New edges are weighted according to `weighting`, which should match how the graph
+/// was built, e.g. [SquaredDistance](enum.EdgeWeighting.html#variant.SquaredDistance) for
+/// [build_knn](fn.build_knn.html).
+///
+/// This compares every pair of points, so it takes `O(n^2)` time.
+///
+/// ```
+/// use talus::LabeledPoint;
+/// use talus::graph::{build_knn, add_spanning_tree, component_labels, EdgeWeighting};
+/// use talus::metric::Euclidean;
+///
+/// let points: Vec<_> = [0., 1., 5., 6.].iter().enumerate()
+///     .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+///     .collect();
+/// let mut graph = build_knn(&points, 1).unwrap();
+/// let report = add_spanning_tree(&mut graph, &Euclidean, EdgeWeighting::SquaredDistance);
+/// assert_eq!(report.num_components, 2);
+/// assert_eq!(report.added_edges.len(), 1);
+/// // the bridge between 1 and 5 has length 4, weighted by its square like the kNN edges
+/// assert!(graph.raw_edges().iter().any(|e| e.weight == 16.));
+/// assert!(component_labels(&graph).iter().all(|&l| l == 0));
+/// ```
+pub fn add_spanning_tree<T, M: Metric<T>>(graph: &mut UnGraph<LabeledPoint<T>, f64>, metric: &M, weighting: EdgeWeighting)
+    -> ConnectivityReport {
+    let num_components = component_labels(graph).iter().max().map(|&l| l + 1).unwrap_or(0);
+    let labels: Vec<usize> = (0..graph.node_count()).collect();
+    let mut added_edges = spanning_edges(graph, &labels, graph.node_count(), metric);
+    added_edges.retain(|&(source, target, _)| graph.find_edge(source, target).is_none());
+    for &(source, target, distance) in &added_edges {
+        graph.add_edge(source, target, weighting.weight(distance));
+    }
+    ConnectivityReport{num_components, added_edges}
+}
+
+// The edges of a minimum spanning tree of the groups of nodes with the given labels, where the
+// distance between two groups is the distance between their closest pair of points
+fn spanning_edges<T, M: Metric<T>>(graph: &UnGraph<LabeledPoint<T>, f64>, labels: &[usize], num_components: usize, metric: &M)
+    -> Vec<(NodeIndex, NodeIndex, f64)> {
+    let mut members = vec![vec![]; num_components];
+    for (i, &label) in labels.iter().enumerate() {
+        members[label].push(i);
+    }
+
+    // Prim's algorithm, adding a whole component at a time: closest[j] is the nearest point to j
+    // among the components joined so far
+    let mut joined = vec![false; graph.node_count()];
+    let mut closest = vec![(f64::INFINITY, 0); graph.node_count()];
+    let mut edges = Vec::with_capacity(num_components.saturating_sub(1));
+    let mut next = if num_components > 0 { Some(0) } else { None };
+    while let Some(j) = next {
+        for &i in &members[labels[j]] {
+            joined[i] = true;
+        }
+        for &i in &members[labels[j]] {
+            for k in (0..graph.node_count()).filter(|&k| !joined[k]) {
+                let distance = metric.distance(&graph[NodeIndex::new(i)].point, &graph[NodeIndex::new(k)].point);
+                if distance < closest[k].0 {
+                    closest[k] = (distance, i);
+                }
+            }
+        }
+        next = (0..graph.node_count())
+            .filter(|&k| !joined[k])
+            .min_by(|&a, &b| closest[a].0.partial_cmp(&closest[b].0).unwrap_or(Ordering::Equal));
+        if let Some(k) = next {
+            let (distance, i) = closest[k];
+            edges.push((NodeIndex::new(i), NodeIndex::new(k), distance));
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::build_knn;
+    use crate::metric::Euclidean;
+
+    #[test]
+    fn test_stitch_components() {
+        // three well separated pairs, the middle one closer to the left one
+        let coords = [0., 1., 5., 6., 20., 21.];
+        let points: Vec<_> = coords.iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect();
+        let mut graph = build_knn(&points, 1).unwrap();
+        assert_eq!(component_labels(&graph), vec![0, 0, 1, 1, 2, 2]);
+
+        let report = stitch_components(&mut graph, &Euclidean, EdgeWeighting::SquaredDistance);
+        assert_eq!(report.num_components, 3);
+        let mut added: Vec<_> = report.added_edges.iter()
+            .map(|&(a, b, d)| (a.index().min(b.index()), a.index().max(b.index()), d))
+            .collect();
+        added.sort_by_key(|&(a, b, _)| (a, b));
+        assert_eq!(added, vec![(1, 2, 4.), (3, 4, 14.)]);
+        assert!(component_labels(&graph).iter().all(|&l| l == 0));
+        // the bridges are weighted on the same scale as the edges build_knn made
+        assert_eq!(graph[graph.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap()], 1.);
+        assert_eq!(graph[graph.find_edge(NodeIndex::new(1), NodeIndex::new(2)).unwrap()], 16.);
+        assert_eq!(graph[graph.find_edge(NodeIndex::new(3), NodeIndex::new(4)).unwrap()], 196.);
+
+        let report = stitch_components(&mut graph, &Euclidean, EdgeWeighting::SquaredDistance);
+        assert_eq!(report.num_components, 1);
+        assert!(report.added_edges.is_empty());
+    }
+
+    #[test]
+    fn test_add_spanning_tree_to_knn() {
+        // two clusters, the kNN graph already holding the short edges of the tree
+        let points: Vec<_> = [0., 1., 5., 6., 9.].iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect();
+        let mut graph = build_knn(&points, 1).unwrap();
+        let report = add_spanning_tree(&mut graph, &Euclidean, EdgeWeighting::SquaredDistance);
+        assert_eq!(report.num_components, 2);
+        assert_eq!(report.added_edges, vec![(NodeIndex::new(1), NodeIndex::new(2), 4.)]);
+        assert!(component_labels(&graph).iter().all(|&l| l == 0));
+        for edge in graph.raw_edges() {
+            let length = graph[edge.source()].point[0] - graph[edge.target()].point[0];
+            assert_eq!(edge.weight, length * length);
+        }
+    }
+
+    #[test]
+    fn test_add_spanning_tree() {
+        let points: Vec<_> = [(0., 0.), (1., 0.), (0., 1.), (5., 5.), (5., 6.), (9., 0.)].iter().enumerate()
+            .map(|(i, &(x, y))| LabeledPoint{id: i as i64, value: x, point: vec![x, y]})
+            .collect();
+        let mut graph = UnGraph::new_undirected();
+        for point in &points {
+            graph.add_node(point.clone());
+        }
+        let report = add_spanning_tree(&mut graph, &Euclidean, EdgeWeighting::Unit);
+        assert_eq!(report.num_components, 6);
+        assert_eq!(graph.edge_count(), 5);
+        assert!(graph.raw_edges().iter().all(|e| e.weight == 1.));
+        let total: f64 = report.added_edges.iter().map(|&(_, _, d)| d).sum();
+        assert!((total - (3. + 41f64.sqrt() + 41f64.sqrt())).abs() < 1e-12);
+
+        // edges that are already there aren't added again
+        let report = add_spanning_tree(&mut graph, &Euclidean, EdgeWeighting::Unit);
+        assert_eq!(report.num_components, 1);
+        assert!(report.added_edges.is_empty());
+    }
+}
@@ -0,0 +1,639 @@
+//! Neighborhood graphs from Delaunay triangulations.
+//!
+//! The edges of a Delaunay triangulation connect each point to the points around it in every
+//! direction, without the arbitrary cutoff of a `k`-NN or `epsilon` graph, which makes them the
+//! canonical neighborhood structure for scalar fields sampled at scattered locations.
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use petgraph::graph::UnGraph;
+use robust::{Coord, Coord3D, orient2d, orient3d, insphere};
+use spade::{DelaunayTriangulation, Point2, Triangulation};
+
+use super::{GraphError, EdgeWeighting};
+use crate::LabeledPoint;
+
+fn euclidean(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt()
+}
+
+fn check_points(points: &[LabeledPoint<Vec<f64>>], dim: usize) -> Result<(), GraphError> {
+    if points.iter().any(|p| p.value.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    if let Some(point) = points.iter().find(|p| p.point.len() != dim) {
+        return Err(GraphError::WrongDimension{expected: dim, found: point.point.len()});
+    }
+    match points.iter().position(|p| p.point.iter().any(|x| !x.is_finite())) {
+        Some(index) => Err(GraphError::NonFiniteCoordinate{index}),
+        None => Ok(())
+    }
+}
+
+// Builds the graph over points from the edges of their triangulation
+fn graph_from_edges(points: &[LabeledPoint<Vec<f64>>], edges: &[(usize, usize)], weighting: EdgeWeighting)
+    -> UnGraph<LabeledPoint<Vec<f64>>, f64> {
+    let mut graph = UnGraph::new_undirected();
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| graph.add_node(point.clone()))
+        .collect();
+    for &(i, j) in edges {
+        graph.update_edge(node_lookup[i], node_lookup[j], weighting.weight(euclidean(&points[i].point, &points[j].point)));
+    }
+    graph
+}
+
+/// Constructs the graph of the edges of the Delaunay triangulation of a set of 2D `points`.
+///
+/// Edges are weighted according to `weighting`, based on the Euclidean distance between their
+/// endpoints. Returns an error if two points are at the same location, since the edge between them
+/// would have length 0 (merge or jitter them first).
+///
+/// The triangulation uses exact geometric predicates, so degenerate inputs such as regular grids
+/// (where many points lie on a common circle) are handled correctly.
+pub fn build_delaunay_2d(points: &[LabeledPoint<Vec<f64>>], weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    check_points(points, 2)?;
+    let edges = triangulate_2d(points.iter().map(|p| (p.point[0], p.point[1])))?;
+    Ok(graph_from_edges(points, &edges, weighting))
+}
+
+// A tetrahedron, with its vertices ordered so that it has positive orientation. neighbors[i] is
+// the tetrahedron across the face opposite vertices[i].
+#[derive(Debug, Clone)]
+struct Tetrahedron {
+    vertices: [usize; 4],
+    neighbors: [Option<usize>; 4],
+    alive: bool
+}
+
+// A Delaunay tetrahedralization built by Bowyer-Watson insertion. Vertices are indices into
+// coordinates, except for `infinite`, a symbolic vertex at infinity that every face of the convex
+// hull is joined to. An infinite tetrahedron counts as positively oriented when its infinite vertex
+// lies beyond its hull face, and its circumsphere is the open half-space beyond that face (plus the
+// inside of the face's circumcircle, within its plane). Unlike a large enclosing tetrahedron, this
+// never cuts off the edges of flat tetrahedra on the hull, however flat they are.
+struct Tetrahedralization {
+    coordinates: Vec<[f64; 3]>,
+    infinite: usize,
+    tetrahedra: Vec<Tetrahedron>,
+    // a finite tetrahedron to start walks from
+    last: usize
+}
+
+impl Tetrahedralization {
+    // Tetrahedralizes coordinates, which must all be distinct. Returns None if they all lie on a
+    // plane, since then there are no tetrahedra to build.
+    fn new(coordinates: Vec<[f64; 3]>) -> Option<Self> {
+        let infinite = coordinates.len();
+        let mut triangulation = Tetrahedralization{coordinates, infinite, tetrahedra: vec![], last: 0};
+        let first = triangulation.first_tetrahedron()?;
+        let mut vertices = first;
+        if triangulation.orient(vertices) < 0. {
+            vertices.swap(0, 1);
+        }
+        triangulation.tetrahedra.push(Tetrahedron{vertices, neighbors: [None; 4], alive: true});
+        for i in 0..4 {
+            // moving a vertex out past the opposite face flips the orientation, so swap two of
+            // the others back
+            let mut outside = vertices;
+            outside[i] = infinite;
+            let others: Vec<usize> = (0..4).filter(|&j| j != i).collect();
+            outside.swap(others[0], others[1]);
+            triangulation.tetrahedra.push(Tetrahedron{vertices: outside, neighbors: [None; 4], alive: true});
+        }
+        let mut open_faces: HashMap<[usize; 3], (usize, usize)> = HashMap::new();
+        for t in 0..triangulation.tetrahedra.len() {
+            for i in 0..4 {
+                let mut face: Vec<usize> = (0..4).filter(|&j| j != i).map(|j| triangulation.tetrahedra[t].vertices[j]).collect();
+                face.sort_unstable();
+                match open_faces.remove(&[face[0], face[1], face[2]]) {
+                    Some((other, other_face)) => {
+                        triangulation.tetrahedra[t].neighbors[i] = Some(other);
+                        triangulation.tetrahedra[other].neighbors[other_face] = Some(t);
+                    },
+                    None => {
+                        open_faces.insert([face[0], face[1], face[2]], (t, i));
+                    }
+                }
+            }
+        }
+
+        for vertex in (0..infinite).filter(|v| !first.contains(v)) {
+            triangulation.insert(vertex);
+        }
+        Some(triangulation)
+    }
+
+    // Four of the points that don't lie on a common plane, if there are any
+    fn first_tetrahedron(&self) -> Option<[usize; 4]> {
+        let n = self.coordinates.len();
+        let (a, b) = (0, 1);
+        if n < 4 {
+            return None;
+        }
+        let c = (2..n).find(|&c| !self.collinear(a, b, c))?;
+        let d = (2..n).find(|&d| self.orient([a, b, c, d]) != 0.)?;
+        Some([a, b, c, d])
+    }
+
+    fn collinear(&self, a: usize, b: usize, c: usize) -> bool {
+        // three points are collinear exactly when all three of their projections onto the
+        // coordinate planes are
+        let [a, b, c] = [self.coordinates[a], self.coordinates[b], self.coordinates[c]];
+        [(0, 1), (1, 2), (0, 2)].iter().all(|&(x, y)| {
+            let project = |p: [f64; 3]| Coord{x: p[x], y: p[y]};
+            orient2d(project(a), project(b), project(c)) == 0.
+        })
+    }
+
+    fn coordinate(&self, vertex: usize) -> Coord3D<f64> {
+        let [x, y, z] = self.coordinates[vertex];
+        Coord3D{x, y, z}
+    }
+
+    fn is_finite(&self, tetrahedron: usize) -> bool {
+        !self.tetrahedra[tetrahedron].vertices.contains(&self.infinite)
+    }
+
+    // The orientation of four finite vertices
+    fn orient(&self, vertices: [usize; 4]) -> f64 {
+        let [a, b, c, d] = vertices;
+        orient3d(self.coordinate(a), self.coordinate(b), self.coordinate(c), self.coordinate(d))
+    }
+
+    // Whether vertex lies strictly inside the circumsphere of the tetrahedron
+    fn in_sphere(&self, tetrahedron: usize, vertex: usize) -> bool {
+        let vertices = self.tetrahedra[tetrahedron].vertices;
+        let k = match vertices.iter().position(|&v| v == self.infinite) {
+            None => {
+                let [a, b, c, d] = vertices;
+                return insphere(self.coordinate(a), self.coordinate(b), self.coordinate(c), self.coordinate(d),
+                                self.coordinate(vertex)) > 0.;
+            },
+            Some(k) => k
+        };
+        let mut beyond = vertices;
+        beyond[k] = vertex;
+        let side = self.orient(beyond);
+        if side != 0. {
+            return side > 0.;
+        }
+        // Every sphere through the hull face meets its plane in the face's circumcircle, so use
+        // the one through the vertex behind the face. That vertex is on the inner side, which
+        // makes the orientation negative and flips the sign of the test.
+        let behind = self.tetrahedra[tetrahedron].neighbors[k].expect("hull faces always have a tetrahedron behind them");
+        let opposite = *self.tetrahedra[behind].vertices.iter()
+            .find(|v| !vertices.contains(v))
+            .expect("adjacent tetrahedra differ in one vertex");
+        let [a, b, c, d] = {
+            let mut inner = vertices;
+            inner[k] = opposite;
+            inner
+        };
+        insphere(self.coordinate(a), self.coordinate(b), self.coordinate(c), self.coordinate(d),
+                 self.coordinate(vertex)) < 0.
+    }
+
+    // Walks from the last created finite tetrahedron towards vertex, returning the finite
+    // tetrahedron that contains it, or the infinite one beyond the hull face it is outside of. The
+    // walk always terminates in a Delaunay triangulation.
+    fn locate(&self, vertex: usize) -> usize {
+        let mut current = self.last;
+        'walk: loop {
+            let tetrahedron = &self.tetrahedra[current];
+            for i in 0..4 {
+                let mut vertices = tetrahedron.vertices;
+                vertices[i] = vertex;
+                if self.orient(vertices) < 0. {
+                    if let Some(next) = tetrahedron.neighbors[i] {
+                        if !self.is_finite(next) {
+                            return next;
+                        }
+                        current = next;
+                        continue 'walk;
+                    }
+                }
+            }
+            return current;
+        }
+    }
+
+    // Inserts a vertex that isn't at the same location as any already inserted
+    fn insert(&mut self, vertex: usize) {
+        let start = self.locate(vertex);
+
+        // the cavity is every tetrahedron whose circumsphere contains the vertex, which is
+        // connected and contains the tetrahedron the vertex is in
+        let mut cavity = vec![start];
+        let mut in_cavity: HashSet<usize> = cavity.iter().copied().collect();
+        let mut pending = vec![start];
+        while let Some(current) = pending.pop() {
+            for neighbor in self.tetrahedra[current].neighbors.iter().flatten() {
+                if !in_cavity.contains(neighbor) && self.in_sphere(*neighbor, vertex) {
+                    in_cavity.insert(*neighbor);
+                    cavity.push(*neighbor);
+                    pending.push(*neighbor);
+                }
+            }
+        }
+
+        // The cavity has to be strictly star-shaped from the vertex, or connecting the vertex to
+        // its boundary would make flat tetrahedra. With exact predicates, a vertex on the plane of
+        // a boundary face is inside the circumcircle of that face, so the tetrahedron across it
+        // is already in the cavity; this only guards against cospherical inputs slipping through.
+        while let Some(outside) = self.flat_face(&cavity, &in_cavity, vertex) {
+            in_cavity.insert(outside);
+            cavity.push(outside);
+        }
+
+        // connect the vertex to every face on the boundary of the cavity. Replacing the vertex
+        // opposite the face keeps the orientation positive, since the cavity is star-shaped.
+        let mut open_faces: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for &old in &cavity {
+            for i in 0..4 {
+                let outside = self.tetrahedra[old].neighbors[i];
+                if outside.map(|n| in_cavity.contains(&n)).unwrap_or(false) {
+                    continue;
+                }
+                let new = self.tetrahedra.len();
+                let mut vertices = self.tetrahedra[old].vertices;
+                vertices[i] = vertex;
+                let mut neighbors = [None; 4];
+                neighbors[i] = outside;
+                if let Some(outside) = outside {
+                    for neighbor in self.tetrahedra[outside].neighbors.iter_mut() {
+                        if *neighbor == Some(old) {
+                            *neighbor = Some(new);
+                        }
+                    }
+                }
+                self.tetrahedra.push(Tetrahedron{vertices, neighbors, alive: true});
+
+                // the other faces all contain the new vertex, and are shared with other new
+                // tetrahedra
+                for j in (0..4).filter(|&j| j != i) {
+                    let others: Vec<usize> = (0..4).filter(|&k| k != i && k != j).map(|k| vertices[k]).collect();
+                    let key = (others[0].min(others[1]), others[0].max(others[1]));
+                    match open_faces.remove(&key) {
+                        Some((other, other_face)) => {
+                            self.tetrahedra[new].neighbors[j] = Some(other);
+                            self.tetrahedra[other].neighbors[other_face] = Some(new);
+                        },
+                        None => {
+                            open_faces.insert(key, (new, j));
+                        }
+                    }
+                }
+                if self.is_finite(new) {
+                    self.last = new;
+                }
+            }
+        }
+        for old in cavity {
+            self.tetrahedra[old].alive = false;
+        }
+    }
+
+    // The tetrahedron outside the cavity across a boundary face that vertex is not strictly in
+    // front of, if there is one. Only faces that would make finite tetrahedra are checked, since
+    // an infinite one is never flat.
+    fn flat_face(&self, cavity: &[usize], in_cavity: &HashSet<usize>, vertex: usize) -> Option<usize> {
+        cavity.iter()
+            .flat_map(|&old| (0..4).map(move |i| (old, i)))
+            .filter_map(|(old, i)| {
+                let outside = self.tetrahedra[old].neighbors[i]?;
+                if in_cavity.contains(&outside) {
+                    return None;
+                }
+                let mut vertices = self.tetrahedra[old].vertices;
+                vertices[i] = vertex;
+                if vertices.contains(&self.infinite) {
+                    return None;
+                }
+                if self.orient(vertices) <= 0. { Some(outside) } else { None }
+            })
+            .next()
+    }
+
+    // Every edge between two of the triangulated points
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = HashSet::new();
+        for tetrahedron in self.tetrahedra.iter().filter(|t| t.alive) {
+            for i in 0..4 {
+                for j in (i + 1)..4 {
+                    let (a, b) = (tetrahedron.vertices[i], tetrahedron.vertices[j]);
+                    if a != self.infinite && b != self.infinite {
+                        edges.insert((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        edges.into_iter().collect()
+    }
+}
+
+// The edges of the Delaunay triangulation of points that all lie on one plane (or line), found by
+// triangulating them in an orthonormal basis of that plane
+fn planar_edges(coordinates: &[[f64; 3]]) -> Result<Vec<(usize, usize)>, GraphError> {
+    if coordinates.len() < 2 {
+        return Ok(vec![]);
+    }
+    let origin = coordinates[0];
+    let difference = |p: [f64; 3]| [p[0] - origin[0], p[1] - origin[1], p[2] - origin[2]];
+    let dot = |a: [f64; 3], b: [f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let normalized = |a: [f64; 3]| {
+        let norm = dot(a, a).sqrt();
+        if norm == 0. { a } else { [a[0] / norm, a[1] / norm, a[2] / norm] }
+    };
+    let u = normalized(difference(coordinates[1]));
+    // the direction in the plane perpendicular to u, from the point farthest off the line
+    let off_line = |p: [f64; 3]| {
+        let d = difference(p);
+        let along = dot(d, u);
+        [d[0] - along * u[0], d[1] - along * u[1], d[2] - along * u[2]]
+    };
+    let v = coordinates.iter()
+        .map(|&p| off_line(p))
+        .max_by(|a, b| dot(*a, *a).partial_cmp(&dot(*b, *b)).unwrap_or(Ordering::Equal))
+        .map(normalized)
+        .unwrap_or([0.; 3]);
+    triangulate_2d(coordinates.iter().map(|&p| (dot(difference(p), u), dot(difference(p), v))))
+}
+
+// The edges of the Delaunay triangulation of 2D points, as pairs of indices
+fn triangulate_2d(points: impl Iterator<Item = (f64, f64)>) -> Result<Vec<(usize, usize)>, GraphError> {
+    let mut triangulation: DelaunayTriangulation<Point2<f64>> = DelaunayTriangulation::new();
+    let mut vertex_to_point: HashMap<usize, usize> = HashMap::new();
+    for (i, (x, y)) in points.enumerate() {
+        let vertex = triangulation.insert(Point2::new(x, y))
+            .map_err(|_| GraphError::TriangulationFailure{index: i})?;
+        if let Some(&original) = vertex_to_point.get(&vertex.index()) {
+            return Err(GraphError::DuplicatePoint{index: i, original});
+        }
+        vertex_to_point.insert(vertex.index(), i);
+    }
+    Ok(triangulation.undirected_edges()
+        .map(|edge| {
+            let [a, b] = edge.vertices();
+            (vertex_to_point[&a.fix().index()], vertex_to_point[&b.fix().index()])
+        })
+        .collect())
+}
+
+/// Constructs the graph of the edges of the Delaunay tetrahedralization of a set of 3D `points`.
+///
+/// This is the 3D counterpart of [build_delaunay_2d](fn.build_delaunay_2d.html): edges are
+/// weighted according to `weighting`, based on the Euclidean distance between their endpoints, and
+/// it is an error for two points to be at the same location. Unlike a `k`-NN graph, it doesn't
+/// add long edges across sparse regions of a volume just because a point has too few close
+/// neighbors.
+///
+/// The tetrahedralization uses exact geometric predicates, so degenerate inputs such as voxel
+/// centers (where many points lie on a common sphere) still give a valid tetrahedralization, and
+/// every edge of the convex hull is kept however flat the points are. Where the Delaunay
+/// tetrahedralization isn't unique, which of the valid ones is used depends on the order of the
+/// points. If the points all lie on one plane, the graph is their Delaunay triangulation within it.
+pub fn build_delaunay_3d(points: &[LabeledPoint<Vec<f64>>], weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    check_points(points, 3)?;
+    // -0. and 0. are the same location, so normalize them before comparing bits
+    let coordinates: Vec<[f64; 3]> = points.iter().map(|p| [p.point[0] + 0., p.point[1] + 0., p.point[2] + 0.]).collect();
+    let mut seen: HashMap<[u64; 3], usize> = HashMap::with_capacity(points.len());
+    for (i, c) in coordinates.iter().enumerate() {
+        if let Some(&original) = seen.get(&[c[0].to_bits(), c[1].to_bits(), c[2].to_bits()]) {
+            return Err(GraphError::DuplicatePoint{index: i, original});
+        }
+        seen.insert([c[0].to_bits(), c[1].to_bits(), c[2].to_bits()], i);
+    }
+    let edges = match Tetrahedralization::new(coordinates.clone()) {
+        Some(triangulation) => triangulation.edges(),
+        None => planar_edges(&coordinates)?
+    };
+    Ok(graph_from_edges(points, &edges, weighting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+    use rand::prelude::*;
+
+    fn points_2d(coordinates: &[(f64, f64)]) -> Vec<LabeledPoint<Vec<f64>>> {
+        coordinates.iter().enumerate()
+            .map(|(i, &(x, y))| LabeledPoint{id: i as i64, value: 0., point: vec![x, y]})
+            .collect()
+    }
+
+    #[test]
+    fn test_delaunay_2d() {
+        // a square with its center: every corner connects to its two neighbors and the center,
+        // and the diagonals are blocked by the center
+        let points = points_2d(&[(0., 0.), (1., 0.), (1., 1.), (0., 1.), (0.5, 0.5)]);
+        let graph = build_delaunay_2d(&points, EdgeWeighting::Distance).unwrap();
+        assert_eq!(graph.edge_count(), 8);
+        assert!(graph.find_edge(NodeIndex::new(0), NodeIndex::new(2)).is_none());
+        let edge = graph.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+        assert_eq!(graph[edge], 1.);
+        for corner in 0..4 {
+            assert!(graph.find_edge(NodeIndex::new(corner), NodeIndex::new(4)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_delaunay_2d_degenerate() {
+        // a regular grid
+        let mut coordinates: Vec<(f64, f64)> = (0..25).map(|i| ((i % 5) as f64, (i / 5) as f64)).collect();
+        let graph = build_delaunay_2d(&points_2d(&coordinates), EdgeWeighting::Distance).unwrap();
+        // 40 grid edges and one diagonal per cell
+        assert_eq!(graph.edge_count(), 40 + 16);
+        assert!(graph.raw_edges().iter().all(|edge| edge.weight > 0.));
+
+        // a repeated point would need an edge of length 0
+        coordinates.push((2., 2.));
+        match build_delaunay_2d(&points_2d(&coordinates), EdgeWeighting::Distance) {
+            Err(GraphError::DuplicatePoint{index: 25, original: 12}) => (),
+            other => panic!("expected a duplicate point error, got {:?}", other)
+        }
+
+        assert!(build_delaunay_2d(&points_2d(&[(0., 0.)]), EdgeWeighting::Distance).is_ok());
+        let bad = vec![LabeledPoint{id: 0, value: 0., point: vec![0., 0., 0.]}];
+        assert!(build_delaunay_2d(&bad, EdgeWeighting::Distance).is_err());
+        match build_delaunay_2d(&points_2d(&[(0., 0.), (f64::NAN, 1.)]), EdgeWeighting::Distance) {
+            Err(GraphError::NonFiniteCoordinate{index: 1}) => (),
+            other => panic!("expected a non-finite coordinate error, got {:?}", other)
+        }
+        // finite, but too small for the predicates to stay exact
+        match build_delaunay_2d(&points_2d(&[(0., 0.), (1., 0.), (1e-300, 1.)]), EdgeWeighting::Distance) {
+            Err(GraphError::TriangulationFailure{index: 2}) => (),
+            other => panic!("expected a triangulation failure, got {:?}", other)
+        }
+    }
+
+    fn points_3d(coordinates: &[[f64; 3]]) -> Vec<LabeledPoint<Vec<f64>>> {
+        coordinates.iter().enumerate()
+            .map(|(i, c)| LabeledPoint{id: i as i64, value: 0., point: c.to_vec()})
+            .collect()
+    }
+
+    fn edge_set(graph: &UnGraph<LabeledPoint<Vec<f64>>, f64>) -> HashSet<(usize, usize)> {
+        graph.raw_edges().iter()
+            .map(|edge| (edge.source().index().min(edge.target().index()), edge.source().index().max(edge.target().index())))
+            .collect()
+    }
+
+    // The edges of the faces of the convex hull of points in general position, by brute force
+    fn hull_edges(coordinates: &[[f64; 3]]) -> HashSet<(usize, usize)> {
+        let triangulation = Tetrahedralization{coordinates: coordinates.to_vec(), infinite: coordinates.len(), tetrahedra: vec![], last: 0};
+        let n = coordinates.len();
+        let mut edges = HashSet::new();
+        for a in 0..n {
+            for b in (a + 1)..n {
+                for c in (b + 1)..n {
+                    let sides: Vec<f64> = (0..n).filter(|&d| d != a && d != b && d != c)
+                        .map(|d| triangulation.orient([a, b, c, d]))
+                        .collect();
+                    if sides.iter().all(|&side| side > 0.) || sides.iter().all(|&side| side < 0.) {
+                        edges.extend(vec![(a, b), (a, c), (b, c)]);
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    // The edges of the Delaunay tetrahedralization of points in general position, by brute force
+    fn brute_force_edges(coordinates: &[[f64; 3]]) -> HashSet<(usize, usize)> {
+        let mut triangulation = Tetrahedralization{coordinates: coordinates.to_vec(), infinite: coordinates.len(), tetrahedra: vec![], last: 0};
+        let n = coordinates.len();
+        let mut edges = HashSet::new();
+        for a in 0..n {
+            for b in (a + 1)..n {
+                for c in (b + 1)..n {
+                    for d in (c + 1)..n {
+                        let mut vertices = [a, b, c, d];
+                        if triangulation.orient(vertices) == 0. {
+                            continue;
+                        }
+                        if triangulation.orient(vertices) < 0. {
+                            vertices.swap(0, 1);
+                        }
+                        triangulation.tetrahedra = vec![Tetrahedron{vertices, neighbors: [None; 4], alive: true}];
+                        if (0..n).all(|e| !triangulation.in_sphere(0, e)) {
+                            edges.extend(vec![(a, b), (a, c), (a, d), (b, c), (b, d), (c, d)]);
+                        }
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    #[test]
+    fn test_delaunay_3d_is_delaunay() {
+        let mut rng = StdRng::seed_from_u64(1260);
+        let coordinates: Vec<[f64; 3]> = (0..60)
+            .map(|_| [rng.gen_range(0., 1.), rng.gen_range(0., 1.), rng.gen_range(0., 1.)])
+            .collect();
+        let triangulation = Tetrahedralization::new(coordinates.clone()).unwrap();
+        for (t, tetrahedron) in triangulation.tetrahedra.iter().enumerate().filter(|(_, t)| t.alive) {
+            if triangulation.is_finite(t) {
+                assert!(triangulation.orient(tetrahedron.vertices) > 0.);
+            }
+            for i in 0..coordinates.len() {
+                assert!(!triangulation.in_sphere(t, i));
+            }
+        }
+        let graph = build_delaunay_3d(&points_3d(&coordinates), EdgeWeighting::Distance).unwrap();
+        assert_eq!(graph.edge_count(), triangulation.edges().len());
+        assert!(hull_edges(&coordinates).is_subset(&edge_set(&graph)));
+    }
+
+    #[test]
+    fn test_delaunay_3d_nearly_flat() {
+        // a thin slab makes very flat tetrahedra all along its hull, and none of their edges
+        // may be lost
+        let mut rng = StdRng::seed_from_u64(1260);
+        let coordinates: Vec<[f64; 3]> = (0..25)
+            .map(|_| [rng.gen_range(0., 1.), rng.gen_range(0., 1.), rng.gen_range(0., 1e-6)])
+            .collect();
+        let edges = edge_set(&build_delaunay_3d(&points_3d(&coordinates), EdgeWeighting::Distance).unwrap());
+        assert_eq!(edges, brute_force_edges(&coordinates));
+        assert!(hull_edges(&coordinates).is_subset(&edges));
+
+        // exactly flat points fall back to their triangulation within the plane
+        let flat: Vec<[f64; 3]> = coordinates.iter().map(|c| [c[0], c[1], 0.5]).collect();
+        let planar: Vec<LabeledPoint<Vec<f64>>> = coordinates.iter().enumerate()
+            .map(|(i, c)| LabeledPoint{id: i as i64, value: 0., point: vec![c[0], c[1]]})
+            .collect();
+        assert_eq!(edge_set(&build_delaunay_3d(&points_3d(&flat), EdgeWeighting::Distance).unwrap()),
+                   edge_set(&build_delaunay_2d(&planar, EdgeWeighting::Distance).unwrap()));
+    }
+
+    #[test]
+    fn test_delaunay_3d_sphere() {
+        // every point is on the hull, and nearly cospherical with every other
+        let mut rng = StdRng::seed_from_u64(1260);
+        let coordinates: Vec<[f64; 3]> = (0..30)
+            .map(|_| {
+                let (z, angle): (f64, f64) = (rng.gen_range(-1., 1.), rng.gen_range(0., 2. * std::f64::consts::PI));
+                let radius = (1. - z * z).sqrt();
+                [radius * angle.cos(), radius * angle.sin(), z]
+            })
+            .collect();
+        let edges = edge_set(&build_delaunay_3d(&points_3d(&coordinates), EdgeWeighting::Distance).unwrap());
+        assert!(hull_edges(&coordinates).is_subset(&edges));
+        assert_eq!(edges, brute_force_edges(&coordinates));
+    }
+
+    #[test]
+    fn test_delaunay_3d_grid() {
+        let mut points: Vec<LabeledPoint<Vec<f64>>> = (0..27)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![(i % 3) as f64, ((i / 3) % 3) as f64, (i / 9) as f64]})
+            .collect();
+        // every cube is cospherical, but none of its tetrahedra may be flat: together they fill
+        // the 2x2x2 grid exactly
+        let coordinates: Vec<[f64; 3]> = points.iter().map(|p| [p.point[0], p.point[1], p.point[2]]).collect();
+        let triangulation = Tetrahedralization::new(coordinates).unwrap();
+        let mut volume = 0.;
+        for (t, tetrahedron) in triangulation.tetrahedra.iter().enumerate().filter(|(_, t)| t.alive) {
+            if triangulation.is_finite(t) {
+                assert!(triangulation.orient(tetrahedron.vertices) > 0.);
+                volume += triangulation.orient(tetrahedron.vertices) / 6.;
+            }
+        }
+        assert!((volume - 8.).abs() < 1e-9);
+
+        let graph = build_delaunay_3d(&points, EdgeWeighting::Distance).unwrap();
+        // 54 grid edges, a diagonal across each of the 36 unit squares, and each of the 8 cubes
+        // split into 6 tetrahedra around a diagonal through it
+        assert_eq!(graph.edge_count(), 54 + 36 + 8);
+        let diagonals = graph.raw_edges().iter().filter(|edge| edge.weight == 2f64.sqrt()).count();
+        assert_eq!(diagonals, 36);
+        // every grid edge is there, and nothing longer than a cube diagonal is
+        for i in 0..27 {
+            for j in (i + 1)..27 {
+                let distance = euclidean(&points[i].point, &points[j].point);
+                let edge = graph.find_edge(NodeIndex::new(i), NodeIndex::new(j));
+                if distance == 1. {
+                    assert!(edge.is_some());
+                }
+                if distance > 3f64.sqrt() {
+                    assert!(edge.is_none());
+                }
+            }
+        }
+
+        // a repeated center would need an edge of length 0
+        points.push(LabeledPoint{id: 27, value: 0., point: vec![1., 1., 1.]});
+        match build_delaunay_3d(&points, EdgeWeighting::Distance) {
+            Err(GraphError::DuplicatePoint{index: 27, original: 13}) => (),
+            other => panic!("expected a duplicate point error, got {:?}", other)
+        }
+
+        points[27].point[2] = f64::INFINITY;
+        match build_delaunay_3d(&points, EdgeWeighting::Distance) {
+            Err(GraphError::NonFiniteCoordinate{index: 27}) => (),
+            other => panic!("expected a non-finite coordinate error, got {:?}", other)
+        }
+    }
+}
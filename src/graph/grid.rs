@@ -0,0 +1,201 @@
+//! Graphs over regular grids of values, such as images, heightfields and volumes.
+//!
+//! Every cell of the grid becomes a node whose point is its position in the grid, connected to
+//! the adjacent cells. This lets the Morse machinery segment an image into the basins of its
+//! extrema directly, without going through a point cloud.
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::GraphError;
+use crate::LabeledPoint;
+
+/// Which cells of a 2D grid count as adjacent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridConnectivity {
+    /// Cells that share an edge.
+    Four,
+
+    /// Cells that share an edge or a corner.
+    Eight
+}
+
+/// Which cells of a 3D grid count as adjacent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoxelConnectivity {
+    /// Voxels that share a face.
+    Six,
+
+    /// Voxels that share a face or an edge.
+    Eighteen,
+
+    /// Voxels that share a face, an edge or a corner.
+    TwentySix
+}
+
+/// Constructs the graph of a 2D grid of `values`, given as a list of equally long rows.
+///
+/// The cell at row `r` and column `c` becomes the node with index (and id) `r * width + c`, with
+/// point `[r, c]`. Adjacent cells are connected by edges weighted by the distance between their
+/// centers, so diagonal edges are `√2` long.
+pub fn build_grid_2d(values: &[Vec<f64>], connectivity: GridConnectivity)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    let width = values.first().map(|row| row.len()).unwrap_or(0);
+    for (r, row) in values.iter().enumerate() {
+        if row.len() != width {
+            return Err(GraphError::RaggedGrid{index: vec![r], expected: width, found: row.len()});
+        }
+    }
+    let max_changed = match connectivity {
+        GridConnectivity::Four => 1,
+        GridConnectivity::Eight => 2
+    };
+    grid_graph(&[values.len(), width], values.iter().flatten().cloned().collect(), max_changed)
+}
+
+/// Constructs the graph of a 3D grid of `values`, such as a CT scan or the output of a simulation.
+///
+/// `values[z][y][x]` is the value of the voxel at `[z, y, x]`, and every slice must have the same
+/// shape. As with [build_grid_2d](fn.build_grid_2d.html), voxels are numbered in that (row-major)
+/// order and edges are weighted by the distance between voxel centers.
+pub fn build_grid_3d(values: &[Vec<Vec<f64>>], connectivity: VoxelConnectivity)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    let height = values.first().map(|slice| slice.len()).unwrap_or(0);
+    let width = values.first().and_then(|slice| slice.first()).map(|row| row.len()).unwrap_or(0);
+    for (z, slice) in values.iter().enumerate() {
+        if slice.len() != height {
+            return Err(GraphError::RaggedGrid{index: vec![z], expected: height, found: slice.len()});
+        }
+        for (y, row) in slice.iter().enumerate() {
+            if row.len() != width {
+                return Err(GraphError::RaggedGrid{index: vec![z, y], expected: width, found: row.len()});
+            }
+        }
+    }
+    let max_changed = match connectivity {
+        VoxelConnectivity::Six => 1,
+        VoxelConnectivity::Eighteen => 2,
+        VoxelConnectivity::TwentySix => 3
+    };
+    grid_graph(&[values.len(), height, width], values.iter().flatten().flatten().cloned().collect(), max_changed)
+}
+
+// Builds the graph of a row-major grid with the given shape, in which cells are adjacent when at
+// most `max_changed` of their coordinates differ, each by one
+fn grid_graph(shape: &[usize], values: Vec<f64>, max_changed: usize)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    if values.iter().any(|v| v.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    let offsets = forward_offsets(shape.len(), max_changed);
+    let mut graph = UnGraph::with_capacity(values.len(), values.len() * offsets.len());
+    for (i, value) in values.into_iter().enumerate() {
+        let point = unravel(i, shape).into_iter().map(|x| x as f64).collect();
+        graph.add_node(LabeledPoint{id: i as i64, value, point});
+    }
+
+    for i in 0..graph.node_count() {
+        let position = unravel(i, shape);
+        'offsets: for offset in offsets.iter() {
+            let mut j = 0;
+            for ((&x, &dx), &size) in position.iter().zip(offset.iter()).zip(shape.iter()) {
+                let y = x as isize + dx;
+                if y < 0 || y as usize >= size {
+                    continue 'offsets;
+                }
+                j = j * size + y as usize;
+            }
+            let weight = (offset.iter().filter(|&&dx| dx != 0).count() as f64).sqrt();
+            graph.add_edge(NodeIndex::new(i), NodeIndex::new(j), weight);
+        }
+    }
+    Ok(graph)
+}
+
+// The position of the `index`th cell of a row-major grid
+fn unravel(mut index: usize, shape: &[usize]) -> Vec<usize> {
+    let mut position = vec![0; shape.len()];
+    for (x, &size) in position.iter_mut().zip(shape.iter()).rev() {
+        *x = index % size;
+        index /= size;
+    }
+    position
+}
+
+// The offsets to the adjacent cells that come later in row-major order (those whose first nonzero
+// coordinate is positive), so that each pair of adjacent cells is only visited once
+fn forward_offsets(dimensions: usize, max_changed: usize) -> Vec<Vec<isize>> {
+    let mut offsets: Vec<Vec<isize>> = vec![vec![]];
+    for _ in 0..dimensions {
+        offsets = offsets.into_iter()
+            .flat_map(|offset| (-1..=1).map(move |dx| {
+                let mut offset = offset.clone();
+                offset.push(dx);
+                offset
+            }))
+            .collect();
+    }
+    offsets.into_iter()
+        .filter(|offset| offset.iter().find(|&&dx| dx != 0) == Some(&1))
+        .filter(|offset| offset.iter().filter(|&&dx| dx != 0).count() <= max_changed)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::MorseSmaleComplex;
+
+    #[test]
+    fn test_grid_2d() {
+        let values = vec![
+            vec![0., 1., 0.],
+            vec![1., 2., 1.],
+            vec![0., 1., 5.]
+        ];
+        let four = build_grid_2d(&values, GridConnectivity::Four).unwrap();
+        assert_eq!(four.node_count(), 9);
+        assert_eq!(four.edge_count(), 12);
+        assert_eq!(four[NodeIndex::new(5)].point, vec![1., 2.]);
+
+        let eight = build_grid_2d(&values, GridConnectivity::Eight).unwrap();
+        assert_eq!(eight.edge_count(), 20);
+        let diagonal = eight.find_edge(NodeIndex::new(4), NodeIndex::new(8)).unwrap();
+        assert!((eight[diagonal] - 2f64.sqrt()).abs() < 1e-12);
+
+        // with 4-connectivity the center and the corner are separate peaks; with 8 they aren't
+        let complex = MorseSmaleComplex::from_graph(&four).unwrap();
+        assert_eq!(complex.descending_complex.get_persistence().values().filter(|&&l| l > 0.).count(), 2);
+        let complex = MorseSmaleComplex::from_graph(&eight).unwrap();
+        assert_eq!(complex.descending_complex.get_persistence().values().filter(|&&l| l > 0.).count(), 1);
+    }
+
+    #[test]
+    fn test_grid_2d_errors() {
+        assert!(build_grid_2d(&[vec![0., 1.], vec![0.]], GridConnectivity::Four).is_err());
+        assert!(build_grid_2d(&[vec![f64::NAN]], GridConnectivity::Four).is_err());
+        assert_eq!(build_grid_2d(&[], GridConnectivity::Eight).unwrap().node_count(), 0);
+    }
+
+    #[test]
+    fn test_grid_3d() {
+        let values: Vec<Vec<Vec<f64>>> = (0..3)
+            .map(|z| (0..3).map(|y| (0..3).map(|x| (x + 3 * y + 9 * z) as f64).collect()).collect())
+            .collect();
+        let six = build_grid_3d(&values, VoxelConnectivity::Six).unwrap();
+        assert_eq!(six.node_count(), 27);
+        assert_eq!(six.edge_count(), 54);
+        assert_eq!(six[NodeIndex::new(13)].point, vec![1., 1., 1.]);
+        assert_eq!(six[NodeIndex::new(13)].value, 13.);
+        assert_eq!(six.neighbors(NodeIndex::new(13)).count(), 6);
+
+        let eighteen = build_grid_3d(&values, VoxelConnectivity::Eighteen).unwrap();
+        assert_eq!(eighteen.neighbors(NodeIndex::new(13)).count(), 18);
+        let twenty_six = build_grid_3d(&values, VoxelConnectivity::TwentySix).unwrap();
+        assert_eq!(twenty_six.neighbors(NodeIndex::new(13)).count(), 26);
+        assert_eq!(twenty_six.neighbors(NodeIndex::new(0)).count(), 7);
+        let corner = twenty_six.find_edge(NodeIndex::new(0), NodeIndex::new(13)).unwrap();
+        assert!((twenty_six[corner] - 3f64.sqrt()).abs() < 1e-12);
+
+        let ragged = vec![vec![vec![0., 1.]], vec![vec![0., 1.], vec![2., 3.]]];
+        assert!(build_grid_3d(&ragged, VoxelConnectivity::Six).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Approximate nearest neighbor search with a Hierarchical Navigable Small World index.
+//!
+//! This is an implementation of [Efficient and robust approximate nearest neighbor search using
+//! Hierarchical Navigable Small World graphs](https://arxiv.org/abs/1603.09320).
+use std::collections::{BinaryHeap, HashSet};
+use petgraph::graph::UnGraph;
+use rand::prelude::*;
+
+use super::{GraphError, KnnOptions, NeighborData, NeighborState, graph_from_neighbordata};
+use super::index::Candidate;
+use super::PointPreMetric;
+use crate::{PreMetric, LabeledPoint};
+use crate::metric::Metric;
+
+struct Hnsw<'a, T, M> {
+    points: &'a [LabeledPoint<T>],
+    metric: &'a M,
+    // layers[l][i] holds the neighbors of point i in layer l (empty if i isn't in layer l)
+    layers: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    max_connections: usize,
+    ef: usize
+}
+
+impl<'a, T, M: Metric<T>> Hnsw<'a, T, M> {
+    fn new(points: &'a [LabeledPoint<T>], metric: &'a M, max_connections: usize, ef: usize) -> Self {
+        Hnsw{points, metric, layers: vec![], entry_point: None, max_connections, ef}
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f64 {
+        self.metric.distance(&self.points[a].point, &self.points[b].point)
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 { 2 * self.max_connections } else { self.max_connections }
+    }
+
+    fn insert(&mut self, idx: usize, level: usize) {
+        while self.layers.len() <= level {
+            self.layers.push(vec![vec![]; self.points.len()]);
+        }
+        let entry_point = match self.entry_point {
+            None => {
+                self.entry_point = Some(idx);
+                return;
+            },
+            Some(entry_point) => entry_point
+        };
+        let top = self.top_layer();
+
+        let mut entry = vec![Candidate{distance: self.distance(idx, entry_point), idx: entry_point}];
+        for layer in ((level + 1)..=top).rev() {
+            entry = self.search_layer(idx, entry, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(idx, entry, self.ef, layer);
+            let neighbors: Vec<usize> = found.iter().take(self.max_connections).map(|c| c.idx).collect();
+            for &neighbor in &neighbors {
+                self.layers[layer][idx].push(neighbor);
+                self.layers[layer][neighbor].push(idx);
+                if self.layers[layer][neighbor].len() > self.max_neighbors(layer) {
+                    self.shrink(neighbor, layer);
+                }
+            }
+            entry = found;
+        }
+        if level > top {
+            self.entry_point = Some(idx);
+        }
+    }
+
+    fn top_layer(&self) -> usize {
+        match self.entry_point {
+            None => 0,
+            Some(entry_point) => (0..self.layers.len())
+                .rev()
+                .find(|&l| l == 0 || !self.layers[l][entry_point].is_empty())
+                .unwrap_or(0)
+        }
+    }
+
+    fn shrink(&mut self, idx: usize, layer: usize) {
+        let mut neighbors: Vec<Candidate> = self.layers[layer][idx].iter()
+            .map(|&n| Candidate{distance: self.distance(idx, n), idx: n})
+            .collect();
+        neighbors.sort();
+        neighbors.truncate(self.max_neighbors(layer));
+        self.layers[layer][idx] = neighbors.iter().map(|c| c.idx).collect();
+    }
+
+    // Returns up to `ef` of the closest points to `query` reachable in `layer`, nearest first
+    fn search_layer(&self, query: usize, entry: Vec<Candidate>, ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry.iter().map(|c| c.idx).collect();
+        let mut candidates: BinaryHeap<std::cmp::Reverse<Candidate>> = entry.iter().map(|&c| std::cmp::Reverse(c)).collect();
+        let mut found: BinaryHeap<Candidate> = entry.into_iter().collect();
+        while let Some(std::cmp::Reverse(current)) = candidates.pop() {
+            let furthest = found.peek().map(|c| c.distance).unwrap_or(f64::INFINITY);
+            if current.distance > furthest && found.len() >= ef {
+                break;
+            }
+            for &neighbor in &self.layers[layer][current.idx] {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = Candidate{distance: self.distance(query, neighbor), idx: neighbor};
+                let furthest = found.peek().map(|c| c.distance).unwrap_or(f64::INFINITY);
+                if found.len() < ef || candidate.distance < furthest {
+                    candidates.push(std::cmp::Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    fn search(&self, query: usize, k: usize) -> Vec<Candidate> {
+        let entry_point = match self.entry_point {
+            None => return vec![],
+            Some(entry_point) => entry_point
+        };
+        let mut entry = vec![Candidate{distance: self.distance(query, entry_point), idx: entry_point}];
+        for layer in (1..=self.top_layer()).rev() {
+            entry = self.search_layer(query, entry, 1, layer);
+        }
+        let mut found = self.search_layer(query, entry, self.ef.max(k + 1), 0);
+        found.retain(|c| c.idx != query);
+        found.truncate(k);
+        found
+    }
+}
+
+/// Constructs an approximate `k`-NN graph from a set of `points` using an HNSW index.
+///
+/// `max_connections` is the number of links each point gets in the index (typically 8-48), and
+/// `ef` is the size of the candidate list used when building and searching it. Higher values of
+/// either increase accuracy at the cost of runtime and memory. The layers of the index each point
+/// is inserted into are drawn from `rng`, so a seeded rng gives the same graph every time.
+///
+/// Building the index takes `O(n log n)` distance computations, which makes this practical for
+/// very large point sets where the exact computation is not. Like
+/// [build_knn_approximate](crate::graph::build_knn_approximate), this does not require the
+/// distance to be a metric.
+pub fn build_knn_hnsw<T: PreMetric + Clone, R: Rng>(points: &[LabeledPoint<T>], k: usize, max_connections: usize, ef: usize,
+                                                    rng: &mut R)
+    -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
+    build_knn_hnsw_with(points, k, max_connections, ef, &PointPreMetric, KnnOptions::default(), rng)
+}
+
+/// Constructs an approximate `k`-NN graph like [build_knn_hnsw](fn.build_knn_hnsw.html), with
+/// distances measured by `metric` instead of the points' own [PreMetric](../trait.PreMetric.html),
+/// and the neighbor lists turned into edges according to `options`.
+pub fn build_knn_hnsw_with<T: Clone, M: Metric<T>, R: Rng>(points: &[LabeledPoint<T>], k: usize, max_connections: usize,
+                                                         ef: usize, metric: &M, options: KnnOptions, rng: &mut R)
+    -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
+    let nans_present = points.iter().any(|p| p.value.is_nan());
+    if nans_present {
+        return Err(GraphError::NanInPoints{})
+    }
+    if k >= points.len() && !points.is_empty() {
+        return Err(GraphError::KTooLarge{k, num_points: points.len()})
+    }
+
+    let level_scale = 1. / (max_connections.max(2) as f64).ln();
+    let mut index = Hnsw::new(points, metric, max_connections.max(1), ef.max(1));
+    for idx in 0..points.len() {
+        let uniform: f64 = rng.gen_range(f64::EPSILON, 1.);
+        let level = (-uniform.ln() * level_scale).floor() as usize;
+        index.insert(idx, level);
+    }
+
+    let neighbors: Vec<Vec<NeighborData>> = (0..points.len())
+        .map(|idx| {
+            index.search(idx, k).iter()
+                .map(|c| NeighborData{distance: c.distance, idx: c.idx, state: NeighborState::Old})
+                .collect()
+        })
+        .collect();
+    Ok(graph_from_neighbordata(points, neighbors, metric, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::build_knn;
+
+    #[test]
+    fn test_knn_hnsw_matches_exact() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let points: Vec<LabeledPoint<Vec<f64>>> = (0..300)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![rng.gen_range(0., 1.), rng.gen_range(0., 1.)]})
+            .collect();
+        let exact = build_knn(&points, 5).unwrap();
+        let approximate = build_knn_hnsw(&points, 5, 16, 64, &mut rng).unwrap();
+
+        let matching = exact.raw_edges().iter()
+            .filter(|edge| approximate.find_edge(edge.source(), edge.target()).is_some())
+            .count();
+        assert!(matching as f64 > 0.95 * exact.edge_count() as f64);
+        assert!(build_knn_hnsw(&points[..3], 5, 16, 64, &mut rng).is_err());
+        assert_eq!(build_knn_hnsw(&points[..0], 5, 16, 64, &mut rng).unwrap().node_count(), 0);
+
+        // the same seed builds the same index, and so the same graph
+        let edges = |seed| {
+            let graph = build_knn_hnsw(&points, 5, 4, 8, &mut StdRng::seed_from_u64(seed)).unwrap();
+            graph.raw_edges().iter().map(|edge| (edge.source(), edge.target())).collect::<Vec<_>>()
+        };
+        assert_eq!(edges(3), edges(3));
+    }
+}
@@ -0,0 +1,152 @@
+//! `k`-NN graphs that grow one point at a time, as the foundation for streaming analyses.
+use std::cmp::Ordering;
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::{GraphError, EdgeWeighting, NeighborIndex};
+use super::index::SpatialIndex;
+use crate::LabeledPoint;
+use crate::metric::Metric;
+
+/// A `k`-NN graph that new points can be inserted into without rebuilding it.
+///
+/// The graph is the union of the directed neighbor lists, as from
+/// [build_knn_with](fn.build_knn_with.html) with
+/// [Symmetrization::Union](enum.Symmetrization.html#variant.Union). Inserting a point connects it
+/// to its `k` nearest neighbors and to every point it's now one of the `k` nearest neighbors of,
+/// and removes the edges that those points no longer need. Nothing else changes, so the node
+/// indices of the existing points stay the same. Until there are more than `k` points, every point
+/// is connected to all of the others.
+///
+/// Each insertion compares the new point against all of the existing ones, so it takes `O(n)`
+/// time, rather than the `O(n log n)` of rebuilding the graph.
+pub struct IncrementalKnn<M> {
+    graph: UnGraph<LabeledPoint<Vec<f64>>, f64>,
+    // the nearest neighbors of each point, nearest first
+    neighbors: Vec<Vec<(f64, usize)>>,
+    k: usize,
+    weighting: EdgeWeighting,
+    metric: M
+}
+
+impl<M: Metric<[f64]>> IncrementalKnn<M> {
+    /// Starts a graph over an initial set of `points`, which may be empty, found with the given kind
+    /// of spatial index.
+    pub fn new(points: &[LabeledPoint<Vec<f64>>], k: usize, weighting: EdgeWeighting, metric: M, index: NeighborIndex)
+        -> Result<Self, GraphError> {
+        if points.iter().any(|p| p.value.is_nan()) {
+            return Err(GraphError::NanInPoints{})
+        }
+        let neighbors = SpatialIndex::new(points, index, &metric)?
+            .all_nearest(k.min(points.len().saturating_sub(1)))?;
+        let mut graph = UnGraph::new_undirected();
+        let node_lookup: Vec<_> = points.iter()
+            .map(|point| graph.add_node(point.clone()))
+            .collect();
+        for (i, data) in neighbors.iter().enumerate() {
+            for &(distance, j) in data {
+                graph.update_edge(node_lookup[i], node_lookup[j], weighting.weight(distance));
+            }
+        }
+        Ok(IncrementalKnn{graph, neighbors, k, weighting, metric})
+    }
+
+    /// Adds `point` to the graph, returning its node.
+    pub fn insert(&mut self, point: LabeledPoint<Vec<f64>>) -> Result<NodeIndex, GraphError> {
+        if point.value.is_nan() {
+            return Err(GraphError::NanInPoints{})
+        }
+        let dim = self.metric.dimension()
+            .or_else(|| self.graph.raw_nodes().first().map(|n| n.weight.point.len()))
+            .unwrap_or(point.point.len());
+        if point.point.len() != dim {
+            return Err(GraphError::WrongDimension{expected: dim, found: point.point.len()});
+        }
+
+        let new = self.graph.node_count();
+        let mut distances: Vec<(f64, usize)> = self.graph.raw_nodes().iter().enumerate()
+            .map(|(j, node)| (self.metric.distance(&point.point, &node.weight.point), j))
+            .collect();
+        let node = self.graph.add_node(point);
+
+        for &(distance, j) in &distances {
+            let list = &mut self.neighbors[j];
+            if list.len() == self.k && list.last().is_none_or(|&(farthest, _)| distance >= farthest) {
+                continue;
+            }
+            let position = list.iter().position(|&(d, _)| distance < d).unwrap_or(list.len());
+            list.insert(position, (distance, new));
+            self.graph.update_edge(NodeIndex::new(j), node, self.weighting.weight(distance));
+            if list.len() > self.k {
+                let (_, dropped) = list.pop().unwrap();
+                self.remove_unneeded_edge(j, dropped);
+            }
+        }
+
+        distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        distances.truncate(self.k);
+        for &(distance, j) in &distances {
+            self.graph.update_edge(node, NodeIndex::new(j), self.weighting.weight(distance));
+        }
+        self.neighbors.push(distances);
+        Ok(node)
+    }
+
+    // Removes the edge between i and j unless one is still among the other's neighbors
+    fn remove_unneeded_edge(&mut self, i: usize, j: usize) {
+        let needed = self.neighbors[i].iter().any(|&(_, n)| n == j) || self.neighbors[j].iter().any(|&(_, n)| n == i);
+        if !needed {
+            if let Some(edge) = self.graph.find_edge(NodeIndex::new(i), NodeIndex::new(j)) {
+                self.graph.remove_edge(edge);
+            }
+        }
+    }
+
+    /// The graph so far.
+    pub fn graph(&self) -> &UnGraph<LabeledPoint<Vec<f64>>, f64> {
+        &self.graph
+    }
+
+    /// Stops inserting points, returning the graph.
+    pub fn into_graph(self) -> UnGraph<LabeledPoint<Vec<f64>>, f64> {
+        self.graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use rand::prelude::*;
+    use crate::graph::{build_knn_with, KnnOptions};
+    use crate::metric::Euclidean;
+
+    fn edge_set(graph: &UnGraph<LabeledPoint<Vec<f64>>, f64>) -> HashSet<(usize, usize, u64)> {
+        graph.raw_edges().iter()
+            .map(|e| {
+                let (a, b) = (e.source().index(), e.target().index());
+                (a.min(b), a.max(b), e.weight.to_bits())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_knn() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let points: Vec<_> = (0..150)
+            .map(|i| LabeledPoint{id: i, value: rng.gen(), point: vec![rng.gen_range(0., 1.), rng.gen_range(0., 1.)]})
+            .collect();
+        let mut incremental = IncrementalKnn::new(&points[..100], 5, EdgeWeighting::Distance, Euclidean, NeighborIndex::KdTree).unwrap();
+        for point in &points[100..] {
+            incremental.insert(point.clone()).unwrap();
+        }
+        let expected = build_knn_with(&points, 5, &Euclidean, NeighborIndex::KdTree, KnnOptions::default()).unwrap();
+        assert_eq!(edge_set(incremental.graph()), edge_set(&expected));
+
+        // starting from nothing works too
+        let mut incremental = IncrementalKnn::new(&[], 5, EdgeWeighting::Distance, Euclidean, NeighborIndex::KdTree).unwrap();
+        for point in &points {
+            incremental.insert(point.clone()).unwrap();
+        }
+        assert_eq!(edge_set(&incremental.into_graph()), edge_set(&expected));
+    }
+}
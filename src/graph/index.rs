@@ -0,0 +1,444 @@
+//! Spatial indices for exact neighbor queries over points in `R^n`.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::{GraphError, NeighborIndex};
+use crate::LabeledPoint;
+use crate::metric::Metric;
+
+// A point index paired with its distance to the current query. Ordered by distance, with ties
+// broken by index so that searches are deterministic given the index structure.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Candidate {
+    pub(crate) distance: f64,
+    pub(crate) idx: usize
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.distance.partial_cmp(&other.distance) {
+            None | Some(Ordering::Equal) => self.idx.cmp(&other.idx),
+            Some(ord) => ord
+        }
+    }
+}
+
+/// An index of the chosen kind over a set of points.
+///
+/// Queries are made by the index of one of the indexed points and return `(distance, index)`
+/// pairs, nearest first, never including the query point itself. If the metric doesn't support
+/// the kind of tree that was asked for, this falls back to brute force.
+pub(crate) enum SpatialIndex<'a, M: Metric<[f64]>> {
+    Kd(KdIndex<'a, M>),
+    Ball(BallTree<'a, M>),
+    BruteForce(BruteForce<'a, M>)
+}
+
+impl<'a, M: Metric<[f64]>> SpatialIndex<'a, M> {
+    pub(crate) fn new(points: &'a [LabeledPoint<Vec<f64>>], kind: NeighborIndex, metric: &'a M) -> Result<Self, GraphError> {
+        if let Some(expected) = metric.dimension() {
+            if let Some(point) = points.iter().find(|p| p.point.len() != expected) {
+                return Err(GraphError::WrongDimension{expected, found: point.point.len()});
+            }
+        }
+        Ok(match kind {
+            NeighborIndex::KdTree if metric.is_minkowski() => SpatialIndex::Kd(KdIndex::new(points, metric)?),
+            NeighborIndex::BallTree if metric.is_metric() => SpatialIndex::Ball(BallTree::new(points, metric)?),
+            _ => SpatialIndex::BruteForce(BruteForce{points, metric})
+        })
+    }
+
+    /// The `k` nearest neighbors of point `idx`.
+    pub(crate) fn nearest(&self, idx: usize, k: usize) -> Result<Vec<(f64, usize)>, GraphError> {
+        match self {
+            SpatialIndex::Kd(index) => index.nearest(idx, k),
+            SpatialIndex::Ball(index) => Ok(index.nearest(idx, k)),
+            SpatialIndex::BruteForce(index) => Ok(index.nearest(idx, k))
+        }
+    }
+
+    /// Every neighbor of point `idx` within `radius` of it.
+    pub(crate) fn within(&self, idx: usize, radius: f64) -> Result<Vec<(f64, usize)>, GraphError> {
+        match self {
+            SpatialIndex::Kd(index) => index.within(idx, radius),
+            SpatialIndex::Ball(index) => Ok(index.within(idx, radius)),
+            SpatialIndex::BruteForce(index) => Ok(index.within(idx, radius))
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            SpatialIndex::Kd(index) => index.points.len(),
+            SpatialIndex::Ball(index) => index.points.len(),
+            SpatialIndex::BruteForce(index) => index.points.len()
+        }
+    }
+
+    /// The `k` nearest neighbors of every point, in order.
+    pub(crate) fn all_nearest(&self, k: usize) -> Result<Vec<Vec<(f64, usize)>>, GraphError> {
+        map_points(self.len(), |idx| self.nearest(idx, k))
+    }
+
+    /// Every neighbor within `radius` of every point, in order.
+    pub(crate) fn all_within(&self, radius: f64) -> Result<Vec<Vec<(f64, usize)>>, GraphError> {
+        map_points(self.len(), |idx| self.within(idx, radius))
+    }
+}
+
+// Queries the points independently of each other, so with the parallel feature they're spread
+// across threads
+#[cfg(feature = "parallel")]
+fn map_points<R: Send, F: Fn(usize) -> Result<R, GraphError> + Sync + Send>(n: usize, query: F)
+    -> Result<Vec<R>, GraphError> {
+    use rayon::prelude::*;
+    (0..n).into_par_iter().map(query).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_points<R, F: Fn(usize) -> Result<R, GraphError>>(n: usize, query: F) -> Result<Vec<R>, GraphError> {
+    (0..n).map(query).collect()
+}
+
+/// Answers queries by comparing the query point against every other point.
+pub(crate) struct BruteForce<'a, M> {
+    points: &'a [LabeledPoint<Vec<f64>>],
+    metric: &'a M
+}
+
+impl<'a, M: Metric<[f64]>> BruteForce<'a, M> {
+    fn candidates(&self, idx: usize) -> impl Iterator<Item = Candidate> + '_ {
+        let query = &self.points[idx].point;
+        self.points.iter().enumerate()
+            .filter(move |&(j, _)| j != idx)
+            .map(move |(j, other)| Candidate{distance: self.metric.distance(query, &other.point), idx: j})
+    }
+
+    pub(crate) fn nearest(&self, idx: usize, k: usize) -> Vec<(f64, usize)> {
+        let mut found: BinaryHeap<Candidate> = BinaryHeap::with_capacity(k + 1);
+        for candidate in self.candidates(idx) {
+            found.push(candidate);
+            if found.len() > k {
+                found.pop();
+            }
+        }
+        found.into_sorted_vec().into_iter().map(|c| (c.distance, c.idx)).collect()
+    }
+
+    pub(crate) fn within(&self, idx: usize, radius: f64) -> Vec<(f64, usize)> {
+        let mut found: Vec<Candidate> = self.candidates(idx).filter(|c| c.distance <= radius).collect();
+        found.sort();
+        found.into_iter().map(|c| (c.distance, c.idx)).collect()
+    }
+}
+
+/// A KD-tree over the coordinates of a set of points.
+///
+/// Building the tree takes `O(n log n)` time and each query takes `O(log n)` time for well
+/// distributed points in low dimensions (up to about 10), so a neighbor graph over all the points
+/// takes `O(n log n)` instead of the `O(n²)` of comparing every pair.
+pub(crate) struct KdIndex<'a, M> {
+    points: &'a [LabeledPoint<Vec<f64>>],
+    metric: &'a M,
+    tree: kdtree::KdTree<f64, usize, &'a Vec<f64>>
+}
+
+impl<'a, M: Metric<[f64]>> KdIndex<'a, M> {
+    pub(crate) fn new(points: &'a [LabeledPoint<Vec<f64>>], metric: &'a M) -> Result<Self, GraphError> {
+        let dim = points.first().map(|p| p.point.len()).unwrap_or(0);
+        let mut tree = kdtree::KdTree::new(dim);
+        for (i, point) in points.iter().enumerate() {
+            tree.add(&point.point, i)?;
+        }
+        Ok(KdIndex{points, metric, tree})
+    }
+
+    fn distance(&self) -> impl Fn(&[f64], &[f64]) -> f64 + '_ {
+        move |a: &[f64], b: &[f64]| self.metric.distance(a, b)
+    }
+
+    pub(crate) fn nearest(&self, idx: usize, k: usize) -> Result<Vec<(f64, usize)>, GraphError> {
+        // ask for one extra, since the point itself (or a duplicate of it) comes back too
+        let mut found: Vec<(f64, usize)> = self.tree.nearest(&self.points[idx].point, k + 1, &self.distance())?
+            .into_iter()
+            .filter(|&(_, &j)| j != idx)
+            .map(|(dist, &j)| (dist, j))
+            .collect();
+        found.truncate(k);
+        Ok(found)
+    }
+
+    pub(crate) fn within(&self, idx: usize, radius: f64) -> Result<Vec<(f64, usize)>, GraphError> {
+        Ok(self.tree.within(&self.points[idx].point, radius, &self.distance())?
+            .into_iter()
+            .filter(|&(_, &j)| j != idx)
+            .map(|(dist, &j)| (dist, j))
+            .collect())
+    }
+}
+
+const LEAF_SIZE: usize = 16;
+
+// A node of a ball tree, covering the points order[start..end]. Every one of those points is
+// within radius of center.
+struct Ball {
+    center: Vec<f64>,
+    radius: f64,
+    start: usize,
+    end: usize,
+    children: Option<(usize, usize)>
+}
+
+/// A ball tree over the coordinates of a set of points.
+///
+/// Each node of the tree bounds its points by a sphere rather than by a box. Queries prune whole
+/// spheres using only distances to their centers, so unlike a KD-tree, whose boxes split on a
+/// single coordinate at a time, it stays effective on high dimensional points as long as they
+/// have some clustered structure. The pruning only relies on the triangle inequality, so this
+/// works with any metric on coordinate vectors.
+pub(crate) struct BallTree<'a, M> {
+    points: &'a [LabeledPoint<Vec<f64>>],
+    metric: &'a M,
+    order: Vec<usize>,
+    balls: Vec<Ball>
+}
+
+impl<'a, M: Metric<[f64]>> BallTree<'a, M> {
+    pub(crate) fn new(points: &'a [LabeledPoint<Vec<f64>>], metric: &'a M) -> Result<Self, GraphError> {
+        let dim = points.first().map(|p| p.point.len()).unwrap_or(0);
+        for (index, point) in points.iter().enumerate() {
+            if point.point.len() != dim {
+                return Err(GraphError::WrongDimension{expected: dim, found: point.point.len()});
+            }
+            if point.point.iter().any(|x| !x.is_finite()) {
+                return Err(GraphError::NonFiniteCoordinate{index});
+            }
+        }
+        let mut tree = BallTree{points, metric, order: (0..points.len()).collect(), balls: vec![]};
+        if !points.is_empty() {
+            tree.build(0, points.len());
+        }
+        Ok(tree)
+    }
+
+    fn coordinates(&self, idx: usize) -> &[f64] {
+        &self.points[idx].point
+    }
+
+    // Builds the ball over order[start..end] and its descendants, returning its index
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let dim = self.coordinates(self.order[start]).len();
+        let mut center = vec![0.; dim];
+        for &idx in &self.order[start..end] {
+            for (c, x) in center.iter_mut().zip(self.coordinates(idx)) {
+                *c += x;
+            }
+        }
+        for c in center.iter_mut() {
+            *c /= (end - start) as f64;
+        }
+        let radius = self.order[start..end].iter()
+            .map(|&idx| self.metric.distance(&center, self.coordinates(idx)))
+            .fold(0., f64::max);
+
+        let ball = self.balls.len();
+        self.balls.push(Ball{center, radius, start, end, children: None});
+        if end - start <= LEAF_SIZE {
+            return ball;
+        }
+
+        // split at the median of the coordinate with the widest spread
+        let spread = |d: usize| {
+            let (low, high) = self.order[start..end].iter()
+                .map(|&idx| self.coordinates(idx)[d])
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), x| (low.min(x), high.max(x)));
+            high - low
+        };
+        let split_dim = (0..dim)
+            .max_by(|&a, &b| spread(a).partial_cmp(&spread(b)).unwrap_or(Ordering::Equal))
+            .unwrap_or(0);
+        let middle = (end - start) / 2;
+        let points = self.points;
+        self.order[start..end].select_nth_unstable_by(middle, |&a, &b| {
+            points[a].point[split_dim].partial_cmp(&points[b].point[split_dim]).unwrap_or(Ordering::Equal)
+        });
+
+        let left = self.build(start, start + middle);
+        let right = self.build(start + middle, end);
+        self.balls[ball].children = Some((left, right));
+        ball
+    }
+
+    // The smallest possible distance from query to a point in the ball
+    fn lower_bound(&self, ball: usize, query: &[f64]) -> f64 {
+        let ball = &self.balls[ball];
+        (self.metric.distance(query, &ball.center) - ball.radius).max(0.)
+    }
+
+    pub(crate) fn nearest(&self, idx: usize, k: usize) -> Vec<(f64, usize)> {
+        let mut found: BinaryHeap<Candidate> = BinaryHeap::with_capacity(k + 1);
+        if !self.balls.is_empty() && k > 0 {
+            self.nearest_in(0, idx, k, &mut found);
+        }
+        found.into_sorted_vec().into_iter().map(|c| (c.distance, c.idx)).collect()
+    }
+
+    fn nearest_in(&self, ball: usize, query: usize, k: usize, found: &mut BinaryHeap<Candidate>) {
+        let worst = |found: &BinaryHeap<Candidate>| {
+            if found.len() < k { f64::INFINITY } else { found.peek().map(|c| c.distance).unwrap_or(f64::INFINITY) }
+        };
+        if self.lower_bound(ball, self.coordinates(query)) > worst(found) {
+            return;
+        }
+        match self.balls[ball].children {
+            None => {
+                let Ball{start, end, ..} = self.balls[ball];
+                for &idx in &self.order[start..end] {
+                    if idx == query {
+                        continue;
+                    }
+                    let candidate = Candidate{distance: self.metric.distance(self.coordinates(query), self.coordinates(idx)), idx};
+                    if found.len() < k {
+                        found.push(candidate);
+                    } else if found.peek().map(|worst| candidate < *worst).unwrap_or(false) {
+                        found.pop();
+                        found.push(candidate);
+                    }
+                }
+            },
+            Some((left, right)) => {
+                // descend into the nearer child first so the other is more likely to be pruned
+                let query_point = self.coordinates(query);
+                let (near, far) = if self.lower_bound(left, query_point) <= self.lower_bound(right, query_point) {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                self.nearest_in(near, query, k, found);
+                self.nearest_in(far, query, k, found);
+            }
+        }
+    }
+
+    pub(crate) fn within(&self, idx: usize, radius: f64) -> Vec<(f64, usize)> {
+        let mut found = vec![];
+        let mut pending = if self.balls.is_empty() { vec![] } else { vec![0] };
+        let query = self.coordinates(idx);
+        while let Some(ball) = pending.pop() {
+            if self.lower_bound(ball, query) > radius {
+                continue;
+            }
+            match self.balls[ball].children {
+                None => {
+                    let Ball{start, end, ..} = self.balls[ball];
+                    found.extend(self.order[start..end].iter()
+                        .filter(|&&other| other != idx)
+                        .map(|&other| Candidate{distance: self.metric.distance(query, self.coordinates(other)), idx: other})
+                        .filter(|c| c.distance <= radius));
+                },
+                Some((left, right)) => {
+                    pending.push(left);
+                    pending.push(right);
+                }
+            }
+        }
+        found.sort();
+        found.into_iter().map(|c| (c.distance, c.idx)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use crate::metric::{Euclidean, Manhattan, Chebyshev, Periodic};
+
+    fn assert_matches_brute_force<M: Metric<[f64]>>(kind: NeighborIndex, dim: usize, metric: &M) {
+        let mut rng = StdRng::seed_from_u64(dim as u64);
+        let points: Vec<LabeledPoint<Vec<f64>>> = (0..200)
+            .map(|i| LabeledPoint{id: i, value: 0., point: (0..dim).map(|_| rng.gen_range(0., 1.)).collect()})
+            .collect();
+        let index = SpatialIndex::new(&points, kind, metric).unwrap();
+        let radius = 0.1 * (dim as f64).sqrt();
+
+        for idx in 0..points.len() {
+            let mut expected: Vec<(f64, usize)> = (0..points.len())
+                .filter(|&j| j != idx)
+                .map(|j| (metric.distance(&points[idx].point, &points[j].point), j))
+                .collect();
+            expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            let nearest = index.nearest(idx, 4).unwrap();
+            assert_eq!(nearest.len(), 4);
+            for (found, exp) in nearest.iter().zip(expected.iter()) {
+                assert!((found.0 - exp.0).abs() < 1e-12);
+            }
+
+            let within: Vec<usize> = index.within(idx, radius).unwrap().iter().map(|&(_, j)| j).collect();
+            let expected_within = expected.iter().filter(|&&(d, _)| d <= radius).count();
+            assert_eq!(within.len(), expected_within);
+            assert!(!within.contains(&idx));
+        }
+    }
+
+    #[test]
+    fn test_kd_index_matches_brute_force() {
+        assert_matches_brute_force(NeighborIndex::KdTree, 3, &Euclidean);
+        assert_matches_brute_force(NeighborIndex::KdTree, 3, &Manhattan);
+        assert_matches_brute_force(NeighborIndex::KdTree, 3, &Chebyshev);
+    }
+
+    #[test]
+    fn test_ball_tree_matches_brute_force() {
+        assert_matches_brute_force(NeighborIndex::BallTree, 3, &Euclidean);
+        assert_matches_brute_force(NeighborIndex::BallTree, 20, &Euclidean);
+        assert_matches_brute_force(NeighborIndex::BallTree, 5, &Manhattan);
+        assert_matches_brute_force(NeighborIndex::BallTree, 3, &Periodic{metric: Euclidean, periods: vec![1.; 3]});
+        assert!(BallTree::new(&[], &Euclidean).unwrap().nearest(0, 3).is_empty());
+
+        let mut points: Vec<_> = (0..3).map(|i| LabeledPoint{id: i, value: 0., point: vec![i as f64, 0.]}).collect();
+        points[1].point[0] = f64::NAN;
+        match BallTree::new(&points, &Euclidean) {
+            Err(GraphError::NonFiniteCoordinate{index: 1}) => {},
+            _ => panic!("expected a NonFiniteCoordinate error")
+        }
+        points[1].point = vec![1.];
+        match BallTree::new(&points, &Euclidean) {
+            Err(GraphError::WrongDimension{expected: 2, found: 1}) => {},
+            _ => panic!("expected a WrongDimension error")
+        }
+    }
+
+    #[test]
+    fn test_metric_dimension() {
+        // the third coordinate has no period, so the builders refuse the points instead of panicking
+        let torus = Periodic{metric: Euclidean, periods: vec![1., 1.]};
+        let points: Vec<_> = (0..5)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![0.1 * i as f64, 0., 0.]})
+            .collect();
+        for &kind in [NeighborIndex::KdTree, NeighborIndex::BallTree].iter() {
+            let options = crate::graph::KnnOptions::default();
+            let results = [crate::graph::build_knn_with(&points, 2, &torus, kind, options),
+                           crate::graph::build_epsilon_with(&points, 0.5, crate::graph::EdgeWeighting::Distance, &torus, kind)];
+            for result in results.iter() {
+                match result {
+                    Err(GraphError::WrongDimension{expected: 2, found: 3}) => {},
+                    _ => panic!("expected a WrongDimension error")
+                }
+            }
+        }
+    }
+}
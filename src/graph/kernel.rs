@@ -0,0 +1,101 @@
+//! Kernel edge weights whose bandwidth adapts to the local density of the points.
+use petgraph::graph::UnGraph;
+
+use super::{GraphError, NeighborIndex};
+use super::index::SpatialIndex;
+use crate::LabeledPoint;
+use crate::metric::Metric;
+
+/// Reweights the edges of `graph` with the self-tuning Gaussian kernel of Zelnik-Manor and Perona.
+///
+/// Each point `i` gets its own bandwidth `σ_i`, its distance to its `scale_k`-th nearest neighbor
+/// among all the points of the graph (found with the given kind of spatial index), and the edge
+/// between `i` and `j` gets the weight `exp(-d(i, j)² / (σ_i σ_j))`. A fixed bandwidth, as with
+/// [EdgeWeighting::Gaussian](enum.EdgeWeighting.html#variant.Gaussian), is too narrow in sparse
+/// regions and too wide in dense ones, which distorts the gradients the Morse complexes follow.
+/// The original paper suggests `scale_k = 7`.
+///
+/// This can be applied to a graph from any of the builders, since the weights only depend on the
+/// points. Points with fewer than `scale_k` others use their farthest neighbor instead. Returns an
+/// error if `scale_k` is 0, which would give every point a bandwidth of 0, or if a point has a
+/// bandwidth of 0 (because at least `scale_k` other points are at its location) and an edge to a
+/// point elsewhere, which would get a weight of 0; either way the graph is left as it was.
+pub fn self_tuning_weights<M: Metric<[f64]>>(graph: &mut UnGraph<LabeledPoint<Vec<f64>>, f64>, scale_k: usize,
+                                             metric: &M, index: NeighborIndex)
+    -> Result<(), GraphError> {
+    if scale_k == 0 {
+        return Err(GraphError::ZeroScale{});
+    }
+    let points: Vec<_> = graph.raw_nodes().iter().map(|node| node.weight.clone()).collect();
+    let index = SpatialIndex::new(&points, index, metric)?;
+    let bandwidths: Vec<f64> = index.all_nearest(scale_k.min(points.len().saturating_sub(1)))?.iter()
+        .map(|neighbors| neighbors.last().map(|&(distance, _)| distance).unwrap_or(0.))
+        .collect();
+
+    let mut weights = Vec::with_capacity(graph.edge_count());
+    for edge in graph.raw_edges() {
+        let (i, j) = (edge.source().index(), edge.target().index());
+        let distance = metric.distance(&points[i].point, &points[j].point);
+        // coincident points are as similar as points can be, whatever their bandwidths
+        if distance == 0. {
+            weights.push(1.);
+            continue;
+        }
+        if let Some(&index) = [i, j].iter().find(|&&k| bandwidths[k] == 0.) {
+            return Err(GraphError::ZeroBandwidth{index});
+        }
+        weights.push((-distance * distance / (bandwidths[i] * bandwidths[j])).exp());
+    }
+    for (weight, new) in graph.edge_weights_mut().zip(weights) {
+        *weight = new;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{build_knn, EdgeWeighting};
+    use crate::metric::Euclidean;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_self_tuning_weights() {
+        // a tight cluster and a spread out one, with the same relative spacing
+        let coords = [0., 0.1, 0.2, 10., 11., 12.];
+        let points: Vec<_> = coords.iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: 0., point: vec![x]})
+            .collect();
+        let mut graph = build_knn(&points, 2).unwrap();
+        self_tuning_weights(&mut graph, 2, &Euclidean, NeighborIndex::KdTree).unwrap();
+
+        let tight = graph[graph.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap()];
+        let spread = graph[graph.find_edge(NodeIndex::new(3), NodeIndex::new(4)).unwrap()];
+        assert!((tight - (-0.5f64).exp()).abs() < 1e-9);
+        assert!((spread - tight).abs() < 1e-9);
+        assert!(graph.raw_edges().iter().all(|e| e.weight > 0. && e.weight <= 1.));
+
+        // with a fixed bandwidth, the two clusters look nothing alike
+        let g = crate::graph::build_epsilon(&points, 1., EdgeWeighting::Gaussian(1.)).unwrap();
+        let tight = g[g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap()];
+        let spread = g[g.find_edge(NodeIndex::new(3), NodeIndex::new(4)).unwrap()];
+        assert!(tight - spread > 0.3);
+
+        // three copies of the first point leave it no bandwidth at scale 2
+        let mut repeated = points.clone();
+        repeated.extend((0..2).map(|i| LabeledPoint{id: 6 + i, value: 0., point: vec![0.]}));
+        let mut graph = build_knn(&repeated, 3).unwrap();
+        let before: Vec<f64> = graph.edge_weights_mut().map(|w| *w).collect();
+        match self_tuning_weights(&mut graph, 2, &Euclidean, NeighborIndex::KdTree) {
+            Err(GraphError::ZeroBandwidth{..}) => (),
+            other => panic!("expected a zero bandwidth error, got {:?}", other)
+        }
+        assert_eq!(graph.edge_weights_mut().map(|w| *w).collect::<Vec<f64>>(), before);
+
+        match self_tuning_weights(&mut graph, 0, &Euclidean, NeighborIndex::KdTree) {
+            Err(GraphError::ZeroScale{}) => (),
+            other => panic!("expected a zero scale error, got {:?}", other)
+        }
+        assert_eq!(graph.edge_weights_mut().map(|w| *w).collect::<Vec<f64>>(), before);
+    }
+}
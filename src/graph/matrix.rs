@@ -0,0 +1,176 @@
+//! Graphs from precomputed distances, for points that have no coordinates of their own.
+use std::cmp::Ordering;
+use petgraph::graph::UnGraph;
+
+use super::{GraphError, EdgeWeighting};
+use crate::LabeledPoint;
+
+/// How to choose the neighbors of each point from its distances to the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborStrategy {
+    /// Connect each point to its `k` nearest neighbors.
+    Knn(usize),
+
+    /// Connect every pair of points within the given distance of each other.
+    Epsilon(f64)
+}
+
+/// Constructs a neighbor graph from a dense matrix of the distances between `points`.
+///
+/// `distances[i][j]` is the distance from `points[i]` to `points[j]`, and is assumed to be
+/// symmetric. Since the distances are all that's used, the points don't need coordinates at all:
+/// `LabeledPoint<()>` works. Edges are weighted according to `weighting`.
+///
+/// Returns an error if the matrix isn't square with one row per point, or if any distance is NaN.
+pub fn from_distance_matrix<T: Clone>(points: &[LabeledPoint<T>], distances: &[Vec<f64>], strategy: NeighborStrategy,
+                                      weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
+    if points.iter().any(|p| p.value.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    if let Some(row) = distances.iter().find(|row| row.len() != points.len()) {
+        return Err(GraphError::MatrixShape{points: points.len(), rows: distances.len(), columns: row.len()});
+    }
+    if distances.len() != points.len() {
+        return Err(GraphError::MatrixShape{points: points.len(), rows: distances.len(), columns: points.len()});
+    }
+    for (i, row) in distances.iter().enumerate() {
+        if let Some(j) = row.iter().position(|d| d.is_nan()) {
+            return Err(GraphError::NanWeight{first: i, second: j})
+        }
+    }
+
+    let mut graph = UnGraph::new_undirected();
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| graph.add_node(point.clone()))
+        .collect();
+    match strategy {
+        NeighborStrategy::Knn(k) => {
+            if k >= points.len() && !points.is_empty() {
+                return Err(GraphError::KTooLarge{k, num_points: points.len()})
+            }
+            for (i, row) in distances.iter().enumerate() {
+                let mut neighbors: Vec<usize> = (0..row.len()).filter(|&j| j != i).collect();
+                neighbors.sort_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap_or(Ordering::Equal));
+                for &j in neighbors.iter().take(k) {
+                    graph.update_edge(node_lookup[i], node_lookup[j], weighting.weight(row[j]));
+                }
+            }
+        },
+        NeighborStrategy::Epsilon(epsilon) => {
+            for (i, row) in distances.iter().enumerate() {
+                for (j, &distance) in row.iter().enumerate().skip(i + 1) {
+                    if distance <= epsilon {
+                        graph.add_edge(node_lookup[i], node_lookup[j], weighting.weight(distance));
+                    }
+                }
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// Constructs a graph from a sparse set of `(i, j, distance)` triplets between `points`.
+///
+/// Every triplet becomes an edge between `points[i]` and `points[j]`, weighted according to
+/// `weighting`. This is the natural input when the neighbors have already been found elsewhere,
+/// e.g. by an approximate nearest neighbor library, since the distances never have to be
+/// densified into a full matrix. Triplets with `i == j` are ignored, and if a pair appears more
+/// than once (such as both `(i, j, d)` and `(j, i, d)`), the smallest distance is used.
+pub fn from_sparse_distances<T: Clone>(points: &[LabeledPoint<T>], triplets: &[(usize, usize, f64)], weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<T>, f64>, GraphError> {
+    if points.iter().any(|p| p.value.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    for &(i, j, distance) in triplets {
+        if i.max(j) >= points.len() {
+            return Err(GraphError::IndexOutOfBounds{index: i.max(j), num_points: points.len()});
+        }
+        if distance.is_nan() {
+            return Err(GraphError::NanWeight{first: i, second: j})
+        }
+    }
+
+    let mut graph = UnGraph::new_undirected();
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| graph.add_node(point.clone()))
+        .collect();
+    let mut distances = Vec::with_capacity(triplets.len());
+    for &(i, j, distance) in triplets.iter().filter(|&&(i, j, _)| i != j) {
+        let edge = graph.update_edge(node_lookup[i], node_lookup[j], distance);
+        if edge.index() == distances.len() {
+            distances.push(distance);
+        } else {
+            distances[edge.index()] = distances[edge.index()].min(distance);
+        }
+    }
+    for (weight, &distance) in graph.edge_weights_mut().zip(distances.iter()) {
+        *weight = weighting.weight(distance);
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    fn unlabeled(values: &[f64]) -> Vec<LabeledPoint<()>> {
+        values.iter().enumerate()
+            .map(|(i, &value)| LabeledPoint{id: i as i64, value, point: ()})
+            .collect()
+    }
+
+    #[test]
+    fn test_from_distance_matrix() {
+        let points = unlabeled(&[1., 2., 3., 4.]);
+        let distances = vec![
+            vec![0., 1., 5., 6.],
+            vec![1., 0., 2., 7.],
+            vec![5., 2., 0., 3.],
+            vec![6., 7., 3., 0.]
+        ];
+        let g = from_distance_matrix(&points, &distances, NeighborStrategy::Knn(1), EdgeWeighting::Distance).unwrap();
+        // 0 -> 1, 1 -> 0, 2 -> 1, 3 -> 2
+        assert_eq!(g.edge_count(), 3);
+        let edge = g.find_edge(NodeIndex::new(2), NodeIndex::new(3)).unwrap();
+        assert_eq!(g[edge], 3.);
+
+        let g = from_distance_matrix(&points, &distances, NeighborStrategy::Epsilon(2.5), EdgeWeighting::Unit).unwrap();
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.find_edge(NodeIndex::new(1), NodeIndex::new(2)).is_some());
+        assert!(g.raw_edges().iter().all(|e| e.weight == 1.));
+    }
+
+    #[test]
+    fn test_from_distance_matrix_errors() {
+        let points = unlabeled(&[1., 2.]);
+        let ragged = vec![vec![0., 1.], vec![1.]];
+        assert!(from_distance_matrix(&points, &ragged, NeighborStrategy::Knn(1), EdgeWeighting::Distance).is_err());
+        let short = vec![vec![0., 1.]];
+        assert!(from_distance_matrix(&points, &short, NeighborStrategy::Knn(1), EdgeWeighting::Distance).is_err());
+        let square = vec![vec![0., 1.], vec![1., 0.]];
+        assert!(from_distance_matrix(&points, &square, NeighborStrategy::Knn(2), EdgeWeighting::Distance).is_err());
+        let missing = vec![vec![0., 1.], vec![f64::NAN, 0.]];
+        match from_distance_matrix(&points, &missing, NeighborStrategy::Knn(1), EdgeWeighting::Distance) {
+            Err(GraphError::NanWeight{first: 1, second: 0}) => (),
+            other => panic!("expected a NaN weight error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_from_sparse_distances() {
+        let points = unlabeled(&[1., 2., 3., 4.]);
+        let triplets = [(0, 1, 1.), (1, 0, 0.5), (1, 2, 2.), (3, 3, 0.), (2, 3, 3.)];
+        let g = from_sparse_distances(&points, &triplets, EdgeWeighting::Distance).unwrap();
+        assert_eq!(g.edge_count(), 3);
+        let edge = g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+        assert_eq!(g[edge], 0.5);
+
+        assert!(from_sparse_distances(&points, &[(0, 4, 1.)], EdgeWeighting::Distance).is_err());
+        match from_sparse_distances(&points, &[(0, 1, f64::NAN)], EdgeWeighting::Distance) {
+            Err(GraphError::NanWeight{first: 0, second: 1}) => (),
+            other => panic!("expected a NaN weight error, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Graphs from triangle meshes, the classic setting for discrete Morse theory.
+use petgraph::graph::UnGraph;
+
+use super::GraphError;
+use crate::LabeledPoint;
+use crate::metric::{Metric, Euclidean};
+
+/// Constructs the graph of the edges of an indexed triangle mesh.
+///
+/// Each of the `vertices` becomes a node, labeled with the matching entry of `values` and with its
+/// index as its id, and each edge of the `faces` becomes an edge of the graph, weighted by its
+/// Euclidean length. Edges shared by several faces only appear once, and degenerate faces that
+/// repeat a vertex don't produce self-loops.
+///
+/// Returns an error if there isn't one value per vertex, if the vertices don't all have the same
+/// dimension, or if a face refers to a vertex that doesn't exist.
+pub fn from_triangle_mesh(vertices: &[Vec<f64>], faces: &[[usize; 3]], values: &[f64])
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    if values.len() != vertices.len() {
+        return Err(GraphError::ValueCount{vertices: vertices.len(), values: values.len()});
+    }
+    if values.iter().chain(vertices.iter().flatten()).any(|v| v.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    let dim = vertices.first().map(|v| v.len()).unwrap_or(0);
+    if let Some(vertex) = vertices.iter().find(|v| v.len() != dim) {
+        return Err(GraphError::WrongDimension{expected: dim, found: vertex.len()});
+    }
+    if let Some(&index) = faces.iter().flatten().find(|&&i| i >= vertices.len()) {
+        return Err(GraphError::IndexOutOfBounds{index, num_points: vertices.len()});
+    }
+
+    let mut graph = UnGraph::with_capacity(vertices.len(), 3 * faces.len() / 2);
+    let node_lookup: Vec<_> = vertices.iter().zip(values.iter()).enumerate()
+        .map(|(i, (vertex, &value))| graph.add_node(LabeledPoint{id: i as i64, value, point: vertex.clone()}))
+        .collect();
+    for face in faces {
+        for &(i, j) in &[(face[0], face[1]), (face[1], face[2]), (face[2], face[0])] {
+            if i != j {
+                let length = Metric::<[f64]>::distance(&Euclidean, &vertices[i], &vertices[j]);
+                graph.update_edge(node_lookup[i], node_lookup[j], length);
+            }
+        }
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_from_triangle_mesh() {
+        // a square pyramid without its base
+        let vertices = vec![
+            vec![0., 0., 0.], vec![1., 0., 0.], vec![1., 1., 0.], vec![0., 1., 0.], vec![0.5, 0.5, 1.]
+        ];
+        let faces = [[0, 1, 4], [1, 2, 4], [2, 3, 4], [3, 0, 4], [0, 0, 1]];
+        let values = [0., 1., 2., 3., 4.];
+        let g = from_triangle_mesh(&vertices, &faces, &values).unwrap();
+        assert_eq!(g.node_count(), 5);
+        assert_eq!(g.edge_count(), 8);
+        assert_eq!(g[NodeIndex::new(4)].value, 4.);
+        let edge = g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+        assert_eq!(g[edge], 1.);
+        let edge = g.find_edge(NodeIndex::new(2), NodeIndex::new(4)).unwrap();
+        assert!((g[edge] - 1.5f64.sqrt()).abs() < 1e-12);
+
+        assert!(from_triangle_mesh(&vertices, &faces, &values[..4]).is_err());
+        assert!(from_triangle_mesh(&vertices, &[[0, 1, 5]], &values).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Removing spurious long edges from neighbor graphs.
+//!
+//! A `k`-NN graph connects every point to `k` others no matter how far away they are, so isolated
+//! points and the edges of clusters pick up long edges to unrelated regions. Since the Morse
+//! complexes follow steepest neighbors, those edges let persistence leak between cells that
+//! should have nothing to do with each other.
+use std::cmp::Ordering;
+use petgraph::graph::UnGraph;
+
+use super::GraphError;
+use crate::util::PointedUnionFind;
+
+/// Removes the edges of `graph` that are longer than the given `percentile` (between 0 and 100) of
+/// its edge weights, returning how many were removed.
+///
+/// This treats the edge weights as lengths, so it should be used on graphs weighted by distance.
+/// With `keep_connected`, just enough of the long edges are kept (the shortest ones possible) so
+/// that points that were connected before pruning are still connected afterwards.
+pub fn prune_long_edges<N>(graph: &mut UnGraph<N, f64>, percentile: f64, keep_connected: bool) -> Result<usize, GraphError> {
+    if !(0. ..=100.).contains(&percentile) {
+        return Err(GraphError::InvalidPercentile{percentile});
+    }
+    if let Some(edge) = graph.raw_edges().iter().find(|e| e.weight.is_nan()) {
+        return Err(GraphError::NanWeight{first: edge.source().index(), second: edge.target().index()})
+    }
+    let mut order: Vec<usize> = (0..graph.edge_count()).collect();
+    order.sort_by(|&a, &b| graph.raw_edges()[a].weight.partial_cmp(&graph.raw_edges()[b].weight).unwrap_or(Ordering::Equal));
+    if order.is_empty() {
+        return Ok(0);
+    }
+    let rank = ((percentile / 100. * order.len() as f64).ceil() as usize).max(1);
+    let threshold = graph.raw_edges()[order[rank - 1]].weight;
+
+    // Kruskal's algorithm over the long edges, after all the short ones are in
+    let mut keep = vec![false; graph.edge_count()];
+    let mut components = PointedUnionFind::new(graph.node_count());
+    for &e in &order {
+        let edge = &graph.raw_edges()[e];
+        let joins = components.union(edge.source().index(), edge.target().index());
+        keep[e] = edge.weight <= threshold || (keep_connected && joins);
+    }
+    let before = graph.edge_count();
+    graph.retain_edges(|_, e| keep[e.index()]);
+    Ok(before - graph.edge_count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+    use crate::graph::{build_knn, component_labels};
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_prune_long_edges() {
+        let coords = [0., 1., 2., 3., 10., 11.];
+        let points: Vec<_> = coords.iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect();
+        // 3 and 4 pick each other up as second nearest neighbors across the gap
+        let graph = build_knn(&points, 2).unwrap();
+        assert_eq!(graph.edge_count(), 8);
+
+        let mut pruned = graph.clone();
+        assert_eq!(prune_long_edges(&mut pruned, 75., false).unwrap(), 2);
+        assert_eq!(component_labels(&pruned), vec![0, 0, 0, 0, 1, 1]);
+
+        let mut pruned = graph.clone();
+        assert_eq!(prune_long_edges(&mut pruned, 75., true).unwrap(), 1);
+        assert!(component_labels(&pruned).iter().all(|&l| l == 0));
+        assert_eq!(prune_long_edges(&mut pruned, 0., false).unwrap(), 3);
+
+        assert!(prune_long_edges(&mut pruned, 101., false).is_err());
+
+        let mut broken = graph.clone();
+        broken.add_edge(NodeIndex::new(0), NodeIndex::new(5), f64::NAN);
+        match prune_long_edges(&mut broken, 50., false) {
+            Err(GraphError::NanWeight{first: 0, second: 5}) => (),
+            other => panic!("expected a NaN weight error, got {:?}", other)
+        }
+    }
+}
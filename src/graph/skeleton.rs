@@ -0,0 +1,150 @@
+//! β-skeletons, a family of proximity graphs with a tunable sparsity.
+//!
+//! Two points are connected in the β-skeleton when a region between them (their "lune") contains
+//! no other point. The lune grows with β, so the graphs get sparser as β increases: β = 1 gives the
+//! Gabriel graph and β = 2 the relative neighborhood graph, while β close to 0 approaches the
+//! complete graph. Sweeping β shows how the sparsity of the graph affects the persistence diagram.
+use petgraph::graph::UnGraph;
+
+use super::{GraphError, EdgeWeighting, build_delaunay_2d, build_delaunay_3d};
+use crate::LabeledPoint;
+
+// Lets points that lie exactly on the boundary of a lune (as happens on grids) count as inside of
+// it despite rounding
+const TOLERANCE: f64 = 1e-12;
+
+fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum()
+}
+
+// Whether r is inside the closed lune of p and q, ignoring points that coincide with either one
+fn in_lune(p: &[f64], q: &[f64], r: &[f64], beta: f64) -> bool {
+    let (rp, rq) = (squared_distance(r, p), squared_distance(r, q));
+    if rp == 0. || rq == 0. {
+        return false;
+    }
+    if beta >= 1. {
+        // the intersection of the two balls of radius βd/2 centered on the line through p and q,
+        // each touching one of them
+        let radius_squared = (beta / 2.).powi(2) * squared_distance(p, q) * (1. + TOLERANCE);
+        let first: Vec<f64> = p.iter().zip(q).map(|(x, y)| (1. - beta / 2.) * x + beta / 2. * y).collect();
+        let second: Vec<f64> = p.iter().zip(q).map(|(x, y)| beta / 2. * x + (1. - beta / 2.) * y).collect();
+        squared_distance(r, &first) <= radius_squared && squared_distance(r, &second) <= radius_squared
+    } else {
+        // the intersection of the balls of radius d/2β through both p and q, which is where pq
+        // subtends an angle of at least π - arcsin(β)
+        let dot: f64 = p.iter().zip(q).zip(r).map(|((x, y), z)| (x - z) * (y - z)).sum();
+        dot / (rp * rq).sqrt() <= -(1. - beta * beta).sqrt() + TOLERANCE
+    }
+}
+
+/// Constructs the (lune-based) β-skeleton of a set of `points`, for any `beta >= 0`.
+///
+/// Lunes are closed, so a point on the boundary of the lune of two others keeps them apart. This
+/// matters for degenerate inputs such as grids: the Gabriel graph of a grid has no diagonals, since
+/// the other corners of each cell lie on the circle around its diagonals. Returns an error if two
+/// points are at the same location, as [build_delaunay_2d](fn.build_delaunay_2d.html) does.
+///
+/// Edges are weighted according to `weighting`, based on the Euclidean distance between their
+/// endpoints. For `beta >= 1`, the skeleton is a subgraph of the Delaunay triangulation, so in 2 and
+/// 3 dimensions only its edges are checked, which takes `O(n^2)` time. Otherwise every pair of
+/// points is checked against every other point, which takes `O(n^3)` time.
+pub fn build_beta_skeleton(points: &[LabeledPoint<Vec<f64>>], beta: f64, weighting: EdgeWeighting)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    if beta.is_nan() || beta < 0. {
+        return Err(GraphError::InvalidBeta{beta});
+    }
+    if points.iter().any(|p| p.value.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    let dim = points.first().map(|p| p.point.len()).unwrap_or(0);
+    if let Some(point) = points.iter().find(|p| p.point.len() != dim) {
+        return Err(GraphError::WrongDimension{expected: dim, found: point.point.len()});
+    }
+
+    let candidates: Vec<(usize, usize)> = match dim {
+        2 | 3 if beta >= 1. => {
+            let delaunay = if dim == 2 {
+                build_delaunay_2d(points, EdgeWeighting::Unit)?
+            } else {
+                build_delaunay_3d(points, EdgeWeighting::Unit)?
+            };
+            delaunay.raw_edges().iter().map(|e| (e.source().index(), e.target().index())).collect()
+        },
+        _ => (0..points.len()).flat_map(|i| ((i + 1)..points.len()).map(move |j| (i, j))).collect()
+    };
+
+    let mut graph = UnGraph::new_undirected();
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| graph.add_node(point.clone()))
+        .collect();
+    for (i, j) in candidates {
+        let (p, q) = (&points[i].point, &points[j].point);
+        if squared_distance(p, q) == 0. {
+            return Err(GraphError::DuplicatePoint{index: i.max(j), original: i.min(j)});
+        }
+        if !points.iter().any(|r| in_lune(p, q, &r.point, beta)) {
+            graph.add_edge(node_lookup[i], node_lookup[j], weighting.weight(squared_distance(p, q).sqrt()));
+        }
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use rand::prelude::*;
+
+    fn edge_set(graph: &UnGraph<LabeledPoint<Vec<f64>>, f64>) -> HashSet<(usize, usize)> {
+        graph.raw_edges().iter()
+            .map(|e| (e.source().index().min(e.target().index()), e.source().index().max(e.target().index())))
+            .collect()
+    }
+
+    #[test]
+    fn test_beta_skeleton_grid() {
+        let points: Vec<_> = (0..9)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![(i / 3) as f64, (i % 3) as f64]})
+            .collect();
+        assert_eq!(build_beta_skeleton(&points, 1., EdgeWeighting::Distance).unwrap().edge_count(), 12);
+        assert_eq!(build_beta_skeleton(&points, 2., EdgeWeighting::Distance).unwrap().edge_count(), 12);
+        // every pair except those with a third point exactly between them
+        assert_eq!(build_beta_skeleton(&points, 0., EdgeWeighting::Distance).unwrap().edge_count(), 28);
+        assert!(build_beta_skeleton(&points, -1., EdgeWeighting::Distance).is_err());
+
+        let mut repeated = points.clone();
+        repeated.push(LabeledPoint{id: 9, value: 0., point: vec![1., 1.]});
+        for &beta in &[0., 1.] {
+            match build_beta_skeleton(&repeated, beta, EdgeWeighting::Distance) {
+                Err(GraphError::DuplicatePoint{index: 9, original: 4}) => (),
+                other => panic!("expected a duplicate point error, got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_beta_skeleton_nested() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let points: Vec<_> = (0..60)
+            .map(|i| LabeledPoint{id: i, value: 0., point: vec![rng.gen_range(0., 1.), rng.gen_range(0., 1.)]})
+            .collect();
+        let skeletons: Vec<_> = [0.5, 1., 1.5, 2.].iter()
+            .map(|&beta| edge_set(&build_beta_skeleton(&points, beta, EdgeWeighting::Distance).unwrap()))
+            .collect();
+        for pair in skeletons.windows(2) {
+            assert!(pair[1].is_subset(&pair[0]));
+        }
+        let delaunay = edge_set(&build_delaunay_2d(&points, EdgeWeighting::Distance).unwrap());
+        assert!(skeletons[1].is_subset(&delaunay));
+
+        // the same points in 4 dimensions check every pair instead of the Delaunay edges
+        let padded: Vec<_> = points.iter()
+            .map(|p| LabeledPoint{id: p.id, value: p.value, point: vec![p.point[0], 0., p.point[1], 0.]})
+            .collect();
+        for (i, &beta) in [1., 2.].iter().enumerate() {
+            let brute = edge_set(&build_beta_skeleton(&padded, beta, EdgeWeighting::Distance).unwrap());
+            assert_eq!(brute, skeletons[2 * i + 1]);
+        }
+    }
+}
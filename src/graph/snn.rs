@@ -0,0 +1,115 @@
+//! Shared nearest neighbor graphs.
+//!
+//! In high dimensions, distances concentrate and a point's nearest neighbors say more about it
+//! than the distances to them do. A shared nearest neighbor (SNN) graph connects points whose
+//! neighborhoods overlap, weighted by how much they overlap, which is far more robust there.
+use std::collections::HashMap;
+use petgraph::graph::UnGraph;
+
+use super::{GraphError, NeighborIndex};
+use super::index::SpatialIndex;
+use crate::LabeledPoint;
+use crate::metric::Metric;
+
+/// Converts a similarity between 0 and 1 into an edge weight.
+///
+/// The Morse complexes treat edge weights like distances, so similarities usually need to be
+/// turned around first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimilarityWeighting {
+    /// The similarity itself.
+    Similarity,
+
+    /// One minus the similarity, so that more similar points are closer together, offset by the
+    /// similarity of a single shared point (`1 / (k + 1)`). Edge weights of 0 make the steepness
+    /// along an edge infinite, so even points with identical neighborhoods are kept apart.
+    Dissimilarity
+}
+
+impl SimilarityWeighting {
+    // The weight of an edge between points sharing `shared` of the `size` points of a neighborhood
+    fn weight(self, shared: usize, size: usize) -> f64 {
+        let similarity = shared as f64 / size as f64;
+        match self {
+            SimilarityWeighting::Similarity => similarity,
+            SimilarityWeighting::Dissimilarity => 1. - similarity + 1. / size as f64
+        }
+    }
+}
+
+/// Constructs a shared nearest neighbor graph from a set of `points`.
+///
+/// The neighborhood of each point is itself together with its `k` nearest neighbors under
+/// `metric`, found with the given kind of spatial index. Every pair of points whose neighborhoods
+/// share at least `min_shared` points is connected, and their similarity is the fraction of the
+/// `k + 1` points of a neighborhood that they share, converted to an edge weight with `weighting`.
+pub fn build_snn<M: Metric<[f64]>>(points: &[LabeledPoint<Vec<f64>>], k: usize, min_shared: usize,
+                                   weighting: SimilarityWeighting, metric: &M, index: NeighborIndex)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    if points.iter().any(|p| p.value.is_nan()) {
+        return Err(GraphError::NanInPoints{})
+    }
+    if k >= points.len() && !points.is_empty() {
+        return Err(GraphError::KTooLarge{k, num_points: points.len()})
+    }
+    let index = SpatialIndex::new(points, index, metric)?;
+
+    // members[m] lists the points whose neighborhoods contain m, so every pair of them shares m
+    let mut members = vec![vec![]; points.len()];
+    for (i, neighbors) in index.all_nearest(k)?.into_iter().enumerate() {
+        members[i].push(i);
+        for (_, j) in neighbors {
+            members[j].push(i);
+        }
+    }
+    let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+    for member in members.iter_mut() {
+        member.sort_unstable();
+        for (a, &i) in member.iter().enumerate() {
+            for &j in &member[(a + 1)..] {
+                *shared.entry((i, j)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut graph = UnGraph::new_undirected();
+    let node_lookup: Vec<_> = points.iter()
+        .map(|point| graph.add_node(point.clone()))
+        .collect();
+    let mut pairs: Vec<_> = shared.into_iter().filter(|&(_, count)| count >= min_shared.max(1)).collect();
+    pairs.sort_unstable();
+    for ((i, j), count) in pairs {
+        graph.add_edge(node_lookup[i], node_lookup[j], weighting.weight(count, k + 1));
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metric::Euclidean;
+    use petgraph::graph::NodeIndex;
+
+    #[test]
+    fn test_build_snn() {
+        let coords = [0., 1., 2.5, 10., 11., 12.5];
+        let points: Vec<_> = coords.iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect();
+        let g = build_snn(&points, 2, 1, SimilarityWeighting::Similarity, &Euclidean, NeighborIndex::KdTree).unwrap();
+        // each triple shares all of its points, and nothing is shared across the gap
+        assert_eq!(g.edge_count(), 6);
+        let edge = g.find_edge(NodeIndex::new(0), NodeIndex::new(2)).unwrap();
+        assert_eq!(g[edge], 1.);
+        assert!(g.find_edge(NodeIndex::new(2), NodeIndex::new(3)).is_none());
+
+        let g = build_snn(&points, 1, 2, SimilarityWeighting::Dissimilarity, &Euclidean, NeighborIndex::BruteForce).unwrap();
+        // 0 and 1 are each other's nearest neighbors; 2's nearest is 1, so it shares only 1 with 0
+        let edge = g.find_edge(NodeIndex::new(0), NodeIndex::new(1)).unwrap();
+        assert_eq!(g[edge], 0.5);
+        assert!(g.raw_edges().iter().all(|e| e.weight > 0.));
+        assert!(g.find_edge(NodeIndex::new(0), NodeIndex::new(2)).is_none());
+
+        assert!(build_snn(&points, 6, 1, SimilarityWeighting::Similarity, &Euclidean, NeighborIndex::KdTree).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Subsampling point clouds down to a set of landmarks.
+//!
+//! Huge point clouds can be analyzed at a reduced resolution by building the graph over a subset
+//! of landmark points instead, then pushing the results back out to every point through its
+//! nearest landmark. Farthest point (maxmin) sampling picks landmarks that cover the cloud evenly,
+//! while random sampling follows its density.
+use std::f64;
+use std::cmp::Ordering;
+use rand::Rng;
+
+use crate::LabeledPoint;
+use crate::metric::Metric;
+
+/// A subsample of a point cloud, along with the landmark nearest to each of the original points.
+#[derive(Debug, Clone)]
+pub struct Landmarks<T> {
+    /// The landmarks themselves, in the order they were chosen.
+    pub points: Vec<LabeledPoint<T>>,
+
+    /// The index of each landmark among the original points.
+    pub indices: Vec<usize>,
+
+    /// For each of the original points, the position in `points` of its nearest landmark.
+    pub nearest: Vec<usize>
+}
+
+impl<T> Landmarks<T> {
+    /// Pushes results computed for each landmark, such as Morse cells of a graph over `points`,
+    /// back out to every one of the original points.
+    pub fn extend<V: Clone>(&self, landmark_results: &[V]) -> Vec<V> {
+        self.nearest.iter().map(|&l| landmark_results[l].clone()).collect()
+    }
+}
+
+/// Chooses `n` landmarks by farthest point sampling: starting from the first point, each
+/// landmark is the point farthest from all of the landmarks chosen before it.
+///
+/// This spreads the landmarks evenly over the cloud, whatever its density, and every point ends up
+/// no farther from its nearest landmark than the landmarks are from each other. It takes `O(nm)`
+/// distance computations for `m` points. At least one landmark is chosen (unless there are no
+/// points), and at most every point.
+pub fn maxmin_landmarks<T: Clone, M: Metric<T>>(points: &[LabeledPoint<T>], n: usize, metric: &M) -> Landmarks<T> {
+    let mut indices = Vec::with_capacity(n);
+    let mut closest = vec![(f64::INFINITY, 0); points.len()];
+    let mut chosen = vec![false; points.len()];
+    let mut next = if points.is_empty() { None } else { Some(0) };
+    while let Some(landmark) = next {
+        let position = indices.len();
+        indices.push(landmark);
+        chosen[landmark] = true;
+        for (point, closest) in points.iter().zip(closest.iter_mut()) {
+            let distance = metric.distance(&points[landmark].point, &point.point);
+            if distance < closest.0 || position == 0 {
+                *closest = (distance, position);
+            }
+        }
+        next = if indices.len() >= n.min(points.len()) {
+            None
+        } else {
+            closest.iter().enumerate()
+                .filter(|&(i, _)| !chosen[i])
+                .max_by(|a, b| (a.1).0.partial_cmp(&(b.1).0).unwrap_or(Ordering::Equal))
+                .map(|(i, _)| i)
+        };
+    }
+    landmarks_at(points, indices, closest.into_iter().map(|(_, l)| l).collect())
+}
+
+/// Chooses `n` distinct landmarks uniformly at random, so that dense regions of the cloud get more
+/// of them.
+///
+/// Finding the nearest landmarks takes `O(nm)` distance computations for `m` points. At least one
+/// landmark is chosen (unless there are no points), and at most every point.
+pub fn random_landmarks<T: Clone, M: Metric<T>, R: Rng>(points: &[LabeledPoint<T>], n: usize, metric: &M, rng: &mut R)
+    -> Landmarks<T> {
+    let count = n.max(1).min(points.len());
+    let indices = rand::seq::index::sample(rng, points.len(), count).into_vec();
+    let nearest = points.iter()
+        .map(|point| {
+            indices.iter().enumerate()
+                .map(|(l, &i)| (metric.distance(&points[i].point, &point.point), l))
+                .fold((f64::INFINITY, 0), |best, candidate| if candidate.0 < best.0 { candidate } else { best })
+                .1
+        })
+        .collect();
+    landmarks_at(points, indices, nearest)
+}
+
+fn landmarks_at<T: Clone>(points: &[LabeledPoint<T>], indices: Vec<usize>, nearest: Vec<usize>) -> Landmarks<T> {
+    Landmarks{points: indices.iter().map(|&i| points[i].clone()).collect(), indices, nearest}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+    use crate::metric::Euclidean;
+
+    fn line(coords: &[f64]) -> Vec<LabeledPoint<Vec<f64>>> {
+        coords.iter().enumerate()
+            .map(|(i, &x)| LabeledPoint{id: i as i64, value: x, point: vec![x]})
+            .collect()
+    }
+
+    #[test]
+    fn test_maxmin_landmarks() {
+        let points = line(&[0., 0.1, 0.2, 5., 5.1, 10.]);
+        let landmarks = maxmin_landmarks(&points, 3, &Euclidean);
+        assert_eq!(landmarks.indices, vec![0, 5, 3]);
+        assert_eq!(landmarks.points[2].value, 5.);
+        assert_eq!(landmarks.nearest, vec![0, 0, 0, 2, 2, 1]);
+        assert_eq!(landmarks.extend(&["a", "b", "c"]), vec!["a", "a", "a", "c", "c", "b"]);
+
+        assert_eq!(maxmin_landmarks(&points, 0, &Euclidean).indices, vec![0]);
+        assert_eq!(maxmin_landmarks(&points, 10, &Euclidean).indices.len(), 6);
+        assert!(maxmin_landmarks(&line(&[]), 3, &Euclidean).nearest.is_empty());
+    }
+
+    #[test]
+    fn test_random_landmarks() {
+        let points = line(&[0., 0.1, 0.2, 5., 5.1, 10.]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let landmarks = random_landmarks(&points, 3, &Euclidean, &mut rng);
+        assert_eq!(landmarks.points.len(), 3);
+        for (i, &l) in landmarks.nearest.iter().enumerate() {
+            let distance = (points[i].value - landmarks.points[l].value).abs();
+            assert!(landmarks.points.iter().all(|p| (points[i].value - p.value).abs() >= distance));
+        }
+        for (position, &i) in landmarks.indices.iter().enumerate() {
+            assert_eq!(landmarks.nearest[i], position);
+        }
+    }
+}
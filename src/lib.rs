@@ -18,17 +18,36 @@ use csv::StringRecord;
 pub mod morse;
 pub mod graph;
 pub mod python;
+pub mod distance;
+pub mod stats;
+pub mod circular;
+pub mod util;
+pub mod metric;
+pub mod synthetic;
+pub mod landmarks;
+pub mod merge_tree;
+pub mod contour_tree;
+pub mod persistence;
+pub mod vega;
+pub mod linkage;
 
+#[cfg(test)]
+mod fixtures;
 
+
+/// The distance between two points of the same type.
+///
+/// Superseded by [metric::Metric](metric/trait.Metric.html), which separates the distance from the
+/// type of the points, so the same points can be compared in different ways.
+#[deprecated(note = "use talus::metric::Metric, e.g. talus::metric::Euclidean")]
 pub trait Metric {
     fn distance(&self, other: &Self) -> f64;
 }
 
+#[allow(deprecated)]
 impl Metric for Vec<f64> {
     fn distance(&self, other:&Self) -> f64 {
-        self.iter().zip(other.iter())
-            .map(|(a, b)| (a - b).powi(2))
-            .sum::<f64>().sqrt()
+        metric::Metric::<[f64]>::distance(&metric::Euclidean, self, other)
     }
 }
 
@@ -38,7 +57,33 @@ pub trait PreMetric {
 
 impl PreMetric for Vec<f64> {
     fn predistance(&self, other:&Self) -> f64 {
-        self.distance(other)
+        metric::Metric::<[f64]>::distance(&metric::Euclidean, self, other)
+    }
+}
+
+/// A node weight carrying the scalar value of a function, as needed to build a Morse complex.
+///
+/// Implementing this lets graphs of any node type be analyzed directly, without copying them into
+/// [LabeledPoint](struct.LabeledPoint.html)s.
+pub trait ScalarValued {
+    /// The value of the function at this node.
+    fn value(&self) -> f64;
+
+    /// A unique identifier for this node, used by
+    /// [TieBreak::Id](morse/enum.TieBreak.html#variant.Id). Nodes without one are identified by
+    /// their index instead.
+    fn id(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl<T> ScalarValued for LabeledPoint<T> {
+    fn value(&self) -> f64 {
+        self.value
+    }
+
+    fn id(&self) -> Option<i64> {
+        Some(self.id)
     }
 }
 
@@ -0,0 +1,221 @@
+//! Single-linkage clustering of the nodes of a graph, by the weights of its edges.
+//!
+//! The Morse sweep grows the components of the level sets of a function, node by node. Sweeping
+//! through the edges in order of weight instead, with every node present from the start, grows
+//! the clusters of single-linkage clustering: two clusters join as soon as any edge between them
+//! is added. Over a kNN graph (or any graph containing the minimum spanning tree of the points),
+//! this is the usual single-linkage hierarchy of the point cloud.
+use std::collections::HashMap;
+use petgraph::graph::{Graph, NodeIndex, EdgeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::EdgeType;
+use thiserror::Error;
+
+use crate::util::PointedUnionFind;
+
+#[derive(Error, Debug)]
+pub enum LinkageError {
+    #[error("Edge {edge:?} had NaN for its weight")]
+    NanWeight {edge: EdgeIndex}
+}
+
+/// One merge of two clusters in a [Dendrogram](struct.Dendrogram.html).
+///
+/// Each cluster is represented by its node with the lowest index, which stays the representative
+/// of every cluster it becomes part of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkageStep {
+    /// The representative of the cluster that keeps it.
+    pub surviving: NodeIndex,
+
+    /// The representative of the cluster that is merged into the other.
+    pub merged: NodeIndex,
+
+    /// The weight of the edge that joined the clusters.
+    pub height: f64,
+
+    /// The number of nodes in the cluster the merge makes.
+    pub size: usize
+}
+
+/// The single-linkage hierarchy of the nodes of a graph.
+///
+/// There is one merge for every edge of a minimum spanning forest of the graph, so a connected
+/// graph with `n` nodes has `n - 1` of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dendrogram {
+    node_count: usize,
+    // in order of height
+    merges: Vec<LinkageStep>
+}
+
+impl Dendrogram {
+    /// The number of nodes, i.e. of the leaves of the dendrogram.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// The merges, from the lowest to the highest.
+    pub fn merges(&self) -> &[LinkageStep] {
+        &self.merges
+    }
+
+    /// The cluster of every node once all the merges up to and including `height` have been
+    /// made, as the representative of its cluster.
+    pub fn cut(&self, height: f64) -> HashMap<NodeIndex, NodeIndex> {
+        let mut clusters = PointedUnionFind::new(self.node_count);
+        for step in self.merges.iter().take_while(|step| step.height <= height) {
+            clusters.union(step.surviving.index(), step.merged.index());
+        }
+        (0..self.node_count).map(|node| (NodeIndex::new(node), NodeIndex::new(clusters.find_mut(node)))).collect()
+    }
+
+    /// The number of clusters once all the merges up to and including `height` have been made.
+    pub fn cluster_count(&self, height: f64) -> usize {
+        self.node_count - self.merges.iter().take_while(|step| step.height <= height).count()
+    }
+
+    /// The hierarchy as a SciPy linkage matrix, with leaf `i` standing for the node with index
+    /// `i`. See [linkage_matrix_with](#method.linkage_matrix_with).
+    pub fn linkage_matrix(&self) -> Vec<[f64; 4]> {
+        self.linkage_matrix_with(f64::INFINITY)
+    }
+
+    /// The hierarchy as a SciPy linkage matrix, as taken by `scipy.cluster.hierarchy.dendrogram`
+    /// and `fcluster`, with the clusters that are never merged (because the graph isn't connected)
+    /// joined at `join_height`.
+    ///
+    /// Each row is `[first, second, height, size]` for one merge, from the lowest to the highest.
+    /// Clusters are numbered the SciPy way: the `n` leaves are `0` to `n - 1`, and the cluster made
+    /// by row `i` is `n + i`. SciPy's plotting needs finite heights, so for a disconnected graph,
+    /// pass something above the last merge rather than the infinity of
+    /// [linkage_matrix](#method.linkage_matrix).
+    pub fn linkage_matrix_with(&self, join_height: f64) -> Vec<[f64; 4]> {
+        let merges = self.merges.iter().map(|step| (step.surviving.index(), step.merged.index(), step.height));
+        linkage_rows(self.node_count, merges, join_height)
+    }
+}
+
+// Builds the rows of a SciPy linkage matrix from merges of the clusters containing two leaves, in
+// order of height. Whatever is left unmerged at the end is joined at `join_height`, so that there
+// are always `n - 1` rows.
+pub(crate) fn linkage_rows<I>(leaves: usize, merges: I, join_height: f64) -> Vec<[f64; 4]>
+    where I: Iterator<Item = (usize, usize, f64)> {
+    let mut clusters = PointedUnionFind::new(leaves);
+    // the SciPy number of the cluster at each representative
+    let mut labels: Vec<usize> = (0..leaves).collect();
+    let mut rows: Vec<[f64; 4]> = Vec::with_capacity(leaves.saturating_sub(1));
+    let mut merge = |first: usize, second: usize, height: f64, rows: &mut Vec<[f64; 4]>| {
+        let (first, second) = (clusters.find_mut(first), clusters.find_mut(second));
+        if first == second {
+            return;
+        }
+        let (a, b) = (labels[first].min(labels[second]), labels[first].max(labels[second]));
+        let size = clusters.size(first) + clusters.size(second);
+        clusters.union(first, second);
+        labels[first] = leaves + rows.len();
+        rows.push([a as f64, b as f64, height, size as f64]);
+    };
+    for (first, second, height) in merges {
+        merge(first, second, height, &mut rows);
+    }
+    for leaf in 1..leaves {
+        merge(0, leaf, join_height, &mut rows);
+    }
+    rows
+}
+
+/// Computes the single-linkage hierarchy of the nodes of `graph`, treating the weight of each edge
+/// as the distance between its ends.
+///
+/// Edges of equal weight are added in order of index. Directed edges are followed both ways.
+/// Returns an error if the weight of an edge is NaN.
+pub fn single_linkage<N, Ty: EdgeType>(graph: &Graph<N, f64, Ty>) -> Result<Dendrogram, LinkageError> {
+    if let Some(edge) = graph.edge_references().find(|edge| edge.weight().is_nan()) {
+        return Err(LinkageError::NanWeight{edge: edge.id()});
+    }
+    let mut edges: Vec<_> = graph.edge_references().collect();
+    edges.sort_by(|a, b| a.weight().partial_cmp(b.weight()).unwrap().then(a.id().cmp(&b.id())));
+
+    let mut clusters = PointedUnionFind::new(graph.node_count());
+    let mut merges = Vec::with_capacity(graph.node_count().saturating_sub(1));
+    for edge in edges {
+        let first = clusters.find_mut(edge.source().index());
+        let second = clusters.find_mut(edge.target().index());
+        if first == second {
+            continue;
+        }
+        let (surviving, merged) = if first < second { (first, second) } else { (second, first) };
+        clusters.union(surviving, merged);
+        merges.push(LinkageStep{
+            surviving: NodeIndex::new(surviving),
+            merged: NodeIndex::new(merged),
+            height: *edge.weight(),
+            size: clusters.size(surviving)
+        });
+    }
+    Ok(Dendrogram{node_count: graph.node_count(), merges})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::UnGraph;
+    use petgraph::algo::connected_components;
+
+    #[test]
+    fn test_single_linkage() {
+        // two tight groups, {0, 1, 2} and {3, 4}, joined by a long edge, and a node on its own
+        let mut graph = UnGraph::<(), f64>::new_undirected();
+        let nodes: Vec<_> = (0..6).map(|_| graph.add_node(())).collect();
+        for &(a, b, weight) in &[(0, 1, 1.), (1, 2, 2.), (0, 2, 1.5), (3, 4, 0.5), (2, 3, 10.)] {
+            graph.add_edge(nodes[a], nodes[b], weight);
+        }
+        let dendrogram = single_linkage(&graph).unwrap();
+        assert_eq!(dendrogram.node_count(), 6);
+        assert_eq!(dendrogram.merges(), &[
+            LinkageStep{surviving: nodes[3], merged: nodes[4], height: 0.5, size: 2},
+            LinkageStep{surviving: nodes[0], merged: nodes[1], height: 1., size: 2},
+            LinkageStep{surviving: nodes[0], merged: nodes[2], height: 1.5, size: 3},
+            LinkageStep{surviving: nodes[0], merged: nodes[3], height: 10., size: 5}
+        ][..]);
+
+        let clusters = dendrogram.cut(1.5);
+        assert_eq!(clusters[&nodes[2]], nodes[0]);
+        assert_eq!(clusters[&nodes[4]], nodes[3]);
+        assert_eq!(clusters[&nodes[5]], nodes[5]);
+        assert_eq!(dendrogram.cut(10.)[&nodes[4]], nodes[0]);
+        assert_eq!(dendrogram.cut(0.)[&nodes[1]], nodes[1]);
+
+        // the clusters at each height are the components of the edges up to it
+        for &height in &[0., 0.5, 1., 1.2, 1.5, 2., 10.] {
+            let below = graph.filter_map(|_, &n| Some(n), |_, &weight| if weight <= height { Some(weight) } else { None });
+            assert_eq!(dendrogram.cluster_count(height), connected_components(&below));
+        }
+
+        graph.add_edge(nodes[4], nodes[5], f64::NAN);
+        assert!(single_linkage(&graph).is_err());
+    }
+
+    #[test]
+    fn test_linkage_matrix() {
+        let mut graph = UnGraph::<(), f64>::new_undirected();
+        let nodes: Vec<_> = (0..6).map(|_| graph.add_node(())).collect();
+        for &(a, b, weight) in &[(0, 1, 1.), (1, 2, 2.), (0, 2, 1.5), (3, 4, 0.5), (2, 3, 10.)] {
+            graph.add_edge(nodes[a], nodes[b], weight);
+        }
+        let dendrogram = single_linkage(&graph).unwrap();
+        // 5 is never merged, so it joins the rest at the given height
+        assert_eq!(dendrogram.linkage_matrix_with(20.), vec![
+            [3., 4., 0.5, 2.],
+            [0., 1., 1., 2.],
+            [2., 7., 1.5, 3.],
+            [6., 8., 10., 5.],
+            [5., 9., 20., 6.]
+        ]);
+        assert_eq!(dendrogram.linkage_matrix()[4][2], f64::INFINITY);
+
+        let empty = single_linkage(&UnGraph::<(), f64>::new_undirected()).unwrap();
+        assert!(empty.linkage_matrix().is_empty());
+    }
+}
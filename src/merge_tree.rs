@@ -0,0 +1,236 @@
+//! Merge trees, which record how the components of the level sets of a function merge.
+//!
+//! Sweeping through the values of a function, a new component of the sublevel set (the nodes with
+//! values `<= t`) is born at every minimum, and two components join at every saddle. The join tree
+//! has a leaf for each minimum and an interior node for each saddle, each connected to the saddle
+//! at which its component next joins another. The split tree is the same for the superlevel sets
+//! (values `>= t`), with a leaf for each maximum. These are exactly the merges recorded by the
+//! sweeps that build a [MorseComplex](../morse/struct.MorseComplex.html), so the trees are read off
+//! of their filtrations rather than computed with another sweep.
+use std::collections::{HashMap, HashSet};
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use crate::ScalarValued;
+use crate::morse::{MorseComplex, MorseKind, MorseError};
+
+/// A merge tree of a function over a graph.
+///
+/// The nodes of the tree are nodes of the graph: the extrema, which are its leaves, and the
+/// saddles at which their components merge. Every node but the root of each connected component
+/// of the graph has a parent, the next saddle its component reaches. The root is the last saddle
+/// (or, if there are none, the only extremum) of its component.
+#[derive(Debug, Clone)]
+pub struct MergeTree {
+    // in sweep order, so every node comes before its parent
+    nodes: Vec<NodeIndex>,
+    parents: HashMap<NodeIndex, NodeIndex>,
+    // the extremum whose branch each node is on, following the elder rule
+    owners: HashMap<NodeIndex, NodeIndex>,
+    // the persistence of each extremum
+    persistence: HashMap<NodeIndex, f64>
+}
+
+impl MergeTree {
+    /// Computes the join tree of the function on `graph`, which tracks the components of its
+    /// sublevel sets, so that its leaves are the minima.
+    pub fn join_tree<N: ScalarValued>(graph: &UnGraph<N, f64>) -> Result<MergeTree, MorseError> {
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, graph)?;
+        MergeTree::from_complex(&complex, graph)
+    }
+
+    /// Computes the split tree of the function on `graph`, which tracks the components of its
+    /// superlevel sets, so that its leaves are the maxima.
+    pub fn split_tree<N: ScalarValued>(graph: &UnGraph<N, f64>) -> Result<MergeTree, MorseError> {
+        let complex = MorseComplex::from_graph(MorseKind::Descending, graph)?;
+        MergeTree::from_complex(&complex, graph)
+    }
+
+    fn from_complex<N: ScalarValued>(complex: &MorseComplex, graph: &UnGraph<N, f64>) -> Result<MergeTree, MorseError> {
+        // The branch of each extremum runs from it through the saddles at which other components
+        // merged into its own, in sweep order, and then to the saddle at which it merged itself
+        let mut branches: HashMap<NodeIndex, Vec<NodeIndex>> = complex.get_persistence_pairs(graph)?.into_iter()
+            .map(|pair| (pair.extremum, vec![pair.extremum]))
+            .collect();
+        let mut merges: Vec<(NodeIndex, NodeIndex)> = complex.filtration.iter()
+            .map(|step| (step.owning_cell, step.merge_node))
+            .collect();
+        merges.sort_by_key(|&(_, saddle)| complex.sweep_position(saddle));
+        for (extremum, saddle) in merges {
+            match branches.get_mut(&extremum) {
+                None => return Err(MorseError::MissingData{node: extremum}),
+                Some(branch) => branch.push(saddle)
+            }
+        }
+        let mut owners = HashMap::new();
+        for (&extremum, branch) in branches.iter_mut() {
+            // several components can merge into one at the same saddle
+            branch.dedup();
+            for &node in branch.iter() {
+                owners.insert(node, extremum);
+            }
+        }
+        for step in complex.filtration.iter() {
+            match branches.get_mut(&step.destroyed_cell) {
+                None => return Err(MorseError::MissingData{node: step.destroyed_cell}),
+                Some(branch) => branch.push(step.merge_node)
+            }
+        }
+
+        let mut parents = HashMap::new();
+        for branch in branches.values() {
+            for pair in branch.windows(2) {
+                parents.insert(pair[0], pair[1]);
+            }
+        }
+        let mut nodes: Vec<NodeIndex> = owners.keys().cloned().collect();
+        nodes.sort_by_key(|&node| complex.sweep_position(node));
+        let persistence = complex.get_persistence().into_iter()
+            .filter(|(node, _)| owners.get(node) == Some(node))
+            .collect();
+        Ok(MergeTree{nodes, parents, owners, persistence})
+    }
+
+    /// Returns the tree with every extremum with persistence below `threshold` removed.
+    ///
+    /// Each extremum is removed along with its branch of the tree: the saddles at which the
+    /// components it outlived merged into its own. Saddles that are left with a single child are
+    /// no longer merges, so they are removed too, and their children connected to their parents.
+    /// This is the tree of the [simplified](../morse/struct.MorseComplex.html#method.simplify)
+    /// complex.
+    pub fn simplify(&self, threshold: f64) -> MergeTree {
+        let kept = |node: &NodeIndex| self.persistence[&self.owners[node]] >= threshold;
+        let mut children: HashMap<NodeIndex, usize> = HashMap::new();
+        for (child, parent) in self.parents.iter() {
+            if kept(child) {
+                *children.entry(*parent).or_insert(0) += 1;
+            }
+        }
+        let retained = |node: &NodeIndex| {
+            kept(node) && (self.owners[node] == *node || children.get(node).cloned().unwrap_or(0) >= 2)
+        };
+
+        let nodes: Vec<NodeIndex> = self.nodes.iter().filter(|node| retained(node)).cloned().collect();
+        let mut parents = HashMap::new();
+        for &node in nodes.iter() {
+            let mut parent = self.parents.get(&node);
+            while let Some(p) = parent.filter(|p| !retained(p)) {
+                parent = self.parents.get(p);
+            }
+            if let Some(&parent) = parent {
+                parents.insert(node, parent);
+            }
+        }
+        let owners = nodes.iter().map(|node| (*node, self.owners[node])).collect();
+        let persistence = self.persistence.iter()
+            .filter(|&(_, &lifetime)| lifetime >= threshold)
+            .map(|(&node, &lifetime)| (node, lifetime))
+            .collect();
+        MergeTree{nodes, parents, owners, persistence}
+    }
+
+    /// The nodes of the tree, in the order of the sweep, so that each node comes before its
+    /// parent.
+    pub fn nodes(&self) -> &[NodeIndex] {
+        &self.nodes
+    }
+
+    /// The arcs of the tree, as `(child, parent)` pairs in the order of the children.
+    pub fn arcs(&self) -> Vec<(NodeIndex, NodeIndex)> {
+        self.nodes.iter()
+            .filter_map(|node| self.parents.get(node).map(|&parent| (*node, parent)))
+            .collect()
+    }
+
+    /// The parent of `node`, or `None` if it is a root or not in the tree.
+    pub fn parent(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.parents.get(&node).cloned()
+    }
+
+    /// The leaves of the tree, i.e. the extrema, in the order of the sweep.
+    pub fn leaves(&self) -> Vec<NodeIndex> {
+        let parents: HashSet<&NodeIndex> = self.parents.values().collect();
+        self.nodes.iter().filter(|node| !parents.contains(node)).cloned().collect()
+    }
+
+    /// The roots of the tree, one per connected component of the graph, in the order of the sweep.
+    pub fn roots(&self) -> Vec<NodeIndex> {
+        self.nodes.iter().filter(|node| !self.parents.contains_key(node)).cloned().collect()
+    }
+
+    /// The persistence of the leaf `extremum`, or `None` if it isn't a leaf of the tree.
+    pub fn persistence(&self, extremum: NodeIndex) -> Option<f64> {
+        self.persistence.get(&extremum).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_join_tree() {
+        // minima at 0, 2 and 4, joined at 1 and then at 3
+        let graph = path_graph(&[0., 5., 1., 6., 2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let tree = MergeTree::join_tree(&graph).unwrap();
+        assert_eq!(tree.nodes(), &[nodes[0], nodes[2], nodes[4], nodes[1], nodes[3]][..]);
+        assert_eq!(tree.leaves(), vec![nodes[0], nodes[2], nodes[4]]);
+        assert_eq!(tree.roots(), vec![nodes[3]]);
+        assert_eq!(tree.arcs(), vec![
+            (nodes[0], nodes[1]), (nodes[2], nodes[1]), (nodes[4], nodes[3]), (nodes[1], nodes[3])
+        ]);
+        assert_eq!(tree.parent(nodes[3]), None);
+        assert_eq!(tree.parent(nodes[2]), Some(nodes[1]));
+    }
+
+    #[test]
+    fn test_split_tree() {
+        let graph = path_graph(&[0., 5., 1., 6., 2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let tree = MergeTree::split_tree(&graph).unwrap();
+        assert_eq!(tree.leaves(), vec![nodes[3], nodes[1]]);
+        assert_eq!(tree.arcs(), vec![(nodes[3], nodes[2]), (nodes[1], nodes[2])]);
+        assert_eq!(tree.persistence(nodes[1]), Some(4.));
+        assert_eq!(tree.persistence(nodes[3]), Some(f64::INFINITY));
+        assert_eq!(tree.persistence(nodes[2]), None);
+    }
+
+    #[test]
+    fn test_simplify() {
+        // minima at 0, 2, 4 and 6 with persistence inf, 4, 1 and 3
+        let graph = path_graph(&[0., 5., 1., 3., 2., 8., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let tree = MergeTree::join_tree(&graph).unwrap();
+        assert_eq!(tree.leaves().len(), 4);
+        assert_eq!(tree.arcs(), vec![
+            (nodes[0], nodes[1]), (nodes[2], nodes[3]), (nodes[4], nodes[3]),
+            (nodes[3], nodes[1]), (nodes[1], nodes[5]), (nodes[6], nodes[5])
+        ]);
+
+        // removing 4 leaves 3 with a single child, so 2 connects straight to 1
+        let simplified = tree.simplify(2.);
+        assert_eq!(simplified.leaves(), vec![nodes[0], nodes[2], nodes[6]]);
+        assert_eq!(simplified.arcs(), vec![(nodes[0], nodes[1]), (nodes[2], nodes[1]), (nodes[1], nodes[5]), (nodes[6], nodes[5])]);
+        assert_eq!(simplified.persistence(nodes[4]), None);
+
+        let simplified = tree.simplify(3.5);
+        assert_eq!(simplified.arcs(), vec![(nodes[0], nodes[1]), (nodes[2], nodes[1])]);
+        assert_eq!(simplified.roots(), vec![nodes[1]]);
+        let simplified = tree.simplify(f64::INFINITY);
+        assert_eq!(simplified.nodes(), &[nodes[0]][..]);
+        assert!(simplified.arcs().is_empty());
+    }
+
+    #[test]
+    fn test_join_tree_forest() {
+        let mut graph = path_graph(&[0., 5., 1.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let lonely = graph.add_node(LabeledPoint{id: 3, value: 3., point: vec![3.]});
+        let tree = MergeTree::join_tree(&graph).unwrap();
+        assert_eq!(tree.roots(), vec![lonely, nodes[1]]);
+        assert_eq!(tree.leaves(), vec![nodes[0], nodes[2], lonely]);
+        assert_eq!(tree.arcs().len(), 2);
+    }
+}
@@ -0,0 +1,275 @@
+//! Distances between points, for use when constructing neighbor graphs.
+//!
+//! The graph builders in [graph](../graph/index.html) take a [Metric](trait.Metric.html) to
+//! decide which points are neighbors. The common distances between coordinate vectors are
+//! provided here, and any other distance can be used by wrapping a closure in
+//! [FnMetric](struct.FnMetric.html).
+
+/// A distance between points of type `T`.
+///
+/// Despite the name, this doesn't have to satisfy the axioms of a metric. Builders that need it to
+/// (such as the tree-based neighbor indices) only rely on it when [is_metric](#method.is_metric)
+/// or [is_minkowski](#method.is_minkowski) says so.
+pub trait Metric<T: ?Sized>: Sync {
+    /// The distance between `a` and `b`.
+    ///
+    /// Every builder assumes this is symmetric, non-negative, never NaN, and 0 from a point to
+    /// itself, since the graphs they produce are undirected and weighted by distance. The triangle
+    /// inequality is only assumed when [is_metric](#method.is_metric) or
+    /// [is_minkowski](#method.is_minkowski) is `true`, since it is what lets ball and KD-trees
+    /// skip whole subtrees; otherwise neighbors are found by brute force.
+    fn distance(&self, a: &T, b: &T) -> f64;
+
+    /// Whether this is a Minkowski (`L_p`) distance between coordinate vectors.
+    ///
+    /// KD-trees prune their searches with bounds that only hold for these. Defaults to `false`.
+    fn is_minkowski(&self) -> bool {
+        false
+    }
+
+    /// Whether this is a true metric, and in particular satisfies the triangle inequality.
+    ///
+    /// Ball trees prune their searches with bounds that only hold for these. Defaults to
+    /// [is_minkowski](#method.is_minkowski), since every Minkowski distance is a metric.
+    fn is_metric(&self) -> bool {
+        self.is_minkowski()
+    }
+
+    /// The number of coordinates this distance requires points to have, if it only works for one.
+    ///
+    /// Builders check the points against this up front, and report a mismatch as an error rather
+    /// than calling [distance](#tymethod.distance) on points it can't handle. Defaults to `None`.
+    fn dimension(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// The Euclidean (`L_2`) distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Euclidean;
+
+/// The Manhattan (`L_1`) distance, the sum of the absolute differences of the coordinates.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Manhattan;
+
+/// The Chebyshev (`L_∞`) distance, the largest absolute difference of any coordinate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chebyshev;
+
+/// The cosine distance, `1 - cos(θ)` where `θ` is the angle between two vectors.
+///
+/// This only depends on the directions of the vectors, which makes it the usual choice for
+/// sparse, high dimensional features like TF-IDF vectors. It ranges from 0 (same direction) to 2
+/// (opposite directions). A zero vector has no direction, so its distance to anything is 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cosine;
+
+impl Metric<[f64]> for Euclidean {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b.iter())
+            .map(|(x, y)| (x - y).powi(2))
+            .sum::<f64>().sqrt()
+    }
+
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+}
+
+impl Metric<[f64]> for Manhattan {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum()
+    }
+
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+}
+
+impl Metric<[f64]> for Chebyshev {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).fold(0., f64::max)
+    }
+
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+}
+
+impl Metric<[f64]> for Cosine {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        let dot: f64 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+        let norms = a.iter().map(|x| x * x).sum::<f64>().sqrt() * b.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norms == 0. {
+            return 1.;
+        }
+        // rounding can push the cosine just outside [-1, 1]
+        1. - (dot / norms).clamp(-1., 1.)
+    }
+}
+
+// Points are usually stored as vectors, so every distance on slices works on them too
+macro_rules! impl_for_vec {
+    ($($metric:ty),*) => {
+        $(
+            impl Metric<Vec<f64>> for $metric {
+                fn distance(&self, a: &Vec<f64>, b: &Vec<f64>) -> f64 {
+                    Metric::<[f64]>::distance(self, a, b)
+                }
+
+                fn is_minkowski(&self) -> bool {
+                    Metric::<[f64]>::is_minkowski(self)
+                }
+
+                fn is_metric(&self) -> bool {
+                    Metric::<[f64]>::is_metric(self)
+                }
+
+                fn dimension(&self) -> Option<usize> {
+                    Metric::<[f64]>::dimension(self)
+                }
+            }
+        )*
+    }
+}
+
+impl_for_vec!(Euclidean, Manhattan, Chebyshev, Cosine);
+
+/// A distance on a periodic domain, such as a simulation box with periodic boundary conditions.
+///
+/// Along each coordinate, points are compared through whichever way around the domain is shorter,
+/// so points near opposite faces of the box are close together. The per-coordinate separations are
+/// then combined with `metric`, which should be translation invariant (as every Minkowski distance
+/// is). Use `f64::INFINITY` as the period of coordinates that don't wrap.
+///
+/// This is a metric whenever `metric` is, so it can be used with a ball tree, but KD-trees can't
+/// handle the wrapping. Its [dimension](trait.Metric.html#method.dimension) is the number of
+/// periods, so the graph builders reject points with any other number of coordinates, and
+/// [distance](trait.Metric.html#tymethod.distance) panics if it is given such points directly.
+///
+/// ```
+/// use talus::metric::{Metric, Periodic, Euclidean};
+///
+/// let torus = Periodic{metric: Euclidean, periods: vec![10., 10.]};
+/// assert_eq!(torus.distance(&[0.5, 5.][..], &[9.5, 5.][..]), 1.);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Periodic<M> {
+    /// The distance used to combine the wrapped per-coordinate separations.
+    pub metric: M,
+
+    /// The length of the domain along each coordinate.
+    pub periods: Vec<f64>
+}
+
+impl<M: Metric<[f64]>> Metric<[f64]> for Periodic<M> {
+    fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        assert!(a.len() == self.periods.len() && b.len() == self.periods.len(),
+                "Expected points with {} coordinates, found {} and {}", self.periods.len(), a.len(), b.len());
+        // move b to its image nearest to a, then measure as usual
+        let image: Vec<f64> = a.iter().zip(b.iter()).zip(self.periods.iter())
+            .map(|((&x, &y), &period)| {
+                if !period.is_finite() {
+                    return y;
+                }
+                let separation = (y - x).rem_euclid(period);
+                if separation > period / 2. { x + separation - period } else { x + separation }
+            })
+            .collect();
+        self.metric.distance(a, &image)
+    }
+
+    fn is_metric(&self) -> bool {
+        self.metric.is_metric()
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        Some(self.periods.len())
+    }
+}
+
+impl<M: Metric<[f64]>> Metric<Vec<f64>> for Periodic<M> {
+    fn distance(&self, a: &Vec<f64>, b: &Vec<f64>) -> f64 {
+        Metric::<[f64]>::distance(self, a, b)
+    }
+
+    fn is_metric(&self) -> bool {
+        Metric::<[f64]>::is_metric(self)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        Metric::<[f64]>::dimension(self)
+    }
+}
+
+/// A custom distance, given by a closure.
+///
+/// ```
+/// use talus::metric::{Metric, FnMetric};
+///
+/// // the number of positions at which two strings differ
+/// let hamming = FnMetric(|a: &String, b: &String| {
+///     a.chars().zip(b.chars()).filter(|(x, y)| x != y).count() as f64
+/// });
+/// assert_eq!(hamming.distance(&"karolin".to_string(), &"kathrin".to_string()), 3.);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FnMetric<F>(pub F);
+
+impl<T: ?Sized, F: Fn(&T, &T) -> f64 + Sync> Metric<T> for FnMetric<F> {
+    fn distance(&self, a: &T, b: &T) -> f64 {
+        (self.0)(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minkowski() {
+        let a = [0., 0., 0.];
+        let b = [3., -4., 1.];
+        assert!((Euclidean.distance(&a[..], &b[..]) - 26f64.sqrt()).abs() < 1e-12);
+        assert_eq!(Manhattan.distance(&a[..], &b[..]), 8.);
+        assert_eq!(Chebyshev.distance(&a[..], &b[..]), 4.);
+        assert_eq!(Euclidean.distance(&vec![0., 3.], &vec![4., 0.]), 5.);
+        assert!(Metric::<[f64]>::is_minkowski(&Manhattan));
+
+        // the old crate-level trait still measures the Euclidean distance
+        #[allow(deprecated)]
+        let old = crate::Metric::distance(&vec![0., 3.], &vec![4., 0.]);
+        assert_eq!(old, 5.);
+    }
+
+    #[test]
+    fn test_cosine() {
+        let a = vec![1., 0.];
+        assert!(Cosine.distance(&a, &vec![5., 0.]).abs() < 1e-12);
+        assert!((Cosine.distance(&a, &vec![0., 2.]) - 1.).abs() < 1e-12);
+        assert!((Cosine.distance(&a, &vec![-1., 0.]) - 2.).abs() < 1e-12);
+        assert_eq!(Cosine.distance(&a, &vec![0., 0.]), 1.);
+        assert!(!Metric::<Vec<f64>>::is_minkowski(&Cosine));
+    }
+
+    #[test]
+    fn test_periodic() {
+        let torus = Periodic{metric: Manhattan, periods: vec![1., f64::INFINITY]};
+        let a = vec![0.1, 0.];
+        assert!((torus.distance(&a, &vec![0.9, 0.]) - 0.2).abs() < 1e-12);
+        assert!((torus.distance(&a, &vec![-0.8, 3.]) - 3.1).abs() < 1e-12);
+        assert!((torus.distance(&a, &vec![2.4, -1.]) - 1.3).abs() < 1e-12);
+        assert!(Metric::<[f64]>::is_metric(&torus));
+        assert!(!Metric::<[f64]>::is_minkowski(&torus));
+        assert_eq!(Metric::<Vec<f64>>::dimension(&torus), Some(2));
+        assert_eq!(Metric::<[f64]>::dimension(&Euclidean), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_periodic_dimension() {
+        // the third coordinate has no period, and would otherwise be dropped from the distance
+        let torus = Periodic{metric: Euclidean, periods: vec![1., 1.]};
+        torus.distance(&[0., 0., 0.][..], &[0., 0., 5.][..]);
+    }
+}
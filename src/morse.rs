@@ -1,17 +1,46 @@
 //! Algorithms for analyzing the behavior of a scalar function over a graph.
-use petgraph::graph::{UnGraph, NodeIndex, EdgeIndex};
-use petgraph::unionfind::UnionFind;
+use petgraph::graph::{Graph, UnGraph, DiGraph, NodeIndex, EdgeIndex};
+use petgraph::EdgeType;
+use petgraph::visit::EdgeRef;
 
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashSet, HashMap, BinaryHeap, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::cmp::Ordering;
 use std::f64;
 
-use super::LabeledPoint;
+use super::ScalarValued;
+use super::util::PointedUnionFind;
+use super::distance::DistanceError;
+use super::stats::{ThresholdHeuristic, ThresholdSuggestion};
 
 use thiserror::Error;
 
+mod stitch;
+mod critical;
+mod filtration;
+mod adjacency;
+mod gradient;
+mod hierarchy;
+mod statistics;
+mod validate;
+mod components;
+mod robustness;
+mod engine;
+mod manifolds;
+mod report;
+pub use critical::{CriticalKind, CriticalPoint};
+pub use filtration::{FiltrationDelta, FiltrationDeltas};
+pub use adjacency::CellAdjacency;
+pub use hierarchy::MergeHierarchy;
+pub use statistics::CellStatistics;
+pub use validate::{validate_graph, GraphDiagnostics};
+pub use components::ComponentComplexes;
+pub use engine::MorseEngine;
+pub use robustness::{Noise, PerturbationTrial};
+pub use report::{DiagramComparison, FeatureMatch};
+
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum MorseError {
     #[error("Node {node:?} had NaN for its value")]
     NanValue {node: NodeIndex},
@@ -32,13 +61,33 @@ pub enum MorseError {
     NoMaximum {node: NodeIndex},
 
     #[error("Could not find data for node {node:?}")]
-    MissingData {node: NodeIndex}
+    MissingData {node: NodeIndex},
+
+    #[error("Expected node {node:?} to have {expected} coordinates but it had {found}")]
+    DimensionMismatch {node: NodeIndex, expected: usize, found: usize},
+
+    #[error("Expected node {node:?} to be an extremum of the complex")]
+    NotAnExtremum {node: NodeIndex},
+
+    #[error("Expected the cells of {node:?} and {other:?} to be adjacent")]
+    NotAdjacent {node: NodeIndex, other: NodeIndex},
+
+    #[error("Cancelling {node:?} into {other:?} would merge the cell of {other:?} into itself")]
+    MergeCycle {node: NodeIndex, other: NodeIndex},
+
+    #[error("Expected {expected} values, one per node, but got {found}")]
+    ValueCountMismatch {expected: usize, found: usize},
+
+    #[error(transparent)]
+    Distance(#[from] DistanceError)
 }
 
 #[derive(Debug)]
 struct MorseData {
     lifetime: f64,
     merge_parent: Option<NodeIndex>,
+    // the node at which this cell was merged into merge_parent
+    merge_node: Option<NodeIndex>,
     ancestor: NodeIndex  // TODO: I dunno what the "proper" name for this is
 }
 
@@ -68,42 +117,6 @@ impl PartialEq for MorseNode {
 
 impl Eq for MorseNode {}
 
-#[derive(Debug)]
-struct PointedUnionFind {
-    unionfind: UnionFind<usize>,
-    reprs: Vec<usize>
-}
-
-impl PointedUnionFind {
-    // this is insanely specific and will break if you use it outside of exactly
-    // how it works in the morse complex code (and maybe even if you use it
-    // exactly that way!)
-    // This turns UnionFind into a structure that always keeps the representative
-    // for the left hand size of a union constant. But to do this O(1)
-    // i can't do things like "ensure consistency" outside of the access patterns
-    // i know the morse complex code will follow
-    // (specifically, this data structure offers no guarantees that
-    // `find(find(x)) will be reasonable)
-    fn new(n: usize) -> Self {
-        let unionfind = UnionFind::new(n);
-        let reprs = (0..n).collect();
-        PointedUnionFind{unionfind, reprs}
-    }
-
-    fn find(&self, x: usize) -> usize {
-        let inner_repr = self.unionfind.find(x);
-        self.reprs[inner_repr]
-    }
-
-    fn union(&mut self, x: usize, y: usize) {
-        // x is privileged!
-        let old_outer = self.find(x);
-        self.unionfind.union(x, y);
-        let new_inner = self.unionfind.find(x);
-        self.reprs[new_inner] = old_outer;
-    }
-}
-
 /// Contains all of the filtration information for a MorseComplex
 ///
 /// A Morse complex, especially one generated from discrete points of empirical data,
@@ -115,16 +128,191 @@ impl PointedUnionFind {
 /// The MorseFiltrationStep struct contains the information corresponding to one
 /// step of this simplification process.
 #[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct MorseFiltrationStep {
     pub time: f64,
     pub destroyed_cell: NodeIndex,
-    pub owning_cell: NodeIndex
+    pub owning_cell: NodeIndex,
+    /// The node at which the two cells met, i.e. the saddle that destroyed the cell.
+    pub merge_node: NodeIndex,
+    /// The value of the destroyed extremum, at which its cell was born.
+    pub birth: f64,
+    /// The value of the merge node, at which the destroyed cell died.
+    pub death: f64
+}
+
+/// An extremum paired with the saddle at which its cell died, as plotted in a persistence diagram.
+///
+/// The extremum is born at its own value and dies at the value of the saddle, so its persistence
+/// is the difference between the two. Extrema whose cells are never merged have no saddle, and die
+/// at infinity in the direction of the sweep: `-∞` for maxima and `+∞` for minima.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PersistencePair {
+    pub extremum: NodeIndex,
+    pub saddle: Option<NodeIndex>,
+    pub birth: f64,
+    pub death: f64
+}
+
+/// How the persistence of an extremum is measured when its cell is merged into another.
+///
+/// Whatever the measure, cells are always merged into the cell with the more extreme value, and
+/// global extrema have infinite persistence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PersistenceMeasure {
+    /// The difference between the values of the extremum and of the saddle at which its cell is
+    /// merged. This is the usual definition of persistence.
+    Value,
+
+    /// The number of nodes in the cell's component when it is merged, i.e. the number of nodes
+    /// above (or below, for an ascending complex) the saddle that flow to it. A sharp spike made
+    /// of a single noisy node has size 1, however tall it is.
+    Size,
+
+    /// The total difference between the values of the nodes in the cell's component and the
+    /// value of the saddle, a discrete integral of the function over the component. This accounts
+    /// for both the height and the extent of a peak.
+    Volume,
+
+    /// The length of the path from the saddle to the extremum on the graph of the function, i.e.
+    /// with each edge as long as the hypotenuse of its weight and the difference between the
+    /// values of its endpoints. The path steps from the saddle to its nearest neighbor in the
+    /// cell's component, and follows integral lines from there, so it ends at the extremum
+    /// unless other cells have already merged into its component. A peak far from where it meets
+    /// the rest of the function outlives one of the same height close by, and since the path only
+    /// climbs, this is never less than the `Value` persistence.
+    Geodesic
+}
+
+/// How nodes with equal values are ordered when constructing a complex.
+///
+/// The sweep needs a strict order of the nodes, so ties must be broken somehow, and which node
+/// counts as higher decides which one becomes the extremum of a flat peak.
+///
+/// The order also settles ties between cells: where the cells of two extrema with equal values
+/// meet, the one whose extremum comes first absorbs the other. Whichever variant is chosen, the
+/// same graph and values always give the same complex.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TieBreak {
+    /// Tied nodes are swept in order of index. The node with the smaller index is treated as the
+    /// higher one in the descending complex, but as the lower one in the ascending complex.
+    Index,
+
+    /// Simulation of simplicity: each value is perturbed by an infinitesimal multiple of its
+    /// node's id, so of two tied nodes the one with the larger id is treated as the higher one, in
+    /// both complexes. The ids act as a user supplied key, so as long as they are unique, the
+    /// results only depend on the values and ids, and not on the order in which nodes were added
+    /// to the graph. Tied nodes that share an id fall back on their indices, the smaller index
+    /// being the higher one in both complexes, so for those the order does matter.
+    Id,
+
+    /// Plateaus (connected nodes with equal values) are flooded from their higher neighbors: tied
+    /// nodes are ordered by their distance across the plateau from higher ground, and then by
+    /// index. Every node of a plateau then flows to a neighbor on the same plateau or above it, so
+    /// a flat region never produces more than one extremum, and only if it is flat at the top.
+    /// Otherwise, the arbitrary order of the nodes in a large plateau creates spurious extrema
+    /// with zero persistence wherever a node happens to come before all of its tied neighbors.
+    Flood
+}
+
+/// How steep the edge from a node to one of its higher (or lower) neighbors is.
+///
+/// Where the neighbors of a node belong to different cells, the node joins the cell of its
+/// steepest neighbor, so this decides where the boundaries between cells run.
+#[derive(Debug, Clone, Copy)]
+pub enum Grade {
+    /// The absolute value of the neighbor's value divided by the weight of the edge. This is the
+    /// default. It ignores the node's own value, so it is only a slope when the values are all of
+    /// one sign and far from zero.
+    ValueOverWeight,
+
+    /// The absolute difference between the values of the node and the neighbor, divided by the
+    /// weight of the edge: the slope of the function along the edge, if the weights are lengths.
+    DifferenceOverWeight,
+
+    /// A custom grade, given the value of the node, the value of the neighbor and the weight of
+    /// the edge between them. Larger grades are steeper.
+    Custom(fn(f64, f64, f64) -> f64)
+}
+
+impl Grade {
+    fn grade(self, value: f64, neighbor_value: f64, weight: f64) -> f64 {
+        match self {
+            Grade::ValueOverWeight => (neighbor_value / weight).abs(),
+            Grade::DifferenceOverWeight => ((neighbor_value - value) / weight).abs(),
+            Grade::Custom(grade) => grade(value, neighbor_value, weight)
+        }
+    }
+}
+
+/// The choices made when constructing a complex, accepted by every constructor.
+///
+/// The default is what [MorseSmaleComplex::from_graph](struct.MorseSmaleComplex.html#method.from_graph)
+/// uses: `PersistenceMeasure::Value`, `TieBreak::Index` and `Grade::ValueOverWeight`. Start from it
+/// and change only what's needed:
+///
+/// ```
+/// use talus::morse::{MorseOptions, PersistenceMeasure, TieBreak};
+///
+/// let options = MorseOptions::default().measure(PersistenceMeasure::Size).tie_break(TieBreak::Flood);
+/// ```
+///
+/// The measure only changes the persistence values, and so the order of the filtration, but the
+/// others can also change the cells.
+#[derive(Debug, Clone, Copy)]
+pub struct MorseOptions {
+    measure: PersistenceMeasure,
+    tie_break: TieBreak,
+    grade: Grade
+}
+
+impl MorseOptions {
+    /// Measures persistence with `measure`.
+    pub fn measure(self, measure: PersistenceMeasure) -> MorseOptions {
+        MorseOptions{measure, ..self}
+    }
+
+    /// Breaks ties between equal values with `tie_break`.
+    pub fn tie_break(self, tie_break: TieBreak) -> MorseOptions {
+        MorseOptions{tie_break, ..self}
+    }
+
+    /// Chooses the steepest neighbors with `grade`.
+    pub fn grade(self, grade: Grade) -> MorseOptions {
+        MorseOptions{grade, ..self}
+    }
+}
+
+impl Default for MorseOptions {
+    fn default() -> MorseOptions {
+        MorseOptions{measure: PersistenceMeasure::Value, tie_break: TieBreak::Index, grade: Grade::ValueOverWeight}
+    }
+}
+
+// Whether `a` comes before `b` in a sweep, given (value, node, id) triples
+fn sweep_order(kind: MorseKind, tie_break: TieBreak, a: (f64, NodeIndex, i64), b: (f64, NodeIndex, i64)) -> Ordering {
+    // we know these aren't nan, but the compiler doesn't, so just handle nans arbitrarily
+    let by_value = match kind {
+        MorseKind::Descending => b.0.partial_cmp(&a.0),
+        MorseKind::Ascending => a.0.partial_cmp(&b.0)
+    };
+    let by_value = match by_value {
+        None => return Ordering::Less,
+        Some(ord) => ord
+    };
+    by_value.then(match (tie_break, kind) {
+        (TieBreak::Index, _) | (TieBreak::Flood, _) => a.1.cmp(&b.1),
+        // repeated ids are ordered by index, in the opposite directions for the two kinds so
+        // that each sweep is the other backwards
+        (TieBreak::Id, MorseKind::Descending) => b.2.cmp(&a.2).then(a.1.cmp(&b.1)),
+        (TieBreak::Id, MorseKind::Ascending) => a.2.cmp(&b.2).then(b.1.cmp(&a.1))
+    })
 }
 
 /// Indicates whether a MorseComplex is Ascending or Descending.
 ///
 /// See [MorseComplex](struct.MorseComplex.html) for a detailed explanation.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MorseKind {
     Ascending,
     Descending
@@ -142,12 +330,164 @@ pub struct MorseSmaleComplex {
 
 impl MorseSmaleComplex {
     /// Constructs a MorseSmaleComplex from the given graph.
-    pub fn from_graph<T>(graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<MorseSmaleComplex, MorseError> {
-        let ascending_complex = MorseComplex::from_graph(MorseKind::Ascending, &graph)?;
-        let descending_complex = MorseComplex::from_graph(MorseKind::Descending, &graph)?;
+    pub fn from_graph<N: ScalarValued>(graph: &UnGraph<N, f64>) -> Result<MorseSmaleComplex, MorseError> {
+        MorseSmaleComplex::from_graph_with(graph, MorseOptions::default())
+    }
+
+    /// Constructs a MorseSmaleComplex from the given graph with the given
+    /// [options](struct.MorseOptions.html).
+    ///
+    /// With the default options, this is the same as [from_graph](#method.from_graph).
+    pub fn from_graph_with<N: ScalarValued>(graph: &UnGraph<N, f64>, options: MorseOptions) -> Result<MorseSmaleComplex, MorseError> {
+        let (ascending_complex, descending_complex) = MorseComplex::pair_from_region(graph, |_| true, options)?;
+
+        Ok(MorseSmaleComplex{ascending_complex, descending_complex})
+    }
+
+    /// Constructs a MorseSmaleComplex from the given directed graph.
+    ///
+    /// Nodes only follow their outgoing edges to higher (or lower) neighbors, so a node whose
+    /// edges all point downhill is a maximum, however high the nodes pointing at it are, and
+    /// cells are the basins of the flow along the edges. The steepest neighbor is also chosen
+    /// among outgoing edges. The persistence methods and the
+    /// [integral lines](struct.MorseComplex.html#method.integral_line) accept the directed graph
+    /// too, and follow the edges the same way.
+    pub fn from_digraph<N: ScalarValued>(graph: &DiGraph<N, f64>, options: MorseOptions) -> Result<MorseSmaleComplex, MorseError> {
+        let (ascending_complex, descending_complex) = MorseComplex::pair_from_region(graph, |_| true, options)?;
+
+        Ok(MorseSmaleComplex{ascending_complex, descending_complex})
+    }
+
+    /// Constructs a MorseSmaleComplex over only the nodes of `graph` in `region`.
+    ///
+    /// The graph is not copied, so all results are indexed by the graph's own `NodeIndex`es, and
+    /// nodes outside of the region (and, depending on `boundary`, its halo) simply don't appear
+    /// in them.
+    pub fn from_region<N: ScalarValued>(graph: &UnGraph<N, f64>, region: &HashSet<NodeIndex>, boundary: RegionBoundary,
+                          options: MorseOptions) -> Result<MorseSmaleComplex, MorseError> {
+        let nodes = match boundary {
+            RegionBoundary::Induced => region.clone(),
+            RegionBoundary::Halo => region.iter()
+                .flat_map(|&node| graph.neighbors(node).chain(std::iter::once(node)))
+                .collect()
+        };
+        let (ascending_complex, descending_complex) = MorseComplex::pair_from_region(graph, |n| nodes.contains(&n), options)?;
 
         Ok(MorseSmaleComplex{ascending_complex, descending_complex})
     }
+
+    /// Constructs a MorseSmaleComplex over only the nodes of `graph` whose entry in `mask` is
+    /// true, with `mask` indexed by `NodeIndex::index`. Nodes past the end of the mask are left
+    /// out.
+    ///
+    /// This is the same as [from_region](#method.from_region), but checking whether a node is in
+    /// the region is a lookup in a slice rather than a hash, which adds up when analyzing many
+    /// regions of one large graph. The mask can be reused (or rewritten in place) between calls.
+    pub fn from_mask<N: ScalarValued>(graph: &UnGraph<N, f64>, mask: &[bool], boundary: RegionBoundary,
+                        options: MorseOptions) -> Result<MorseSmaleComplex, MorseError> {
+        let in_mask = |node: NodeIndex| mask.get(node.index()).cloned().unwrap_or(false);
+        let (ascending_complex, descending_complex) = match boundary {
+            RegionBoundary::Induced => MorseComplex::pair_from_region(graph, in_mask, options)?,
+            RegionBoundary::Halo => {
+                let mut halo = vec![false; graph.node_count()];
+                for node in graph.node_indices().filter(|&node| in_mask(node)) {
+                    halo[node.index()] = true;
+                    for neighbor in graph.neighbors(node) {
+                        halo[neighbor.index()] = true;
+                    }
+                }
+                MorseComplex::pair_from_region(graph, |node| halo[node.index()], options)?
+            }
+        };
+
+        Ok(MorseSmaleComplex{ascending_complex, descending_complex})
+    }
+
+    /// Returns a HashMap mapping nodes to their Morse-Smale crystals.
+    ///
+    /// The crystal of a node is the pair of its cell's extremum in the ascending complex (a
+    /// minimum) and in the descending complex (a maximum). Nodes that only appear in one of the
+    /// complexes are left out.
+    pub fn get_cells(&self) -> HashMap<NodeIndex, (NodeIndex, NodeIndex)> {
+        let descending = self.descending_complex.get_complex();
+        self.ascending_complex.get_complex().into_iter()
+            .filter_map(|(node, minimum)| descending.get(&node).map(|&maximum| (node, (minimum, maximum))))
+            .collect()
+    }
+
+    /// Returns the distinct Morse-Smale crystals, as (minimum, maximum) pairs, each mapped to the
+    /// nodes in it, sorted by index.
+    pub fn get_crystals(&self) -> HashMap<(NodeIndex, NodeIndex), Vec<NodeIndex>> {
+        let mut crystals: HashMap<_, Vec<NodeIndex>> = HashMap::new();
+        for (node, crystal) in self.get_cells() {
+            crystals.entry(crystal).or_default().push(node);
+        }
+        for nodes in crystals.values_mut() {
+            nodes.sort();
+        }
+        crystals
+    }
+}
+
+/// Determines how the edges leaving a region are treated when analyzing only part of a graph.
+///
+/// See [MorseSmaleComplex::from_region](struct.MorseSmaleComplex.html#method.from_region).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionBoundary {
+    /// Only the region itself is analyzed, as if the rest of the graph did not exist. Nodes on
+    /// the edge of the region can become extrema simply because their higher (or lower)
+    /// neighbors were cut off.
+    Induced,
+
+    /// The region is analyzed along with every node adjacent to it (its halo). Nodes on the edge
+    /// of the region then flow into the halo where the function does, so no extrema are created by
+    /// the cut itself. The halo nodes are part of the resulting complexes.
+    Halo
+}
+
+/// How the length of a path through the graph is measured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCost {
+    /// The sum of the weights of the edges along the path.
+    EdgeWeight,
+
+    /// The number of edges along the path.
+    Hops,
+
+    /// The total absolute change in value along the path.
+    ValueChange
+}
+
+// A node on the frontier of a shortest path search, ordered so that BinaryHeap pops the
+// nearest node first
+#[derive(Debug, Clone, Copy)]
+struct PathState {
+    distance: f64,
+    node: NodeIndex
+}
+
+impl PartialEq for PathState {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PathState {}
+
+impl PartialOrd for PathState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PathState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, and ties broken by node, so that results are deterministic
+        match other.distance.partial_cmp(&self.distance) {
+            None | Some(Ordering::Equal) => other.node.cmp(&self.node),
+            Some(ord) => ord
+        }
+    }
 }
 
 /// The Morse complex constructed from a graph.
@@ -173,90 +513,593 @@ pub struct MorseComplex {
     ordered_points: Vec<MorseNode>,
     cells: PointedUnionFind,
     pub filtration: Vec<MorseFiltrationStep>,
-    kind: MorseKind
+    kind: MorseKind,
+    options: MorseOptions,
+    // the position of each node in ordered_points
+    positions: HashMap<NodeIndex, usize>
+}
+
+// Running totals kept during the sweep, for the persistence measures that need them
+#[derive(Debug, Clone, Default)]
+struct SweepTotals {
+    // the total value of each cell, kept at its representative, for measuring volumes
+    sums: Vec<f64>,
+    // the length of the integral line from each point to its cell's extremum, for geodesic
+    // persistence
+    lengths: Vec<f64>
+}
+
+impl SweepTotals {
+    // Starts the totals over for a sweep through the given (value, node, id) triples
+    fn reset(&mut self, nodes: &[(f64, NodeIndex, i64)]) {
+        self.sums.clear();
+        self.sums.extend(nodes.iter().map(|&(value, _, _)| value));
+        self.lengths.clear();
+        self.lengths.resize(nodes.len(), 0.);
+    }
+}
+
+// What a MorseEngine keeps from one sweep of its graph to the next: the neighbors of each node and
+// the position of each in the latest sweep (both by node index), the two sweep orders and the
+// running totals. Only the values change between sweeps, so only these have to be rewritten
+#[derive(Debug, Clone)]
+pub(crate) struct SweepBuffers {
+    adjacency: Vec<Vec<NodeIndex>>,
+    positions: Vec<usize>,
+    descending: Vec<(f64, NodeIndex, i64)>,
+    ascending: Vec<(f64, NodeIndex, i64)>,
+    totals: SweepTotals
+}
+
+impl SweepBuffers {
+    pub(crate) fn new<N, Ty: EdgeType>(graph: &Graph<N, f64, Ty>) -> SweepBuffers {
+        SweepBuffers{
+            adjacency: graph.node_indices().map(|node| graph.neighbors(node).collect()).collect(),
+            positions: vec![0; graph.node_count()],
+            descending: Vec::with_capacity(graph.node_count()),
+            ascending: Vec::with_capacity(graph.node_count()),
+            totals: SweepTotals::default()
+        }
+    }
 }
 
 impl MorseComplex {
-    fn from_graph<T>(kind: MorseKind, graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<MorseComplex, MorseError> {
-        let ordered_points = MorseComplex::get_ordered_points(kind, &graph)?;
-        let num_points = ordered_points.len();
-        let cells = PointedUnionFind::new(num_points);
-        let mut complex = MorseComplex{kind, ordered_points, cells, filtration: vec![]};
-        complex.construct_complex(graph)?;
+    pub(crate) fn from_graph<N: ScalarValued>(kind: MorseKind, graph: &UnGraph<N, f64>) -> Result<MorseComplex, MorseError> {
+        MorseComplex::from_region(kind, graph, |_| true, MorseOptions::default())
+    }
+
+    fn from_region<N: ScalarValued, F, Ty: EdgeType>(kind: MorseKind, graph: &Graph<N, f64, Ty>, in_region: F,
+                         options: MorseOptions) -> Result<MorseComplex, MorseError>
+        where F: Fn(NodeIndex) -> bool {
+        let mut nodes = MorseComplex::get_region_nodes(graph, graph.node_indices().filter(|&node| in_region(node)))?;
+        nodes.sort_by(|&a, &b| sweep_order(kind, options.tie_break, a, b));
+        if options.tie_break == TieBreak::Flood {
+            MorseComplex::flood_runs(&mut nodes, kind, graph, &in_region);
+        }
+        MorseComplex::from_ordered_nodes(kind, &nodes, graph, options)
+    }
+
+    // Builds the ascending and descending complexes of a region together, so that its nodes only
+    // need to be collected and sorted once
+    fn pair_from_region<N: ScalarValued, F, Ty: EdgeType>(graph: &Graph<N, f64, Ty>, in_region: F, options: MorseOptions)
+        -> Result<(MorseComplex, MorseComplex), MorseError>
+        where F: Fn(NodeIndex) -> bool {
+        let nodes = MorseComplex::get_region_nodes(graph, graph.node_indices().filter(|&node| in_region(node)))?;
+        MorseComplex::pair_from_nodes(graph, nodes, &in_region, options)
+    }
+
+    // Builds both complexes from the (value, node, id) triples of the nodes in a region, in any
+    // order. `in_region` must agree with the nodes given.
+    fn pair_from_nodes<N: ScalarValued, F, Ty: EdgeType>(graph: &Graph<N, f64, Ty>, mut descending: Vec<(f64, NodeIndex, i64)>, in_region: &F,
+                                           options: MorseOptions) -> Result<(MorseComplex, MorseComplex), MorseError>
+        where F: Fn(NodeIndex) -> bool {
+        let mut ascending = Vec::with_capacity(descending.len());
+        MorseComplex::order_pair(graph, &mut descending, &mut ascending, in_region, options.tie_break);
+        let mut totals = SweepTotals::default();
+        let ascending = MorseComplex::sweep(MorseKind::Ascending, &ascending, graph, options, &mut totals, None)?;
+        let descending = MorseComplex::sweep(MorseKind::Descending, &descending, graph, options, &mut totals, None)?;
+        Ok((ascending, descending))
+    }
+
+    // Builds both complexes of the whole of `graph` with the buffers of a MorseEngine, which must
+    // have been made for the same graph
+    pub(crate) fn pair_from_buffers<N: ScalarValued, Ty: EdgeType>(graph: &Graph<N, f64, Ty>, buffers: &mut SweepBuffers,
+                                                                 options: MorseOptions) -> Result<(MorseComplex, MorseComplex), MorseError> {
+        let SweepBuffers{adjacency, positions, descending, ascending, totals} = buffers;
+        descending.clear();
+        for node in graph.node_indices() {
+            let weight = &graph[node];
+            if weight.value().is_nan() {
+                return Err(MorseError::NanValue{node});
+            }
+            descending.push((weight.value(), node, weight.id().unwrap_or(node.index() as i64)));
+        }
+        MorseComplex::order_pair(graph, descending, ascending, &|_| true, options.tie_break);
+        let ascending = MorseComplex::sweep(MorseKind::Ascending, ascending, graph, options, totals, Some((adjacency, positions)))?;
+        let descending = MorseComplex::sweep(MorseKind::Descending, descending, graph, options, totals, Some((adjacency, positions)))?;
+        Ok((ascending, descending))
+    }
+
+    // Sorts the (value, node, id) triples in `descending` into the order of the descending sweep,
+    // and fills `ascending` with the order of the ascending one
+    fn order_pair<N: ScalarValued, F, Ty: EdgeType>(graph: &Graph<N, f64, Ty>, descending: &mut [(f64, NodeIndex, i64)],
+                                      ascending: &mut Vec<(f64, NodeIndex, i64)>, in_region: &F, tie_break: TieBreak)
+        where F: Fn(NodeIndex) -> bool {
+        descending.sort_by(|&a, &b| sweep_order(MorseKind::Descending, tie_break, a, b));
+        // The ascending sweep is the descending one backwards, except that TieBreak::Index (which
+        // Flood starts from) puts tied nodes in order of index in both, so each run of ties has to
+        // be turned back around. That takes linear time however large the plateaus are
+        ascending.clear();
+        ascending.extend(descending.iter().rev().cloned());
+        if tie_break != TieBreak::Id {
+            MorseComplex::for_each_run(ascending, |run| run.reverse());
+        }
+        if tie_break == TieBreak::Flood {
+            MorseComplex::flood_runs(ascending, MorseKind::Ascending, graph, in_region);
+            MorseComplex::flood_runs(descending, MorseKind::Descending, graph, in_region);
+        }
+    }
+
+    // Builds a complex by sweeping through (value, node, id) triples in the given order
+    fn from_ordered_nodes<N: ScalarValued, Ty: EdgeType>(kind: MorseKind, nodes: &[(f64, NodeIndex, i64)], graph: &Graph<N, f64, Ty>,
+                             options: MorseOptions) -> Result<MorseComplex, MorseError> {
+        MorseComplex::sweep(kind, nodes, graph, options, &mut SweepTotals::default(), None)
+    }
+
+    // Sweeps through (value, node, id) triples in the given order, keeping the running totals in
+    // `totals`. `cached` holds the neighbors of every node in the graph and room for the position
+    // of each in the sweep, both by node index, which then stand in for the graph and a hash of
+    // the positions when looking up the earlier neighbors of each point
+    fn sweep<N: ScalarValued, Ty: EdgeType>(kind: MorseKind, nodes: &[(f64, NodeIndex, i64)], graph: &Graph<N, f64, Ty>,
+                options: MorseOptions, totals: &mut SweepTotals, cached: Option<(&[Vec<NodeIndex>], &mut [usize])>)
+        -> Result<MorseComplex, MorseError> {
+        totals.reset(nodes);
+        let ordered_points: Vec<MorseNode> = nodes.iter().map(|&(_, node, _)| MorseNode::new(node)).collect();
+        let positions = ordered_points.iter().enumerate().map(|(p, point)| (point.node, p)).collect();
+        let cells = PointedUnionFind::new(ordered_points.len());
+        let mut complex = MorseComplex{kind, ordered_points, cells, filtration: vec![], options, positions};
+        let cached = cached.map(|(adjacency, lookup)| {
+            for (p, &(_, node, _)) in nodes.iter().enumerate() {
+                lookup[node.index()] = p;
+            }
+            (adjacency, &*lookup)
+        });
+        complex.construct_complex(graph, totals, cached)?;
         Ok(complex)
     }
 
-    fn get_ordered_points<T>(kind: MorseKind,
-                             graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<Vec<MorseNode>, MorseError> {
-        let nodes: Result<Vec<(NodeIndex, f64)>, MorseError> = graph.node_indices()
+    // The (value, node, id) triples of the given nodes, in the same order
+    fn get_region_nodes<N: ScalarValued, I, Ty: EdgeType>(graph: &Graph<N, f64, Ty>, nodes: I) -> Result<Vec<(f64, NodeIndex, i64)>, MorseError>
+        where I: Iterator<Item=NodeIndex> {
+        nodes
             .map(|node_idx| {
                 match graph.node_weight(node_idx) {
                     None => Err(MorseError::MissingNode{node: node_idx}),
                     Some(weight) => {
-                        if weight.value.is_nan() {
+                        if weight.value().is_nan() {
                             Err(MorseError::NanValue{node: node_idx})
                         } else{
-                            Ok((node_idx, weight.value))
+                            Ok((weight.value(), node_idx, weight.id().unwrap_or(node_idx.index() as i64)))
                         }
                     }
                 }
             })
+            .collect()
+    }
+
+    // Calls `f` on each run of equal values in sorted nodes
+    fn for_each_run<G>(nodes: &mut [(f64, NodeIndex, i64)], mut f: G)
+        where G: FnMut(&mut [(f64, NodeIndex, i64)]) {
+        let mut start = 0;
+        while start < nodes.len() {
+            let end = start + nodes[start..].iter().take_while(|&&(value, _, _)| value == nodes[start].0).count();
+            f(&mut nodes[start..end]);
+            start = end;
+        }
+    }
+
+    fn flood_runs<N: ScalarValued, F, Ty: EdgeType>(nodes: &mut [(f64, NodeIndex, i64)], kind: MorseKind, graph: &Graph<N, f64, Ty>, in_region: &F)
+        where F: Fn(NodeIndex) -> bool {
+        MorseComplex::for_each_run(nodes, |run| MorseComplex::flood_ties(run, kind, graph, in_region));
+    }
+
+    // Reorders a run of tied nodes (already sorted by index) breadth first across their plateaus,
+    // starting from the nodes with a neighbor in the region that comes earlier in the sweep
+    fn flood_ties<N: ScalarValued, F, Ty: EdgeType>(tied: &mut [(f64, NodeIndex, i64)], kind: MorseKind, graph: &Graph<N, f64, Ty>,
+                        in_region: &F)
+        where F: Fn(NodeIndex) -> bool {
+        if tied.len() < 2 {
+            return;
+        }
+        let level = tied[0].0;
+        let in_run: HashMap<NodeIndex, usize> = tied.iter().enumerate().map(|(i, &(_, node, _))| (node, i)).collect();
+        let mut visited = vec![false; tied.len()];
+        let mut queue: VecDeque<usize> = (0..tied.len())
+            .filter(|&i| graph.neighbors(tied[i].1).any(|n| {
+                in_region(n) && graph.node_weight(n).is_some_and(|w| match kind {
+                    MorseKind::Descending => w.value() > level,
+                    MorseKind::Ascending => w.value() < level
+                })
+            }))
             .collect();
-        let mut nodes = nodes?;
-
-        nodes.sort_by(|(_, a), (_, b)| {
-                // we know these aren't nan, but the compiler doesn't, so just handle nans
-                // arbitrarily
-                match kind {
-                    MorseKind::Descending => match b.partial_cmp(&a) {
-                        None => Ordering::Less,
-                        Some(ord) => ord
-                    },
-                    MorseKind::Ascending => match a.partial_cmp(&b) {
-                        None => Ordering::Less,
-                        Some(ord) => ord
-                    }
+        for &i in queue.iter() {
+            visited[i] = true;
+        }
+        let mut order = Vec::with_capacity(tied.len());
+        let mut seeds = 0..tied.len();
+        loop {
+            while let Some(i) = queue.pop_front() {
+                order.push(i);
+                let mut next: Vec<usize> = graph.neighbors(tied[i].1)
+                    .filter_map(|n| in_run.get(&n).cloned())
+                    .filter(|&j| !visited[j])
+                    .collect();
+                next.sort_unstable();
+                next.dedup();
+                for j in next {
+                    visited[j] = true;
+                    queue.push_back(j);
+                }
+            }
+            // plateaus with no way up are extrema, flooded from their first node
+            match seeds.find(|&i| !visited[i]) {
+                None => break,
+                Some(i) => {
+                    visited[i] = true;
+                    queue.push_back(i);
                 }
-            });
-        Ok(nodes.iter().map(|(n, _)| MorseNode::new(*n)).collect())
+            }
+        }
+        let reordered: Vec<_> = order.into_iter().map(|i| tied[i]).collect();
+        tied.copy_from_slice(&reordered);
     }
 
-    fn compute_filtration(&self) -> Vec<MorseFiltrationStep> {
-        let mut filtration = self.ordered_points.iter() 
-            .filter_map(|point| {
-                match point.data.as_ref() {
-                    Some(data) => {
-                        if let Some(parent) = data.merge_parent {
-                            Some(MorseFiltrationStep{time: data.lifetime, destroyed_cell: point.node, owning_cell: parent})
-                        } else {
-                            None
-                        }
-                    }
-                    None => None
-                }
-             })
-             .collect::<Vec<_>>();
+    fn compute_filtration<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>) -> Result<Vec<MorseFiltrationStep>, MorseError> {
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let mut filtration = vec![];
+        for point in self.ordered_points.iter() {
+            let data = match point.data.as_ref() {
+                None => continue,
+                Some(data) => data
+            };
+            if let (Some(parent), Some(merge_node)) = (data.merge_parent, data.merge_node) {
+                filtration.push(MorseFiltrationStep{time: data.lifetime, destroyed_cell: point.node, owning_cell: parent, merge_node,
+                    birth: value(point.node)?, death: value(merge_node)?});
+            }
+        }
         // there _shouldn't_ be nans in here, looking forward to being confused in a month when
         // there are!
         filtration.sort_by(|a, b| match a.time.partial_cmp(&b.time) {
             None => Ordering::Less,
             Some(ord) => ord
         });
-        filtration
+        Ok(filtration)
+    }
+
+    /// Whether this is an ascending complex, whose extrema are minima, or a descending one, whose
+    /// extrema are maxima.
+    pub fn kind(&self) -> MorseKind {
+        self.kind
+    }
+
+    /// Returns the extrema of the Morse cells with their persistence, from the most to the least
+    /// persistent, so the global extrema come first. Ties are in the order of the sweep.
+    ///
+    /// These are minima or maxima depending on the [kind](#method.kind) of the complex. An extremum
+    /// whose cell merged at a saddle with the same value has a persistence of 0, so this is not
+    /// the same as the nodes with nonzero [persistence](#method.get_persistence).
+    pub fn extrema(&self) -> Vec<(NodeIndex, f64)> {
+        let mut extrema: Vec<(NodeIndex, f64)> = self.ordered_points.iter()
+            .filter_map(|point| point.data.as_ref()
+                .filter(|data| data.ancestor == point.node)
+                .map(|data| (point.node, data.lifetime)))
+            .collect();
+        // stable, so ties stay in sweep order
+        extrema.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        extrema
     }
 
     /// Returns a HashMap mapping nodex to their Morse cell extrema
     pub fn get_complex(&self) -> HashMap<NodeIndex, NodeIndex> {
         self.ordered_points.iter() 
             .filter_map(|point| {
-                match point.data.as_ref() {
-                    Some(data) => Some((point.node, data.ancestor)),
-                    None => None
-                }
+                point.data.as_ref().map(|data| (point.node, data.ancestor))
              })
              .collect()
     }
 
+    // The position of `node` in the sweep, or None if it isn't part of the complex
+    pub(crate) fn sweep_position(&self, node: NodeIndex) -> Option<usize> {
+        self.positions.get(&node).cloned()
+    }
+
+    /// Returns the nodes in the Morse cell of `extremum`, sorted by index.
+    ///
+    /// This is empty if `extremum` isn't an extremum of this complex. Looking up many cells this
+    /// way takes a pass over the complex for each; use [get_members](#method.get_members) instead.
+    pub fn members_of(&self, extremum: NodeIndex) -> Vec<NodeIndex> {
+        let mut members: Vec<NodeIndex> = self.ordered_points.iter()
+            .filter(|point| point.data.as_ref().is_some_and(|data| data.ancestor == extremum))
+            .map(|point| point.node)
+            .collect();
+        members.sort();
+        members
+    }
+
+    /// Returns a HashMap mapping each extremum to the nodes in its Morse cell, sorted by index.
+    ///
+    /// This is the inverse of [get_complex](#method.get_complex).
+    pub fn get_members(&self) -> HashMap<NodeIndex, Vec<NodeIndex>> {
+        let mut members: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::new();
+        for (node, extremum) in self.get_complex() {
+            members.entry(extremum).or_default().push(node);
+        }
+        for nodes in members.values_mut() {
+            nodes.sort();
+        }
+        members
+    }
+
+    /// Returns a HashMap mapping nodes to their Morse cell extrema once the complex has been
+    /// simplified up to time `t`.
+    ///
+    /// Every step of the filtration with a time of at most `t` is applied, so the cells of all of
+    /// the extrema with persistence `<= t` are merged into the cells that destroyed them.
+    /// `complex_at(0.)` is the same as [get_complex](#method.get_complex), unless there are
+    /// extrema with zero persistence.
+    pub fn complex_at(&self, t: f64) -> HashMap<NodeIndex, NodeIndex> {
+        let merged_into = MorseComplex::merged_into(self.filtration.iter().take_while(|step| step.time <= t));
+        self.get_complex().into_iter()
+            .map(|(node, extremum)| (node, MorseComplex::survivor(&merged_into, extremum)))
+            .collect()
+    }
+
+    /// Returns a HashMap mapping nodes to their Morse cell extrema once every extremum with
+    /// persistence strictly below `t` has been merged away.
+    ///
+    /// Unlike [complex_at](#method.complex_at), extrema with persistence exactly `t` keep their
+    /// cells. This is the convention of [simplify](#method.simplify), so `complex_below(t)` is
+    /// `simplify(t).get_complex()` without building the rest of the complex.
+    pub fn complex_below(&self, t: f64) -> HashMap<NodeIndex, NodeIndex> {
+        let merged_into = MorseComplex::merged_into(self.filtration.iter().take_while(|step| step.time < t));
+        self.get_complex().into_iter()
+            .map(|(node, extremum)| (node, MorseComplex::survivor(&merged_into, extremum)))
+            .collect()
+    }
+
+    /// Returns the partition of the graph simplified up to each of `thresholds`, in the same order,
+    /// as [complex_at](#method.complex_at) would for each.
+    ///
+    /// This makes a single pass over the filtration, and only looks up the surviving extremum of
+    /// each cell once per threshold, rather than once per node.
+    pub fn complexes_at(&self, thresholds: &[f64]) -> Vec<HashMap<NodeIndex, NodeIndex>> {
+        let complex = self.get_complex();
+        let mut extrema: Vec<NodeIndex> = complex.values().cloned().collect();
+        extrema.sort();
+        extrema.dedup();
+
+        let mut order: Vec<usize> = (0..thresholds.len()).collect();
+        order.sort_by(|&a, &b| thresholds[a].partial_cmp(&thresholds[b]).unwrap_or(Ordering::Equal));
+        let mut snapshots = vec![HashMap::new(); thresholds.len()];
+        let mut merged_into = HashMap::new();
+        let mut steps = self.filtration.iter().peekable();
+        for i in order {
+            while let Some(step) = steps.next_if(|step| step.time <= thresholds[i]) {
+                merged_into.insert(step.destroyed_cell, step.owning_cell);
+            }
+            let survivors: HashMap<NodeIndex, NodeIndex> = extrema.iter()
+                .map(|&extremum| (extremum, MorseComplex::survivor(&merged_into, extremum)))
+                .collect();
+            snapshots[i] = complex.iter().map(|(&node, extremum)| (node, survivors[extremum])).collect();
+        }
+        snapshots
+    }
+
+    /// Returns a copy of this complex in which every extremum with persistence below `threshold`
+    /// has been merged away.
+    ///
+    /// Extrema with persistence exactly `threshold` are kept, as they are by
+    /// [complex_below](#method.complex_below), which gives the partition of the result without
+    /// building the rest of the complex.
+    ///
+    /// The cells of the removed extrema are merged into the cells that destroyed them, the removed
+    /// extrema become regular points with zero persistence, and the filtration only contains the
+    /// steps that are still to come. Every method then behaves as if the removed extrema had never
+    /// been there, so e.g. `simplify(t).get_complex()` gives the segmentation at that scale.
+    pub fn simplify(&self, threshold: f64) -> MorseComplex {
+        let (removed, remaining): (Vec<_>, Vec<_>) = self.filtration.iter()
+            .partition(|step| step.time < threshold);
+        let merged_into = MorseComplex::merged_into(removed.into_iter());
+        let ordered_points = self.ordered_points.iter()
+            .map(|point| MorseNode{node: point.node, data: point.data.as_ref().map(|data| {
+                if merged_into.contains_key(&point.node) {
+                    MorseData{lifetime: 0., merge_parent: None, merge_node: None, ancestor: MorseComplex::survivor(&merged_into, point.node)}
+                } else {
+                    MorseData{
+                        lifetime: data.lifetime,
+                        merge_parent: data.merge_parent.map(|parent| MorseComplex::survivor(&merged_into, parent)),
+                        merge_node: data.merge_node,
+                        ancestor: MorseComplex::survivor(&merged_into, data.ancestor)
+                    }
+                }
+            })})
+            .collect();
+        let filtration = remaining.into_iter()
+            .map(|step| MorseFiltrationStep{owning_cell: MorseComplex::survivor(&merged_into, step.owning_cell), ..*step})
+            .collect();
+        MorseComplex{ordered_points, cells: self.cells.clone(), filtration, kind: self.kind, options: self.options,
+            positions: self.positions.clone()}
+    }
+
+    /// Returns new values for the nodes of the complex, in which every extremum with persistence
+    /// below `threshold` has been flattened away.
+    ///
+    /// When the cell of a removed extremum merges into another at a saddle, every node that had
+    /// already flowed into it is lowered (or raised, for an ascending complex) to the value of
+    /// the saddle, so the peak becomes a plateau level with the saddle. No other values change,
+    /// and the function stays the same everywhere the simplified complex doesn't differ from this
+    /// one. Since the result has plateaus, build complexes of it with
+    /// [TieBreak::Flood](enum.TieBreak.html#variant.Flood), which drains them through the saddles
+    /// so that only the extrema that [simplify](#method.simplify) keeps remain.
+    ///
+    /// Call this on the complex before it is simplified, as simplifying forgets where the removed
+    /// cells were merged. Returns an error if a node of the complex is missing from `graph`.
+    pub fn simplified_values<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>, threshold: f64)
+        -> Result<HashMap<NodeIndex, f64>, MorseError> {
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let flatten = |current: f64, saddle: f64| match self.kind {
+            MorseKind::Descending => current.min(saddle),
+            MorseKind::Ascending => current.max(saddle)
+        };
+        let mut values = HashMap::with_capacity(self.ordered_points.len());
+        for (position, point) in self.ordered_points.iter().enumerate() {
+            let mut flattened = value(point.node)?;
+            // Follow the merges of the node's cell. The node is part of the component of each
+            // extremum on the way that dies after the node was swept
+            let mut extremum = self.ancestor_at(position)?;
+            while let Some(data) = self.sweep_position(extremum).and_then(|p| self.ordered_points[p].data.as_ref()) {
+                let (parent, saddle) = match (data.merge_parent, data.merge_node) {
+                    (Some(parent), Some(saddle)) => (parent, saddle),
+                    _ => break
+                };
+                if data.lifetime < threshold && self.sweep_position(saddle).is_some_and(|p| p > position) {
+                    flattened = flatten(flattened, value(saddle)?);
+                }
+                extremum = parent;
+            }
+            values.insert(point.node, flattened);
+        }
+        Ok(values)
+    }
+
+    /// Returns a copy of this complex in which the cell of `extremum` has been merged into the
+    /// neighboring cell of `into`, regardless of their persistence.
+    ///
+    /// `extremum` becomes a regular point, and the cells it would have destroyed later in the
+    /// filtration are destroyed by `into` instead. If `extremum` would have destroyed `into`
+    /// itself, `into` takes its place in the filtration, with its persistence. Otherwise, the
+    /// persistence of every other extremum is kept as it was, so after a cancellation that goes
+    /// against the persistence order they need not be what a sweep of the function would give.
+    /// This is meant for exploring a complex interactively, alongside [simplify](#method.simplify).
+    ///
+    /// Returns an error if either node isn't an extremum of this complex, or if their cells don't
+    /// share an edge in `graph`. It's also an error if `into` would have been destroyed by
+    /// `extremum` by way of some other cell, since that cell would then be destroyed by `into`
+    /// while destroying it, leaving the merge hierarchy without a root.
+    pub fn cancel<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>, extremum: NodeIndex,
+                                                 into: NodeIndex) -> Result<MorseComplex, MorseError> {
+        for &node in &[extremum, into] {
+            if self.extremum_of(node) != Some(node) {
+                return Err(MorseError::NotAnExtremum{node});
+            }
+        }
+        let adjacent = extremum != into && self.ordered_points.iter()
+            .filter(|point| point.data.as_ref().is_some_and(|data| data.ancestor == extremum))
+            .any(|point| graph.neighbors_undirected(point.node).any(|neighbor| self.extremum_of(neighbor) == Some(into)));
+        if !adjacent {
+            return Err(MorseError::NotAdjacent{node: extremum, other: into});
+        }
+
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let position = |node: NodeIndex| self.positions[&node];
+        let merge_parent = |node: NodeIndex| self.ordered_points[position(node)].data.as_ref().and_then(|data| data.merge_parent);
+        let cancelled = self.ordered_points[position(extremum)].data.as_ref().ok_or(MorseError::MissingData{node: extremum})?;
+        let inherits = merge_parent(into) == Some(extremum);
+        // the children of `extremum` are handed to `into`, so if `into` is further down one of
+        // their chains it would end up destroyed by its own descendant
+        if !inherits {
+            let mut ancestor = merge_parent(into);
+            while let Some(node) = ancestor {
+                if node == extremum {
+                    return Err(MorseError::MergeCycle{node: extremum, other: into});
+                }
+                ancestor = merge_parent(node);
+            }
+        }
+        let rename = |node: NodeIndex| if node == extremum { into } else { node };
+        let ordered_points = self.ordered_points.iter()
+            .map(|point| MorseNode{node: point.node, data: point.data.as_ref().map(|data| {
+                if point.node == extremum {
+                    MorseData{lifetime: 0., merge_parent: None, merge_node: None, ancestor: into}
+                } else if point.node == into && inherits {
+                    MorseData{lifetime: cancelled.lifetime, merge_parent: cancelled.merge_parent, merge_node: cancelled.merge_node, ancestor: into}
+                } else {
+                    MorseData{
+                        lifetime: data.lifetime,
+                        merge_parent: data.merge_parent.map(rename),
+                        merge_node: data.merge_node,
+                        ancestor: rename(data.ancestor)
+                    }
+                }
+            })})
+            .collect();
+        let mut filtration = Vec::with_capacity(self.filtration.len());
+        for step in self.filtration.iter() {
+            if step.destroyed_cell == extremum {
+                if inherits {
+                    filtration.push(MorseFiltrationStep{destroyed_cell: into, birth: value(into)?, ..*step});
+                }
+            } else if !(inherits && step.destroyed_cell == into) {
+                filtration.push(MorseFiltrationStep{owning_cell: rename(step.owning_cell), ..*step});
+            }
+        }
+        Ok(MorseComplex{ordered_points, cells: self.cells.clone(), filtration, kind: self.kind, options: self.options,
+            positions: self.positions.clone()})
+    }
+
+    // Maps the cells destroyed by the given filtration steps to the cells that destroyed them
+    fn merged_into<'a>(steps: impl Iterator<Item=&'a MorseFiltrationStep>) -> HashMap<NodeIndex, NodeIndex> {
+        steps.map(|step| (step.destroyed_cell, step.owning_cell)).collect()
+    }
+
+    // Follows the merges of `extremum`'s cell to the extremum whose cell it ends up in
+    fn survivor(merged_into: &HashMap<NodeIndex, NodeIndex>, mut extremum: NodeIndex) -> NodeIndex {
+        while let Some(&owner) = merged_into.get(&extremum) {
+            extremum = owner;
+        }
+        extremum
+    }
+
+    /// Returns the number of cells left by [simplify](#method.simplify) as the threshold grows,
+    /// as `(persistence, count)` pairs in increasing order of persistence.
+    ///
+    /// Each count is the number of cells once every extremum with persistence up to and including
+    /// its persistence has been merged away, i.e. for thresholds just above it. Below the first
+    /// entry, every one of the [extrema](#method.extrema) has its own cell. This is the step
+    /// function whose elbow is a common choice of threshold.
+    pub fn persistence_curve(&self) -> Vec<(f64, usize)> {
+        let mut count = self.extrema().len();
+        let mut curve: Vec<(f64, usize)> = vec![];
+        for step in self.filtration.iter() {
+            count -= 1;
+            match curve.last_mut() {
+                Some(last) if last.0 == step.time => last.1 = count,
+                _ => curve.push((step.time, count))
+            }
+        }
+        curve
+    }
+
+    /// Returns the persistence pair of every extremum, in the order the sweep reached them.
+    pub fn get_persistence_pairs<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>) -> Result<Vec<PersistencePair>, MorseError> {
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let never = match self.kind {
+            MorseKind::Descending => f64::NEG_INFINITY,
+            MorseKind::Ascending => f64::INFINITY
+        };
+        self.ordered_points.iter()
+            .filter_map(|point| point.data.as_ref().map(|data| (point.node, data)))
+            .filter(|(node, data)| data.ancestor == *node)
+            .map(|(extremum, data)| {
+                let death = match data.merge_node {
+                    Some(saddle) => value(saddle)?,
+                    None => never
+                };
+                Ok(PersistencePair{extremum, saddle: data.merge_node, birth: value(extremum)?, death})
+            })
+            .collect()
+    }
+
     /// Returns a mapping of NodeIndices to persistence values.
     ///
     /// Note that, by definition, global extrema have infinite persistence, and non-extrema have 0
@@ -271,44 +1114,194 @@ impl MorseComplex {
         result
     }
 
-    fn construct_complex<T>(&mut self, graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<&Self, MorseError>{
-        // We iterate through the points in descending (or ascending, depends on self.kind) 
-        // order, which means we are essentially building the morse complex at the same time
-        // that we compute persistence.
+    /// Returns the [persistent entropy](../stats/fn.persistent_entropy.html) of the persistence
+    /// values of the extrema, in the measure the complex was built with.
+    ///
+    /// The global extrema, whose persistence is infinite, are left out.
+    pub fn persistent_entropy(&self) -> f64 {
+        let lifetimes: Vec<f64> = self.get_persistence().values().cloned().collect();
+        super::stats::persistent_entropy(&lifetimes)
+    }
 
-        let inverse_lookup: HashMap<NodeIndex, usize> = self.ordered_points.iter().enumerate()
-            .map(|x| (x.1.node, x.0))
-            .collect();
+    /// Suggests a threshold for [simplify](#method.simplify) from the persistence values of the
+    /// extrema, as in [suggest_threshold](../stats/fn.suggest_threshold.html).
+    pub fn suggest_threshold(&self, heuristic: ThresholdHeuristic) -> Option<ThresholdSuggestion> {
+        let lifetimes: Vec<f64> = self.get_persistence().values().cloned().collect();
+        super::stats::suggest_threshold(&lifetimes, heuristic)
+    }
 
-        for i in 0..self.ordered_points.len() {
-            // find all *already processed* points that we have an edge to
-            let this_value = match graph.node_weight(self.ordered_points[i].node) {
-                None => return Err(MorseError::MissingNode{node: self.ordered_points[i].node}),
-                Some(weight) => weight.value
+    /// Returns a mapping of NodeIndices to persistence values, as fractions of the range of values.
+    ///
+    /// The persistence values are divided by the largest persistence any extremum could have, so
+    /// that they fall in `[0, 1]` (besides the infinite persistence of global extrema). Thresholds
+    /// on these carry over between functions on different scales. That is the difference between
+    /// the largest and the smallest value in the complex, the number of nodes, or their product,
+    /// depending on the [measure](enum.PersistenceMeasure.html). For geodesic persistence it is
+    /// that difference plus the total weight of the edges between the nodes of the complex, which
+    /// no path that only climbs can exceed. If the function is constant, the values are returned
+    /// as they are.
+    pub fn get_relative_persistence<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>) -> Result<HashMap<NodeIndex, f64>, MorseError> {
+        let mut low = f64::INFINITY;
+        let mut high = f64::NEG_INFINITY;
+        for point in self.ordered_points.iter() {
+            let value = match graph.node_weight(point.node) {
+                None => return Err(MorseError::MissingNode{node: point.node}),
+                Some(weight) => weight.value()
             };
-            let higher_indices: Result<Vec<usize>, MorseError> = graph.neighbors(self.ordered_points[i].node)
-                .filter(|n| { 
-                    // I don't love silently dropping missing node weights, but the problem will
-                    // throw an error farther down the line
-                    let value = match graph.node_weight(*n) {
-                        None => return false,
-                        Some(weight) => weight.value
+            low = low.min(value);
+            high = high.max(value);
+        }
+        let range = if high > low { high - low } else { 1. };
+        let range = match self.options.measure {
+            PersistenceMeasure::Value => range,
+            PersistenceMeasure::Size => self.ordered_points.len() as f64,
+            PersistenceMeasure::Volume => range * self.ordered_points.len() as f64,
+            PersistenceMeasure::Geodesic => range + graph.raw_edges().iter()
+                .filter(|edge| self.positions.contains_key(&edge.source()) && self.positions.contains_key(&edge.target()))
+                .map(|edge| edge.weight)
+                .sum::<f64>()
+        };
+        Ok(self.get_persistence().into_iter()
+            .map(|(node, lifetime)| (node, lifetime / range))
+            .collect())
+    }
+
+    /// Returns the number of connected components of the level sets of the function at each of
+    /// the given `thresholds`.
+    ///
+    /// For a descending complex these are the superlevel sets (the subgraphs induced by the nodes
+    /// with values `>= t`), and for an ascending complex the sublevel sets (values `<= t`). If
+    /// `thresholds` is `None`, the counts are reported at every value at which the count changes,
+    /// in sweep order.
+    ///
+    /// Every component is born at an extremum and dies when its cell is merged, so this is read
+    /// off of the persistence values rather than computed with another sweep. These counts are the
+    /// Betti-0 curve of the filtration by sublevel (or superlevel) sets.
+    pub fn component_counts<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>, thresholds: Option<&[f64]>)
+        -> Result<Vec<(f64, usize)>, MorseError> {
+        // work with values negated for ascending complexes, so that both are superlevel sets
+        let sign = match self.kind {
+            MorseKind::Descending => 1.,
+            MorseKind::Ascending => -1.
+        };
+        let mut births = vec![];
+        let mut deaths = vec![];
+        for point in &self.ordered_points {
+            if let Some(data) = point.data.as_ref() {
+                if data.ancestor == point.node {
+                    let value = match graph.node_weight(point.node) {
+                        None => return Err(MorseError::MissingNode{node: point.node}),
+                        Some(weight) => weight.value() * sign
                     };
-                    match self.kind {
-                        MorseKind::Ascending => value <= this_value,
-                        MorseKind::Descending => value >= this_value
+                    births.push(value);
+                    if let Some(saddle) = data.merge_node {
+                        match graph.node_weight(saddle) {
+                            None => return Err(MorseError::MissingNode{node: saddle}),
+                            Some(weight) => deaths.push(weight.value() * sign)
+                        }
                     }
-                })
-                .map(|n| match inverse_lookup.get(&n) {
-                    None => Err(MorseError::MissingNode{node: n}),
-                    Some(&n_idx) => Ok(n_idx)
-                })
-                .filter(|n_idx| match n_idx {
-                    Err(_) => true,
-                    Ok(n_idx) => *n_idx < i
-                })
-                .collect();
-            let higher_indices = higher_indices?;
+                }
+            }
+        }
+        let by_value = |a: &f64, b: &f64| a.partial_cmp(b).unwrap_or(Ordering::Less);
+        births.sort_by(by_value);
+        deaths.sort_by(by_value);
+
+        let thresholds: Vec<f64> = match thresholds {
+            Some(thresholds) => thresholds.to_vec(),
+            None => {
+                let mut critical: Vec<f64> = births.iter().chain(deaths.iter()).map(|v| v * sign).collect();
+                critical.sort_by(|a, b| by_value(&(b * sign), &(a * sign)));
+                critical.dedup();
+                critical
+            }
+        };
+        Ok(thresholds.iter()
+            .map(|&t| {
+                let t_signed = t * sign;
+                let born = births.len() - births.partition_point(|&v| v < t_signed);
+                let died = deaths.len() - deaths.partition_point(|&v| v < t_signed);
+                (t, born - died)
+            })
+            .collect())
+    }
+
+    /// Returns the `m` extrema closest to `node`, along with their distances, nearest first.
+    ///
+    /// Only extrema with at least `min_persistence` persistence are considered, so passing a
+    /// simplification threshold restricts the search to the extrema that survive it. Distances
+    /// are shortest path lengths through `graph`, with each edge costing as determined by `cost`.
+    pub fn nearest_extrema<N: ScalarValued>(&self, graph: &UnGraph<N, f64>, node: NodeIndex, m: usize,
+                              min_persistence: f64, cost: PathCost) -> Result<Vec<(NodeIndex, f64)>, MorseError> {
+        let surviving: HashSet<NodeIndex> = self.ordered_points.iter()
+            .filter_map(|point| match point.data.as_ref() {
+                Some(data) if data.ancestor == point.node && data.lifetime >= min_persistence => Some(point.node),
+                _ => None
+            })
+            .collect();
+        let value = |node: NodeIndex| match graph.node_weight(node) {
+            None => Err(MorseError::MissingNode{node}),
+            Some(weight) => Ok(weight.value())
+        };
+
+        let mut found = Vec::with_capacity(m);
+        let mut distances: HashMap<NodeIndex, f64> = HashMap::new();
+        let mut frontier = BinaryHeap::new();
+        distances.insert(node, 0.);
+        frontier.push(PathState{distance: 0., node});
+        while let Some(PathState{distance, node}) = frontier.pop() {
+            if found.len() >= m {
+                break;
+            }
+            if distance > distances[&node] {
+                continue;
+            }
+            if surviving.contains(&node) {
+                found.push((node, distance));
+            }
+            for edge in graph.edges(node) {
+                let neighbor = edge.target();
+                let step = match cost {
+                    PathCost::EdgeWeight => *edge.weight(),
+                    PathCost::Hops => 1.,
+                    PathCost::ValueChange => (value(neighbor)? - value(node)?).abs()
+                };
+                let next = distance + step;
+                let improved = match distances.get(&neighbor) {
+                    None => true,
+                    Some(&current) => next < current
+                };
+                if improved {
+                    distances.insert(neighbor, next);
+                    frontier.push(PathState{distance: next, node: neighbor});
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn construct_complex<N: ScalarValued, Ty: EdgeType>(&mut self, graph: &Graph<N, f64, Ty>, totals: &mut SweepTotals,
+                                                         cached: Option<(&[Vec<NodeIndex>], &[usize])>) -> Result<&Self, MorseError>{
+        // We iterate through the points in descending (or ascending, depends on self.kind) 
+        // order, which means we are essentially building the morse complex at the same time
+        // that we compute persistence. `totals` starts out with the value of each point, in the
+        // same order.
+
+        let mut higher_indices = Vec::new();
+        for i in 0..self.ordered_points.len() {
+            // find all *already processed* points that we have an edge to. Since the points are
+            // sorted, these are exactly the neighbors that are at least as high (or low)
+            let node = self.ordered_points[i].node;
+            higher_indices.clear();
+            match cached {
+                Some((adjacency, positions)) => higher_indices.extend(adjacency[node.index()].iter()
+                    .map(|n| positions[n.index()])
+                    .filter(|&n_idx| n_idx < i)),
+                None => higher_indices.extend(graph.neighbors(node)
+                    // neighbors that weren't ordered are outside of the region being analyzed
+                    .filter_map(|n| self.positions.get(&n).cloned())
+                    .filter(|&n_idx| n_idx < i))
+            }
 
             // Nothing to do if we have no neighbors, but if we do then we
             // have to merge the correspond morse cells
@@ -317,20 +1310,20 @@ impl MorseComplex {
             } else {
                 0.
             };
-            let ancestor = self.add_point_to_complex(i, &higher_indices, graph)?;
+            let ancestor = self.add_point_to_complex(i, &higher_indices, totals, graph)?;
 
             // this is not a maximum so it has no lifetime
-            self.ordered_points[i].data = Some(MorseData{lifetime, ancestor, merge_parent: None});
+            self.ordered_points[i].data = Some(MorseData{lifetime, ancestor, merge_parent: None, merge_node: None});
         }
-        self.filtration = self.compute_filtration();
+        self.filtration = self.compute_filtration(graph)?;
         Ok(self)
     }
 
     // FIXME: I don't like this signature. Not at all clear what this returned nodeindex means
     // FIXME: another type issue: usize gets used in two different ways (as cell and as index into
     // ordered_points). Would be good to clarify which was which
-    fn add_point_to_complex<T>(&mut self, ordered_index: usize, ascending_neighbors: &[usize],
-                      graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<NodeIndex, MorseError> {
+    fn add_point_to_complex<N: ScalarValued, Ty: EdgeType>(&mut self, ordered_index: usize, ascending_neighbors: &[usize], totals: &mut SweepTotals,
+                      graph: &Graph<N, f64, Ty>) -> Result<NodeIndex, MorseError> {
         // If there are no neighbors, there's nothing to merge
         if ascending_neighbors.is_empty() {
             return Ok(self.ordered_points[ordered_index].node);
@@ -339,7 +1332,8 @@ impl MorseComplex {
         // one neighbor is easy, just union this point in to that neighbor's cell
         if ascending_neighbors.len() == 1 {
             let neighbor_index = ascending_neighbors[0];
-            self.cells.union(neighbor_index, ordered_index);
+            self.extend_line(ordered_index, neighbor_index, totals, graph)?;
+            self.union_cells(neighbor_index, ordered_index, totals);
             let neighbor = &self.ordered_points[neighbor_index];
             return match neighbor.data.as_ref() {
                 None => Err(MorseError::MissingData{node: neighbor.node}),
@@ -352,10 +1346,12 @@ impl MorseComplex {
             .map(|&idx| self.cells.find(idx))
             .collect();
 
-        // If they are all in the same cell, it's the same as if there was just one neighbor
+        // If they are all in the same cell, it's the same as if there was just one neighbor: the
+        // steepest of them, so the line and the extremum it ends at don't depend on edge order
         if connected_cells.len() == 1 {
-            let neighbor_index = ascending_neighbors[0];
-            self.cells.union(neighbor_index, ordered_index);
+            let neighbor_index = self.find_steepest_neighbor(ordered_index, ascending_neighbors, graph)?;
+            self.extend_line(ordered_index, neighbor_index, totals, graph)?;
+            self.union_cells(neighbor_index, ordered_index, totals);
             let neighbor = &self.ordered_points[neighbor_index];
             return match neighbor.data.as_ref() {
                 None => Err(MorseError::MissingData{node: neighbor.node}),
@@ -367,7 +1363,8 @@ impl MorseComplex {
         // first figure out what the global max is
         let max_cell = self.find_max_cell(ordered_index, &connected_cells, graph)?;
         let steepest_neighbor = self.find_steepest_neighbor(ordered_index, ascending_neighbors, graph)?;
-        self.merge_cells(ordered_index, max_cell, &connected_cells, graph)?;
+        self.extend_line(ordered_index, steepest_neighbor, totals, graph)?;
+        self.merge_cells(ordered_index, max_cell, &connected_cells, ascending_neighbors, totals, graph)?;
         let ancestor = &self.ordered_points[steepest_neighbor];
 
         match ancestor.data.as_ref() {
@@ -376,23 +1373,28 @@ impl MorseComplex {
         }
     }
 
-    fn find_max_cell<T>(&self, joining_index: usize, connected_cells: &HashSet<usize>, 
-                        graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<usize, MorseError> {
+    fn find_max_cell<N: ScalarValued, Ty: EdgeType>(&self, joining_index: usize, connected_cells: &HashSet<usize>, 
+                        graph: &Graph<N, f64, Ty>) -> Result<usize, MorseError> {
         let mut current_max = None;
         let mut max_index = Err(MorseError::NoMaximum{node: self.ordered_points[joining_index].node});
         for &cell_index in connected_cells {
             let node = self.ordered_points[cell_index].node;
             let value = match graph.node_weight(node) {
                 None => return Err(MorseError::MissingNode{node}),
-                Some(weight) => weight.value
+                Some(weight) => weight.value()
             };
-            let should_update = match current_max {
-                None => true,
-                Some(max_val) => match self.kind {
+            // cells are represented by their extrema, which come earlier in the sweep the higher
+            // they are, so ties go to the earlier one rather than to whichever the set yields first
+            let should_update = match (current_max, max_index.as_ref()) {
+                (Some(max_val), Ok(&max_cell)) => {
+                    let more_extreme = match self.kind {
                         MorseKind::Descending => value > max_val,
                         MorseKind::Ascending => value < max_val
-                    }
-                };
+                    };
+                    more_extreme || (value == max_val && cell_index < max_cell)
+                },
+                _ => true
+            };
             if should_update {
                 current_max = Some(value);
                 max_index = Ok(cell_index);
@@ -401,18 +1403,22 @@ impl MorseComplex {
         max_index
     }
 
-    fn find_steepest_neighbor<T>(&self, joining_index: usize, neighbors: &[usize],
-                                 graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<usize, MorseError> {
+    fn find_steepest_neighbor<N: ScalarValued, Ty: EdgeType>(&self, joining_index: usize, neighbors: &[usize],
+                                 graph: &Graph<N, f64, Ty>) -> Result<usize, MorseError> {
         // TODO: Really similar logic here and in max cell. Could probably unify them
         // NB this doesn't check signs; it assumes neighbors has been filtered appropriately
         let joining_node = &self.ordered_points[joining_index];
+        let joining_value = match graph.node_weight(joining_node.node) {
+            None => return Err(MorseError::MissingNode{node: joining_node.node}),
+            Some(weight) => weight.value()
+        };
         let mut current_max = None;
         let mut max_index = Err(MorseError::MissingNeighbors{node: joining_node.node});
         for &neighbor_idx in neighbors {
             let node = &self.ordered_points[neighbor_idx];
             let value = match graph.node_weight(node.node) {
                 None => return Err(MorseError::MissingNode{node: node.node}),
-                Some(weight) => weight.value
+                Some(weight) => weight.value()
             };
             let edge = match graph.find_edge(joining_node.node, node.node) {
                 None => return Err(MorseError::MissingEdge{node: joining_node.node, other: node.node}),
@@ -420,7 +1426,7 @@ impl MorseComplex {
             };
             let grade = match graph.edge_weight(edge) {
                 None => return Err(MorseError::MissingEdgeWeight{edge}),
-                Some(val) => (value / val).abs()
+                Some(&val) => self.options.grade.grade(joining_value, value, val)
             };
 
             let should_update = match current_max {
@@ -435,21 +1441,49 @@ impl MorseComplex {
         max_index
     }
 
-    fn merge_cells<T>(&mut self, joining_index: usize, owning_cell: usize, merged_cells: &HashSet<usize>,
-                      graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<(), MorseError> {
+    // Unions the cell of `other` into the cell of `owner`, keeping track of the total value
+    fn union_cells(&mut self, owner: usize, other: usize, totals: &mut SweepTotals) {
+        let (owner, other) = (self.cells.find(owner), self.cells.find(other));
+        if owner != other {
+            totals.sums[owner] += totals.sums[other];
+            self.cells.union(owner, other);
+        }
+    }
+
+    // Records the length of the integral line of the point at `index`, which continues through
+    // its neighbor at `next`
+    fn extend_line<N: ScalarValued, Ty: EdgeType>(&self, index: usize, next: usize, totals: &mut SweepTotals,
+                                                   graph: &Graph<N, f64, Ty>) -> Result<(), MorseError> {
+        if self.options.measure == PersistenceMeasure::Geodesic {
+            totals.lengths[index] = totals.lengths[next] + self.segment_length(index, next, graph)?;
+        }
+        Ok(())
+    }
+
+    // The length of the edge between two points on the graph of the function
+    fn segment_length<N: ScalarValued, Ty: EdgeType>(&self, a: usize, b: usize, graph: &Graph<N, f64, Ty>) -> Result<f64, MorseError> {
+        let (a, b) = (self.ordered_points[a].node, self.ordered_points[b].node);
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let edge = graph.find_edge(a, b).ok_or(MorseError::MissingEdge{node: a, other: b})?;
+        let weight = graph.edge_weight(edge).ok_or(MorseError::MissingEdgeWeight{edge})?;
+        Ok(weight.hypot(value(a)? - value(b)?))
+    }
+
+    fn merge_cells<N: ScalarValued, Ty: EdgeType>(&mut self, joining_index: usize, owning_cell: usize, merged_cells: &HashSet<usize>,
+                                                  neighbors: &[usize], totals: &mut SweepTotals, graph: &Graph<N, f64, Ty>) -> Result<(), MorseError> {
         let merge_parent = self.ordered_points[owning_cell].node;
         let joining_node = self.ordered_points[joining_index].node;
         let joining_value = match graph.node_weight(joining_node) {
             None => return Err(MorseError::MissingNode{node: joining_node}),
-            Some(weight) => weight.value
+            Some(weight) => weight.value()
         };
-        self.cells.union(owning_cell, joining_index);
+        self.union_cells(owning_cell, joining_index, totals);
         for &cell in merged_cells {
             if cell != owning_cell {
                 let cell_node = &self.ordered_points[cell];
                 let cell_value = match graph.node_weight(cell_node.node) {
                     None => return Err(MorseError::MissingNode{node: cell_node.node}),
-                    Some(weight) => weight.value
+                    Some(weight) => weight.value()
                 };
                 let ancestor = match self.ordered_points[cell].data.as_ref() {
                     None => return Err(MorseError::MissingData{node: cell_node.node}),
@@ -457,10 +1491,22 @@ impl MorseComplex {
                 };
 
                 // abs here so that the math works for ascending or descending
-                let lifetime = (cell_value - joining_value).abs();
+                let size = self.cells.size(cell) as f64;
+                let lifetime = match self.options.measure {
+                    PersistenceMeasure::Value => (cell_value - joining_value).abs(),
+                    PersistenceMeasure::Size => size,
+                    PersistenceMeasure::Volume => (totals.sums[cell] - size * joining_value).abs(),
+                    PersistenceMeasure::Geodesic => {
+                        let mut shortest = f64::INFINITY;
+                        for &neighbor in neighbors.iter().filter(|&&n| self.cells.find(n) == cell) {
+                            shortest = shortest.min(totals.lengths[neighbor] + self.segment_length(joining_index, neighbor, graph)?);
+                        }
+                        shortest
+                    }
+                };
                 self.ordered_points[cell].data = Some(MorseData{ancestor, lifetime, 
-                    merge_parent: Some(merge_parent)});
-                self.cells.union(owning_cell, cell);
+                    merge_parent: Some(merge_parent), merge_node: Some(joining_node)});
+                self.union_cells(owning_cell, cell, totals);
             }
         }
         Ok(())
@@ -472,6 +1518,8 @@ impl MorseComplex {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::LabeledPoint;
+    use crate::fixtures::{path_graph, path_graph_of, weighted_path_graph};
 
     #[test]
     fn test_single() {
@@ -627,6 +1675,529 @@ mod tests {
         assert_eq!(lifetimes[&node_lookup[8]], 0.);
     }
 
+    #[test]
+    fn test_crystals() {
+        let graph = path_graph(&[-3., 0., -1., -2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let cells = complex.get_cells();
+        assert_eq!(cells[&nodes[0]], (nodes[0], nodes[1]));
+        assert_eq!(cells[&nodes[1]], (nodes[0], nodes[1]));
+        assert_eq!(cells[&nodes[2]], (nodes[3], nodes[1]));
+        assert_eq!(cells[&nodes[3]], (nodes[3], nodes[1]));
+
+        let crystals = complex.get_crystals();
+        assert_eq!(crystals.len(), 2);
+        assert_eq!(crystals[&(nodes[3], nodes[1])], vec![nodes[2], nodes[3]]);
+    }
+
+    #[test]
+    fn test_complex_at() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        // the maximum at 0 has persistence 1, and the one at 2 has persistence 2. Both are owned by
+        // the global maximum at 4, since it is the largest in the component they merge into
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.complex_at(0.5), complex.get_complex());
+        let simplified = complex.complex_at(1.);
+        assert_eq!(simplified[&nodes[0]], nodes[4]);
+        assert_eq!(simplified[&nodes[1]], nodes[2]);
+        assert_eq!(simplified[&nodes[2]], nodes[2]);
+        let simplified = complex.complex_at(f64::INFINITY);
+        assert!(nodes.iter().all(|node| simplified[node] == nodes[4]));
+    }
+
+    #[test]
+    fn test_complex_below() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        // the maximum at 0 has persistence exactly 1, so it keeps its cell
+        assert_eq!(complex.complex_below(1.), complex.get_complex());
+        assert_ne!(complex.complex_below(1.), complex.complex_at(1.));
+        assert_eq!(complex.complex_below(2.), complex.complex_at(1.));
+        for &t in [0., 1., 1.5, 2., f64::INFINITY].iter() {
+            assert_eq!(complex.complex_below(t), complex.simplify(t).get_complex());
+        }
+        assert_eq!(complex.complex_below(2.)[&nodes[0]], nodes[4]);
+    }
+
+    #[test]
+    fn test_filtration_values() {
+        let graph = path_graph(&[3., 0., 5., 1., 6., 4., 4.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let step = complex.filtration[0];
+        assert_eq!((step.destroyed_cell, step.merge_node), (nodes[6], nodes[5]));
+        assert_eq!((step.birth, step.death), (4.5, 4.));
+        for step in complex.filtration.iter() {
+            assert_eq!(step.time, step.birth - step.death);
+        }
+    }
+
+    #[test]
+    fn test_complexes_at() {
+        let graph = path_graph(&[3., 0., 5., 1., 6., 4., 4.5]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let thresholds = [4., 0., 3.5, f64::INFINITY, 0.5];
+        let snapshots = complex.complexes_at(&thresholds);
+        assert_eq!(snapshots.len(), thresholds.len());
+        for (snapshot, &t) in snapshots.iter().zip(thresholds.iter()) {
+            assert_eq!(*snapshot, complex.complex_at(t));
+        }
+        assert!(complex.complexes_at(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_persistence_measures() {
+        let graph = path_graph(&[1., 0., 4., 3.5, 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let by_value = MorseSmaleComplex::from_graph(&graph).unwrap().descending_complex;
+        let by_size = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().measure(PersistenceMeasure::Size)).unwrap().descending_complex;
+        let by_volume = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().measure(PersistenceMeasure::Volume)).unwrap().descending_complex;
+        let persistence = by_size.get_persistence();
+        assert_eq!(persistence[&nodes[0]], 1.);
+        assert_eq!(persistence[&nodes[2]], 2.);
+        assert_eq!(persistence[&nodes[5]], f64::INFINITY);
+        let persistence = by_volume.get_persistence();
+        assert_eq!(persistence[&nodes[0]], 1.);
+        assert_eq!(persistence[&nodes[2]], 3.5);
+        assert_eq!(by_volume.get_relative_persistence(&graph).unwrap()[&nodes[2]], 3.5 / 30.);
+
+        // only the persistence depends on the measure
+        assert_eq!(by_size.get_complex(), by_value.get_complex());
+        assert_eq!(by_volume.component_counts(&graph, None).unwrap(), by_value.component_counts(&graph, None).unwrap());
+        let ascending = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().measure(PersistenceMeasure::Volume)).unwrap().ascending_complex;
+        // the minimum at 4 holds {3, 4} below 4, while the minimum at 1 holds only itself
+        assert_eq!(ascending.get_persistence()[&nodes[4]], 2.5);
+    }
+
+    #[test]
+    fn test_shared_cell_follows_steepest_neighbor() {
+        // 0 and 1 share a cell once 2 joins them, and 3 sits below both, with 1 far steeper
+        let build = |edges: &[(usize, usize, f64)]| {
+            let mut graph = UnGraph::new_undirected();
+            let nodes: Vec<_> = [10., 9., 5., 1.].iter().enumerate()
+                .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+                .collect();
+            for &(a, b, weight) in edges {
+                graph.add_edge(nodes[a], nodes[b], weight);
+            }
+            graph
+        };
+        let one_way = build(&[(0, 2, 1.), (1, 2, 1.), (3, 1, 0.1), (3, 0, 1.)]);
+        let other_way = build(&[(0, 2, 1.), (1, 2, 1.), (3, 0, 1.), (3, 1, 0.1)]);
+        for graph in [one_way, other_way].iter() {
+            let nodes: Vec<_> = graph.node_indices().collect();
+            let complex = MorseComplex::from_graph(MorseKind::Descending, graph).unwrap();
+            assert_eq!(complex.get_complex()[&nodes[3]], nodes[1]);
+            assert_eq!(complex.integral_line(graph, nodes[3]).unwrap(), vec![nodes[3], nodes[1]]);
+            assert_eq!(complex.integral_line_lengths(graph).unwrap()[&nodes[3]], (1, 0.1));
+        }
+    }
+
+    #[test]
+    fn test_geodesic_persistence() {
+        // peaks at 0 and 4 of the same height, 0 right next to the saddle at 1 and 4 far from it
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [3., 0., 5., 1.5, 3.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        graph.add_edge(nodes[0], nodes[1], 1.);
+        graph.add_edge(nodes[1], nodes[2], 1.);
+        graph.add_edge(nodes[1], nodes[3], 4.);
+        graph.add_edge(nodes[3], nodes[4], 4.);
+        let by_value = MorseSmaleComplex::from_graph(&graph).unwrap().descending_complex;
+        let geodesic = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().measure(PersistenceMeasure::Geodesic)).unwrap().descending_complex;
+        assert_eq!(by_value.get_persistence()[&nodes[0]], by_value.get_persistence()[&nodes[4]]);
+        let persistence = geodesic.get_persistence();
+        assert_eq!(persistence[&nodes[0]], 10f64.sqrt());
+        assert_eq!(persistence[&nodes[4]], 2. * 4f64.hypot(1.5));
+        assert_eq!(persistence[&nodes[2]], f64::INFINITY);
+        assert_eq!(geodesic.get_relative_persistence(&graph).unwrap()[&nodes[0]], 10f64.sqrt() / 15.);
+        assert_eq!(geodesic.get_complex(), by_value.get_complex());
+
+        // 3 sits below both 1 and 2 in the cell of 0, and its integral line goes through 1, the
+        // steeper of the two, rather than the long way round through 2
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [10., 9., 8., 7., 1., 20.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        graph.add_edge(nodes[0], nodes[1], 1.);
+        graph.add_edge(nodes[0], nodes[2], 100.);
+        graph.add_edge(nodes[3], nodes[1], 1.);
+        graph.add_edge(nodes[3], nodes[2], 1.);
+        graph.add_edge(nodes[3], nodes[4], 1.);
+        graph.add_edge(nodes[4], nodes[5], 1.);
+        let geodesic = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().measure(PersistenceMeasure::Geodesic)).unwrap().descending_complex;
+        let expected = 1f64.hypot(1.) + 1f64.hypot(2.) + 1f64.hypot(6.);
+        assert_eq!(geodesic.get_persistence()[&nodes[0]], expected);
+    }
+
+    #[test]
+    fn test_tie_break() {
+        // the same plateaued function, with the nodes added in opposite orders
+        let values = [(4, 1.), (5, 3.), (2, 3.), (1, 1.)];
+        let extrema = |reversed: bool, tie_break: TieBreak| {
+            let mut points: Vec<_> = values.iter()
+                .map(|&(id, value)| LabeledPoint{id, value, point: vec![id as f64]})
+                .collect();
+            if reversed {
+                points.reverse();
+            }
+            let graph = path_graph_of(points);
+            let nodes: Vec<_> = graph.node_indices().collect();
+            let complex = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().tie_break(tie_break)).unwrap();
+            let extremum = |complex: &MorseComplex| {
+                let persistence = complex.get_persistence();
+                let global = nodes.iter().find(|node| persistence[node] == f64::INFINITY).unwrap();
+                graph[*global].id
+            };
+            (extremum(&complex.ascending_complex), extremum(&complex.descending_complex))
+        };
+        assert_eq!(extrema(false, TieBreak::Index), (4, 5));
+        assert_eq!(extrema(true, TieBreak::Index), (1, 2));
+        assert_eq!(extrema(false, TieBreak::Id), (1, 5));
+        assert_eq!(extrema(true, TieBreak::Id), (1, 5));
+
+        // repeated ids fall back on the indices, the smaller index being the higher node
+        let graph = path_graph_of([(3, 1.), (7, 3.), (7, 3.), (3, 1.)].iter()
+            .map(|&(id, value)| LabeledPoint{id, value, point: ()}));
+        let complex = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().tie_break(TieBreak::Id)).unwrap();
+        assert_eq!(complex.descending_complex.extrema()[0], (NodeIndex::new(1), f64::INFINITY));
+        assert_eq!(complex.ascending_complex.extrema()[0], (NodeIndex::new(3), f64::INFINITY));
+    }
+
+    #[test]
+    fn test_scalar_valued() {
+        // any node weight with a value works, and gives the same complex as LabeledPoints
+        struct Elevation(f64);
+        impl ScalarValued for Elevation {
+            fn value(&self) -> f64 {
+                self.0
+            }
+        }
+        let values = [1., 0., 4., 2., 5., 2.];
+        let mut graph = UnGraph::new_undirected();
+        let mut labeled = UnGraph::new_undirected();
+        for (i, &value) in values.iter().enumerate() {
+            graph.add_node(Elevation(value));
+            labeled.add_node(LabeledPoint{id: i as i64, value, point: ()});
+        }
+        for i in 1..values.len() {
+            graph.add_edge(NodeIndex::new(i - 1), NodeIndex::new(i), 1.);
+            labeled.add_edge(NodeIndex::new(i - 1), NodeIndex::new(i), 1.);
+        }
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let expected = MorseSmaleComplex::from_graph(&labeled).unwrap();
+        assert_eq!(complex.descending_complex.get_complex(), expected.descending_complex.get_complex());
+        assert_eq!(complex.ascending_complex.get_persistence(), expected.ascending_complex.get_persistence());
+
+        // without ids, ties are broken by index
+        let by_id = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().tie_break(TieBreak::Id)).unwrap();
+        assert_eq!(by_id.ascending_complex.get_complex(), expected.ascending_complex.get_complex());
+    }
+
+    #[test]
+    fn test_digraph() {
+        // 0 flows up into 1, which flows down into 2, which has nowhere to go
+        let mut graph = DiGraph::new();
+        let nodes: Vec<_> = [1., 5., 3.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        graph.add_edge(nodes[0], nodes[1], 1.);
+        graph.add_edge(nodes[1], nodes[2], 1.);
+        let complex = MorseSmaleComplex::from_digraph(&graph, MorseOptions::default()).unwrap();
+        let descending = complex.descending_complex.get_complex();
+        assert_eq!(descending[&nodes[0]], nodes[1]);
+        assert_eq!(descending[&nodes[2]], nodes[2]);
+        let ascending = complex.ascending_complex.get_complex();
+        assert_eq!(ascending[&nodes[0]], nodes[0]);
+        assert_eq!(ascending[&nodes[1]], nodes[2]);
+        assert_eq!(complex.descending_complex.get_persistence_pairs(&graph).unwrap().len(), 2);
+        assert_eq!(complex.ascending_complex.integral_line(&graph, nodes[1]).unwrap(), vec![nodes[1], nodes[2]]);
+
+        // with the edges going both ways, it's the same as the undirected graph
+        graph.add_edge(nodes[1], nodes[0], 1.);
+        graph.add_edge(nodes[2], nodes[1], 1.);
+        let complex = MorseSmaleComplex::from_digraph(&graph, MorseOptions::default()).unwrap();
+        assert!(complex.descending_complex.get_complex().values().all(|&extremum| extremum == nodes[1]));
+    }
+
+    #[test]
+    fn test_shared_sort() {
+        // building both complexes from one sort gives the same sweeps as sorting for each
+        let mut graph = path_graph_of([(3, 2.), (1, 2.), (4, 0.), (0, 5.), (2, 2.), (5, 5.), (6, 0.)].iter()
+            .map(|&(id, value)| LabeledPoint{id, value, point: vec![value]}));
+        graph.add_edge(NodeIndex::new(0), NodeIndex::new(4), 1.);
+        for &tie_break in &[TieBreak::Index, TieBreak::Id, TieBreak::Flood] {
+            let pair = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().tie_break(tie_break)).unwrap();
+            for &(complex, kind) in &[(&pair.ascending_complex, MorseKind::Ascending), (&pair.descending_complex, MorseKind::Descending)] {
+                let single = MorseComplex::from_region(kind, &graph, |_| true, MorseOptions::default().tie_break(tie_break)).unwrap();
+                let sweep = |c: &MorseComplex| c.ordered_points.iter().map(|point| point.node).collect::<Vec<_>>();
+                assert_eq!(sweep(complex), sweep(&single));
+                assert_eq!(complex.get_complex(), single.get_complex());
+                assert_eq!(complex.get_persistence(), single.get_persistence());
+            }
+        }
+    }
+
+    #[test]
+    fn test_flood_plateaus() {
+        use crate::graph::{build_grid_2d, GridConnectivity};
+
+        // a U-shaped mask, whose top corners come before the rest of it
+        let mask = build_grid_2d(&[vec![1., 0., 1.], vec![1., 0., 1.], vec![1., 1., 1.]], GridConnectivity::Four).unwrap();
+        let by_index = MorseSmaleComplex::from_graph(&mask).unwrap();
+        let flooded = MorseSmaleComplex::from_graph_with(&mask, MorseOptions::default().tie_break(TieBreak::Flood)).unwrap();
+        assert_eq!(by_index.descending_complex.get_members().len(), 2);
+        assert_eq!(flooded.descending_complex.get_members().len(), 1);
+        assert_eq!(flooded.ascending_complex.get_members().len(), 1);
+
+        // a shelf below a peak flows up to it, rather than starting at its first node
+        let shelf = build_grid_2d(&[vec![0., 2., 2., 2., 3.]], GridConnectivity::Four).unwrap();
+        let by_index = MorseSmaleComplex::from_graph(&shelf).unwrap();
+        let flooded = MorseSmaleComplex::from_graph_with(&shelf, MorseOptions::default().tie_break(TieBreak::Flood)).unwrap();
+        assert_eq!(by_index.descending_complex.get_members().len(), 2);
+        assert_eq!(flooded.descending_complex.get_members().len(), 1);
+        assert_eq!(flooded.descending_complex.integral_line(&shelf, NodeIndex::new(1)).unwrap(),
+                   (1..5).map(NodeIndex::new).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_grade() {
+        let graph = weighted_path_graph(&[-1., -5., -4.], &[1., 2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let cell = |grade: Grade| {
+            let complex = MorseSmaleComplex::from_graph_with(&graph, MorseOptions::default().grade(grade)).unwrap();
+            complex.descending_complex.get_complex()[&nodes[1]]
+        };
+        // |-4 / 2| > |-1 / 1|, even though the slope up to -1 is much steeper
+        assert_eq!(cell(Grade::ValueOverWeight), nodes[2]);
+        assert_eq!(cell(Grade::DifferenceOverWeight), nodes[0]);
+        assert_eq!(cell(Grade::Custom(|_, neighbor, _| -neighbor)), nodes[2]);
+    }
+
+    #[test]
+    fn test_relative_persistence() {
+        let graph = path_graph(&[10., 0., 40., 20., 50.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let persistence = complex.get_relative_persistence(&graph).unwrap();
+        assert!((persistence[&nodes[0]] - 0.2).abs() < 1e-12);
+        assert!((persistence[&nodes[2]] - 0.4).abs() < 1e-12);
+        assert_eq!(persistence[&nodes[1]], 0.);
+        assert_eq!(persistence[&nodes[4]], f64::INFINITY);
+
+        let mut flat = UnGraph::new_undirected();
+        let a = flat.add_node(LabeledPoint{id: 0, value: 3., point: ()});
+        let b = flat.add_node(LabeledPoint{id: 1, value: 3., point: ()});
+        flat.add_edge(a, b, 1.);
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &flat).unwrap();
+        assert!(complex.get_relative_persistence(&flat).unwrap().values().all(|p| !p.is_nan()));
+    }
+
+    #[test]
+    fn test_extrema() {
+        // maxima at 0, 2 and 4, and minima at 1, 3 and the plateau at 5 and 6
+        let graph = path_graph(&[1., 0., 4., 2., 5., 3., 3.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.kind(), MorseKind::Descending);
+        assert_eq!(complex.extrema(), vec![(nodes[4], f64::INFINITY), (nodes[2], 2.), (nodes[0], 1.)]);
+
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &graph).unwrap();
+        assert_eq!(complex.extrema(), vec![(nodes[1], f64::INFINITY), (nodes[3], 2.), (nodes[5], 2.)]);
+        assert_eq!(complex.simplify(2.5).extrema(), vec![(nodes[1], f64::INFINITY)]);
+
+        // 0 is a maximum until 1, at the same value, connects it to 2
+        let graph = path_graph(&[3., 3., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.extrema(), vec![(nodes[2], f64::INFINITY), (nodes[0], 0.)]);
+    }
+
+    #[test]
+    fn test_members() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.members_of(nodes[0]), vec![nodes[0]]);
+        assert_eq!(complex.members_of(nodes[2]), vec![nodes[1], nodes[2]]);
+        assert_eq!(complex.members_of(nodes[4]), vec![nodes[3], nodes[4]]);
+        assert!(complex.members_of(nodes[1]).is_empty());
+
+        let members = complex.get_members();
+        assert_eq!(members.len(), 3);
+        assert!(members.iter().all(|(&extremum, cell)| *cell == complex.members_of(extremum)));
+    }
+
+    #[test]
+    fn test_simplify() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let simplified = complex.simplify(1.5);
+        assert_eq!(simplified.get_complex(), complex.complex_at(1.5));
+        assert_eq!(simplified.filtration.len(), 1);
+        assert_eq!(simplified.filtration[0].destroyed_cell, nodes[2]);
+        let persistence = simplified.get_persistence();
+        assert_eq!(persistence[&nodes[0]], 0.);
+        assert_eq!(persistence[&nodes[2]], 2.);
+        let pairs = simplified.get_persistence_pairs(&graph).unwrap();
+        assert_eq!(pairs.iter().map(|pair| pair.extremum).collect::<Vec<_>>(), vec![nodes[4], nodes[2]]);
+
+        // the threshold itself survives, and simplifying in two stages is the same as in one
+        assert_eq!(complex.simplify(1.).filtration.len(), 2);
+        let twice = complex.simplify(1.5).simplify(3.);
+        assert_eq!(twice.get_complex(), complex.simplify(3.).get_complex());
+        assert!(twice.filtration.is_empty());
+        assert!(twice.get_complex().values().all(|&extremum| extremum == nodes[4]));
+    }
+
+    #[test]
+    fn test_simplified_values() {
+        // maxima at 0, 2 and 4 with persistence 1, 2 and inf, and minima at 1 and 3
+        let values = [1., 0., 4., 2., 5.];
+        let graph = path_graph(&values);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let flattened = complex.simplified_values(&graph, 1.5).unwrap();
+        assert_eq!(nodes.iter().map(|node| flattened[node]).collect::<Vec<_>>(), vec![0., 0., 4., 2., 5.]);
+        let flattened = complex.simplified_values(&graph, 2.5).unwrap();
+        assert_eq!(nodes.iter().map(|node| flattened[node]).collect::<Vec<_>>(), vec![0., 0., 2., 2., 5.]);
+        assert_eq!(complex.simplified_values(&graph, 0.).unwrap(), complex.simplified_values(&graph, 1.).unwrap());
+
+        // the flattened function has exactly the extrema that survive simplification
+        for &kind in &[MorseKind::Descending, MorseKind::Ascending] {
+            let complex = MorseComplex::from_graph(kind, &graph).unwrap();
+            for &threshold in &[0.5, 1.5, 2.5, f64::INFINITY] {
+                let flattened = complex.simplified_values(&graph, threshold).unwrap();
+                let mut flat_graph = graph.clone();
+                for &node in nodes.iter() {
+                    flat_graph[node].value = flattened[&node];
+                }
+                let rebuilt = MorseComplex::from_region(kind, &flat_graph, |_| true,
+                                                        MorseOptions::default().tie_break(TieBreak::Flood)).unwrap();
+                let mut expected: Vec<NodeIndex> = complex.simplify(threshold).extrema().into_iter().map(|(node, _)| node).collect();
+                let mut found: Vec<NodeIndex> = rebuilt.extrema().into_iter().map(|(node, _)| node).collect();
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel() {
+        // maxima at 0, 2 and 4, with cells {0}, {1, 2} and {3, 4}. Both 0 and 2 are destroyed by 4
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+
+        // once 2's cell is part of 4's, 0's cell borders it too
+        let cancelled = complex.cancel(&graph, nodes[2], nodes[4]).unwrap()
+            .cancel(&graph, nodes[0], nodes[4]).unwrap();
+        assert_eq!(cancelled.get_complex(), complex.simplify(f64::INFINITY).get_complex());
+        assert!(cancelled.filtration.is_empty());
+
+        let cancelled = complex.cancel(&graph, nodes[0], nodes[2]).unwrap();
+        assert_eq!(cancelled.get_complex()[&nodes[0]], nodes[2]);
+        assert_eq!(cancelled.extrema(), vec![(nodes[4], f64::INFINITY), (nodes[2], 2.)]);
+        let cancelled = cancelled.cancel(&graph, nodes[2], nodes[4]).unwrap();
+        assert_eq!(cancelled.get_complex(), complex.simplify(f64::INFINITY).get_complex());
+        assert!(cancelled.filtration.is_empty());
+
+        // the other way around, 0's cell takes in 2's, and keeps its own persistence
+        let cancelled = complex.cancel(&graph, nodes[2], nodes[0]).unwrap();
+        let cells = cancelled.get_complex();
+        assert_eq!(cells[&nodes[1]], nodes[0]);
+        assert_eq!(cells[&nodes[2]], nodes[0]);
+        assert_eq!(cells[&nodes[3]], nodes[4]);
+        assert_eq!(cancelled.extrema(), vec![(nodes[4], f64::INFINITY), (nodes[0], 1.)]);
+        assert_eq!(cancelled.filtration.len(), 1);
+        assert_eq!(cancelled.filtration[0].destroyed_cell, nodes[0]);
+        assert_eq!(cancelled.merge_hierarchy().roots(), &[nodes[4]][..]);
+
+        // merging the global maximum hands its place to the cell that absorbs it
+        let cancelled = complex.cancel(&graph, nodes[4], nodes[2]).unwrap();
+        assert_eq!(cancelled.extrema(), vec![(nodes[2], f64::INFINITY), (nodes[0], 1.)]);
+        assert!(cancelled.get_complex().values().all(|&extremum| extremum != nodes[4]));
+
+        assert!(complex.cancel(&graph, nodes[1], nodes[2]).is_err());
+        assert!(complex.cancel(&graph, nodes[0], nodes[4]).is_err());
+        assert!(complex.cancel(&graph, nodes[2], nodes[2]).is_err());
+
+        // maxima at 0, 2 and 4, with 0 destroyed by 2 at 1 and 2 by 4 at 5, which also borders 0
+        let mut graph = path_graph(&[4., 3., 5., 1., 6., 2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        graph.add_edge(nodes[5], nodes[0], 1.);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        // handing 2 to 0 would leave 0 destroyed by 2 and 2 by 0
+        match complex.cancel(&graph, nodes[4], nodes[0]) {
+            Err(MorseError::MergeCycle{node, other}) => assert_eq!((node, other), (nodes[4], nodes[0])),
+            other => panic!("expected a merge cycle error, got {:?}", other)
+        }
+        let cancelled = complex.cancel(&graph, nodes[4], nodes[2]).unwrap();
+        assert_eq!(cancelled.merge_hierarchy().roots(), &[nodes[2]][..]);
+        assert!(cancelled.simplify(f64::INFINITY).get_complex().values().all(|&extremum| extremum == nodes[2]));
+    }
+
+    #[test]
+    fn test_persistence_curve() {
+        // maxima at 0, 2, 4 and 6, with persistence 1, 2, inf and 2
+        let graph = path_graph(&[1., 0., 4., 2., 5., 3., 5., 3.]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let curve = complex.persistence_curve();
+        assert_eq!(curve, vec![(1., 3), (2., 1)]);
+        // each count is what simplifying just above its persistence leaves
+        for &(persistence, count) in curve.iter() {
+            assert_eq!(complex.simplify(persistence + 1e-9).extrema().len(), count);
+        }
+        assert_eq!(complex.extrema().len(), 4);
+        assert!(complex.simplify(f64::INFINITY).persistence_curve().is_empty());
+    }
+
+    #[test]
+    fn test_persistent_entropy() {
+        let graph = path_graph(&[1., 0., 4., 2., 5., 3., 5., 3.]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        // persistence 1, 2 and 2, out of a total of 5
+        let expected = -(0.2f64 * 0.2f64.ln() + 2. * 0.4 * 0.4f64.ln());
+        assert!((complex.persistent_entropy() - expected).abs() < 1e-12);
+        assert_eq!(complex.simplify(f64::INFINITY).persistent_entropy(), 0.);
+    }
+
+    #[test]
+    fn test_suggest_threshold() {
+        // maxima at 0, 2, 4 and 6, with persistence 0.5, 6, inf and 0.2
+        let graph = path_graph(&[1., 0.5, 7., 1., 9., 3., 3.2, 0.]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let suggestion = complex.suggest_threshold(ThresholdHeuristic::LargestGap).unwrap();
+        assert_eq!(suggestion.interval, (0.5, 6.));
+        assert_eq!(complex.simplify(suggestion.threshold).extrema().len(), suggestion.kept);
+        assert_eq!(suggestion.kept, 2);
+    }
+
+    #[test]
+    fn test_persistence_pairs() {
+        let graph = path_graph(&[1., 0., 3., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let pairs = complex.get_persistence_pairs(&graph).unwrap();
+        assert_eq!(pairs, vec![
+            PersistencePair{extremum: nodes[4], saddle: None, birth: 5., death: f64::NEG_INFINITY},
+            PersistencePair{extremum: nodes[2], saddle: Some(nodes[3]), birth: 3., death: 2.},
+            PersistencePair{extremum: nodes[0], saddle: Some(nodes[1]), birth: 1., death: 0.}
+        ]);
+        assert!(complex.filtration.iter().all(|step| step.merge_node == nodes[1] || step.merge_node == nodes[3]));
+
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &graph).unwrap();
+        let pairs = complex.get_persistence_pairs(&graph).unwrap();
+        assert_eq!(pairs[0], PersistencePair{extremum: nodes[1], saddle: None, birth: 0., death: f64::INFINITY});
+        assert_eq!(pairs[1], PersistencePair{extremum: nodes[3], saddle: Some(nodes[2]), birth: 2., death: 3.});
+    }
+
     #[test]
     fn test_filtration() {
         let mut graph = UnGraph::new_undirected();
@@ -664,4 +2235,86 @@ mod tests {
             assert_eq!(actual.owning_cell, expected.2);
         }
     }
+
+    #[test]
+    fn test_region() {
+        let values = [0., 5., 1., 2., 3., 10.];
+        let graph = path_graph(&values);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let region: HashSet<NodeIndex> = nodes[2..5].iter().copied().collect();
+
+        let complex = MorseSmaleComplex::from_region(&graph, &region, RegionBoundary::Induced, MorseOptions::default()).unwrap();
+        let lifetimes = complex.descending_complex.get_persistence();
+        assert_eq!(lifetimes.len(), 3);
+        assert_eq!(lifetimes[&nodes[4]], f64::INFINITY);
+        assert_eq!(lifetimes[&nodes[2]], 0.);
+        let cells = complex.descending_complex.get_complex();
+        assert_eq!(cells[&nodes[2]], nodes[4]);
+
+        // with the halo, the region drains into the maxima outside of it
+        let complex = MorseSmaleComplex::from_region(&graph, &region, RegionBoundary::Halo, MorseOptions::default()).unwrap();
+        let lifetimes = complex.descending_complex.get_persistence();
+        assert_eq!(lifetimes.len(), 5);
+        assert_eq!(lifetimes[&nodes[4]], 0.);
+        assert_eq!(lifetimes[&nodes[5]], f64::INFINITY);
+        assert_eq!(lifetimes[&nodes[1]], 4.);
+        let cells = complex.descending_complex.get_complex();
+        assert_eq!(cells[&nodes[2]], nodes[1]);
+        assert_eq!(cells[&nodes[3]], nodes[5]);
+    }
+
+    #[test]
+    fn test_mask() {
+        let values = [0., 5., 1., 2., 3., 10.];
+        let graph = path_graph(&values);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        // the same region as in test_region, with the mask too short to cover the last node
+        let mask = [false, false, true, true, true];
+        let region: HashSet<NodeIndex> = nodes[2..5].iter().copied().collect();
+        for &boundary in &[RegionBoundary::Induced, RegionBoundary::Halo] {
+            let masked = MorseSmaleComplex::from_mask(&graph, &mask, boundary, MorseOptions::default()).unwrap();
+            let expected = MorseSmaleComplex::from_region(&graph, &region, boundary, MorseOptions::default()).unwrap();
+            assert_eq!(masked.get_cells(), expected.get_cells());
+            assert_eq!(masked.descending_complex.get_persistence(), expected.descending_complex.get_persistence());
+        }
+        let empty = MorseSmaleComplex::from_mask(&graph, &[], RegionBoundary::Halo, MorseOptions::default()).unwrap();
+        assert!(empty.get_cells().is_empty());
+    }
+
+    #[test]
+    fn test_nearest_extrema() {
+        let values = [5., 0., 3., 1., 2., 0., 10.];
+        let weights = [1., 1., 1., 3., 4., 1.];
+        let graph = weighted_path_graph(&values, &weights);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+
+        let nearest = complex.nearest_extrema(&graph, nodes[3], 2, 0., PathCost::EdgeWeight).unwrap();
+        assert_eq!(nearest, vec![(nodes[2], 1.), (nodes[0], 3.)]);
+
+        let nearest = complex.nearest_extrema(&graph, nodes[3], 2, 0., PathCost::Hops).unwrap();
+        assert_eq!(nearest, vec![(nodes[2], 1.), (nodes[4], 1.)]);
+
+        // the maxima at 3 and 2 have persistence 3 and 1, so they don't survive a threshold of 4
+        let nearest = complex.nearest_extrema(&graph, nodes[3], 5, 4., PathCost::ValueChange).unwrap();
+        assert_eq!(nearest, vec![(nodes[0], 10.), (nodes[6], 13.)]);
+    }
+
+    #[test]
+    fn test_component_counts() {
+        let values = [5., 0., 3., 1., 2., 0., 10.];
+        let graph = path_graph(&values);
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+
+        let counts = complex.descending_complex.component_counts(&graph, Some(&[11., 4., 2.5, 1.5, 0.5, 0.])).unwrap();
+        let counts: Vec<usize> = counts.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, vec![0, 2, 3, 4, 3, 1]);
+
+        let counts = complex.descending_complex.component_counts(&graph, None).unwrap();
+        assert_eq!(counts, vec![(10., 1), (5., 2), (3., 3), (2., 4), (1., 3), (0., 1)]);
+
+        let counts = complex.ascending_complex.component_counts(&graph, Some(&[0., 1.5, 10.])).unwrap();
+        let counts: Vec<usize> = counts.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, vec![2, 3, 1]);
+    }
 }
@@ -0,0 +1,172 @@
+//! Where Morse cells touch each other: the graph of adjacent cells, and the boundaries between them.
+use std::collections::HashMap;
+use petgraph::graph::{UnGraph, NodeIndex, EdgeIndex};
+
+use super::{MorseComplex, MorseKind, MorseError};
+use crate::ScalarValued;
+
+/// How the edges of a [cell graph](struct.MorseComplex.html#method.cell_graph) are weighted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CellAdjacency {
+    /// The number of edges of the underlying graph that cross between the two cells.
+    CrossingEdges,
+
+    /// The value of the saddle between the two cells: the highest point at which the cells of a
+    /// descending complex meet, or the lowest for an ascending one. An edge meets its cells at the
+    /// lower (or higher) of the values of its endpoints.
+    SaddleValue
+}
+
+impl MorseComplex {
+    /// Returns the graph of adjacent Morse cells.
+    ///
+    /// There is one node per extremum, whose weight is the extremum's index in `graph`, in order of
+    /// index. Two cells are connected whenever some edge of `graph` has one endpoint in each, and
+    /// the connecting edge is weighted as given by `adjacency`. Edges to nodes that aren't part of
+    /// the complex are ignored.
+    pub fn cell_graph<N: ScalarValued>(&self, graph: &UnGraph<N, f64>, adjacency: CellAdjacency)
+        -> Result<UnGraph<NodeIndex, f64>, MorseError> {
+        let complex = self.get_complex();
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+
+        let mut extrema: Vec<NodeIndex> = complex.values().cloned().collect();
+        extrema.sort();
+        extrema.dedup();
+        let mut cells = UnGraph::with_capacity(extrema.len(), 0);
+        let cell_lookup: HashMap<NodeIndex, NodeIndex> = extrema.iter()
+            .map(|&extremum| (extremum, cells.add_node(extremum)))
+            .collect();
+
+        for edge in graph.raw_edges() {
+            let (a, b) = match (complex.get(&edge.source()), complex.get(&edge.target())) {
+                (Some(&a), Some(&b)) if a != b => (cell_lookup[&a], cell_lookup[&b]),
+                _ => continue
+            };
+            let (first, second) = (value(edge.source())?, value(edge.target())?);
+            match adjacency {
+                CellAdjacency::CrossingEdges => match cells.find_edge(a, b) {
+                    Some(existing) => cells[existing] += 1.,
+                    None => { cells.add_edge(a, b, 1.); }
+                },
+                CellAdjacency::SaddleValue => {
+                    let (height, higher): (f64, fn(f64, f64) -> bool) = match self.kind {
+                        MorseKind::Descending => (first.min(second), |x, y| x > y),
+                        MorseKind::Ascending => (first.max(second), |x, y| x < y)
+                    };
+                    match cells.find_edge(a, b) {
+                        Some(existing) if higher(height, cells[existing]) => cells[existing] = height,
+                        Some(_) => {},
+                        None => { cells.add_edge(a, b, height); }
+                    }
+                }
+            }
+        }
+        Ok(cells)
+    }
+
+    /// Returns the edges of `graph` whose endpoints are in different Morse cells, in order of index.
+    ///
+    /// These are the discrete separatrices of the complex. Edges to nodes that aren't part of the
+    /// complex are not boundary edges.
+    pub fn boundary_edges<N: ScalarValued>(&self, graph: &UnGraph<N, f64>) -> Vec<EdgeIndex> {
+        let complex = self.get_complex();
+        graph.edge_indices()
+            .filter(|&edge| match graph.edge_endpoints(edge) {
+                Some((a, b)) => matches!((complex.get(&a), complex.get(&b)), (Some(x), Some(y)) if x != y),
+                None => false
+            })
+            .collect()
+    }
+
+    /// Returns the [boundary edges](#method.boundary_edges) grouped by the pair of cells they
+    /// separate, in order of index.
+    ///
+    /// Each pair of cells is given by their extrema, the one with the smaller index first.
+    pub fn boundaries_between<N: ScalarValued>(&self, graph: &UnGraph<N, f64>) -> HashMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> {
+        let complex = self.get_complex();
+        let mut boundaries: HashMap<(NodeIndex, NodeIndex), Vec<EdgeIndex>> = HashMap::new();
+        for edge in self.boundary_edges(graph) {
+            if let Some((a, b)) = graph.edge_endpoints(edge) {
+                let (a, b) = (complex[&a], complex[&b]);
+                boundaries.entry((a.min(b), a.max(b))).or_default().push(edge);
+            }
+        }
+        boundaries
+    }
+
+    /// Returns the nodes with a neighbor in a different Morse cell, sorted by index.
+    ///
+    /// These are the endpoints of the [boundary edges](#method.boundary_edges), so each boundary
+    /// is two nodes thick, one on either side.
+    pub fn boundary_nodes<N: ScalarValued>(&self, graph: &UnGraph<N, f64>) -> Vec<NodeIndex> {
+        let mut nodes: Vec<NodeIndex> = self.boundary_edges(graph).into_iter()
+            .filter_map(|edge| graph.edge_endpoints(edge))
+            .flat_map(|(a, b)| vec![a, b])
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_cell_graph() {
+        let mut graph = UnGraph::new_undirected();
+        // two peaks at 0 and 2 in a ring, with a second, higher pass between them through 3
+        let nodes: Vec<_> = [5., 1., 4., 2.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        for i in 0..4 {
+            graph.add_edge(nodes[i], nodes[(i + 1) % 4], 1.);
+        }
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let cells = complex.cell_graph(&graph, CellAdjacency::CrossingEdges).unwrap();
+        assert_eq!(cells.node_count(), 2);
+        assert_eq!(cells.edge_count(), 1);
+        assert_eq!(cells.raw_nodes().iter().map(|n| n.weight).collect::<Vec<_>>(), vec![nodes[0], nodes[2]]);
+        assert_eq!(cells.raw_edges()[0].weight, 2.);
+
+        let cells = complex.cell_graph(&graph, CellAdjacency::SaddleValue).unwrap();
+        assert_eq!(cells.raw_edges()[0].weight, 2.);
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &graph).unwrap();
+        let cells = complex.cell_graph(&graph, CellAdjacency::SaddleValue).unwrap();
+        assert_eq!(cells.raw_edges()[0].weight, 4.);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let edges: Vec<_> = graph.edge_indices().collect();
+        // the cells are {0}, {1, 2} and {3, 4}
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.boundary_edges(&graph), vec![edges[0], edges[2]]);
+        assert_eq!(complex.boundary_nodes(&graph), vec![nodes[0], nodes[1], nodes[2], nodes[3]]);
+        assert!(complex.simplify(f64::INFINITY).boundary_edges(&graph).is_empty());
+    }
+
+    #[test]
+    fn test_boundaries_between() {
+        let mut graph = UnGraph::new_undirected();
+        // peaks at 0, 2 and 4 in a ring, so each pair of cells shares one boundary edge
+        let nodes: Vec<_> = [5., 1., 4., 2., 6., 0.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        let edges: Vec<_> = (0..6).map(|i| graph.add_edge(nodes[i], nodes[(i + 1) % 6], 1.)).collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let boundaries = complex.boundaries_between(&graph);
+        assert_eq!(boundaries.len(), 3);
+        assert_eq!(boundaries[&(nodes[0], nodes[2])], vec![edges[1]]);
+        assert_eq!(boundaries[&(nodes[2], nodes[4])], vec![edges[2]]);
+        // 5 flows to 4, the steeper of its neighbors, so the boundary runs between 5 and 0
+        assert_eq!(boundaries[&(nodes[0], nodes[4])], vec![edges[5]]);
+        let total: usize = boundaries.values().map(|edges| edges.len()).sum();
+        assert_eq!(total, complex.boundary_edges(&graph).len());
+    }
+}
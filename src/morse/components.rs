@@ -0,0 +1,132 @@
+//! Building a separate complex for each connected component of a graph.
+//!
+//! A complex of a disconnected graph has a global maximum and minimum with infinite persistence
+//! in every component, with nothing to tell which is which. Building each component on its own
+//! keeps them apart.
+use std::collections::HashMap;
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::{MorseComplex, MorseSmaleComplex, MorseError, MorseOptions};
+use crate::ScalarValued;
+use crate::util::PointedUnionFind;
+
+/// The complexes of the connected components of a graph.
+///
+/// Created by [MorseSmaleComplex::from_components](struct.MorseSmaleComplex.html#method.from_components).
+/// Components are numbered in order of their lowest node index, and each complex is indexed by the
+/// graph's own `NodeIndex`es.
+#[derive(Debug)]
+pub struct ComponentComplexes {
+    complexes: Vec<MorseSmaleComplex>,
+    components: HashMap<NodeIndex, usize>
+}
+
+impl ComponentComplexes {
+    /// The complex of each component, in order.
+    pub fn complexes(&self) -> &[MorseSmaleComplex] {
+        &self.complexes
+    }
+
+    /// The number of the component that `node` belongs to, or `None` if it isn't in the graph.
+    pub fn component(&self, node: NodeIndex) -> Option<usize> {
+        self.components.get(&node).cloned()
+    }
+
+    /// The complex of the component that `node` belongs to.
+    pub fn complex_of(&self, node: NodeIndex) -> Option<&MorseSmaleComplex> {
+        self.component(node).map(|c| &self.complexes[c])
+    }
+
+    /// The minima of every component, with the component each belongs to. The minima of each
+    /// component are in order of persistence.
+    pub fn minima(&self) -> Vec<(NodeIndex, usize)> {
+        self.extrema(|complex| &complex.ascending_complex)
+    }
+
+    /// The maxima of every component, with the component each belongs to. The maxima of each
+    /// component are in order of persistence.
+    pub fn maxima(&self) -> Vec<(NodeIndex, usize)> {
+        self.extrema(|complex| &complex.descending_complex)
+    }
+
+    fn extrema(&self, side: fn(&MorseSmaleComplex) -> &MorseComplex) -> Vec<(NodeIndex, usize)> {
+        self.complexes.iter().enumerate()
+            .flat_map(|(c, complex)| side(complex).extrema().into_iter().map(move |(node, _)| (node, c)))
+            .collect()
+    }
+
+    /// Consumes this, returning the complex of each component.
+    pub fn into_complexes(self) -> Vec<MorseSmaleComplex> {
+        self.complexes
+    }
+}
+
+impl MorseSmaleComplex {
+    /// Constructs a MorseSmaleComplex for each connected component of `graph`, so that every
+    /// complex has exactly one global minimum and one global maximum, all with the given
+    /// [options](struct.MorseOptions.html).
+    pub fn from_components<N: ScalarValued>(graph: &UnGraph<N, f64>, options: MorseOptions) -> Result<ComponentComplexes, MorseError> {
+        let mut sets = PointedUnionFind::new(graph.node_count());
+        for edge in graph.raw_edges() {
+            sets.union(edge.source().index(), edge.target().index());
+        }
+        let mut numbers: HashMap<usize, usize> = HashMap::new();
+        let mut members: Vec<Vec<NodeIndex>> = vec![];
+        let mut components = HashMap::with_capacity(graph.node_count());
+        for node in graph.node_indices() {
+            let set = sets.find_mut(node.index());
+            let c = *numbers.entry(set).or_insert_with(|| {
+                members.push(vec![]);
+                members.len() - 1
+            });
+            members[c].push(node);
+            components.insert(node, c);
+        }
+
+        let complexes = members.into_iter()
+            .map(|nodes| {
+                let nodes = MorseComplex::get_region_nodes(graph, nodes.into_iter())?;
+                // every neighbor of a node is in its component
+                let (ascending_complex, descending_complex) = MorseComplex::pair_from_nodes(graph, nodes, &|_| true, options)?;
+                Ok(MorseSmaleComplex{ascending_complex, descending_complex})
+            })
+            .collect::<Result<Vec<_>, MorseError>>()?;
+        Ok(ComponentComplexes{complexes, components})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+
+    #[test]
+    fn test_from_components() {
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [1., 3., 2., 5., 0., 4.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        // {0, 1, 2} is a path, {3, 5} an edge and 4 is alone
+        graph.add_edge(nodes[0], nodes[1], 1.);
+        graph.add_edge(nodes[1], nodes[2], 1.);
+        graph.add_edge(nodes[3], nodes[5], 1.);
+
+        let components = MorseSmaleComplex::from_components(&graph, MorseOptions::default()).unwrap();
+        assert_eq!(components.complexes().len(), 3);
+        assert_eq!(components.component(nodes[2]), Some(0));
+        assert_eq!(components.component(nodes[5]), Some(1));
+        assert_eq!(components.component(nodes[4]), Some(2));
+        assert_eq!(components.component(NodeIndex::new(6)), None);
+
+        assert_eq!(components.minima(), vec![(nodes[0], 0), (nodes[2], 0), (nodes[5], 1), (nodes[4], 2)]);
+        assert_eq!(components.maxima(), vec![(nodes[1], 0), (nodes[3], 1), (nodes[4], 2)]);
+        for complex in components.complexes() {
+            let infinite = complex.descending_complex.get_persistence().values().filter(|p| p.is_infinite()).count();
+            assert_eq!(infinite, 1);
+        }
+
+        let complex = components.complex_of(nodes[3]).unwrap();
+        assert_eq!(complex.descending_complex.get_complex().len(), 2);
+        assert!(!complex.descending_complex.get_complex().contains_key(&nodes[0]));
+    }
+}
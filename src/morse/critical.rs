@@ -0,0 +1,155 @@
+//! Classifying the nodes of a graph by the local behavior of the function around them.
+//!
+//! A node's link is the set of its neighbors, split into the lower link (the neighbors with lower
+//! values) and the upper link (those with higher values). The number of connected components of
+//! each, in the subgraph induced by the link, is what distinguishes extrema and saddles: a saddle
+//! is where separate regions of lower (or higher) values meet, and so where Morse cells merge.
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::{MorseSmaleComplex, MorseError};
+use crate::ScalarValued;
+use crate::util::PointedUnionFind;
+
+/// The kind of a critical point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CriticalKind {
+    /// A node with no lower neighbors.
+    Minimum,
+
+    /// A node with no higher neighbors. Nodes with no neighbors at all are also counted as maxima.
+    Maximum,
+
+    /// A node whose lower or upper link is disconnected.
+    Saddle
+}
+
+/// A node at which the topology of the level sets of the function changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticalPoint {
+    pub node: NodeIndex,
+    pub kind: CriticalKind,
+
+    /// The number of connected components of the lower link, minus one (or 0 for minima). This is
+    /// the number of merges of ascending cells that happen at this node.
+    pub lower_multiplicity: usize,
+
+    /// The number of connected components of the upper link, minus one (or 0 for maxima). This is
+    /// the number of merges of descending cells that happen at this node.
+    pub upper_multiplicity: usize,
+
+    /// The minima of the ascending cells that this node or its neighbors belong to, sorted.
+    pub ascending_cells: Vec<NodeIndex>,
+
+    /// The maxima of the descending cells that this node or its neighbors belong to, sorted.
+    pub descending_cells: Vec<NodeIndex>
+}
+
+impl MorseSmaleComplex {
+    /// Returns every critical point of `graph`, in order of node index.
+    ///
+    /// Nodes with equal values are ordered by their index, so that every pair of neighbors has
+    /// one that counts as the lower of the two. Saddles are reported along with which cells of
+    /// each complex meet around them, which is where separatrices run and merges happen.
+    pub fn critical_points<N: ScalarValued>(&self, graph: &UnGraph<N, f64>) -> Result<Vec<CriticalPoint>, MorseError> {
+        let ascending = self.ascending_complex.get_complex();
+        let descending = self.descending_complex.get_complex();
+        let mut critical_points = vec![];
+        for node in graph.node_indices() {
+            let value = graph.node_weight(node).ok_or(MorseError::MissingNode{node})?.value();
+            let below = |other: NodeIndex| -> Result<bool, MorseError> {
+                let other_value = graph.node_weight(other).ok_or(MorseError::MissingNode{node: other})?.value();
+                Ok(match other_value.partial_cmp(&value) {
+                    Some(Ordering::Less) => true,
+                    Some(Ordering::Equal) => other < node,
+                    _ => false
+                })
+            };
+            let mut lower = HashSet::new();
+            let mut upper = HashSet::new();
+            for neighbor in graph.neighbors(node) {
+                if below(neighbor)? {
+                    lower.insert(neighbor);
+                } else {
+                    upper.insert(neighbor);
+                }
+            }
+
+            let lower_multiplicity = link_components(graph, &lower).saturating_sub(1);
+            let upper_multiplicity = link_components(graph, &upper).saturating_sub(1);
+            let kind = if upper.is_empty() {
+                CriticalKind::Maximum
+            } else if lower.is_empty() {
+                CriticalKind::Minimum
+            } else if lower_multiplicity > 0 || upper_multiplicity > 0 {
+                CriticalKind::Saddle
+            } else {
+                continue;
+            };
+
+            let touched = |cells: &HashMap<NodeIndex, NodeIndex>| {
+                let mut touched: Vec<NodeIndex> = graph.neighbors(node).chain(std::iter::once(node))
+                    .filter_map(|n| cells.get(&n).cloned())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                touched.sort();
+                touched
+            };
+            critical_points.push(CriticalPoint{
+                node, kind, lower_multiplicity, upper_multiplicity,
+                ascending_cells: touched(&ascending),
+                descending_cells: touched(&descending)
+            });
+        }
+        Ok(critical_points)
+    }
+}
+
+// The number of connected components of the subgraph induced by the given nodes
+fn link_components<N: ScalarValued>(graph: &UnGraph<N, f64>, link: &HashSet<NodeIndex>) -> usize {
+    let nodes: Vec<NodeIndex> = link.iter().cloned().collect();
+    let mut components = PointedUnionFind::new(nodes.len());
+    for (i, &a) in nodes.iter().enumerate() {
+        for (j, &b) in nodes.iter().enumerate().skip(i + 1) {
+            if graph.find_edge(a, b).is_some() {
+                components.union(i, j);
+            }
+        }
+    }
+    (0..nodes.len()).filter(|&i| components.find(i) == i).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+
+    #[test]
+    fn test_critical_points() {
+        // the center of a square whose corners alternate high and low is a simple saddle
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [0., 1., -1., 1., -1.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: ()}))
+            .collect();
+        for i in 1..5 {
+            graph.add_edge(nodes[0], nodes[i], 1.);
+            graph.add_edge(nodes[i], nodes[i % 4 + 1], 1.);
+        }
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let critical = complex.critical_points(&graph).unwrap();
+        assert_eq!(critical.len(), 5);
+
+        let saddle = &critical[0];
+        assert_eq!(saddle.kind, CriticalKind::Saddle);
+        assert_eq!(saddle.lower_multiplicity, 1);
+        assert_eq!(saddle.upper_multiplicity, 1);
+        assert_eq!(saddle.ascending_cells, vec![nodes[2], nodes[4]]);
+        assert_eq!(saddle.descending_cells, vec![nodes[1], nodes[3]]);
+
+        assert_eq!(critical[1].kind, CriticalKind::Maximum);
+        assert_eq!(critical[2].kind, CriticalKind::Minimum);
+        assert!(critical[2].ascending_cells.contains(&nodes[2]));
+    }
+}
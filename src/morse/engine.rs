@@ -0,0 +1,117 @@
+//! Repeated construction of complexes over one graph with different values.
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::EdgeType;
+
+use super::{MorseComplex, MorseSmaleComplex, MorseError, MorseOptions, SweepBuffers};
+use crate::{ScalarValued, LabeledPoint};
+
+/// Builds complexes for many functions over the same graph.
+///
+/// The engine keeps its own copy of the graph's structure, made once, along with the neighbors of
+/// each node and the buffers the sweeps are sorted in. Each call to [recompute](#method.recompute)
+/// writes the new values into them in place and sweeps the graph again, so many scalar fields
+/// (e.g. the columns of a table) can be analyzed over one graph without rebuilding it each time.
+///
+/// Since the complexes only record nodes, the persistence methods and the like still need a graph
+/// with the values they were built from. The engine's own [graph](#method.graph) holds the values
+/// of the latest call, so it can be passed to them until the next one.
+#[derive(Debug, Clone)]
+pub struct MorseEngine<Ty: EdgeType> {
+    graph: Graph<LabeledPoint<()>, f64, Ty>,
+    options: MorseOptions,
+    buffers: SweepBuffers
+}
+
+impl<Ty: EdgeType> MorseEngine<Ty> {
+    /// Creates an engine for the structure of `graph`, whose values are used until the first
+    /// call to [recompute](#method.recompute).
+    pub fn new<N: ScalarValued>(graph: &Graph<N, f64, Ty>) -> MorseEngine<Ty> {
+        MorseEngine::new_with(graph, MorseOptions::default())
+    }
+
+    /// Creates an engine for the structure of `graph` that builds complexes with the given
+    /// [options](struct.MorseOptions.html), as in
+    /// [MorseSmaleComplex::from_graph_with](struct.MorseSmaleComplex.html#method.from_graph_with).
+    ///
+    /// The ids of the nodes are kept for `TieBreak::Id`, and don't change with the values.
+    pub fn new_with<N: ScalarValued>(graph: &Graph<N, f64, Ty>, options: MorseOptions) -> MorseEngine<Ty> {
+        let graph = graph.map(
+            |node, weight| LabeledPoint{id: weight.id().unwrap_or(node.index() as i64), value: weight.value(), point: ()},
+            |_, &weight| weight
+        );
+        let buffers = SweepBuffers::new(&graph);
+        MorseEngine{graph, options, buffers}
+    }
+
+    /// Builds both complexes of the function with the given values, one per node in order of
+    /// `NodeIndex`.
+    ///
+    /// Returns an error if there isn't exactly one value per node, or if any of them is NaN, in
+    /// which case the engine keeps the values it had.
+    pub fn recompute(&mut self, values: &[f64]) -> Result<MorseSmaleComplex, MorseError> {
+        if values.len() != self.graph.node_count() {
+            return Err(MorseError::ValueCountMismatch{expected: self.graph.node_count(), found: values.len()});
+        }
+        if let Some(i) = values.iter().position(|value| value.is_nan()) {
+            return Err(MorseError::NanValue{node: NodeIndex::new(i)});
+        }
+        for (weight, &value) in self.graph.node_weights_mut().zip(values.iter()) {
+            weight.value = value;
+        }
+        self.complexes()
+    }
+
+    /// Builds both complexes of the function with the current values.
+    pub fn complexes(&mut self) -> Result<MorseSmaleComplex, MorseError> {
+        let (ascending_complex, descending_complex) = MorseComplex::pair_from_buffers(&self.graph, &mut self.buffers, self.options)?;
+        Ok(MorseSmaleComplex{ascending_complex, descending_complex})
+    }
+
+    /// The engine's copy of the graph, with the values of the latest call to
+    /// [recompute](#method.recompute).
+    pub fn graph(&self) -> &Graph<LabeledPoint<()>, f64, Ty> {
+        &self.graph
+    }
+
+    /// The current value of `node`, or `None` if it isn't in the graph.
+    pub fn value(&self, node: NodeIndex) -> Option<f64> {
+        self.graph.node_weight(node).map(|weight| weight.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_engine() {
+        let mut graph = path_graph(&[0., 5., 1., 6., 2.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let mut engine = MorseEngine::new(&graph);
+        let first = engine.complexes().unwrap();
+        assert_eq!(first.get_cells(), MorseSmaleComplex::from_graph(&graph).unwrap().get_cells());
+
+        // each set of values gives the same complexes as a graph built with them
+        for values in &[[3., 1., 4., 1., 5.], [9., 2., 6., 5., 3.], [0., 0., 0., 0., 0.]] {
+            let complex = engine.recompute(values).unwrap();
+            for (&node, &value) in nodes.iter().zip(values.iter()) {
+                graph[node].value = value;
+                assert_eq!(engine.value(node), Some(value));
+            }
+            let expected = MorseSmaleComplex::from_graph(&graph).unwrap();
+            assert_eq!(complex.get_cells(), expected.get_cells());
+            assert_eq!(complex.descending_complex.get_persistence_pairs(engine.graph()).unwrap(),
+                       expected.descending_complex.get_persistence_pairs(&graph).unwrap());
+        }
+
+        assert!(engine.recompute(&[1., 2.]).is_err());
+        match engine.recompute(&[1., 2., f64::NAN, 4., 5.]) {
+            Err(MorseError::NanValue{node}) => assert_eq!(node, nodes[2]),
+            other => panic!("expected a NaN value error, got {:?}", other)
+        }
+        // a failed call leaves the values of the last successful one in place
+        assert_eq!(engine.value(nodes[2]), Some(0.));
+        assert_eq!(engine.complexes().unwrap().get_cells(), MorseSmaleComplex::from_graph(&graph).unwrap().get_cells());
+    }
+}
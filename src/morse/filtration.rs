@@ -0,0 +1,116 @@
+//! Walking through the filtration of a complex one step at a time.
+//!
+//! Materializing the partition of the graph after every step of the filtration takes time and
+//! memory proportional to the number of steps times the number of nodes. Usually only the nodes
+//! that change cell are of interest (e.g. to update a rendering), so the iterator here only keeps
+//! the current membership of each cell and reports what moved.
+use std::collections::HashMap;
+use petgraph::graph::NodeIndex;
+
+use super::{MorseComplex, MorseFiltrationStep};
+
+/// The change to the partition of a graph made by one step of a filtration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiltrationDelta {
+    /// The time at which the step happens, i.e. the persistence of the destroyed cell.
+    pub time: f64,
+
+    /// The extremum whose cell was destroyed.
+    pub destroyed_cell: NodeIndex,
+
+    /// The extremum whose cell the nodes were moved to.
+    pub owning_cell: NodeIndex,
+
+    /// The nodes that were moved, sorted by index.
+    pub reassigned: Vec<NodeIndex>
+}
+
+/// An iterator over the changes made by each step of a filtration, in order.
+///
+/// Created by [MorseComplex::filtration_deltas](struct.MorseComplex.html#method.filtration_deltas).
+#[derive(Debug)]
+pub struct FiltrationDeltas<'a> {
+    steps: std::slice::Iter<'a, MorseFiltrationStep>,
+    members: HashMap<NodeIndex, Vec<NodeIndex>>,
+    merged_into: HashMap<NodeIndex, NodeIndex>
+}
+
+impl<'a> Iterator for FiltrationDeltas<'a> {
+    type Item = FiltrationDelta;
+
+    fn next(&mut self) -> Option<FiltrationDelta> {
+        let step = self.steps.next()?;
+        // the owner may itself have been destroyed by an earlier step with the same time
+        let owning_cell = MorseComplex::survivor(&self.merged_into, step.owning_cell);
+        self.merged_into.insert(step.destroyed_cell, owning_cell);
+        let mut reassigned = self.members.remove(&step.destroyed_cell).unwrap_or_default();
+        reassigned.sort();
+        self.members.entry(owning_cell).or_default().extend(reassigned.iter().cloned());
+        Some(FiltrationDelta{time: step.time, destroyed_cell: step.destroyed_cell, owning_cell, reassigned})
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.steps.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for FiltrationDeltas<'a> {}
+
+impl MorseComplex {
+    /// Returns an iterator over the nodes reassigned by each step of the filtration.
+    ///
+    /// Applying the deltas up to some time to [get_complex](#method.get_complex) gives the same
+    /// partition as [complex_at](#method.complex_at), but without building a new map at every step.
+    ///
+    /// ```
+    /// use talus::LabeledPoint;
+    /// use talus::morse::MorseSmaleComplex;
+    /// use petgraph::graph::UnGraph;
+    ///
+    /// let mut graph = UnGraph::new_undirected();
+    /// let nodes: Vec<_> = [3., 0., 5.].iter().enumerate()
+    ///     .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+    ///     .collect();
+    /// graph.add_edge(nodes[0], nodes[1], 1.);
+    /// graph.add_edge(nodes[1], nodes[2], 1.);
+    ///
+    /// let complex = MorseSmaleComplex::from_graph(&graph).unwrap().descending_complex;
+    /// let mut partition = complex.get_complex();
+    /// for delta in complex.filtration_deltas() {
+    ///     for node in delta.reassigned {
+    ///         partition.insert(node, delta.owning_cell);
+    ///     }
+    /// }
+    /// assert!(partition.values().all(|&extremum| extremum == nodes[2]));
+    /// ```
+    pub fn filtration_deltas(&self) -> FiltrationDeltas<'_> {
+        FiltrationDeltas{steps: self.filtration.iter(), members: self.get_members(), merged_into: HashMap::new()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+    use crate::morse::MorseKind;
+
+    #[test]
+    fn test_filtration_deltas() {
+        let graph = path_graph(&[1., 0., 4., 2., 5., 3.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let deltas: Vec<_> = complex.filtration_deltas().collect();
+        assert_eq!(deltas.len(), complex.filtration.len());
+
+        let mut partition = complex.get_complex();
+        for (delta, step) in deltas.iter().zip(complex.filtration.iter()) {
+            assert_eq!(delta.time, step.time);
+            assert!(delta.reassigned.contains(&delta.destroyed_cell));
+            for &node in delta.reassigned.iter() {
+                partition.insert(node, delta.owning_cell);
+            }
+            assert_eq!(partition, complex.complex_at(step.time));
+        }
+        assert!(partition.values().all(|&extremum| extremum == nodes[4]));
+    }
+}
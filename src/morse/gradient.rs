@@ -0,0 +1,264 @@
+//! The discrete gradient of the function, as followed by the nodes of a complex to their extrema.
+use std::collections::HashMap;
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::EdgeType;
+
+use super::{MorseComplex, MorseError};
+use crate::ScalarValued;
+
+impl MorseComplex {
+    /// Returns the integral line from `node` to the extremum of its Morse cell, starting with
+    /// `node` itself.
+    ///
+    /// Each step goes to the steepest of the neighbors that are higher (or lower, for an ascending
+    /// complex) and in the same cell, with steepness measured as during construction, so the line
+    /// is a path through `graph` along which the function is monotonic. In a
+    /// [simplified](#method.simplify) complex, lines from merged cells end at the extremum of the
+    /// original cell, since no path continues uphill from there.
+    ///
+    /// Returns an error if `node` isn't part of the complex.
+    pub fn integral_line<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>, node: NodeIndex) -> Result<Vec<NodeIndex>, MorseError> {
+        let mut position = self.sweep_position(node).ok_or(MorseError::MissingNode{node})?;
+        let ancestor = self.ancestor_at(position)?;
+        let mut line = vec![node];
+        while self.ordered_points[position].node != ancestor {
+            let current = self.ordered_points[position].node;
+            let mut higher = vec![];
+            for neighbor in graph.neighbors(current) {
+                if let Some(p) = self.sweep_position(neighbor) {
+                    if p < position && self.ancestor_at(p)? == ancestor {
+                        higher.push(p);
+                    }
+                }
+            }
+            if higher.is_empty() {
+                break;
+            }
+            position = self.find_steepest_neighbor(position, &higher, graph)?;
+            line.push(self.ordered_points[position].node);
+        }
+        Ok(line)
+    }
+
+    /// Returns the length of the [integral line](#method.integral_line) from each node to the
+    /// extremum of its cell, as the number of edges and their total weight.
+    ///
+    /// This is how deep each node lies within its cell, measured along the steepest path. Each
+    /// line continues the line of the node it steps to, so all of them are found in one pass over
+    /// the sweep rather than by following each one.
+    pub fn integral_line_lengths<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>)
+        -> Result<HashMap<NodeIndex, (usize, f64)>, MorseError> {
+        let mut lengths: Vec<(usize, f64)> = Vec::with_capacity(self.ordered_points.len());
+        for (position, point) in self.ordered_points.iter().enumerate() {
+            let ancestor = self.ancestor_at(position)?;
+            let mut higher = vec![];
+            for neighbor in graph.neighbors(point.node) {
+                if let Some(p) = self.sweep_position(neighbor) {
+                    if p < position && self.ancestor_at(p)? == ancestor {
+                        higher.push(p);
+                    }
+                }
+            }
+            if point.node == ancestor || higher.is_empty() {
+                lengths.push((0, 0.));
+                continue;
+            }
+            let next = self.find_steepest_neighbor(position, &higher, graph)?;
+            let other = self.ordered_points[next].node;
+            let weight = graph.find_edge(point.node, other)
+                .ok_or(MorseError::MissingEdge{node: point.node, other})
+                .and_then(|edge| graph.edge_weight(edge).cloned().ok_or(MorseError::MissingEdgeWeight{edge}))?;
+            let (hops, total) = lengths[next];
+            lengths.push((hops + 1, total + weight));
+        }
+        Ok(self.ordered_points.iter().map(|point| point.node).zip(lengths).collect())
+    }
+
+    /// Returns the discrete gradient of the function: a HashMap mapping each node to the steepest
+    /// of its neighbors that are higher (or lower, for an ascending complex).
+    ///
+    /// Steepness is measured as during construction, but unlike an
+    /// [integral line](#method.integral_line), the steepest neighbor can be in another cell: nodes
+    /// that border several cells are assigned by the sweep, not by their arrow. Extrema have no
+    /// such neighbor, and don't appear in the map.
+    pub fn steepest_neighbors<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>) -> Result<HashMap<NodeIndex, NodeIndex>, MorseError> {
+        let mut arrows = HashMap::with_capacity(self.ordered_points.len());
+        for (position, point) in self.ordered_points.iter().enumerate() {
+            let higher: Vec<usize> = graph.neighbors(point.node)
+                .filter_map(|neighbor| self.sweep_position(neighbor))
+                .filter(|&p| p < position)
+                .collect();
+            if !higher.is_empty() {
+                let steepest = self.find_steepest_neighbor(position, &higher, graph)?;
+                arrows.insert(point.node, self.ordered_points[steepest].node);
+            }
+        }
+        Ok(arrows)
+    }
+
+    /// Returns a soft assignment of each node to the Morse cells: for each node, the fraction of
+    /// the monotone paths from it to an extremum that end at each extremum, as `(extremum,
+    /// fraction)` pairs sorted by index.
+    ///
+    /// The paths are the ones an integral line could take if it didn't always pick the steepest
+    /// neighbor: every step goes to a neighbor that is higher (or lower, for an ascending complex).
+    /// Nodes deep inside a cell have all of their paths end at its extremum, while nodes near a
+    /// boundary split between the cells on either side. In a [simplified](#method.simplify)
+    /// complex, the paths ending at removed extrema count towards the cells they were merged into.
+    ///
+    /// The number of paths grows exponentially with their length, so they are counted in log
+    /// space, and the fractions are exact up to rounding.
+    pub fn soft_assignment<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>)
+        -> Result<HashMap<NodeIndex, Vec<(NodeIndex, f64)>>, MorseError> {
+        // the log of the number of paths from each point, and where those paths end
+        let mut log_counts: Vec<f64> = Vec::with_capacity(self.ordered_points.len());
+        let mut endings: Vec<HashMap<NodeIndex, f64>> = Vec::with_capacity(self.ordered_points.len());
+        for (position, point) in self.ordered_points.iter().enumerate() {
+            let mut higher: Vec<usize> = graph.neighbors(point.node)
+                .filter_map(|neighbor| self.sweep_position(neighbor))
+                .filter(|&p| p < position)
+                .collect();
+            higher.sort_unstable();
+            higher.dedup();
+            if higher.is_empty() {
+                log_counts.push(0.);
+                endings.push(std::iter::once((self.ancestor_at(position)?, 1.)).collect());
+                continue;
+            }
+            let largest = higher.iter().map(|&p| log_counts[p]).fold(f64::NEG_INFINITY, f64::max);
+            let total: f64 = higher.iter().map(|&p| (log_counts[p] - largest).exp()).sum();
+            let mut ending = HashMap::new();
+            for &p in higher.iter() {
+                let share = (log_counts[p] - largest).exp() / total;
+                for (&extremum, &fraction) in endings[p].iter() {
+                    *ending.entry(extremum).or_insert(0.) += share * fraction;
+                }
+            }
+            log_counts.push(largest + total.ln());
+            endings.push(ending);
+        }
+
+        Ok(self.ordered_points.iter().zip(endings)
+            .map(|(point, ending)| {
+                let mut ending: Vec<(NodeIndex, f64)> = ending.into_iter().collect();
+                ending.sort_by_key(|&(extremum, _)| extremum);
+                (point.node, ending)
+            })
+            .collect())
+    }
+
+    // The cell extremum of the point at `position` in the sweep
+    pub(super) fn ancestor_at(&self, position: usize) -> Result<NodeIndex, MorseError> {
+        let point = &self.ordered_points[position];
+        point.data.as_ref().map(|data| data.ancestor).ok_or(MorseError::MissingData{node: point.node})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{path_graph, weighted_path_graph};
+    use crate::LabeledPoint;
+    use petgraph::graph::UnGraph;
+    use crate::morse::MorseKind;
+
+    #[test]
+    fn test_integral_line() {
+        // a ridge from 0 up to the peak at 4, with a shortcut from 1 to 3 and a plateau at 3 and 5
+        let mut graph = path_graph(&[1., 2., 3., 4., 5., 4.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        graph.add_edge(nodes[1], nodes[3], 1.);
+        graph.add_edge(nodes[3], nodes[5], 1.);
+
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        // from 1, the grade of 3 is |4 / 1|, steeper than 2's |3 / 1|
+        assert_eq!(complex.integral_line(&graph, nodes[0]).unwrap(), vec![nodes[0], nodes[1], nodes[3], nodes[4]]);
+        assert_eq!(complex.integral_line(&graph, nodes[4]).unwrap(), vec![nodes[4]]);
+        assert_eq!(complex.integral_line(&graph, nodes[5]).unwrap(), vec![nodes[5], nodes[4]]);
+
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &graph).unwrap();
+        assert_eq!(complex.integral_line(&graph, nodes[2]).unwrap(), vec![nodes[2], nodes[1], nodes[0]]);
+        for &node in &nodes {
+            let line = complex.integral_line(&graph, node).unwrap();
+            assert_eq!(*line.last().unwrap(), complex.get_complex()[&node]);
+        }
+    }
+
+    #[test]
+    fn test_integral_line_lengths() {
+        let mut graph = weighted_path_graph(&[1., 2., 3., 4., 5., 4.], &[1., 2., 0.5, 3., 1.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        graph.add_edge(nodes[1], nodes[3], 1.);
+        graph.add_edge(nodes[3], nodes[5], 1.);
+
+        for &kind in &[MorseKind::Descending, MorseKind::Ascending] {
+            let complex = MorseComplex::from_graph(kind, &graph).unwrap();
+            let lengths = complex.integral_line_lengths(&graph).unwrap();
+            assert_eq!(lengths.len(), nodes.len());
+            // each length agrees with the line itself
+            for &node in nodes.iter() {
+                let line = complex.integral_line(&graph, node).unwrap();
+                let total: f64 = line.windows(2).map(|pair| graph[graph.find_edge(pair[0], pair[1]).unwrap()]).sum();
+                assert_eq!(lengths[&node], (line.len() - 1, total));
+            }
+        }
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let lengths = complex.integral_line_lengths(&graph).unwrap();
+        assert_eq!(lengths[&nodes[0]], (3, 5.));
+        assert_eq!(lengths[&nodes[4]], (0, 0.));
+    }
+
+    #[test]
+    fn test_steepest_neighbors() {
+        let graph = path_graph(&[1., 0., 4., 3., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let arrows = complex.steepest_neighbors(&graph).unwrap();
+        assert_eq!(arrows.len(), 2);
+        assert_eq!(arrows[&nodes[1]], nodes[2]);
+        assert_eq!(arrows[&nodes[3]], nodes[4]);
+
+        // following the arrows from a node that isn't on a boundary gives its integral line
+        let complex = MorseComplex::from_graph(MorseKind::Ascending, &graph).unwrap();
+        let arrows = complex.steepest_neighbors(&graph).unwrap();
+        let mut line = vec![nodes[4]];
+        while let Some(&next) = arrows.get(line.last().unwrap()) {
+            line.push(next);
+        }
+        assert_eq!(line, complex.integral_line(&graph, nodes[4]).unwrap());
+    }
+
+    #[test]
+    fn test_soft_assignment() {
+        // peaks at 0 and 4; from 2, one path climbs to 0 and two to 4, through 3 and then either
+        // straight up or through 5
+        let mut graph = path_graph(&[5., 3., 1., 2., 6., 4.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        graph.add_edge(nodes[3], nodes[5], 1.);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let soft = complex.soft_assignment(&graph).unwrap();
+        assert_eq!(soft[&nodes[0]], vec![(nodes[0], 1.)]);
+        assert_eq!(soft[&nodes[1]], vec![(nodes[0], 1.)]);
+        assert_eq!(soft[&nodes[3]], vec![(nodes[4], 1.)]);
+        assert_eq!(soft[&nodes[5]], vec![(nodes[4], 1.)]);
+        let (to_0, to_4) = (soft[&nodes[2]][0], soft[&nodes[2]][1]);
+        assert_eq!((to_0.0, to_4.0), (nodes[0], nodes[4]));
+        assert!((to_0.1 - 1. / 3.).abs() < 1e-12 && (to_4.1 - 2. / 3.).abs() < 1e-12);
+
+        // every node's fractions add up to 1, and in the fully simplified complex go to one peak
+        assert!(soft.values().all(|ending| (ending.iter().map(|(_, f)| f).sum::<f64>() - 1.).abs() < 1e-12));
+        let soft = complex.simplify(f64::INFINITY).soft_assignment(&graph).unwrap();
+        assert!(soft.values().all(|ending| *ending == vec![(nodes[4], 1.)]));
+    }
+
+    #[test]
+    fn test_integral_line_errors() {
+        let mut graph = UnGraph::new_undirected();
+        let a = graph.add_node(LabeledPoint{id: 0, value: 0., point: ()});
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        graph.add_node(LabeledPoint{id: 1, value: 0., point: ()});
+        assert_eq!(complex.integral_line(&graph, a).unwrap(), vec![a]);
+        assert!(complex.integral_line(&graph, NodeIndex::new(1)).is_err());
+        assert!(complex.integral_line(&graph, NodeIndex::new(2)).is_err());
+    }
+}
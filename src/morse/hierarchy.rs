@@ -0,0 +1,194 @@
+//! The filtration of a complex as a tree of extrema, for hierarchical segmentation.
+//!
+//! Each step of the filtration merges the cell of one extremum into the cell of another, so the
+//! steps form a forest: the destroyed extremum is a child of the owning one. Cutting the forest at
+//! a time gives the segmentation at that scale, and drawing it gives a dendrogram.
+use std::collections::{HashMap, HashSet};
+use petgraph::graph::NodeIndex;
+
+use super::{MorseComplex, MorseError};
+use crate::linkage::linkage_rows;
+
+/// The forest of merges recorded by the filtration of a complex.
+///
+/// Created by [MorseComplex::merge_hierarchy](struct.MorseComplex.html#method.merge_hierarchy).
+/// Its nodes are the extrema of the complex, and its roots are the extrema that are never
+/// destroyed, one per connected component of the graph.
+#[derive(Debug, Clone)]
+pub struct MergeHierarchy {
+    roots: Vec<NodeIndex>,
+    // the extremum each destroyed extremum merged into, and when
+    parents: HashMap<NodeIndex, (NodeIndex, f64)>,
+    // in order of merge time
+    children: HashMap<NodeIndex, Vec<NodeIndex>>
+}
+
+impl MergeHierarchy {
+    /// The extrema that are never destroyed, from the most to the least persistent.
+    pub fn roots(&self) -> &[NodeIndex] {
+        &self.roots
+    }
+
+    /// The extremum that the cell of `extremum` is merged into, and the time of the merge, or
+    /// `None` if it is a root or not an extremum.
+    pub fn parent(&self, extremum: NodeIndex) -> Option<(NodeIndex, f64)> {
+        self.parents.get(&extremum).cloned()
+    }
+
+    /// The extrema whose cells are merged into the cell of `extremum`, in the order of the
+    /// filtration.
+    pub fn children(&self, extremum: NodeIndex) -> &[NodeIndex] {
+        self.children.get(&extremum).map(|c| c.as_slice()).unwrap_or(&[])
+    }
+
+    /// `extremum` and every extremum below it in the hierarchy, depth first, with the children of
+    /// each in the order of the filtration.
+    ///
+    /// These are the extrema whose cells make up the cell of `extremum` once the filtration has
+    /// run its course.
+    pub fn descendants(&self, extremum: NodeIndex) -> Vec<NodeIndex> {
+        let mut descendants = vec![];
+        let mut stack = vec![extremum];
+        while let Some(current) = stack.pop() {
+            descendants.push(current);
+            stack.extend(self.children(current).iter().rev());
+        }
+        descendants
+    }
+
+    /// The extrema that the cell of `extremum` belongs to as the filtration proceeds, starting
+    /// with `extremum` itself, each with the time at which it hands the cell on to the next. The
+    /// last is a root, which never does, so its time is infinite.
+    ///
+    /// An extremum can be merged into one that has already been destroyed, when persistence isn't
+    /// measured by value. The cell then goes straight to wherever that one went, as in
+    /// [complex_at](struct.MorseComplex.html#method.complex_at).
+    pub fn chain(&self, extremum: NodeIndex) -> Vec<(NodeIndex, f64)> {
+        let mut chain = vec![];
+        let mut current = extremum;
+        while let Some(&(mut owner, time)) = self.parents.get(&current) {
+            chain.push((current, time));
+            while let Some(&(next, _)) = self.parents.get(&owner).filter(|&&(_, t)| t <= time) {
+                owner = next;
+            }
+            current = owner;
+        }
+        chain.push((current, f64::INFINITY));
+        chain
+    }
+
+    /// The hierarchy as a SciPy linkage matrix, with the extrema as the leaves, and the times of
+    /// the merges as the heights. Returns the matrix and the extremum of each leaf.
+    ///
+    /// The leaves are in order of index, and the rows in order of time, with the same format as
+    /// [Dendrogram::linkage_matrix_with](../linkage/struct.Dendrogram.html#method.linkage_matrix_with).
+    /// The roots, which are never merged, are joined at `join_height`, so with a single root
+    /// (i.e. a connected graph) it doesn't appear.
+    pub fn linkage_matrix(&self, join_height: f64) -> (Vec<[f64; 4]>, Vec<NodeIndex>) {
+        let mut leaves: Vec<NodeIndex> = self.roots.iter().chain(self.parents.keys()).cloned().collect();
+        leaves.sort();
+        let index: HashMap<NodeIndex, usize> = leaves.iter().enumerate().map(|(i, &leaf)| (leaf, i)).collect();
+        let mut merges: Vec<(usize, usize, f64)> = self.parents.iter()
+            .map(|(destroyed, &(owner, time))| (index[&owner], index[destroyed], time))
+            .collect();
+        merges.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap().then(a.1.cmp(&b.1)));
+        (linkage_rows(leaves.len(), merges.into_iter(), join_height), leaves)
+    }
+}
+
+impl MorseComplex {
+    /// Returns the filtration as a forest, in which each destroyed extremum points to the
+    /// extremum it was merged into.
+    pub fn merge_hierarchy(&self) -> MergeHierarchy {
+        let destroyed: HashSet<NodeIndex> = self.filtration.iter().map(|step| step.destroyed_cell).collect();
+        let roots = self.extrema().into_iter()
+            .filter(|(extremum, _)| !destroyed.contains(extremum))
+            .map(|(extremum, _)| extremum)
+            .collect();
+        let mut parents = HashMap::with_capacity(self.filtration.len());
+        let mut children: HashMap<NodeIndex, Vec<NodeIndex>> = HashMap::new();
+        for step in self.filtration.iter() {
+            parents.insert(step.destroyed_cell, (step.owning_cell, step.time));
+            children.entry(step.owning_cell).or_default().push(step.destroyed_cell);
+        }
+        MergeHierarchy{roots, parents, children}
+    }
+
+    /// Returns the extrema that `node`'s cell belongs to as the filtration proceeds, from the
+    /// extremum of its own cell to the global extremum, with the time at which each hands the cell
+    /// on. See [MergeHierarchy::chain](struct.MergeHierarchy.html#method.chain).
+    ///
+    /// This builds the hierarchy for every call, so for many nodes, build it once with
+    /// [merge_hierarchy](#method.merge_hierarchy) and follow the chain of each cell instead.
+    /// Returns an error if `node` isn't part of the complex.
+    pub fn extremum_chain(&self, node: NodeIndex) -> Result<Vec<(NodeIndex, f64)>, MorseError> {
+        let position = self.sweep_position(node).ok_or(MorseError::MissingNode{node})?;
+        Ok(self.merge_hierarchy().chain(self.ancestor_at(position)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+    use crate::morse::MorseKind;
+
+    #[test]
+    fn test_merge_hierarchy() {
+        // maxima at 0, 2, 4 and 6. By the time 0 meets the others at 1, 2 has already merged into 4
+        let graph = path_graph(&[3., 0., 5., 1., 6., 4., 4.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let hierarchy = complex.merge_hierarchy();
+        assert_eq!(hierarchy.roots(), &[nodes[4]][..]);
+        assert_eq!(hierarchy.parent(nodes[4]), None);
+        assert_eq!(hierarchy.parent(nodes[6]), Some((nodes[4], 0.5)));
+        assert_eq!(hierarchy.parent(nodes[0]), Some((nodes[4], 3.)));
+        assert_eq!(hierarchy.parent(nodes[2]), Some((nodes[4], 4.)));
+        assert_eq!(hierarchy.children(nodes[4]), &[nodes[6], nodes[0], nodes[2]][..]);
+        assert!(hierarchy.children(nodes[1]).is_empty());
+        assert_eq!(hierarchy.descendants(nodes[4]), vec![nodes[4], nodes[6], nodes[0], nodes[2]]);
+        assert_eq!(hierarchy.descendants(nodes[2]), vec![nodes[2]]);
+    }
+
+    #[test]
+    fn test_linkage_matrix() {
+        let graph = path_graph(&[3., 0., 5., 1., 6., 4., 4.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let (matrix, leaves) = complex.merge_hierarchy().linkage_matrix(f64::INFINITY);
+        assert_eq!(leaves, vec![nodes[0], nodes[2], nodes[4], nodes[6]]);
+        // 6 merges into 4 at 0.5, then 0 at 3, then 2 at 4
+        assert_eq!(matrix, vec![[2., 3., 0.5, 2.], [0., 4., 3., 3.], [1., 5., 4., 4.]]);
+    }
+
+    #[test]
+    fn test_extremum_chain() {
+        let graph = path_graph(&[3., 0., 5., 1., 6., 4., 4.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        assert_eq!(complex.extremum_chain(nodes[1]).unwrap(), vec![(nodes[2], 4.), (nodes[4], f64::INFINITY)]);
+        assert_eq!(complex.extremum_chain(nodes[4]).unwrap(), vec![(nodes[4], f64::INFINITY)]);
+        assert!(complex.extremum_chain(NodeIndex::new(7)).is_err());
+        // the chain agrees with the complex at each time
+        for &node in nodes.iter() {
+            let chain = complex.extremum_chain(node).unwrap();
+            for window in chain.windows(2) {
+                let (before, time) = window[0];
+                assert_eq!(complex.complex_at(time - 0.01)[&node], before);
+                assert_eq!(complex.complex_at(time)[&node], window[1].0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_through_destroyed_owner() {
+        // 2 is merged into 1 at time 3, after 1 was merged into 0 at time 2
+        let hierarchy = MergeHierarchy{
+            roots: vec![NodeIndex::new(0)],
+            parents: vec![(NodeIndex::new(1), (NodeIndex::new(0), 2.)), (NodeIndex::new(2), (NodeIndex::new(1), 3.))].into_iter().collect(),
+            children: HashMap::new()
+        };
+        assert_eq!(hierarchy.chain(NodeIndex::new(2)), vec![(NodeIndex::new(2), 3.), (NodeIndex::new(0), f64::INFINITY)]);
+    }
+}
@@ -0,0 +1,95 @@
+//! The complexes in the terms of the Morse theory literature.
+//!
+//! Morse theory describes a function by the flow along its gradient. Following the usual
+//! convention, points flow downhill, along `-∇f`: the stable manifold of a critical point is the
+//! set of points that flow into it, and its unstable manifold the set of points that flow out of
+//! it. On a graph, only the extrema have manifolds that are a set of nodes:
+//!
+//! * the stable manifold of a minimum is its basin, the cell of the
+//!   [ascending complex](struct.MorseSmaleComplex.html#structfield.ascending_complex) whose nodes
+//!   descend to it, and
+//! * the unstable manifold of a maximum is its mountain, the cell of the
+//!   [descending complex](struct.MorseSmaleComplex.html#structfield.descending_complex) whose
+//!   nodes climb to it.
+//!
+//! The stable manifold of a maximum and the unstable manifold of a minimum are the extrema
+//! themselves. Their intersections are the crystals of the Morse-Smale complex. Some tools flow
+//! uphill instead, which swaps the names, so check the convention before comparing results.
+use petgraph::graph::NodeIndex;
+
+use super::{MorseComplex, MorseSmaleComplex};
+
+impl MorseComplex {
+    /// The extremum of the Morse cell containing `node`, or `None` if `node` isn't part of the
+    /// complex.
+    pub fn extremum_of(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.sweep_position(node).and_then(|position| self.ancestor_at(position).ok())
+    }
+}
+
+impl MorseSmaleComplex {
+    /// The nodes that flow down into `critical`, sorted by index.
+    ///
+    /// For a minimum, this is its cell in the ascending complex. For a maximum, nothing flows into
+    /// it but itself. For any other node, this is empty.
+    pub fn stable_manifold(&self, critical: NodeIndex) -> Vec<NodeIndex> {
+        let manifold = self.ascending_complex.members_of(critical);
+        if manifold.is_empty() && self.descending_complex.extremum_of(critical) == Some(critical) {
+            return vec![critical];
+        }
+        manifold
+    }
+
+    /// The nodes that flow down out of `critical`, i.e. that `critical` can be reached from by
+    /// climbing, sorted by index.
+    ///
+    /// For a maximum, this is its cell in the descending complex. For a minimum, nothing flows out
+    /// of it but itself. For any other node, this is empty.
+    pub fn unstable_manifold(&self, critical: NodeIndex) -> Vec<NodeIndex> {
+        let manifold = self.descending_complex.members_of(critical);
+        if manifold.is_empty() && self.ascending_complex.extremum_of(critical) == Some(critical) {
+            return vec![critical];
+        }
+        manifold
+    }
+
+    /// The Morse-Smale crystal containing `node`, as the (minimum, maximum) pair whose stable and
+    /// unstable manifolds it is in, or `None` if it isn't part of both complexes.
+    ///
+    /// This looks up a single node; [get_cells](#method.get_cells) gives the crystals of all of them.
+    pub fn crystal(&self, node: NodeIndex) -> Option<(NodeIndex, NodeIndex)> {
+        Some((self.ascending_complex.extremum_of(node)?, self.descending_complex.extremum_of(node)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_manifolds() {
+        // minima at 0 and 4, maxima at 2 and 5
+        let graph = path_graph(&[0., 3., 5., 2., 1., 4.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        assert_eq!(complex.stable_manifold(nodes[0]), vec![nodes[0], nodes[1], nodes[2]]);
+        assert_eq!(complex.stable_manifold(nodes[4]), vec![nodes[3], nodes[4], nodes[5]]);
+        assert_eq!(complex.unstable_manifold(nodes[2]), vec![nodes[0], nodes[1], nodes[2], nodes[3]]);
+        assert_eq!(complex.unstable_manifold(nodes[5]), vec![nodes[4], nodes[5]]);
+        assert_eq!(complex.stable_manifold(nodes[2]), vec![nodes[2]]);
+        assert_eq!(complex.unstable_manifold(nodes[0]), vec![nodes[0]]);
+        assert!(complex.stable_manifold(nodes[1]).is_empty());
+        assert!(complex.unstable_manifold(NodeIndex::new(6)).is_empty());
+
+        let cells = complex.get_cells();
+        for &node in nodes.iter() {
+            assert_eq!(complex.crystal(node), Some(cells[&node]));
+            let (minimum, maximum) = cells[&node];
+            assert!(complex.stable_manifold(minimum).contains(&node));
+            assert!(complex.unstable_manifold(maximum).contains(&node));
+        }
+        assert_eq!(complex.crystal(NodeIndex::new(6)), None);
+        assert_eq!(complex.ascending_complex.extremum_of(nodes[3]), Some(nodes[4]));
+    }
+}
@@ -0,0 +1,141 @@
+//! A side-by-side comparison of the minima and the maxima of a function.
+use petgraph::graph::Graph;
+use petgraph::EdgeType;
+
+use super::{MorseSmaleComplex, MorseError};
+use crate::ScalarValued;
+use crate::distance::{bottleneck_distance, wasserstein_matching};
+use crate::persistence::{DiagramPoint, PersistenceDiagram};
+use crate::stats::total_persistence;
+
+/// A minimum and a maximum that were matched to each other by
+/// [compare_diagrams](struct.MorseSmaleComplex.html#method.compare_diagrams).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeatureMatch {
+    /// The point of the minimum, as it appears in the diagram of the minima.
+    pub minimum: DiagramPoint,
+
+    /// The point of the maximum, as it appears in the diagram of the maxima, i.e. before it is
+    /// reflected for the comparison.
+    pub maximum: DiagramPoint
+}
+
+impl FeatureMatch {
+    /// How much longer the minimum lasts than the maximum, negative if it is shorter. Global
+    /// extrema, which never die, are only matched to each other, and last equally long.
+    pub fn persistence_difference(&self) -> f64 {
+        if self.minimum.is_essential() && self.maximum.is_essential() {
+            return 0.;
+        }
+        self.minimum.persistence() - self.maximum.persistence()
+    }
+}
+
+/// The diagrams of the minima and the maxima of a function, and how they compare.
+///
+/// To compare them, the maxima are reflected about the middle of the range of the function, so
+/// that a function that looks the same upside down has the same diagram on both sides. A deep
+/// pit and a tall peak of the same size are then matched to each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagramComparison {
+    /// The diagram of the ascending complex, with a point for each minimum.
+    pub minima: PersistenceDiagram,
+
+    /// The diagram of the descending complex, with a point for each maximum.
+    pub maxima: PersistenceDiagram,
+
+    /// The pairs of a minimum and a maximum that are matched to each other by an optimal
+    /// 2-Wasserstein matching of the diagrams, in the order of the minima. The other extrema have
+    /// no counterpart on the other side.
+    pub matches: Vec<FeatureMatch>,
+
+    /// The bottleneck distance between the minima and the reflected maxima. This is 0 for a
+    /// function whose minima mirror its maxima.
+    pub asymmetry: f64
+}
+
+impl DiagramComparison {
+    /// The numbers of minima and of maxima with persistence above `threshold`, i.e. the numbers
+    /// that [simplify](struct.MorseComplex.html#method.simplify) keeps.
+    pub fn counts_above(&self, threshold: f64) -> (usize, usize) {
+        let count = |diagram: &PersistenceDiagram| diagram.points().iter().filter(|point| point.persistence() >= threshold).count();
+        (count(&self.minima), count(&self.maxima))
+    }
+
+    /// The difference between the total finite persistence of the minima and of the maxima, as a
+    /// fraction of the sum of the two. This is in `[-1, 1]`, positive when the structure of the
+    /// function is mostly in its minima, and 0 when neither side has any.
+    pub fn persistence_balance(&self) -> f64 {
+        let minima = total_persistence(&self.minima.lifetimes(), 1.);
+        let maxima = total_persistence(&self.maxima.lifetimes(), 1.);
+        if minima + maxima == 0. {
+            return 0.;
+        }
+        (minima - maxima) / (minima + maxima)
+    }
+}
+
+impl MorseSmaleComplex {
+    /// Compares the diagrams of the minima and the maxima of the function on `graph`.
+    ///
+    /// Returns an error if a node of the complex is missing from `graph`.
+    pub fn compare_diagrams<N: ScalarValued, Ty: EdgeType>(&self, graph: &Graph<N, f64, Ty>) -> Result<DiagramComparison, MorseError> {
+        let minima = PersistenceDiagram::from_complex(&self.ascending_complex, graph)?;
+        let maxima = PersistenceDiagram::from_complex(&self.descending_complex, graph)?;
+        let lowest = minima.points().iter().map(|point| point.birth).fold(f64::INFINITY, f64::min);
+        let highest = maxima.points().iter().map(|point| point.birth).fold(f64::NEG_INFINITY, f64::max);
+        let reflected: Vec<(f64, f64)> = maxima.pairs().into_iter()
+            .map(|(birth, death)| (lowest + highest - birth, lowest + highest - death))
+            .collect();
+
+        let pairs = minima.pairs();
+        let asymmetry = bottleneck_distance(&pairs, &reflected)?;
+        let matches = wasserstein_matching(&pairs, &reflected, 2., f64::INFINITY)?.into_iter()
+            .map(|(i, j)| FeatureMatch{minimum: minima.points()[i], maximum: maxima.points()[j]})
+            .collect();
+        Ok(DiagramComparison{minima, maxima, matches, asymmetry})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::UnGraph;
+    use crate::LabeledPoint;
+    use crate::fixtures::path_graph;
+
+    #[test]
+    fn test_compare_diagrams() {
+        // the values are symmetric about 5, so the minima mirror the maxima
+        let graph = path_graph(&[0., 8., 3., 7., 2., 10.]);
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let comparison = complex.compare_diagrams(&graph).unwrap();
+        assert_eq!(comparison.minima.len(), 3);
+        assert_eq!(comparison.maxima.len(), 3);
+        assert_eq!(comparison.asymmetry, 0.);
+        assert_eq!(comparison.persistence_balance(), 0.);
+        assert_eq!(comparison.matches.len(), 3);
+        assert!(comparison.matches.iter().all(|m| m.persistence_difference() == 0.));
+        // the pit at 2 and the peak at 8 both last 6
+        assert!(comparison.matches.iter().any(|m| m.minimum.birth == 0. && m.maximum.birth == 10.));
+        assert!(comparison.matches.iter().any(|m| m.minimum.birth == 2. && m.maximum.birth == 8.));
+        assert_eq!(comparison.counts_above(4.5), (2, 2));
+        assert_eq!(comparison.counts_above(f64::INFINITY), (1, 1));
+
+        // a star has three pits around a single peak, so its structure is all in the minima
+        let mut graph = UnGraph::new_undirected();
+        let center = graph.add_node(LabeledPoint{id: 0, value: 5., point: ()});
+        for (i, &value) in [0., 1., 2.].iter().enumerate() {
+            let leaf = graph.add_node(LabeledPoint{id: i as i64 + 1, value, point: ()});
+            graph.add_edge(center, leaf, 1.);
+        }
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let comparison = complex.compare_diagrams(&graph).unwrap();
+        assert_eq!(comparison.counts_above(0.), (3, 1));
+        assert_eq!(comparison.persistence_balance(), 1.);
+        // the minimum at 1 lasts 4, and nothing on the other side is closer to it than the diagonal
+        assert_eq!(comparison.asymmetry, 2.);
+        assert_eq!(comparison.matches.len(), 1);
+        assert_eq!(comparison.maxima, PersistenceDiagram::from_complex(&complex.descending_complex, &graph).unwrap());
+    }
+}
@@ -0,0 +1,191 @@
+//! Estimates of how much the cells of a complex depend on small changes to the function.
+use std::collections::HashMap;
+use std::f64;
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::EdgeType;
+use rand::Rng;
+
+use super::{MorseComplex, MorseError, TieBreak, sweep_order};
+use crate::{ScalarValued, LabeledPoint};
+use crate::persistence::PersistenceDiagram;
+
+/// The noise added to every value by
+/// [perturbation_trials](struct.MorseComplex.html#method.perturbation_trials).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Noise {
+    /// Noise drawn uniformly from `[-a, a]`.
+    Uniform(f64),
+
+    /// Normally distributed noise with mean 0 and the given standard deviation.
+    Gaussian(f64)
+}
+
+impl Noise {
+    fn sample<R: Rng>(self, rng: &mut R) -> f64 {
+        match self {
+            Noise::Uniform(amplitude) => amplitude * (2. * rng.gen::<f64>() - 1.),
+            Noise::Gaussian(deviation) => {
+                // Box-Muller, with the first draw in (0, 1] so that its log is finite
+                let radius = (-2. * (1. - rng.gen::<f64>()).ln()).sqrt();
+                deviation * radius * (2. * f64::consts::PI * rng.gen::<f64>()).cos()
+            }
+        }
+    }
+}
+
+/// How one perturbed function compares to the original.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerturbationTrial {
+    /// The largest change to any one value, the sup-norm distance between the functions.
+    pub magnitude: f64,
+
+    /// The bottleneck distance between the persistence diagrams of the two complexes.
+    pub bottleneck_distance: f64,
+
+    /// The fraction of nodes that stayed in the same cell.
+    pub agreement: f64
+}
+
+impl PerturbationTrial {
+    /// Whether the diagrams moved no further than the stability theorem allows, i.e. the
+    /// bottleneck distance is at most the magnitude of the perturbation, up to rounding.
+    ///
+    /// This holds whenever the graph is valid, so a failing trial points at a problem with the
+    /// graph or its values rather than with the noise.
+    pub fn within_bound(&self) -> bool {
+        self.bottleneck_distance <= self.magnitude * (1. + 1e-9)
+    }
+}
+
+impl MorseComplex {
+    /// Returns, for each node, the fraction of `trials` in which it stays in the same cell when
+    /// the complex is rebuilt with randomly perturbed values.
+    ///
+    /// Each trial adds noise drawn uniformly from `[-noise, noise]` to the value of every node,
+    /// breaks any remaining ties in a random order, and sweeps the nodes again. The perturbed
+    /// values only change the order of the sweep: steepness is still measured with the values in
+    /// `graph`. A node stays in its cell if the extremum it flows to in the trial belongs to the
+    /// same cell of this complex as the node itself, so a flat peak whose extremum moves around
+    /// doesn't count as unstable. Nodes near a boundary that noise can shift, or in cells too
+    /// shallow to survive it, get low scores. In a [simplified](#method.simplify) complex, a
+    /// node stays in its cell as long as it flows to any extremum that was merged into it.
+    ///
+    /// Returns an error if a node of the complex is missing from `graph`.
+    pub fn label_stability<N: ScalarValued, Ty: EdgeType, R: Rng>(&self, graph: &Graph<N, f64, Ty>, trials: usize, noise: f64,
+                                                                 rng: &mut R) -> Result<HashMap<NodeIndex, f64>, MorseError> {
+        let labels = self.get_complex();
+        let values = self.ordered_points.iter()
+            .map(|point| graph.node_weight(point.node).map(|w| w.value()).ok_or(MorseError::MissingNode{node: point.node}))
+            .collect::<Result<Vec<f64>, MorseError>>()?;
+        let mut agreements: HashMap<NodeIndex, usize> = labels.keys().map(|&node| (node, 0)).collect();
+        for _ in 0..trials {
+            let mut nodes: Vec<(f64, NodeIndex, i64)> = self.ordered_points.iter().zip(values.iter())
+                .map(|(point, &value)| (value + noise * (2. * rng.gen::<f64>() - 1.), point.node, rng.gen()))
+                .collect();
+            nodes.sort_by(|&a, &b| sweep_order(self.kind, TieBreak::Id, a, b));
+            let trial = MorseComplex::from_ordered_nodes(self.kind, &nodes, graph, self.options.tie_break(TieBreak::Id))?;
+            for (node, extremum) in trial.get_complex() {
+                if labels.get(&extremum) == labels.get(&node) {
+                    *agreements.entry(node).or_insert(0) += 1;
+                }
+            }
+        }
+        Ok(agreements.into_iter()
+            .map(|(node, count)| (node, if trials == 0 { 1. } else { count as f64 / trials as f64 }))
+            .collect())
+    }
+
+    /// Rebuilds the complex `trials` times with `noise` added to the values in `graph`, and
+    /// compares each result to this complex.
+    ///
+    /// Unlike [label_stability](#method.label_stability), the perturbed values are used for
+    /// everything, steepness and persistence included, as if the function had been measured
+    /// again. Each trial reports how far the function moved, how far its persistence diagram
+    /// moved, and the fraction of nodes that flow to an extremum in the same cell of this complex
+    /// as the node itself. By the stability theorem, the diagrams never move further than the
+    /// function, so with small noise, a large drop in agreement means the cells hinge on details
+    /// the diagram doesn't see. The diagram of a [simplified](#method.simplify) complex only has
+    /// the extrema that are left, while the perturbed diagrams have them all, so the distances are
+    /// only meaningful for complexes that haven't been simplified.
+    ///
+    /// Returns an error if a node of the complex is missing from `graph`.
+    pub fn perturbation_trials<N: ScalarValued, Ty: EdgeType, R: Rng>(&self, graph: &Graph<N, f64, Ty>, noise: Noise, trials: usize,
+                                                                     rng: &mut R) -> Result<Vec<PerturbationTrial>, MorseError> {
+        let labels = self.get_complex();
+        let diagram = PersistenceDiagram::from_complex(self, graph)?;
+        let mut results = Vec::with_capacity(trials);
+        for _ in 0..trials {
+            let mut magnitude: f64 = 0.;
+            let perturbed = graph.map(
+                |node, weight| {
+                    let change = if self.positions.contains_key(&node) { noise.sample(rng) } else { 0. };
+                    let value = weight.value() + change;
+                    // the change that was actually made, after rounding
+                    magnitude = magnitude.max((value - weight.value()).abs());
+                    LabeledPoint{id: weight.id().unwrap_or(node.index() as i64), value, point: ()}
+                },
+                |_, &weight| weight
+            );
+            let trial = MorseComplex::from_region(self.kind, &perturbed, |node| self.positions.contains_key(&node), self.options)?;
+            let bottleneck_distance = diagram.bottleneck_distance(&PersistenceDiagram::from_complex(&trial, &perturbed)?)?;
+            let agreeing = trial.get_complex().into_iter()
+                .filter(|(node, extremum)| labels.get(extremum) == labels.get(node))
+                .count();
+            let agreement = if labels.is_empty() { 1. } else { agreeing as f64 / labels.len() as f64 };
+            results.push(PerturbationTrial{magnitude, bottleneck_distance, agreement});
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+    use rand::prelude::*;
+    use crate::morse::MorseKind;
+
+    #[test]
+    fn test_label_stability() {
+        // a tall peak at 1 and a barely separate one at 5, with a flat top at 1 and 2
+        let graph = path_graph(&[0., 9., 9., 5., 3.9, 4., 0.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let stability = complex.label_stability(&graph, 50, 0.5, &mut rng).unwrap();
+        assert_eq!(stability.len(), nodes.len());
+        // the top of the tall peak moves between 1 and 2, but everything near it stays put
+        for &i in &[0, 1, 2, 3] {
+            assert_eq!(stability[&nodes[i]], 1.);
+        }
+        // the small peak is often swallowed by the noise
+        assert!(stability[&nodes[5]] < 1.);
+        assert!(stability[&nodes[6]] < 1.);
+
+        // without noise, only the ties are shuffled
+        let stability = complex.label_stability(&graph, 10, 0., &mut rng).unwrap();
+        assert!(stability.values().all(|&s| s == 1.));
+        // the fully simplified complex has a single cell, which nothing can leave
+        let simplified = complex.simplify(f64::INFINITY);
+        let stability = simplified.label_stability(&graph, 10, 0.5, &mut rng).unwrap();
+        assert!(stability.values().all(|&s| s == 1.));
+    }
+
+    #[test]
+    fn test_perturbation_trials() {
+        let graph = path_graph(&[0., 9., 8., 5., 3.9, 4., 0.]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        for &noise in &[Noise::Uniform(0.3), Noise::Gaussian(0.2)] {
+            let trials = complex.perturbation_trials(&graph, noise, 50, &mut rng).unwrap();
+            assert_eq!(trials.len(), 50);
+            assert!(trials.iter().all(|trial| trial.within_bound()));
+            // the small peak at 5 sometimes disappears, taking 4, 5 and 6 with it
+            assert!(trials.iter().all(|trial| trial.agreement >= 4. / 7.));
+            assert!(trials.iter().any(|trial| trial.agreement < 1.));
+        }
+
+        let trials = complex.perturbation_trials(&graph, Noise::Uniform(0.), 3, &mut rng).unwrap();
+        assert!(trials.iter().all(|trial| *trial == PerturbationTrial{magnitude: 0., bottleneck_distance: 0., agreement: 1.}));
+    }
+}
@@ -0,0 +1,92 @@
+//! Summaries of the values and points in each Morse cell.
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::{MorseComplex, MorseError};
+use crate::LabeledPoint;
+
+/// Summary statistics of the nodes in one Morse cell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellStatistics {
+    /// The extremum of the cell.
+    pub extremum: NodeIndex,
+
+    /// The number of nodes in the cell, including the extremum.
+    pub count: usize,
+
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+
+    /// The population variance of the values, i.e. divided by `count` rather than `count - 1`.
+    pub variance: f64,
+
+    /// The smallest and the largest coordinates of the points in the cell, one of each per
+    /// dimension.
+    pub bounding_box: (Vec<f64>, Vec<f64>)
+}
+
+impl MorseComplex {
+    /// Returns summary statistics for each Morse cell, in order of the index of its extremum.
+    ///
+    /// Returns an error if a node of the complex is missing from `graph`, or if its point doesn't
+    /// have as many coordinates as the others in its cell.
+    pub fn cell_statistics<T: AsRef<[f64]>>(&self, graph: &UnGraph<LabeledPoint<T>, f64>) -> Result<Vec<CellStatistics>, MorseError> {
+        let mut cells: Vec<(NodeIndex, Vec<NodeIndex>)> = self.get_members().into_iter().collect();
+        cells.sort_by_key(|&(extremum, _)| extremum);
+        cells.into_iter()
+            .map(|(extremum, members)| {
+                let weights = members.iter()
+                    .map(|&node| graph.node_weight(node).ok_or(MorseError::MissingNode{node}))
+                    .collect::<Result<Vec<_>, MorseError>>()?;
+                let count = weights.len();
+                let values = weights.iter().map(|w| w.value);
+                let min = values.clone().fold(f64::INFINITY, f64::min);
+                let max = values.clone().fold(f64::NEG_INFINITY, f64::max);
+                let mean = values.clone().sum::<f64>() / count as f64;
+                let variance = values.map(|value| (value - mean).powi(2)).sum::<f64>() / count as f64;
+
+                let dimension = weights[0].point.as_ref().len();
+                let mut lower = vec![f64::INFINITY; dimension];
+                let mut upper = vec![f64::NEG_INFINITY; dimension];
+                for (&node, weight) in members.iter().zip(weights.iter()) {
+                    let point = weight.point.as_ref();
+                    if point.len() != dimension {
+                        return Err(MorseError::DimensionMismatch{node, expected: dimension, found: point.len()});
+                    }
+                    for (d, &x) in point.iter().enumerate() {
+                        lower[d] = lower[d].min(x);
+                        upper[d] = upper[d].max(x);
+                    }
+                }
+                Ok(CellStatistics{extremum, count, min, max, mean, variance, bounding_box: (lower, upper)})
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph_of;
+    use crate::morse::MorseKind;
+
+    #[test]
+    fn test_cell_statistics() {
+        let mut graph = path_graph_of([1., 0., 4., 2., 5.].iter().enumerate()
+            .map(|(i, &value)| LabeledPoint{id: i as i64, value, point: vec![i as f64, -(i as f64)]}));
+        let nodes: Vec<_> = graph.node_indices().collect();
+        // the cells are {0}, {1, 2} and {3, 4}
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let statistics = complex.cell_statistics(&graph).unwrap();
+        assert_eq!(statistics.len(), 3);
+        assert_eq!(statistics[0], CellStatistics{extremum: nodes[0], count: 1, min: 1., max: 1., mean: 1., variance: 0.,
+                                                 bounding_box: (vec![0., 0.], vec![0., 0.])});
+        assert_eq!(statistics[1], CellStatistics{extremum: nodes[2], count: 2, min: 0., max: 4., mean: 2., variance: 4.,
+                                                 bounding_box: (vec![1., -2.], vec![2., -1.])});
+        assert_eq!(statistics[2].extremum, nodes[4]);
+        assert_eq!(statistics[2].mean, 3.5);
+
+        graph[nodes[3]].point.push(0.);
+        assert!(complex.cell_statistics(&graph).is_err());
+    }
+}
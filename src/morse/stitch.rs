@@ -0,0 +1,303 @@
+//! Merging the complexes of two graphs that have been joined by new edges.
+//!
+//! Rather than sweeping through the joined graph again, each complex is reduced to a skeleton of
+//! its merge tree: its extrema, the nodes at which their cells merged, and the endpoints of the
+//! new edges, connected so that they are joined at exactly the same values as they were in the
+//! original graph. Sweeping through that skeleton plus the new edges gives the persistence of the
+//! joined graph, at a cost that depends on the number of extrema and new edges rather than on the
+//! size of the graphs.
+use std::collections::HashMap;
+use std::f64;
+use petgraph::graph::{UnGraph, NodeIndex};
+
+use super::{MorseComplex, MorseSmaleComplex, MorseKind, MorseNode, MorseData, MorseError, MorseOptions};
+use crate::ScalarValued;
+use crate::util::PointedUnionFind;
+
+impl MorseSmaleComplex {
+    /// Joins two graphs with the `bridges` edges and merges their complexes into the complex of
+    /// the joined graph.
+    ///
+    /// Each bridge is a `(node in first_graph, node in second_graph, weight)` triple. In the
+    /// returned graph, the nodes of `first_graph` keep their indices and the nodes of
+    /// `second_graph` are shifted up by `first_graph.node_count()`.
+    ///
+    /// The persistence values and filtration are identical to those from constructing the complex
+    /// of the joined graph from scratch (up to which extremum survives when two cells with equal
+    /// values merge, which is arbitrary either way). Cells are updated where the bridges cancel an extremum
+    /// (its members move to the cell it now flows into), but nodes that were not extrema keep
+    /// their original cell even if their steepest neighbor is now across a bridge. Persistence is
+    /// always measured by [value](enum.PersistenceMeasure.html#variant.Value) in the result, and
+    /// ties are broken by [index](enum.TieBreak.html#variant.Index). Integral lines and gradients
+    /// use [the default grade](enum.Grade.html#variant.ValueOverWeight).
+    pub fn stitch<N: ScalarValued + Clone>(first_graph: &UnGraph<N, f64>, first: &MorseSmaleComplex,
+                            second_graph: &UnGraph<N, f64>, second: &MorseSmaleComplex,
+                            bridges: &[(NodeIndex, NodeIndex, f64)])
+        -> Result<(UnGraph<N, f64>, MorseSmaleComplex), MorseError> {
+        let offset = first_graph.node_count();
+        let shift = |node: NodeIndex| NodeIndex::new(node.index() + offset);
+
+        let mut graph = first_graph.clone();
+        for node in second_graph.raw_nodes() {
+            graph.add_node(node.weight.clone());
+        }
+        for edge in second_graph.raw_edges() {
+            graph.add_edge(shift(edge.source()), shift(edge.target()), edge.weight);
+        }
+        let bridges: Vec<(NodeIndex, NodeIndex)> = bridges.iter()
+            .map(|&(a, b, weight)| {
+                graph.add_edge(a, shift(b), weight);
+                (a, shift(b))
+            })
+            .collect();
+
+        let ascending_complex = MorseComplex::stitch(&first.ascending_complex, &second.ascending_complex,
+                                                     offset, &bridges, &graph)?;
+        let descending_complex = MorseComplex::stitch(&first.descending_complex, &second.descending_complex,
+                                                      offset, &bridges, &graph)?;
+        Ok((graph, MorseSmaleComplex{ascending_complex, descending_complex}))
+    }
+}
+
+// The state of a node of either complex, translated into the joined graph's indices
+struct SideData {
+    ancestor: NodeIndex,
+    merge_parent: Option<NodeIndex>,
+    merge_node: Option<NodeIndex>
+}
+
+impl MorseComplex {
+    fn stitch<N: ScalarValued + Clone>(first: &MorseComplex, second: &MorseComplex, offset: usize, bridges: &[(NodeIndex, NodeIndex)],
+                 graph: &UnGraph<N, f64>) -> Result<MorseComplex, MorseError> {
+        let kind = first.kind;
+        let value = |node: NodeIndex| match graph.node_weight(node) {
+            None => Err(MorseError::MissingNode{node}),
+            Some(weight) => Ok(weight.value())
+        };
+        let shift = |node: NodeIndex| NodeIndex::new(node.index() + offset);
+
+        // Merging the two orders gives exactly the order a fresh sort would: ties are broken by
+        // index, and every node of the first graph has a lower index than every node of the
+        // second
+        let mut order: Vec<NodeIndex> = Vec::with_capacity(first.ordered_points.len() + second.ordered_points.len());
+        let mut side: HashMap<NodeIndex, SideData> = HashMap::with_capacity(order.capacity());
+        let (mut i, mut j) = (0, 0);
+        while i < first.ordered_points.len() || j < second.ordered_points.len() {
+            let take_second = if i == first.ordered_points.len() {
+                true
+            } else if j == second.ordered_points.len() {
+                false
+            } else {
+                let a = value(first.ordered_points[i].node)?;
+                let b = value(shift(second.ordered_points[j].node))?;
+                match kind {
+                    MorseKind::Descending => b > a,
+                    MorseKind::Ascending => b < a
+                }
+            };
+            let (point, translate): (&MorseNode, &dyn Fn(NodeIndex) -> NodeIndex) = if take_second {
+                j += 1;
+                (&second.ordered_points[j - 1], &shift)
+            } else {
+                i += 1;
+                (&first.ordered_points[i - 1], &|node| node)
+            };
+            let data = match point.data.as_ref() {
+                None => return Err(MorseError::MissingData{node: point.node}),
+                Some(data) => data
+            };
+            let node = translate(point.node);
+            order.push(node);
+            side.insert(node, SideData{ancestor: translate(data.ancestor),
+                merge_parent: data.merge_parent.map(translate),
+                merge_node: data.merge_node.map(translate)});
+        }
+        let position: HashMap<NodeIndex, usize> = order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let pos = |node: NodeIndex| match position.get(&node) {
+            None => Err(MorseError::MissingNode{node}),
+            Some(&p) => Ok(p)
+        };
+
+        // The skeleton: every extremum, every merge node, and every bridge endpoint
+        let is_extremum = |node: NodeIndex| side[&node].ancestor == node;
+        let mut skeleton: Vec<NodeIndex> = order.iter().copied()
+            .filter(|&node| is_extremum(node))
+            .collect();
+        skeleton.extend(skeleton.clone().iter().filter_map(|node| side[node].merge_node));
+        for &(a, b) in bridges {
+            skeleton.push(a);
+            skeleton.push(b);
+        }
+        let mut skeleton_positions = skeleton.iter().map(|&n| pos(n)).collect::<Result<Vec<usize>, MorseError>>()?;
+        skeleton_positions.sort_unstable();
+        skeleton_positions.dedup();
+        let skeleton_index: HashMap<usize, usize> = skeleton_positions.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+
+        // A cell is alive (as a branch of the merge tree) until its merge node is processed
+        let death = |extremum: NodeIndex| -> Result<usize, MorseError> {
+            match side[&extremum].merge_node {
+                None => Ok(usize::MAX),
+                Some(merge_node) => pos(merge_node)
+            }
+        };
+        let mut branches: HashMap<NodeIndex, Vec<usize>> = HashMap::new();
+        for &p in &skeleton_positions {
+            let node = order[p];
+            let mut branch = side[&node].ancestor;
+            while death(branch)? <= p {
+                branch = match side[&branch].merge_parent {
+                    None => return Err(MorseError::MissingData{node: branch}),
+                    Some(parent) => parent
+                };
+            }
+            branches.entry(branch).or_insert_with(Vec::new).push(p);
+        }
+
+        let mut neighbors: Vec<Vec<usize>> = vec![vec![]; skeleton_positions.len()];
+        let mut connect = |a: usize, b: usize| {
+            neighbors[skeleton_index[&a]].push(b);
+            neighbors[skeleton_index[&b]].push(a);
+        };
+        for (extremum, members) in &branches {
+            // members were pushed in order, so the branch runs downward from its extremum
+            for pair in members.windows(2) {
+                connect(pair[0], pair[1]);
+            }
+            if let Some(merge_node) = side[extremum].merge_node {
+                connect(members[members.len() - 1], pos(merge_node)?);
+            }
+        }
+        for &(a, b) in bridges {
+            connect(pos(a)?, pos(b)?);
+        }
+
+        // The sweep itself, over the skeleton only. Each component is represented by its
+        // extremum.
+        let mut components = PointedUnionFind::new(skeleton_positions.len());
+        let mut new_data: HashMap<usize, MorseData> = HashMap::new();
+        let mut flows_to: HashMap<usize, usize> = HashMap::new();
+        for (s, &p) in skeleton_positions.iter().enumerate() {
+            let higher: Vec<usize> = neighbors[s].iter().copied().filter(|&q| q < p).collect();
+            if higher.is_empty() {
+                new_data.insert(p, MorseData{lifetime: f64::INFINITY, merge_parent: None, merge_node: None,
+                    ancestor: order[p]});
+                continue;
+            }
+            if is_extremum(order[p]) {
+                // a bridge cancelled this extremum, so its cell now flows into the highest neighbor
+                flows_to.insert(p, *higher.iter().min().unwrap_or(&p));
+            }
+            // skeleton indices are in sweep order, so the smallest extremum is the oldest
+            let mut extrema: Vec<usize> = higher.iter().map(|&q| components.find_mut(skeleton_index[&q])).collect();
+            extrema.sort_unstable();
+            extrema.dedup();
+            let survivor = extrema[0];
+            let joining_value = value(order[p])?;
+            for &dying in &extrema[1..] {
+                let dying_position = skeleton_positions[dying];
+                let lifetime = (value(order[dying_position])? - joining_value).abs();
+                new_data.insert(dying_position, MorseData{lifetime, ancestor: order[dying_position],
+                    merge_parent: Some(order[skeleton_positions[survivor]]), merge_node: Some(order[p])});
+                components.union(survivor, dying);
+            }
+            components.union(survivor, s);
+        }
+
+        // Resolve the cell of every node, following cancelled extrema to where they now flow
+        let mut resolved: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        let mut ordered_points = Vec::with_capacity(order.len());
+        for (p, &node) in order.iter().enumerate() {
+            let data = match new_data.remove(&p) {
+                Some(data) => data,
+                None => {
+                    let target = match flows_to.get(&p) {
+                        Some(&q) if !is_extremum(order[q]) => side[&order[q]].ancestor,
+                        Some(&q) => order[q],
+                        None => side[&node].ancestor
+                    };
+                    // the target comes earlier in the order, so it has already been resolved
+                    let ancestor = match resolved.get(&target) {
+                        Some(&ancestor) => ancestor,
+                        None => target
+                    };
+                    MorseData{lifetime: 0., merge_parent: None, merge_node: None, ancestor}
+                }
+            };
+            if is_extremum(node) {
+                resolved.insert(node, data.ancestor);
+            }
+            ordered_points.push(MorseNode{node, data: Some(data)});
+        }
+
+        let mut cells = PointedUnionFind::new(ordered_points.len());
+        for p in 0..ordered_points.len() {
+            let mut root = ordered_points[p].data.as_ref().map(|data| data.ancestor).unwrap_or(order[p]);
+            while let Some(parent) = ordered_points[position[&root]].data.as_ref().and_then(|data| data.merge_parent) {
+                root = parent;
+            }
+            cells.union(position[&root], p);
+        }
+
+        let mut complex = MorseComplex{ordered_points, cells, filtration: vec![], kind, options: MorseOptions::default(),
+            positions: position};
+        complex.filtration = complex.compute_filtration(graph)?;
+        Ok(complex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::path_graph;
+
+    fn assert_same_persistence(stitched: &MorseComplex, fresh: &MorseComplex) {
+        let stitched = stitched.get_persistence();
+        let fresh = fresh.get_persistence();
+        assert_eq!(stitched.len(), fresh.len());
+        for (node, lifetime) in fresh {
+            assert_eq!(stitched[&node], lifetime, "lifetime of {:?}", node);
+        }
+    }
+
+    #[test]
+    fn test_stitch() {
+        let first_graph = path_graph(&[0., 5., 1., 3., 2.]);
+        let second_graph = path_graph(&[4., 0.5, 6., 1.5, 2.5]);
+        let first = MorseSmaleComplex::from_graph(&first_graph).unwrap();
+        let second = MorseSmaleComplex::from_graph(&second_graph).unwrap();
+        let bridges = [(NodeIndex::new(4), NodeIndex::new(0), 1.), (NodeIndex::new(0), NodeIndex::new(4), 1.)];
+
+        let (graph, stitched) = MorseSmaleComplex::stitch(&first_graph, &first, &second_graph, &second, &bridges).unwrap();
+        let fresh = MorseSmaleComplex::from_graph(&graph).unwrap();
+        assert_same_persistence(&stitched.descending_complex, &fresh.descending_complex);
+        assert_same_persistence(&stitched.ascending_complex, &fresh.ascending_complex);
+        assert_eq!(stitched.descending_complex.filtration.len(), fresh.descending_complex.filtration.len());
+
+        // away from the bridges, the cells match as well
+        let cells = stitched.descending_complex.get_complex();
+        let fresh_cells = fresh.descending_complex.get_complex();
+        for node in graph.node_indices() {
+            if bridges.iter().all(|&(a, b, _)| node != a && node.index() != b.index() + 5) {
+                assert_eq!(cells[&node], fresh_cells[&node], "cell of {:?}", node);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stitch_cancels_extremum() {
+        let first_graph = path_graph(&[0., 1., 2.]);
+        let second_graph = path_graph(&[5., 4., 3.]);
+        let first = MorseSmaleComplex::from_graph(&first_graph).unwrap();
+        let second = MorseSmaleComplex::from_graph(&second_graph).unwrap();
+        let bridges = [(NodeIndex::new(2), NodeIndex::new(2), 1.)];
+
+        let (graph, stitched) = MorseSmaleComplex::stitch(&first_graph, &first, &second_graph, &second, &bridges).unwrap();
+        let fresh = MorseSmaleComplex::from_graph(&graph).unwrap();
+        assert_same_persistence(&stitched.descending_complex, &fresh.descending_complex);
+        assert_same_persistence(&stitched.ascending_complex, &fresh.ascending_complex);
+        let cells = stitched.descending_complex.get_complex();
+        for node in graph.node_indices() {
+            assert_eq!(cells[&node], NodeIndex::new(3));
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Checking a graph for problems before building a complex from it.
+//!
+//! Construction stops at the first problem it runs into, so a graph with several problems has to
+//! be fixed one error at a time. Validating first reports all of them at once. (A graph can't be
+//! missing the weight of one of its own nodes or edges; those errors come from querying a complex
+//! with a different graph than the one it was built from.)
+use petgraph::graph::{Graph, NodeIndex, EdgeIndex};
+use petgraph::EdgeType;
+
+use crate::ScalarValued;
+use crate::util::PointedUnionFind;
+
+/// The problems found in a graph by [validate_graph](fn.validate_graph.html).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GraphDiagnostics {
+    /// The nodes whose value is NaN, which construction rejects.
+    pub nan_values: Vec<NodeIndex>,
+
+    /// The edges whose weight is NaN, infinite, or not positive. Steepness is measured by dividing
+    /// by the weight, so these make the choice of steepest neighbor meaningless.
+    pub invalid_weights: Vec<EdgeIndex>,
+
+    /// The nodes with no edges at all, each of which is its own extremum in both complexes.
+    pub isolated_nodes: Vec<NodeIndex>,
+
+    /// The number of connected components of the graph. Each has its own global extrema with
+    /// infinite persistence.
+    pub components: usize
+}
+
+impl GraphDiagnostics {
+    /// Whether the graph is fit to build a complex from, i.e. there are no NaN values or invalid
+    /// weights. Isolated nodes and disconnection are allowed, though they may not be intended.
+    ///
+    /// This is advisory: construction only fails on NaN values. It accepts invalid weights, but
+    /// the cells it builds with them can't be trusted.
+    pub fn is_valid(&self) -> bool {
+        self.nan_values.is_empty() && self.invalid_weights.is_empty()
+    }
+
+    /// Whether there are no problems at all, including isolated nodes and disconnection.
+    pub fn is_clean(&self) -> bool {
+        self.is_valid() && self.isolated_nodes.is_empty() && self.components <= 1
+    }
+}
+
+/// Checks `graph` for everything that can make constructing a complex from it fail or give
+/// surprising results, returning all of the problems found, in order of index.
+///
+/// Edges are followed in both directions when counting components, even in a directed graph.
+pub fn validate_graph<N: ScalarValued, Ty: EdgeType>(graph: &Graph<N, f64, Ty>) -> GraphDiagnostics {
+    let nan_values = graph.node_indices().filter(|&node| graph[node].value().is_nan()).collect();
+    let invalid_weights = graph.edge_indices()
+        .filter(|&edge| {
+            let weight = graph[edge];
+            !weight.is_finite() || weight <= 0.
+        })
+        .collect();
+    let isolated_nodes = graph.node_indices()
+        .filter(|&node| graph.neighbors_undirected(node).next().is_none())
+        .collect();
+
+    let mut components = PointedUnionFind::new(graph.node_count());
+    let mut count = graph.node_count();
+    for edge in graph.raw_edges() {
+        if components.union(edge.source().index(), edge.target().index()) {
+            count -= 1;
+        }
+    }
+    GraphDiagnostics{nan_values, invalid_weights, isolated_nodes, components: count}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LabeledPoint;
+    use crate::morse::MorseSmaleComplex;
+    use petgraph::graph::UnGraph;
+
+    #[test]
+    fn test_validate_graph() {
+        let mut graph = UnGraph::new_undirected();
+        let nodes: Vec<_> = [1., f64::NAN, 2., 3., 4.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: ()}))
+            .collect();
+        graph.add_edge(nodes[0], nodes[1], 1.);
+        let zero = graph.add_edge(nodes[1], nodes[2], 0.);
+        graph.add_edge(nodes[3], nodes[4], 2.);
+        let infinite = graph.add_edge(nodes[3], nodes[4], f64::INFINITY);
+
+        let diagnostics = validate_graph(&graph);
+        assert_eq!(diagnostics.nan_values, vec![nodes[1]]);
+        assert_eq!(diagnostics.invalid_weights, vec![zero, infinite]);
+        assert!(diagnostics.isolated_nodes.is_empty());
+        assert_eq!(diagnostics.components, 2);
+        assert!(!diagnostics.is_valid());
+
+        let mut graph = UnGraph::<LabeledPoint<()>, f64>::new_undirected();
+        let a = graph.add_node(LabeledPoint{id: 0, value: 0., point: ()});
+        let b = graph.add_node(LabeledPoint{id: 1, value: 1., point: ()});
+        let diagnostics = validate_graph(&graph);
+        assert_eq!(diagnostics.isolated_nodes, vec![a, b]);
+        assert!(diagnostics.is_valid() && !diagnostics.is_clean());
+        graph.add_edge(a, b, 1.);
+        assert!(validate_graph(&graph).is_clean());
+        // invalid weights are flagged, but don't stop construction
+        graph.add_edge(a, b, 0.);
+        assert!(!validate_graph(&graph).is_valid());
+        assert!(MorseSmaleComplex::from_graph(&graph).is_ok());
+        assert_eq!(validate_graph(&UnGraph::<LabeledPoint<()>, f64>::new_undirected()), GraphDiagnostics::default());
+    }
+}
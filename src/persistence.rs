@@ -0,0 +1,1018 @@
+//! Persistence diagrams, the summary of a function's topology that is compared across functions.
+//!
+//! A diagram has a point for each extremum of a [MorseComplex](../morse/struct.MorseComplex.html),
+//! at the value where its cell was born and the value where it died. The
+//! [distances](../distance/index.html) and [statistics](../stats/index.html) take their inputs as
+//! plain slices, which [pairs](struct.PersistenceDiagram.html#method.pairs) and
+//! [lifetimes](struct.PersistenceDiagram.html#method.lifetimes) produce.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::EdgeType;
+use thiserror::Error;
+
+use crate::ScalarValued;
+use crate::morse::{MorseComplex, MorseSmaleComplex, MorseKind, MorseError};
+use crate::util::PointedUnionFind;
+use crate::distance::{bottleneck_distance, bottleneck_matching, wasserstein_distance, wasserstein_matching, sliced_wasserstein_kernel, DistanceError};
+use crate::stats;
+
+/// The ways a [persistence image](struct.PersistenceDiagram.html#method.persistence_image) can
+/// be misconfigured.
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("An image needs at least one pixel in each direction, got {columns:?} by {rows:?}")]
+    EmptyResolution {columns: usize, rows: usize},
+
+    #[error("An image range must be finite and increasing, got {range:?}")]
+    InvalidRange {range: (f64, f64)},
+
+    #[error("The spread of each point must be positive and finite, got {sigma:?}")]
+    InvalidSigma {sigma: f64}
+}
+
+/// A point of a persistence diagram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiagramPoint {
+    /// The value at which the feature appears.
+    pub birth: f64,
+
+    /// The value at which the feature disappears, infinite for the features that never do.
+    pub death: f64,
+
+    /// A node that stands for the feature, e.g. the extremum whose cell it is.
+    pub representative: NodeIndex
+}
+
+impl DiagramPoint {
+    /// How long the feature lasts, the distance between its birth and its death.
+    pub fn persistence(&self) -> f64 {
+        (self.death - self.birth).abs()
+    }
+
+    /// Whether the feature never disappears.
+    pub fn is_essential(&self) -> bool {
+        self.death.is_infinite()
+    }
+}
+
+/// A persistence diagram: the birth and death of each topological feature of a function.
+///
+/// With the `serialize` feature, diagrams can be serialized and deserialized with serde.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct PersistenceDiagram {
+    points: Vec<DiagramPoint>
+}
+
+impl PersistenceDiagram {
+    /// Creates a diagram with the given points.
+    pub fn new(points: Vec<DiagramPoint>) -> PersistenceDiagram {
+        PersistenceDiagram{points}
+    }
+
+    /// Creates the diagram of a complex, with a point for each extremum, represented by the
+    /// extremum itself, in the order the sweep reached them.
+    ///
+    /// Points are born at the value of their extremum and die at the value of the saddle that
+    /// destroyed its cell, whatever [measure](../morse/enum.PersistenceMeasure.html) of
+    /// persistence the complex was built with. Maxima that are never destroyed die at `-∞`, and
+    /// minima at `+∞`. Returns an error if a node of the complex is missing from `graph`.
+    pub fn from_complex<N: ScalarValued, Ty: EdgeType>(complex: &MorseComplex, graph: &Graph<N, f64, Ty>)
+        -> Result<PersistenceDiagram, MorseError> {
+        let points = complex.get_persistence_pairs(graph)?.into_iter()
+            .map(|pair| DiagramPoint{birth: pair.birth, death: pair.death, representative: pair.extremum})
+            .collect();
+        Ok(PersistenceDiagram{points})
+    }
+
+    /// Creates the diagram of one of the complexes of `complex`, chosen by `kind`, with extended
+    /// persistence for the global extrema, so that every point is finite.
+    ///
+    /// In the ordinary diagram, the global maximum of each connected component never dies. Here
+    /// it dies at the global minimum of the same component (and the global minimum at the global
+    /// maximum, for the ascending complex), so its persistence is the range of the function over
+    /// the component. The other points are the same as in
+    /// [from_complex](#method.from_complex). Distances between such diagrams are finite even when
+    /// the functions have different numbers of components.
+    pub fn extended<N: ScalarValued, Ty: EdgeType>(complex: &MorseSmaleComplex, kind: MorseKind, graph: &Graph<N, f64, Ty>)
+        -> Result<PersistenceDiagram, MorseError> {
+        let (this, other) = match kind {
+            MorseKind::Descending => (&complex.descending_complex, &complex.ascending_complex),
+            MorseKind::Ascending => (&complex.ascending_complex, &complex.descending_complex)
+        };
+        // Once fully simplified, this complex has one cell per component, and the global extremum
+        // of the other complex lies in it
+        let components = this.simplify(f64::INFINITY);
+        let mut opposite = HashMap::new();
+        for (extremum, persistence) in other.extrema() {
+            if persistence.is_infinite() {
+                let root = components.extremum_of(extremum).ok_or(MorseError::MissingData{node: extremum})?;
+                opposite.insert(root, extremum);
+            }
+        }
+        let value = |node: NodeIndex| graph.node_weight(node).map(|w| w.value()).ok_or(MorseError::MissingNode{node});
+        let points = this.get_persistence_pairs(graph)?.into_iter()
+            .map(|pair| {
+                let death = match pair.saddle {
+                    Some(_) => pair.death,
+                    None => value(*opposite.get(&pair.extremum).ok_or(MorseError::MissingData{node: pair.extremum})?)?
+                };
+                Ok(DiagramPoint{birth: pair.birth, death, representative: pair.extremum})
+            })
+            .collect::<Result<Vec<_>, MorseError>>()?;
+        Ok(PersistenceDiagram{points})
+    }
+
+    /// Writes the diagram to `writer` as CSV, with a header and then one row per point: its
+    /// birth, death, persistence and the index of its representative node.
+    ///
+    /// Infinite deaths are written as `inf` and `-inf`, which pandas and R both read back as
+    /// infinities.
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        self.write_rows(writer, |point| Ok(point.representative.index() as i64))
+    }
+
+    /// Writes the diagram to `writer` as CSV, like [write_csv](#method.write_csv), but with the
+    /// [id](../trait.ScalarValued.html#method.id) of each representative node in `graph` rather
+    /// than its index, for nodes that have one.
+    ///
+    /// Returns an error if a representative node is missing from `graph`.
+    pub fn write_csv_with<W: Write, N: ScalarValued, Ty: EdgeType>(&self, writer: W, graph: &Graph<N, f64, Ty>)
+        -> Result<(), Box<dyn Error>> {
+        self.write_rows(writer, |point| {
+            let node = point.representative;
+            let weight = graph.node_weight(node).ok_or(MorseError::MissingNode{node})?;
+            Ok(weight.id().unwrap_or(node.index() as i64))
+        })
+    }
+
+    fn write_rows<W, F>(&self, writer: W, id: F) -> Result<(), Box<dyn Error>>
+        where W: Write, F: Fn(&DiagramPoint) -> Result<i64, MorseError> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["birth", "death", "persistence", "node"])?;
+        for point in self.points.iter() {
+            writer.write_record(&[
+                point.birth.to_string(), point.death.to_string(), point.persistence().to_string(), id(point)?.to_string()
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// The points of the diagram.
+    pub fn points(&self) -> &[DiagramPoint] {
+        &self.points
+    }
+
+    /// The number of points in the diagram.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Whether the diagram has no points.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The `(birth, death)` pair of each point, as taken by the functions in
+    /// [distance](../distance/index.html).
+    pub fn pairs(&self) -> Vec<(f64, f64)> {
+        self.points.iter().map(|point| (point.birth, point.death)).collect()
+    }
+
+    /// The persistence of each point, as taken by the functions in [stats](../stats/index.html).
+    pub fn lifetimes(&self) -> Vec<f64> {
+        self.points.iter().map(|point| point.persistence()).collect()
+    }
+
+    /// The [persistent entropy](../stats/fn.persistent_entropy.html) of the diagram, ignoring
+    /// its essential points.
+    pub fn persistent_entropy(&self) -> f64 {
+        stats::persistent_entropy(&self.lifetimes())
+    }
+
+    /// The [summaries](../stats/fn.summarize.html) of the persistence of the points, counting the
+    /// points with persistence at or above `threshold`.
+    pub fn summary(&self, threshold: f64) -> stats::PersistenceSummary {
+        stats::summarize(&self.lifetimes(), threshold)
+    }
+
+    /// The persistent entropy of the diagram as a fraction of its largest possible value, as in
+    /// [normalized_persistent_entropy](../stats/fn.normalized_persistent_entropy.html).
+    pub fn normalized_persistent_entropy(&self) -> f64 {
+        stats::normalized_persistent_entropy(&self.lifetimes())
+    }
+
+    /// The [bottleneck distance](../distance/fn.bottleneck_distance.html) between this diagram and
+    /// `other`.
+    pub fn bottleneck_distance(&self, other: &PersistenceDiagram) -> Result<f64, DistanceError> {
+        bottleneck_distance(&self.pairs(), &other.pairs())
+    }
+
+    /// The exact [`p`-Wasserstein distance](../distance/fn.wasserstein_distance.html) between this
+    /// diagram and `other`, with points compared in the `q`-norm.
+    pub fn wasserstein_distance(&self, other: &PersistenceDiagram, p: f64, q: f64) -> Result<f64, DistanceError> {
+        wasserstein_distance(&self.pairs(), &other.pairs(), p, q)
+    }
+
+    /// A matching of the points of this diagram and `other` that is optimal for the [bottleneck
+    /// distance](../distance/fn.bottleneck_matching.html), showing which features correspond and
+    /// which have no counterpart.
+    pub fn bottleneck_matching(&self, other: &PersistenceDiagram) -> Result<DiagramMatching, DistanceError> {
+        let pairs = bottleneck_matching(&self.pairs(), &other.pairs())?;
+        Ok(DiagramMatching::from_pairs(self, other, &pairs))
+    }
+
+    /// A matching of the points of this diagram and `other` that is optimal for the [`p`-Wasserstein
+    /// distance](../distance/fn.wasserstein_matching.html), with points compared in the `q`-norm.
+    pub fn wasserstein_matching(&self, other: &PersistenceDiagram, p: f64, q: f64) -> Result<DiagramMatching, DistanceError> {
+        let pairs = wasserstein_matching(&self.pairs(), &other.pairs(), p, q)?;
+        Ok(DiagramMatching::from_pairs(self, other, &pairs))
+    }
+
+    /// The [sliced Wasserstein kernel](../distance/fn.sliced_wasserstein_kernel.html) between this
+    /// diagram and `other`, with `directions` lines and bandwidth `sigma`.
+    pub fn sliced_wasserstein_kernel(&self, other: &PersistenceDiagram, directions: usize, sigma: f64) -> Result<f64, DistanceError> {
+        sliced_wasserstein_kernel(&self.pairs(), &other.pairs(), directions, sigma)
+    }
+
+    /// Rasterizes the diagram into a persistence image, a grid of `resolution.0` by
+    /// `resolution.1` pixels, as a flat vector in row-major order.
+    ///
+    /// Each finite point is moved to `(birth, persistence)` and spread out into a Gaussian with
+    /// standard deviation `sigma`, weighted by its persistence as a fraction of the top of
+    /// `persistence_range`, so that points near the diagonal fade away. The image covers
+    /// `birth_range` along its columns and `persistence_range` along its rows, from the lowest
+    /// persistence up. Each pixel holds the density at its center times its area, so a point well
+    /// inside the image contributes about its weight to the total. Diagrams to be compared must be
+    /// rasterized over the same ranges, so that their pixels line up.
+    ///
+    /// It is an error for either dimension of `resolution` to be 0, for either range to be empty,
+    /// reversed or infinite, for the top of `persistence_range` not to be positive, or for `sigma`
+    /// not to be positive and finite.
+    pub fn persistence_image(&self, birth_range: (f64, f64), persistence_range: (f64, f64), resolution: (usize, usize),
+                             sigma: f64) -> Result<Vec<f64>, ImageError> {
+        let top = persistence_range.1;
+        if top.is_nan() || top <= 0. {
+            return Err(ImageError::InvalidRange{range: persistence_range});
+        }
+        self.persistence_image_with(birth_range, persistence_range, resolution, sigma, |persistence| (persistence / top).clamp(0., 1.))
+    }
+
+    /// Rasterizes the diagram as in [persistence_image](#method.persistence_image), weighting each
+    /// point by `weight` applied to its persistence. The arguments are checked in the same way,
+    /// except that `persistence_range` may end at or below 0.
+    pub fn persistence_image_with<F>(&self, birth_range: (f64, f64), persistence_range: (f64, f64), resolution: (usize, usize),
+                                     sigma: f64, weight: F) -> Result<Vec<f64>, ImageError>
+        where F: Fn(f64) -> f64 {
+        let (columns, rows) = resolution;
+        if columns == 0 || rows == 0 {
+            return Err(ImageError::EmptyResolution{columns, rows});
+        }
+        for &range in &[birth_range, persistence_range] {
+            if !range.0.is_finite() || !range.1.is_finite() || range.0 >= range.1 {
+                return Err(ImageError::InvalidRange{range});
+            }
+        }
+        if !sigma.is_finite() || sigma <= 0. {
+            return Err(ImageError::InvalidSigma{sigma});
+        }
+        let pixel_width = (birth_range.1 - birth_range.0) / columns as f64;
+        let pixel_height = (persistence_range.1 - persistence_range.0) / rows as f64;
+        let scale = pixel_width * pixel_height / (2. * std::f64::consts::PI * sigma * sigma);
+        let mut image = vec![0.; columns * rows];
+        for point in self.points.iter().filter(|point| !point.is_essential()) {
+            let (x, y) = (point.birth, point.persistence());
+            let height = weight(y) * scale;
+            for row in 0..rows {
+                let center_y = persistence_range.0 + (row as f64 + 0.5) * pixel_height;
+                for column in 0..columns {
+                    let center_x = birth_range.0 + (column as f64 + 0.5) * pixel_width;
+                    let squared = (center_x - x).powi(2) + (center_y - y).powi(2);
+                    image[row * columns + column] += height * (-squared / (2. * sigma * sigma)).exp();
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// The rank function of the filtration on `grid`: the number of points alive at both
+    /// `grid[i]` and `grid[j]`, at row `i` and column `j`.
+    ///
+    /// A point is alive from its birth up to its death, in the direction of the sweep, as in
+    /// [Barcode::alive_at](struct.Barcode.html#method.alive_at). Since a point is alive over an
+    /// interval, this counts the components of the level set at the earlier value in the sweep
+    /// that are still separate components at the later one, i.e. the rank of the map between
+    /// their 0th homology groups. The diagonal is the Betti-0 curve. The matrix is symmetric, and
+    /// the grid can be in any order.
+    pub fn rank_function(&self, grid: &[f64]) -> Vec<Vec<usize>> {
+        let mut ranks = vec![vec![0; grid.len()]; grid.len()];
+        for point in self.points.iter() {
+            let alive: Vec<usize> = (0..grid.len())
+                .filter(|&i| if point.birth <= point.death {
+                    point.birth <= grid[i] && grid[i] < point.death
+                } else {
+                    point.death < grid[i] && grid[i] <= point.birth
+                })
+                .collect();
+            for &i in alive.iter() {
+                for &j in alive.iter() {
+                    ranks[i][j] += 1;
+                }
+            }
+        }
+        ranks
+    }
+
+    /// The diagram with only the points for which `predicate` is true, in the same order and with
+    /// the same representatives.
+    pub fn filter<F: Fn(&DiagramPoint) -> bool>(&self, predicate: F) -> PersistenceDiagram {
+        PersistenceDiagram{points: self.points.iter().filter(|point| predicate(point)).cloned().collect()}
+    }
+
+    /// The diagram without its essential points, which have infinite persistence.
+    pub fn finite(&self) -> PersistenceDiagram {
+        self.filter(|point| !point.is_essential())
+    }
+
+    /// The diagram without the points with persistence below `threshold`.
+    pub fn above(&self, threshold: f64) -> PersistenceDiagram {
+        self.filter(|point| point.persistence() >= threshold)
+    }
+
+    /// The diagram with only the points whose persistence is in `range`, ends included.
+    pub fn persistence_in(&self, range: (f64, f64)) -> PersistenceDiagram {
+        self.filter(|point| contains(range, point.persistence()))
+    }
+
+    /// The diagram with only the points born in `range`, ends included.
+    pub fn born_in(&self, range: (f64, f64)) -> PersistenceDiagram {
+        self.filter(|point| contains(range, point.birth))
+    }
+
+    /// The diagram with only the points that die in `range`, ends included. The range can be
+    /// infinite at either end, to take in the essential points.
+    pub fn dying_in(&self, range: (f64, f64)) -> PersistenceDiagram {
+        self.filter(|point| contains(range, point.death))
+    }
+
+    /// The diagram with only the points in the rectangle of births in `birth_range` and deaths in
+    /// `death_range`, ends included.
+    pub fn within(&self, birth_range: (f64, f64), death_range: (f64, f64)) -> PersistenceDiagram {
+        self.filter(|point| contains(birth_range, point.birth) && contains(death_range, point.death))
+    }
+
+    /// The number of points in the rectangle of births in `birth_range` and deaths in
+    /// `death_range`, ends included. With one infinite end to each range, this counts the points
+    /// in a quadrant, e.g. `((f64::NEG_INFINITY, b), (d, f64::INFINITY))` counts the minima born
+    /// by `b` that are still alive at `d`.
+    pub fn count_within(&self, birth_range: (f64, f64), death_range: (f64, f64)) -> usize {
+        self.points.iter().filter(|point| contains(birth_range, point.birth) && contains(death_range, point.death)).count()
+    }
+}
+
+fn contains(range: (f64, f64), value: f64) -> bool {
+    range.0 <= value && value <= range.1
+}
+
+/// Computes the diagram of the minima of the function with the given `values` on the graph with
+/// the given `edges`, as `(i, j)` pairs of indices into `values`.
+///
+/// This is the same diagram as [from_complex](struct.PersistenceDiagram.html#method.from_complex)
+/// gives for an ascending complex built with the default options, with each point represented by
+/// the `NodeIndex` of its position in `values`, but without building the complex. Only the
+/// components of the sublevel sets are tracked, with a single union-find, so this is much faster
+/// when only the diagram is needed. Returns an error if any value is NaN or an edge refers to a
+/// node past the end of `values`.
+pub fn zero_dim_persistence(values: &[f64], edges: &[(usize, usize)]) -> Result<PersistenceDiagram, MorseError> {
+    zero_dim_persistence_with(values, edges, MorseKind::Ascending)
+}
+
+/// Computes the diagram of the minima, like [zero_dim_persistence](fn.zero_dim_persistence.html),
+/// or of the maxima, for `MorseKind::Descending`.
+pub fn zero_dim_persistence_with(values: &[f64], edges: &[(usize, usize)], kind: MorseKind) -> Result<PersistenceDiagram, MorseError> {
+    if let Some(node) = values.iter().position(|value| value.is_nan()) {
+        return Err(MorseError::NanValue{node: NodeIndex::new(node)});
+    }
+    let mut neighbors: Vec<Vec<usize>> = vec![vec![]; values.len()];
+    for &(i, j) in edges {
+        if let Some(&node) = [i, j].iter().find(|&&node| node >= values.len()) {
+            return Err(MorseError::MissingNode{node: NodeIndex::new(node)});
+        }
+        neighbors[i].push(j);
+        neighbors[j].push(i);
+    }
+    // the same order as the sweep of a complex, with ties broken by index
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| {
+        let by_value = match kind {
+            MorseKind::Ascending => values[a].partial_cmp(&values[b]),
+            MorseKind::Descending => values[b].partial_cmp(&values[a])
+        };
+        by_value.unwrap().then(a.cmp(&b))
+    });
+    let mut position = vec![0; values.len()];
+    for (p, &node) in order.iter().enumerate() {
+        position[node] = p;
+    }
+
+    // Each component is represented by its extremum, which is the first of its nodes in the
+    // sweep, and the younger of two merging components dies
+    let mut components = PointedUnionFind::new(values.len());
+    let mut deaths: Vec<Option<f64>> = vec![None; values.len()];
+    let mut extrema = vec![];
+    for &node in order.iter() {
+        let mut earlier: Vec<usize> = neighbors[node].iter()
+            .filter(|&&neighbor| position[neighbor] < position[node])
+            .map(|&neighbor| components.find_mut(neighbor))
+            .collect();
+        earlier.sort_by_key(|&extremum| position[extremum]);
+        earlier.dedup();
+        match earlier.split_first() {
+            None => extrema.push(node),
+            Some((&oldest, younger)) => {
+                for &extremum in younger {
+                    deaths[extremum] = Some(values[node]);
+                    components.union(oldest, extremum);
+                }
+                components.union(oldest, node);
+            }
+        }
+    }
+    let never = match kind {
+        MorseKind::Ascending => f64::INFINITY,
+        MorseKind::Descending => f64::NEG_INFINITY
+    };
+    let points = extrema.into_iter()
+        .map(|extremum| DiagramPoint{birth: values[extremum], death: deaths[extremum].unwrap_or(never), representative: NodeIndex::new(extremum)})
+        .collect();
+    Ok(PersistenceDiagram{points})
+}
+
+/// A matching of the points of two diagrams, as found by
+/// [bottleneck_matching](struct.PersistenceDiagram.html#method.bottleneck_matching) or
+/// [wasserstein_matching](struct.PersistenceDiagram.html#method.wasserstein_matching).
+///
+/// Every point is either matched to a point of the other diagram or to the diagonal, meaning the
+/// feature it stands for has no counterpart in the other function.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiagramMatching {
+    /// The pairs of a point of the first diagram and the point of the second it is matched to,
+    /// in the order of the first diagram.
+    pub matched: Vec<(DiagramPoint, DiagramPoint)>,
+
+    /// The points of the first diagram that are matched to the diagonal.
+    pub first_unmatched: Vec<DiagramPoint>,
+
+    /// The points of the second diagram that are matched to the diagonal.
+    pub second_unmatched: Vec<DiagramPoint>
+}
+
+impl DiagramMatching {
+    // Builds the matching from the `(i, j)` pairs of indices of matched points, in order of `i`
+    fn from_pairs(first: &PersistenceDiagram, second: &PersistenceDiagram, pairs: &[(usize, usize)]) -> DiagramMatching {
+        let mut first_matched = vec![false; first.len()];
+        let mut second_matched = vec![false; second.len()];
+        for &(i, j) in pairs {
+            first_matched[i] = true;
+            second_matched[j] = true;
+        }
+        let unmatched = |diagram: &PersistenceDiagram, matched: &[bool]| -> Vec<DiagramPoint> {
+            diagram.points.iter().zip(matched.iter()).filter(|&(_, &matched)| !matched).map(|(&point, _)| point).collect()
+        };
+        DiagramMatching{
+            matched: pairs.iter().map(|&(i, j)| (first.points[i], second.points[j])).collect(),
+            first_unmatched: unmatched(first, &first_matched),
+            second_unmatched: unmatched(second, &second_matched)
+        }
+    }
+
+    /// The largest distance in the L-infinity norm that a point is moved by the matching, either
+    /// to the point it is matched to or to the diagonal. For a bottleneck matching, this is the
+    /// bottleneck distance.
+    pub fn largest_move(&self) -> f64 {
+        let to_diagonal = |point: &DiagramPoint| point.persistence() / 2.;
+        let matched = self.matched.iter().map(|(a, b)| {
+            if a.is_essential() && b.is_essential() {
+                (a.birth - b.birth).abs()
+            } else {
+                (a.birth - b.birth).abs().max((a.death - b.death).abs())
+            }
+        });
+        matched
+            .chain(self.first_unmatched.iter().map(to_diagonal))
+            .chain(self.second_unmatched.iter().map(to_diagonal))
+            .fold(0., f64::max)
+    }
+}
+
+/// The bars of a persistence diagram, from the longest to the shortest.
+///
+/// This is the same information as a [PersistenceDiagram](struct.PersistenceDiagram.html),
+/// with each point as an interval from its birth to its death, represented by its extremum. Bars
+/// of equal length are in order of birth, so the longest bars come first whatever order the
+/// diagram was in.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Barcode {
+    bars: Vec<DiagramPoint>
+}
+
+impl Barcode {
+    /// Creates the barcode of a diagram.
+    pub fn from_diagram(diagram: &PersistenceDiagram) -> Barcode {
+        let mut bars = diagram.points.clone();
+        bars.sort_by(|a, b| b.persistence().partial_cmp(&a.persistence()).unwrap_or(Ordering::Equal)
+            .then(a.birth.partial_cmp(&b.birth).unwrap_or(Ordering::Equal)));
+        Barcode{bars}
+    }
+
+    /// The diagram with the same points, in the order of the bars.
+    pub fn to_diagram(&self) -> PersistenceDiagram {
+        PersistenceDiagram{points: self.bars.clone()}
+    }
+
+    /// The bars, from the longest to the shortest.
+    pub fn bars(&self) -> &[DiagramPoint] {
+        &self.bars
+    }
+
+    /// Iterates over the bars, from the longest to the shortest.
+    pub fn iter(&self) -> std::slice::Iter<'_, DiagramPoint> {
+        self.bars.iter()
+    }
+
+    /// The number of bars.
+    pub fn len(&self) -> usize {
+        self.bars.len()
+    }
+
+    /// Whether there are no bars.
+    pub fn is_empty(&self) -> bool {
+        self.bars.is_empty()
+    }
+
+    /// The barcode without the bars shorter than `threshold`, i.e. of the complex
+    /// [simplified](../morse/struct.MorseComplex.html#method.simplify) at `threshold`.
+    pub fn truncate(&self, threshold: f64) -> Barcode {
+        // sorted, so the bars to keep are a prefix
+        let kept = self.bars.iter().take_while(|bar| bar.persistence() >= threshold).count();
+        Barcode{bars: self.bars[..kept].to_vec()}
+    }
+
+    /// The bars alive at `value`: those born at or before it and dying after it, in the direction
+    /// of the sweep. Their number is the number of components of the level set at `value`.
+    pub fn alive_at(&self, value: f64) -> Vec<DiagramPoint> {
+        self.bars.iter()
+            .filter(|bar| if bar.birth <= bar.death {
+                bar.birth <= value && value < bar.death
+            } else {
+                bar.death < value && value <= bar.birth
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// The persistence landscape of a diagram, as exact piecewise linear functions.
+///
+/// Each finite point of the diagram, as an interval from `lo` to `hi` (its birth and death, in
+/// increasing order), becomes a tent that rises with slope 1 from `lo` and falls back to 0 at
+/// `hi`. The `k`th landscape function `λ_k(x)` is the `k`th largest of the tents at `x`. Unlike
+/// diagrams, landscapes live in a vector space, so they can be averaged and compared with the
+/// usual statistics. Essential points, whose tents would never come down, are left out.
+///
+/// Levels are numbered from 0, so level `k` is `λ_(k+1)`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PersistenceLandscape {
+    // the breakpoints of each level, in increasing order of x, starting and ending at 0
+    levels: Vec<Vec<(f64, f64)>>
+}
+
+impl PersistenceLandscape {
+    /// Computes the landscape of a diagram.
+    ///
+    /// Between any two of the points at which the tents start, peak, end or cross, every tent is
+    /// linear and their order doesn't change, so each level is found exactly by evaluating it at
+    /// those points. There can be `O(n²)` of them for `n` points.
+    pub fn from_diagram(diagram: &PersistenceDiagram) -> PersistenceLandscape {
+        let tents: Vec<(f64, f64)> = diagram.points.iter()
+            .filter(|point| !point.is_essential())
+            .map(|point| (point.birth.min(point.death), point.birth.max(point.death)))
+            .filter(|(lo, hi)| hi > lo)
+            .collect();
+        let mut xs = vec![];
+        for &(lo, hi) in tents.iter() {
+            xs.extend(&[lo, (lo + hi) / 2., hi]);
+            // where this tent's rising side crosses another's falling side
+            for &(other_lo, other_hi) in tents.iter() {
+                let crossing = (lo + other_hi) / 2.;
+                if lo < crossing && crossing < other_hi && crossing < (lo + hi) / 2. && crossing > (other_lo + other_hi) / 2. {
+                    xs.push(crossing);
+                }
+            }
+        }
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        xs.dedup();
+
+        let mut levels: Vec<Vec<(f64, f64)>> = vec![];
+        for &x in xs.iter() {
+            let mut heights: Vec<f64> = tents.iter()
+                .map(|&(lo, hi)| (x - lo).min(hi - x))
+                .filter(|&height| height > 0.)
+                .collect();
+            heights.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+            while levels.len() < heights.len() {
+                levels.push(vec![]);
+            }
+            for (k, level) in levels.iter_mut().enumerate() {
+                level.push((x, heights.get(k).cloned().unwrap_or(0.)));
+            }
+        }
+        // a level that starts late missed the zeros before it
+        for level in levels.iter_mut() {
+            *level = xs.iter().take(xs.len() - level.len()).map(|&x| (x, 0.)).chain(level.drain(..)).collect();
+        }
+        PersistenceLandscape{levels: levels.into_iter().map(simplify_level).collect()}
+    }
+
+    /// The average of several landscapes, which is again a landscape (though usually not that of
+    /// any diagram).
+    pub fn average(landscapes: &[PersistenceLandscape]) -> PersistenceLandscape {
+        let depth = landscapes.iter().map(|landscape| landscape.len()).max().unwrap_or(0);
+        let levels = (0..depth)
+            .map(|k| {
+                let mut xs: Vec<f64> = landscapes.iter().flat_map(|landscape| landscape.level(k).iter().map(|&(x, _)| x)).collect();
+                xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                xs.dedup();
+                let level = xs.into_iter()
+                    .map(|x| (x, landscapes.iter().map(|landscape| landscape.value(k, x)).sum::<f64>() / landscapes.len() as f64))
+                    .collect();
+                simplify_level(level)
+            })
+            .collect();
+        PersistenceLandscape{levels}
+    }
+
+    /// The number of levels, i.e. the largest number of points whose intervals overlap.
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Whether the landscape is 0 everywhere.
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// The breakpoints of level `k`, in increasing order of `x`, between which it is linear. It is
+    /// 0 outside of them, and empty if there is no such level.
+    pub fn level(&self, k: usize) -> &[(f64, f64)] {
+        self.levels.get(k).map(|level| level.as_slice()).unwrap_or(&[])
+    }
+
+    /// The value of level `k` at `x`.
+    pub fn value(&self, k: usize, x: f64) -> f64 {
+        let level = self.level(k);
+        let after = level.iter().take_while(|&&(breakpoint, _)| breakpoint <= x).count();
+        if after == 0 || after == level.len() {
+            return if after > 0 && level[after - 1].0 == x { level[after - 1].1 } else { 0. };
+        }
+        let ((x0, y0), (x1, y1)) = (level[after - 1], level[after]);
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+
+    /// Samples the first `levels` levels at `samples` evenly spaced points from `range.0` to
+    /// `range.1` inclusive, one vector per level, for use as a feature vector.
+    pub fn sample(&self, levels: usize, range: (f64, f64), samples: usize) -> Vec<Vec<f64>> {
+        let step = if samples > 1 { (range.1 - range.0) / (samples - 1) as f64 } else { 0. };
+        (0..levels)
+            .map(|k| (0..samples).map(|i| self.value(k, range.0 + i as f64 * step)).collect())
+            .collect()
+    }
+}
+
+// Removes the breakpoints of a level that lie on the line through their neighbors, and the runs
+// of zeros at either end
+fn simplify_level(level: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let mut simplified: Vec<(f64, f64)> = Vec::with_capacity(level.len());
+    for point in level {
+        while simplified.len() >= 2 {
+            let (a, b) = (simplified[simplified.len() - 2], simplified[simplified.len() - 1]);
+            if (b.1 - a.1) * (point.0 - b.0) == (point.1 - b.1) * (b.0 - a.0) {
+                simplified.pop();
+            } else {
+                break;
+            }
+        }
+        simplified.push(point);
+    }
+    let start = simplified.windows(2).take_while(|pair| pair[0].1 == 0. && pair[1].1 == 0.).count();
+    let end = simplified.len() - simplified.windows(2).rev().take_while(|pair| pair[0].1 == 0. && pair[1].1 == 0.).count();
+    if start >= end {
+        return vec![];
+    }
+    simplified[start..end].to_vec()
+}
+
+impl From<&PersistenceDiagram> for Barcode {
+    fn from(diagram: &PersistenceDiagram) -> Barcode {
+        Barcode::from_diagram(diagram)
+    }
+}
+
+impl From<&Barcode> for PersistenceDiagram {
+    fn from(barcode: &Barcode) -> PersistenceDiagram {
+        barcode.to_diagram()
+    }
+}
+
+impl<'a> IntoIterator for &'a Barcode {
+    type Item = &'a DiagramPoint;
+    type IntoIter = std::slice::Iter<'a, DiagramPoint>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bars.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::UnGraph;
+    use crate::LabeledPoint;
+    use crate::stats::total_persistence;
+    use crate::fixtures::{path_graph, path_graph_of};
+
+    #[test]
+    fn test_from_complex() {
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let diagram = PersistenceDiagram::from_complex(&complex, &graph).unwrap();
+        assert_eq!(diagram.pairs(), vec![(5., f64::NEG_INFINITY), (4., 2.), (1., 0.)]);
+        assert_eq!(diagram.points()[1].representative, nodes[2]);
+        assert_eq!(diagram.lifetimes(), vec![f64::INFINITY, 2., 1.]);
+        // the lifetimes agree with the complex's own persistence
+        let persistence = complex.get_persistence();
+        for point in diagram.points() {
+            assert_eq!(point.persistence(), persistence[&point.representative]);
+        }
+        assert_eq!(total_persistence(&diagram.lifetimes(), 1.), 3.);
+
+        let finite = diagram.finite();
+        assert_eq!(finite.len(), 2);
+        assert!(finite.points().iter().all(|point| !point.is_essential()));
+        assert_eq!(diagram.above(1.5).len(), 2);
+        assert!(PersistenceDiagram::default().is_empty());
+    }
+
+    #[test]
+    fn test_rank_function() {
+        let point = |birth, death| DiagramPoint{birth, death, representative: NodeIndex::new(0)};
+        let minima = PersistenceDiagram::new(vec![point(0., f64::INFINITY), point(1., 3.), point(2., 2.5)]);
+        let grid = [0.5, 1.5, 2.2, 2.7, 4.];
+        let ranks = minima.rank_function(&grid);
+        assert_eq!(ranks, vec![
+            vec![1, 1, 1, 1, 1],
+            vec![1, 2, 2, 2, 1],
+            vec![1, 2, 3, 2, 1],
+            vec![1, 2, 2, 2, 1],
+            vec![1, 1, 1, 1, 1]
+        ]);
+
+        // for maxima, the sweep runs downward, and the diagonal agrees with the barcode
+        let maxima = PersistenceDiagram::new(vec![point(5., f64::NEG_INFINITY), point(4., 2.), point(3., 1.)]);
+        let grid = [4.5, 3.5, 2.5, 1.5, 0.5];
+        let ranks = maxima.rank_function(&grid);
+        let barcode = Barcode::from_diagram(&maxima);
+        for (i, &value) in grid.iter().enumerate() {
+            assert_eq!(ranks[i][i], barcode.alive_at(value).len());
+        }
+        // the peak at 4 is alive at 3.5 and 2.5, but dies before 1.5
+        assert_eq!(ranks[1][2], 2);
+        assert_eq!(ranks[1][3], 1);
+        assert!(PersistenceDiagram::default().rank_function(&grid).iter().flatten().all(|&rank| rank == 0));
+    }
+
+    #[test]
+    fn test_filters() {
+        let point = |birth, death, node| DiagramPoint{birth, death, representative: NodeIndex::new(node)};
+        let diagram = PersistenceDiagram::new(vec![
+            point(0., f64::INFINITY, 0), point(1., 5., 3), point(2., 2.5, 4), point(4., 6., 7)
+        ]);
+        assert_eq!(diagram.persistence_in((1.5, 4.)).points(), &[point(1., 5., 3), point(4., 6., 7)][..]);
+        assert_eq!(diagram.persistence_in((2., f64::INFINITY)).len(), 3);
+        assert_eq!(diagram.born_in((1., 2.)).pairs(), vec![(1., 5.), (2., 2.5)]);
+        assert_eq!(diagram.dying_in((5., f64::INFINITY)).pairs(), vec![(0., f64::INFINITY), (1., 5.), (4., 6.)]);
+        let region = diagram.within((0.5, 4.), (2., 5.5));
+        assert_eq!(region.points(), &[point(1., 5., 3), point(2., 2.5, 4)][..]);
+        assert_eq!(diagram.count_within((0.5, 4.), (2., 5.5)), 2);
+        // the minima born by 1.5 that are still alive at 5
+        assert_eq!(diagram.count_within((f64::NEG_INFINITY, 1.5), (5., f64::INFINITY)), 2);
+        assert_eq!(diagram.filter(|point| point.representative.index() % 2 == 1).len(), 2);
+        assert!(diagram.born_in((10., 11.)).is_empty());
+    }
+
+    #[test]
+    fn test_persistent_entropy() {
+        let point = |birth, death| DiagramPoint{birth, death, representative: NodeIndex::new(0)};
+        let diagram = PersistenceDiagram::new(vec![point(5., f64::NEG_INFINITY), point(4., 2.), point(3., 1.), point(1., 1.)]);
+        assert!((diagram.persistent_entropy() - 2f64.ln()).abs() < 1e-12);
+        assert!((diagram.normalized_persistent_entropy() - 1.).abs() < 1e-12);
+        assert_eq!(PersistenceDiagram::default().persistent_entropy(), 0.);
+        let summary = diagram.summary(2.);
+        assert_eq!((summary.count, summary.essential, summary.above), (3, 1, 3));
+        assert_eq!(summary.total, 4.);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut graph = path_graph_of([1., 0., 4., 2., 5.].iter().enumerate()
+            .map(|(i, &value)| LabeledPoint{id: 10 * i as i64, value, point: ()}));
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let diagram = PersistenceDiagram::from_complex(&complex, &graph).unwrap();
+
+        let mut output = vec![];
+        diagram.write_csv(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "birth,death,persistence,node\n5,-inf,inf,4\n4,2,2,2\n1,0,1,0\n");
+        let mut output = vec![];
+        diagram.write_csv_with(&mut output, &graph).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "birth,death,persistence,node\n5,-inf,inf,40\n4,2,2,20\n1,0,1,0\n");
+
+        graph.remove_node(NodeIndex::new(4));
+        assert!(diagram.write_csv_with(vec![], &graph).is_err());
+    }
+
+    #[test]
+    fn test_zero_dim_persistence() {
+        // a path with minima at 0, 2 and 4, and a separate node with the same value as 2
+        let values = [0., 5., 1., 6., 2., 1.];
+        let edges = [(0, 1), (1, 2), (2, 3), (3, 4)];
+        let minima = zero_dim_persistence(&values, &edges).unwrap();
+        assert_eq!(minima.pairs(), vec![(0., f64::INFINITY), (1., 5.), (1., f64::INFINITY), (2., 6.)]);
+        assert_eq!(minima.points()[2].representative, NodeIndex::new(5));
+        let maxima = zero_dim_persistence_with(&values, &edges, MorseKind::Descending).unwrap();
+        assert_eq!(maxima.pairs(), vec![(6., f64::NEG_INFINITY), (5., 1.), (1., f64::NEG_INFINITY)]);
+
+        assert!(zero_dim_persistence(&[], &[]).unwrap().is_empty());
+        assert!(zero_dim_persistence(&values, &[(0, 6)]).is_err());
+        assert!(zero_dim_persistence(&[0., f64::NAN], &[(0, 1)]).is_err());
+    }
+
+    #[test]
+    fn test_extended() {
+        let mut graph = UnGraph::new_undirected();
+        // two components, with ranges 5 and 2
+        let nodes: Vec<_> = [1., 0., 4., 2., 5., 7., 9.].iter().enumerate()
+            .map(|(i, &value)| graph.add_node(LabeledPoint{id: i as i64, value, point: vec![i as f64]}))
+            .collect();
+        for i in 0..4 {
+            graph.add_edge(nodes[i], nodes[i + 1], 1.);
+        }
+        graph.add_edge(nodes[5], nodes[6], 1.);
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+
+        let descending = PersistenceDiagram::extended(&complex, MorseKind::Descending, &graph).unwrap();
+        let ordinary = PersistenceDiagram::from_complex(&complex.descending_complex, &graph).unwrap();
+        assert!(descending.points().iter().all(|point| !point.is_essential()));
+        assert_eq!(descending.len(), ordinary.len());
+        let mut lifetimes = descending.lifetimes();
+        lifetimes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(lifetimes, vec![1., 2., 2., 5.]);
+        for (point, original) in descending.points().iter().zip(ordinary.points()) {
+            if !original.is_essential() {
+                assert_eq!(point, original);
+            }
+        }
+
+        let ascending = PersistenceDiagram::extended(&complex, MorseKind::Ascending, &graph).unwrap();
+        let pairs = ascending.pairs();
+        assert!(pairs.contains(&(0., 5.)));
+        assert!(pairs.contains(&(7., 9.)));
+        assert!(descending.bottleneck_distance(&ascending).unwrap().is_finite());
+    }
+
+    #[test]
+    fn test_bottleneck_distance() {
+        let point = |birth, death, i| DiagramPoint{birth, death, representative: NodeIndex::new(i)};
+        let first = PersistenceDiagram::new(vec![point(5., f64::NEG_INFINITY, 0), point(4., 2., 1)]);
+        let second = PersistenceDiagram::new(vec![point(5.5, f64::NEG_INFINITY, 0), point(4., 1., 3), point(2., 1.5, 2)]);
+        assert_eq!(first.bottleneck_distance(&second).unwrap(), 1.);
+        assert_eq!(first.bottleneck_distance(&first).unwrap(), 0.);
+        assert_eq!(first.bottleneck_distance(&first.finite()).unwrap(), f64::INFINITY);
+        // 0.5 for the maxima, 1 for (4, 2) to (4, 1), and 0.25 for (2, 1.5) to the diagonal
+        assert_eq!(first.wasserstein_distance(&second, 1., f64::INFINITY).unwrap(), 1.75);
+        assert_eq!(first.sliced_wasserstein_kernel(&first, 50, 1.).unwrap(), 1.);
+    }
+
+    #[test]
+    fn test_diagram_matching() {
+        let point = |birth, death, i| DiagramPoint{birth, death, representative: NodeIndex::new(i)};
+        let first = PersistenceDiagram::new(vec![point(5., f64::NEG_INFINITY, 0), point(4., 2., 1)]);
+        let second = PersistenceDiagram::new(vec![point(5.5, f64::NEG_INFINITY, 0), point(4., 1., 3), point(2., 1.5, 2)]);
+        let matching = first.bottleneck_matching(&second).unwrap();
+        assert_eq!(matching.matched, vec![(first.points()[0], second.points()[0]), (first.points()[1], second.points()[1])]);
+        assert!(matching.first_unmatched.is_empty());
+        assert_eq!(matching.second_unmatched, vec![second.points()[2]]);
+        assert_eq!(matching.largest_move(), first.bottleneck_distance(&second).unwrap());
+        assert_eq!(first.wasserstein_matching(&second, 1., f64::INFINITY).unwrap(), matching);
+
+        // the essential point has nothing to go to, which makes the distance infinite
+        let matching = first.bottleneck_matching(&second.finite()).unwrap();
+        assert_eq!(matching.first_unmatched, vec![first.points()[0]]);
+        assert_eq!(matching.largest_move(), f64::INFINITY);
+        assert_eq!(first.bottleneck_matching(&first).unwrap().largest_move(), 0.);
+    }
+
+    #[test]
+    fn test_persistence_image() {
+        let point = |birth, death| DiagramPoint{birth, death, representative: NodeIndex::new(0)};
+        let diagram = PersistenceDiagram::new(vec![point(5., 3.), point(4., f64::NEG_INFINITY)]);
+        let image = diagram.persistence_image((0., 10.), (0., 4.), (40, 16), 0.5).unwrap();
+        assert_eq!(image.len(), 640);
+        // the point is at (5, 2) with weight 1/2, so the pixels around it hold all of that
+        let total: f64 = image.iter().sum();
+        assert!((total - 0.5).abs() < 1e-3);
+        let brightest = (0..image.len()).max_by(|&a, &b| image[a].partial_cmp(&image[b]).unwrap()).unwrap();
+        assert!([7 * 40 + 19, 7 * 40 + 20, 8 * 40 + 19, 8 * 40 + 20].contains(&brightest));
+
+        let flat = diagram.persistence_image_with((0., 10.), (0., 4.), (40, 16), 0.5, |_| 1.).unwrap();
+        assert!((flat.iter().sum::<f64>() - 1.).abs() < 1e-3);
+        assert!(PersistenceDiagram::default().persistence_image((0., 1.), (0., 1.), (3, 3), 0.1).unwrap().iter().all(|&p| p == 0.));
+
+        match diagram.persistence_image((0., 10.), (0., 4.), (0, 16), 0.5) {
+            Err(ImageError::EmptyResolution{columns: 0, rows: 16}) => (),
+            other => panic!("expected an empty resolution error, got {:?}", other)
+        }
+        for &range in &[(10., 0.), (1., 1.), (0., f64::INFINITY), (f64::NAN, 1.)] {
+            match diagram.persistence_image(range, (0., 4.), (40, 16), 0.5) {
+                Err(ImageError::InvalidRange{..}) => (),
+                other => panic!("expected an invalid range error, got {:?}", other)
+            }
+            match diagram.persistence_image_with((0., 10.), range, (40, 16), 0.5, |_| 1.) {
+                Err(ImageError::InvalidRange{..}) => (),
+                other => panic!("expected an invalid range error, got {:?}", other)
+            }
+        }
+        for &sigma in &[0., -0.5, f64::NAN] {
+            match diagram.persistence_image((0., 10.), (0., 4.), (40, 16), sigma) {
+                Err(ImageError::InvalidSigma{..}) => (),
+                other => panic!("expected an invalid sigma error, got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_barcode() {
+        let graph = path_graph(&[1., 0., 4., 2., 5., 3., 3.5]);
+        let nodes: Vec<_> = graph.node_indices().collect();
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let diagram = PersistenceDiagram::from_complex(&complex, &graph).unwrap();
+        let barcode = Barcode::from(&diagram);
+        let representatives: Vec<NodeIndex> = barcode.iter().map(|bar| bar.representative).collect();
+        assert_eq!(representatives, vec![nodes[4], nodes[2], nodes[0], nodes[6]]);
+        assert_eq!(barcode.len(), diagram.len());
+        assert_eq!(Barcode::from(&PersistenceDiagram::from(&barcode)), barcode);
+
+        // truncating agrees with simplifying
+        let truncated = barcode.truncate(1.);
+        assert_eq!(truncated.len(), 3);
+        assert_eq!(truncated.len(), complex.simplify(1.).extrema().len());
+        assert!(barcode.truncate(f64::INFINITY).bars().iter().all(|bar| bar.is_essential()));
+
+        // the bars alive at a value count the components of the superlevel set there
+        for &(value, count) in &[(5., 1), (4., 2), (3.5, 3), (3., 2), (2.5, 2), (1., 2), (0.5, 2), (0., 1)] {
+            assert_eq!(barcode.alive_at(value).len(), count, "at {}", value);
+        }
+        assert_eq!((&barcode).into_iter().count(), 4);
+    }
+
+    #[test]
+    fn test_persistence_landscape() {
+        let point = |birth, death| DiagramPoint{birth, death, representative: NodeIndex::new(0)};
+        // two crossing tents, and one nested inside of the first
+        let diagram = PersistenceDiagram::new(vec![point(0., 4.), point(6., 2.), point(1., 3.), point(0., f64::INFINITY)]);
+        let landscape = PersistenceLandscape::from_diagram(&diagram);
+        assert_eq!(landscape.len(), 3);
+        assert_eq!(landscape.level(0), &[(0., 0.), (2., 2.), (3., 1.), (4., 2.), (6., 0.)][..]);
+        assert_eq!(landscape.level(1), &[(1., 0.), (2., 1.), (2.5, 0.5), (3., 1.), (4., 0.)][..]);
+        assert_eq!(landscape.level(2), &[(2., 0.), (2.5, 0.5), (3., 0.)][..]);
+        assert!(landscape.level(3).is_empty());
+        assert_eq!(landscape.value(0, 1.5), 1.5);
+        assert_eq!(landscape.value(1, 3.5), 0.5);
+        assert_eq!(landscape.value(0, -1.), 0.);
+        assert_eq!(landscape.value(0, 6.), 0.);
+        assert_eq!(landscape.sample(2, (0., 6.), 7), vec![vec![0., 1., 2., 1., 2., 1., 0.], vec![0., 0., 1., 1., 0., 0., 0.]]);
+
+        // the average of a landscape with itself is itself, and with nothing is half of it
+        assert_eq!(PersistenceLandscape::average(&[landscape.clone(), landscape.clone()]), landscape);
+        let half = PersistenceLandscape::average(&[landscape.clone(), PersistenceLandscape::default()]);
+        assert_eq!(half.level(0), &[(0., 0.), (2., 1.), (3., 0.5), (4., 1.), (6., 0.)][..]);
+        assert_eq!(half.value(2, 2.5), 0.25);
+        assert!(PersistenceLandscape::from_diagram(&PersistenceDiagram::default()).is_empty());
+    }
+}
@@ -0,0 +1,537 @@
+//! Scalar summaries of persistence.
+//!
+//! These reduce the persistence values of a complex (e.g. the values of
+//! [get_persistence](../morse/struct.MorseComplex.html#method.get_persistence)) to a single
+//! number, for use as per-sample topological descriptors.
+//!
+//! Infinite persistence values (which belong to global extrema) can't be meaningfully summed, so
+//! they are ignored by every function here. Zero persistence values contribute nothing, so
+//! non-extrema can be passed in without filtering them out first.
+//!
+//! This module also contains hypothesis tests for comparing collections of samples by their
+//! topology, and resampled confidence bands for telling the features of a diagram apart from
+//! sampling noise.
+use std::error::Error;
+use std::f64;
+use rand::prelude::*;
+use thiserror::Error;
+
+use super::LabeledPoint;
+use super::distance::{DistanceMatrix, DistanceError, pairwise_distances};
+use super::persistence::{DiagramPoint, PersistenceDiagram};
+
+#[derive(Error, Debug)]
+pub enum StatsError {
+    #[error("Both groups need at least one sample, got {first:?} and {second:?}")]
+    EmptyGroup {first: usize, second: usize},
+
+    #[error("The significance level must be in (0, 1), got {alpha:?}")]
+    InvalidLevel {alpha: f64},
+
+    #[error("Cannot draw a subsample of {size:?} from {available:?} points")]
+    SampleTooLarge {size: usize, available: usize},
+
+    #[error("At least one resampling trial is needed")]
+    NoTrials {},
+
+    #[error("Could not compute a diagram: {source}")]
+    Diagram {source: Box<dyn Error + Send + Sync>},
+
+    #[error(transparent)]
+    Distance(#[from] DistanceError)
+}
+
+fn finite(lifetimes: &[f64]) -> impl Iterator<Item = f64> + '_ {
+    lifetimes.iter().copied().filter(|l| l.is_finite() && *l > 0.)
+}
+
+/// Returns the total `p`-persistence, the sum of each persistence value raised to the `p`.
+///
+/// With `p = 1` this is just the sum of the finite persistence values.
+pub fn total_persistence(lifetimes: &[f64], p: f64) -> f64 {
+    finite(lifetimes).map(|l| l.powf(p)).sum()
+}
+
+/// Returns the `p`-norm of the persistence values, `(Σ lᵖ)^(1/p)`, or their maximum if `p` is
+/// infinite.
+pub fn persistence_norm(lifetimes: &[f64], p: f64) -> f64 {
+    if p.is_infinite() {
+        return max_persistence(lifetimes);
+    }
+    total_persistence(lifetimes, p).powf(1. / p)
+}
+
+/// Returns the largest finite persistence value, or 0 if there are none.
+pub fn max_persistence(lifetimes: &[f64]) -> f64 {
+    finite(lifetimes).fold(0., f64::max)
+}
+
+/// Returns the mean of the finite, nonzero persistence values, or 0 if there are none.
+pub fn mean_persistence(lifetimes: &[f64]) -> f64 {
+    let count = finite(lifetimes).count();
+    if count == 0 {
+        return 0.;
+    }
+    total_persistence(lifetimes, 1.) / count as f64
+}
+
+/// Returns the persistent entropy of the persistence values.
+///
+/// This is the Shannon entropy (in nats) of the persistence values after normalizing them to
+/// sum to 1. It is high when many extrema are about equally persistent and low when a few
+/// extrema dominate.
+pub fn persistent_entropy(lifetimes: &[f64]) -> f64 {
+    let total = total_persistence(lifetimes, 1.);
+    if total == 0. {
+        return 0.;
+    }
+    -finite(lifetimes)
+        .map(|l| {
+            let p = l / total;
+            p * p.ln()
+        })
+        .sum::<f64>()
+}
+
+/// Returns the persistent entropy divided by its maximum possible value, `ln(n)`.
+///
+/// The result is in `[0, 1]`, which makes it comparable between samples with different numbers
+/// of extrema. Returns 0 when there are fewer than 2 finite persistence values.
+pub fn normalized_persistent_entropy(lifetimes: &[f64]) -> f64 {
+    let count = finite(lifetimes).count();
+    if count < 2 {
+        return 0.;
+    }
+    persistent_entropy(lifetimes) / (count as f64).ln()
+}
+
+/// The scalar summaries of a set of persistence values, together, as returned by
+/// [summarize](fn.summarize.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct PersistenceSummary {
+    /// The number of extrema with positive persistence, including those with infinite persistence.
+    pub count: usize,
+
+    /// The number of extrema with infinite persistence, which the other summaries leave out.
+    pub essential: usize,
+
+    /// The sum of the finite persistence values.
+    pub total: f64,
+
+    /// The 2-norm of the finite persistence values.
+    pub norm: f64,
+
+    /// The largest finite persistence value.
+    pub max: f64,
+
+    /// The [persistent entropy](fn.persistent_entropy.html) of the persistence values.
+    pub entropy: f64,
+
+    /// The threshold that `above` counts from.
+    pub threshold: f64,
+
+    /// The number of extrema with persistence at or above `threshold`, including those with
+    /// infinite persistence.
+    pub above: usize
+}
+
+/// Computes all of the summaries of the persistence values at once, counting the extrema with
+/// persistence at or above `threshold`.
+pub fn summarize(lifetimes: &[f64], threshold: f64) -> PersistenceSummary {
+    PersistenceSummary{
+        count: lifetimes.iter().filter(|&&l| l > 0.).count(),
+        essential: lifetimes.iter().filter(|l| l.is_infinite()).count(),
+        total: total_persistence(lifetimes, 1.),
+        norm: persistence_norm(lifetimes, 2.),
+        max: max_persistence(lifetimes),
+        entropy: persistent_entropy(lifetimes),
+        threshold,
+        above: lifetimes.iter().filter(|&&l| l >= threshold).count()
+    }
+}
+
+/// A rule for choosing the threshold that separates the extrema worth keeping from noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdHeuristic {
+    /// Split at the largest gap between consecutive persistence values.
+    LargestGap,
+
+    /// Split at the elbow of the [persistence
+    /// curve](../morse/struct.MorseComplex.html#method.persistence_curve), the point furthest
+    /// below the line from its first to its last point once both axes are scaled to `[0, 1]`.
+    Elbow,
+
+    /// Split the persistence values into the two groups with the largest between-group variance,
+    /// as Otsu's method does for the brightness of pixels.
+    Otsu
+}
+
+/// A threshold suggested by [suggest_threshold](fn.suggest_threshold.html), with what it does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThresholdSuggestion {
+    /// The suggested threshold, halfway between the largest persistence value it removes and the
+    /// smallest it keeps.
+    pub threshold: f64,
+
+    /// The largest persistence value removed and the smallest kept. Any threshold above the first
+    /// and at most the second simplifies the same way.
+    pub interval: (f64, f64),
+
+    /// The number of extrema with positive persistence below the threshold.
+    pub removed: usize,
+
+    /// The number of extrema at or above the threshold, including the global extrema.
+    pub kept: usize,
+
+    /// How strongly the heuristic prefers this split, in `[0, 1]`: the size of the gap relative to
+    /// the largest persistence value, the scaled distance of the elbow from the line, or the
+    /// fraction of the variance between the groups.
+    pub score: f64,
+
+    /// The heuristic that chose this split, and that `score` is measured by.
+    pub heuristic: ThresholdHeuristic
+}
+
+/// Suggests a threshold for [simplify](../morse/struct.MorseComplex.html#method.simplify) from the
+/// persistence values of the extrema, using `heuristic`.
+///
+/// Only splits between distinct finite, positive persistence values are considered, since no
+/// threshold can separate equal ones. Returns `None` if there are fewer than two distinct values
+/// to split between.
+pub fn suggest_threshold(lifetimes: &[f64], heuristic: ThresholdHeuristic) -> Option<ThresholdSuggestion> {
+    let mut values: Vec<f64> = finite(lifetimes).collect();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let essential = lifetimes.iter().filter(|l| l.is_infinite()).count();
+    let n = values.len();
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+
+    // Splitting at k removes the first k values
+    let score = |k: usize| -> f64 {
+        match heuristic {
+            ThresholdHeuristic::LargestGap => (values[k] - values[k - 1]) / values[n - 1],
+            ThresholdHeuristic::Elbow => {
+                let x = (values[k - 1] - values[0]) / (values[n - 1] - values[0]);
+                let y = (n - k) as f64 / (n - 1) as f64;
+                ((1. - x - y) / f64::consts::SQRT_2).max(0.)
+            },
+            ThresholdHeuristic::Otsu => {
+                let low = values[..k].iter().sum::<f64>() / k as f64;
+                let high = values[k..].iter().sum::<f64>() / (n - k) as f64;
+                k as f64 * (n - k) as f64 / (n * n) as f64 * (high - low).powi(2) / variance
+            }
+        }
+    };
+    let mut best: Option<(usize, f64)> = None;
+    for k in (1..n).filter(|&k| values[k - 1] < values[k]) {
+        let candidate = score(k);
+        if best.is_none_or(|(_, score)| candidate > score) {
+            best = Some((k, candidate));
+        }
+    }
+    best.map(|(k, score)| ThresholdSuggestion{
+        threshold: (values[k - 1] + values[k]) / 2.,
+        interval: (values[k - 1], values[k]),
+        removed: k,
+        kept: n - k + essential,
+        score,
+        heuristic
+    })
+}
+
+/// The outcome of a two-sample permutation test.
+#[derive(Debug, Clone, Copy)]
+pub struct PermutationTestResult {
+    /// The test statistic for the observed grouping. Lower values mean the groups are more
+    /// separated.
+    pub statistic: f64,
+
+    /// The estimated probability of seeing a statistic at least this extreme if both groups were
+    /// drawn from the same distribution.
+    pub p_value: f64,
+
+    /// The number of random relabelings the p-value was estimated from.
+    pub permutations: usize
+}
+
+/// Tests whether two collections of samples differ, using a permutation test.
+///
+/// The samples are typically persistence diagrams compared with a diagram distance, but anything
+/// with a distance works (e.g. vectorized diagrams with the Euclidean distance). The distances
+/// between all samples are computed once (in parallel with the `parallel` feature), and then reused
+/// for every permutation.
+///
+/// See [two_sample_test_from_matrix](fn.two_sample_test_from_matrix.html) for the statistic used.
+/// The relabelings are drawn from `rng`, so a seeded rng gives a reproducible p-value.
+pub fn two_sample_test<D, F, R>(first: &[D], second: &[D], distance: F, permutations: usize, rng: &mut R)
+    -> Result<PermutationTestResult, StatsError>
+    where D: Sync + Clone, F: Fn(&D, &D) -> f64 + Sync, R: Rng {
+    if first.is_empty() || second.is_empty() {
+        return Err(StatsError::EmptyGroup{first: first.len(), second: second.len()});
+    }
+    let samples: Vec<D> = first.iter().chain(second.iter()).cloned().collect();
+    let matrix = pairwise_distances(&samples, distance);
+    two_sample_test_from_matrix(&matrix, first.len(), permutations, rng)
+}
+
+/// Runs a two-sample permutation test on precomputed distances.
+///
+/// The first `first_size` samples of `distances` form the first group and the rest form the
+/// second. The test statistic is the sum over both groups of the mean distance between samples in
+/// the same group, as proposed in [Hypothesis testing for topological data
+/// analysis](https://arxiv.org/abs/1310.7467). Samples are randomly relabeled `permutations`
+/// times with `rng`, and the p-value is the fraction of relabelings whose statistic is at most the
+/// observed one (counting the observed labeling itself).
+pub fn two_sample_test_from_matrix<R: Rng>(distances: &DistanceMatrix, first_size: usize, permutations: usize, rng: &mut R)
+    -> Result<PermutationTestResult, StatsError> {
+    let size = distances.len();
+    if first_size == 0 || first_size >= size {
+        return Err(StatsError::EmptyGroup{first: first_size, second: size.saturating_sub(first_size)});
+    }
+
+    let mut labels: Vec<bool> = (0..size).map(|i| i < first_size).collect();
+    let statistic = within_group_loss(distances, &labels);
+
+    let mut at_least_as_extreme = 1;
+    for _ in 0..permutations {
+        labels.shuffle(rng);
+        // a tiny tolerance so that relabelings that merely reorder the samples count as ties
+        if within_group_loss(distances, &labels) <= statistic + 1e-12 * statistic.abs() {
+            at_least_as_extreme += 1;
+        }
+    }
+    let p_value = at_least_as_extreme as f64 / (permutations + 1) as f64;
+    Ok(PermutationTestResult{statistic, p_value, permutations})
+}
+
+fn within_group_loss(distances: &DistanceMatrix, labels: &[bool]) -> f64 {
+    let mut sums = [0., 0.];
+    let mut counts = [0usize, 0];
+    for i in 0..labels.len() {
+        for j in (i + 1)..labels.len() {
+            if labels[i] == labels[j] {
+                let group = labels[i] as usize;
+                sums[group] += distances.get(i, j);
+                counts[group] += 1;
+            }
+        }
+    }
+    sums.iter().zip(counts.iter())
+        .map(|(&sum, &count)| if count == 0 { 0. } else { sum / count as f64 })
+        .sum()
+}
+
+/// How [confidence_band](fn.confidence_band.html) resamples the points.
+#[derive(Debug, Clone, Copy)]
+pub enum Resampling {
+    /// Draw as many points as there are, with replacement. Points drawn more than once are
+    /// repeated, so the graph construction has to cope with duplicate points.
+    Bootstrap,
+
+    /// Draw this many distinct points, without replacement.
+    Subsample(usize)
+}
+
+/// A confidence band around a persistence diagram, estimated by resampling.
+///
+/// With probability about `1 - alpha`, the diagram of the underlying function is within
+/// `radius` of the estimated diagram in the bottleneck distance. A point closer than `radius`
+/// to the diagonal could therefore be matched to it, so only points with persistence above
+/// `2 * radius` are distinguishable from sampling noise.
+#[derive(Debug, Clone)]
+pub struct ConfidenceBand {
+    /// The diagram of all of the points.
+    pub diagram: PersistenceDiagram,
+
+    /// The `1 - alpha` quantile of `distances`.
+    pub radius: f64,
+
+    /// The bottleneck distance from `diagram` to the diagram of each resample.
+    pub distances: Vec<f64>
+}
+
+impl ConfidenceBand {
+    /// Whether `point` persists for longer than the band can explain by noise.
+    pub fn is_significant(&self, point: &DiagramPoint) -> bool {
+        point.persistence() > 2. * self.radius
+    }
+
+    /// The points of the diagram that are [significant](#method.is_significant).
+    pub fn significant(&self) -> PersistenceDiagram {
+        PersistenceDiagram::new(self.diagram.points().iter().filter(|point| self.is_significant(point)).cloned().collect())
+    }
+}
+
+/// Estimates a confidence band for the persistence diagram of `points`, as proposed in
+/// [Confidence sets for persistence diagrams](https://arxiv.org/abs/1303.7117).
+///
+/// `diagram` computes the diagram of a set of points, e.g. by building a graph over them and
+/// sweeping its [MorseComplex](../morse/struct.MorseComplex.html). It is called once on all of
+/// `points` and then on each of `trials` resamples of them, and the radius of the band is the
+/// `1 - alpha` quantile of the bottleneck distances between the resampled diagrams and the full
+/// one. Returns an error if `alpha` isn't in `(0, 1)`, `trials` is 0, the subsample is larger
+/// than `points`, or `diagram` fails.
+pub fn confidence_band<T, F, E, R>(points: &[LabeledPoint<T>], diagram: F, resampling: Resampling, trials: usize, alpha: f64,
+                                   rng: &mut R) -> Result<ConfidenceBand, StatsError>
+    where T: Clone, F: Fn(&[LabeledPoint<T>]) -> Result<PersistenceDiagram, E>, E: Into<Box<dyn Error + Send + Sync>>, R: Rng {
+    if !(alpha > 0. && alpha < 1.) {
+        return Err(StatsError::InvalidLevel{alpha});
+    }
+    if trials == 0 {
+        return Err(StatsError::NoTrials{});
+    }
+    if let Resampling::Subsample(size) = resampling {
+        if size > points.len() {
+            return Err(StatsError::SampleTooLarge{size, available: points.len()});
+        }
+    }
+    let compute = |points: &[LabeledPoint<T>]| diagram(points).map_err(|e| StatsError::Diagram{source: e.into()});
+
+    let full = compute(points)?;
+    let mut distances = Vec::with_capacity(trials);
+    for _ in 0..trials {
+        let sample: Vec<LabeledPoint<T>> = match resampling {
+            Resampling::Bootstrap => (0..points.len()).map(|_| points[rng.gen_range(0, points.len())].clone()).collect(),
+            Resampling::Subsample(size) => points.choose_multiple(rng, size).cloned().collect()
+        };
+        distances.push(full.bottleneck_distance(&compute(&sample)?)?);
+    }
+    let mut sorted = distances.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = ((1. - alpha) * trials as f64).ceil() as usize;
+    let radius = sorted[rank.clamp(1, trials) - 1];
+    Ok(ConfidenceBand{diagram: full, radius, distances})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_persistence() {
+        let lifetimes = [0., 1., 2., f64::INFINITY, 3.];
+        assert_eq!(total_persistence(&lifetimes, 1.), 6.);
+        assert_eq!(total_persistence(&lifetimes, 2.), 14.);
+        assert_eq!(mean_persistence(&lifetimes), 2.);
+        assert_eq!(mean_persistence(&[f64::INFINITY]), 0.);
+    }
+
+    #[test]
+    fn test_persistence_norm() {
+        let lifetimes = [3., 0., 4., f64::INFINITY];
+        assert_eq!(persistence_norm(&lifetimes, 1.), 7.);
+        assert_eq!(persistence_norm(&lifetimes, 2.), 5.);
+        assert_eq!(persistence_norm(&lifetimes, f64::INFINITY), 4.);
+        assert_eq!(max_persistence(&[f64::INFINITY]), 0.);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&[3., 0., 4., f64::INFINITY, 1.], 3.);
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.essential, 1);
+        assert_eq!(summary.total, 8.);
+        assert!((summary.norm - 26f64.sqrt()).abs() < 1e-12);
+        assert_eq!(summary.max, 4.);
+        assert_eq!(summary.entropy, persistent_entropy(&[3., 4., 1.]));
+        assert_eq!(summary.above, 3);
+        assert_eq!(summarize(&[], 1.).total, 0.);
+    }
+
+    #[test]
+    fn test_persistent_entropy() {
+        let uniform = [2., 2., 2., 2., 0., f64::INFINITY];
+        assert!((persistent_entropy(&uniform) - 4f64.ln()).abs() < 1e-12);
+        assert!((normalized_persistent_entropy(&uniform) - 1.).abs() < 1e-12);
+
+        let skewed = [100., 1., 1., 1.];
+        assert!(normalized_persistent_entropy(&skewed) < 0.5);
+        assert_eq!(persistent_entropy(&[5.]), 0.);
+        assert_eq!(persistent_entropy(&[]), 0.);
+    }
+
+    #[test]
+    fn test_two_sample_test() {
+        let distance = |a: &f64, b: &f64| (a - b).abs();
+        let first = [0., 0.1, 0.2, 0.15, 0.05, 0.12];
+        let second = [10., 10.1, 10.2, 10.15, 10.05, 10.12];
+        let mut rng = StdRng::seed_from_u64(13);
+        let result = two_sample_test(&first, &second, distance, 200, &mut rng).unwrap();
+        assert!(result.p_value < 0.05);
+        // the same seed gives the same p-value
+        let again = two_sample_test(&first, &second, distance, 200, &mut StdRng::seed_from_u64(13)).unwrap();
+        assert_eq!(again.p_value, result.p_value);
+
+        let first = [0., 10., 0.1, 10.1, 0.2, 10.2];
+        let second = [0.05, 10.05, 0.15, 10.15, 0.12, 10.12];
+        let result = two_sample_test(&first, &second, distance, 200, &mut rng).unwrap();
+        assert!(result.p_value > 0.05);
+
+        assert!(two_sample_test(&first, &[], distance, 10, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_confidence_band() {
+        use crate::graph::build_knn;
+        use crate::morse::{MorseComplex, MorseKind};
+
+        // two tall bumps along a line, with a little noise on top
+        let mut rng = StdRng::seed_from_u64(11);
+        let points: Vec<_> = (0..200)
+            .map(|i| {
+                let x = i as f64 / 20.;
+                let value = 3. * (-(x - 3.).powi(2)).exp() + 2. * (-(x - 7.).powi(2)).exp() + 0.05 * rng.gen::<f64>();
+                LabeledPoint{id: i, value, point: vec![x]}
+            })
+            .collect();
+        let diagram = |points: &[LabeledPoint<Vec<f64>>]| -> Result<PersistenceDiagram, Box<dyn Error + Send + Sync>> {
+            let graph = build_knn(points, 10)?;
+            let complex = MorseComplex::from_graph(MorseKind::Descending, &graph)?;
+            Ok(PersistenceDiagram::from_complex(&complex, &graph)?)
+        };
+
+        let band = confidence_band(&points, diagram, Resampling::Subsample(150), 20, 0.1, &mut rng).unwrap();
+        assert_eq!(band.distances.len(), 20);
+        assert!(band.radius > 0. && band.radius < 0.5);
+        // the noise makes many small peaks, but only the two bumps stand out
+        assert!(band.diagram.len() > 2);
+        let significant = band.significant();
+        assert_eq!(significant.len(), 2);
+        assert!(significant.points().iter().all(|point| point.birth > 1.9));
+
+        let band = confidence_band(&points, diagram, Resampling::Bootstrap, 5, 0.5, &mut rng).unwrap();
+        assert_eq!(band.distances.len(), 5);
+        assert!(confidence_band(&points, diagram, Resampling::Subsample(201), 5, 0.1, &mut rng).is_err());
+        assert!(confidence_band(&points, diagram, Resampling::Bootstrap, 5, 1., &mut rng).is_err());
+        assert!(confidence_band(&points, diagram, Resampling::Bootstrap, 0, 0.1, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_suggest_threshold() {
+        // four small lifetimes, two large ones and a global extremum
+        let lifetimes = [0.1, 5., 0.2, 0., 0.15, f64::INFINITY, 6., 0.12];
+        for &heuristic in &[ThresholdHeuristic::LargestGap, ThresholdHeuristic::Elbow, ThresholdHeuristic::Otsu] {
+            let suggestion = suggest_threshold(&lifetimes, heuristic).unwrap();
+            assert_eq!(suggestion.interval, (0.2, 5.));
+            assert_eq!(suggestion.threshold, 2.6);
+            assert_eq!((suggestion.removed, suggestion.kept), (4, 3));
+            assert!(suggestion.score > 0. && suggestion.score <= 1.);
+            assert_eq!(suggestion.heuristic, heuristic);
+        }
+        let gap = suggest_threshold(&lifetimes, ThresholdHeuristic::LargestGap).unwrap();
+        assert!((gap.score - 4.8 / 6.).abs() < 1e-12);
+
+        // equal values can't be split
+        assert!(suggest_threshold(&[1., 1., f64::INFINITY], ThresholdHeuristic::Otsu).is_none());
+        assert!(suggest_threshold(&[], ThresholdHeuristic::Elbow).is_none());
+        let suggestion = suggest_threshold(&[1., 1., 3.], ThresholdHeuristic::Elbow).unwrap();
+        assert_eq!(suggestion.removed, 2);
+    }
+
+    #[test]
+    fn test_two_sample_test_empty_group() {
+        let matrix = pairwise_distances(&[0., 1.], |a: &f64, b: &f64| (a - b).abs());
+        assert!(two_sample_test_from_matrix(&matrix, 2, 10, &mut StdRng::seed_from_u64(0)).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Random graphs with scalar fields on them, for testing pipelines built on this crate.
+//!
+//! The generators take the random number generator to use, so seeding it gives reproducible
+//! graphs, and the scalar field as a closure of the point's coordinates. Fuzzing a pipeline with
+//! these is a cheap way of finding the inputs it breaks on, and the crate uses them to check the
+//! invariants of the Morse complexes on many random graphs.
+use std::f64;
+use petgraph::graph::UnGraph;
+use rand::prelude::*;
+
+use crate::LabeledPoint;
+use crate::graph::{GraphError, EdgeWeighting, build_epsilon};
+
+/// A canonical test function, with a known number of extrema on its domain.
+///
+/// Sampling one of these with [sample_test_function](fn.sample_test_function.html) gives a point
+/// cloud whose persistent extrema are known in advance, to validate persistence output against.
+/// The counts only include extrema that the function has in the interior of its domain, none of
+/// which are on its boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestFunction {
+    /// Himmelblau's function, `(x² + y - 11)² + (x + y² - 7)²`, on `[-5, 5]²`.
+    ///
+    /// It has four minima, all with the value 0, so it's a good check that equally deep minima are
+    /// all found. It's only defined in two dimensions.
+    Himmelblau,
+
+    /// The Ackley function on `[-1.5, 1.5]^d`.
+    ///
+    /// A global minimum at the origin is surrounded by shallower local minima near the other
+    /// points with integer coordinates, so there are `3^d` minima in all.
+    Ackley,
+
+    /// An egg crate terrain, the product of `sin(2π periods x_i)` over the coordinates, on
+    /// `[0, 1]^d`.
+    ///
+    /// It has `(2 periods)^d / 2` maxima and as many minima, laid out on a regular grid.
+    Sinusoid {
+        periods: usize
+    },
+
+    /// A sum of isotropic Gaussian bumps of the given width, centered on `centers`, on `[0, 1]^d`.
+    ///
+    /// As long as the centers are several widths apart from each other and away from the boundary,
+    /// each of them is a maximum. There are no minima in the interior.
+    GaussianMixture {
+        centers: Vec<Vec<f64>>,
+        width: f64
+    }
+}
+
+impl TestFunction {
+    /// The value of the function at `point`.
+    pub fn evaluate(&self, point: &[f64]) -> f64 {
+        match self {
+            TestFunction::Himmelblau => {
+                let (x, y) = (point[0], point[1]);
+                (x * x + y - 11.).powi(2) + (x + y * y - 7.).powi(2)
+            },
+            TestFunction::Ackley => {
+                let n = point.len() as f64;
+                let squares = point.iter().map(|x| x * x).sum::<f64>() / n;
+                let cosines = point.iter().map(|x| (2. * f64::consts::PI * x).cos()).sum::<f64>() / n;
+                -20. * (-0.2 * squares.sqrt()).exp() - cosines.exp() + f64::consts::E + 20.
+            },
+            TestFunction::Sinusoid{periods} => {
+                point.iter().map(|x| (2. * f64::consts::PI * *periods as f64 * x).sin()).product()
+            },
+            TestFunction::GaussianMixture{centers, width} => {
+                centers.iter()
+                    .map(|center| {
+                        let squared_distance: f64 = center.iter().zip(point).map(|(c, x)| (c - x).powi(2)).sum();
+                        (-squared_distance / (2. * width * width)).exp()
+                    })
+                    .sum()
+            }
+        }
+    }
+
+    /// The lower and upper bound of every coordinate of the domain.
+    pub fn bounds(&self) -> (f64, f64) {
+        match self {
+            TestFunction::Himmelblau => (-5., 5.),
+            TestFunction::Ackley => (-1.5, 1.5),
+            TestFunction::Sinusoid{..} | TestFunction::GaussianMixture{..} => (0., 1.)
+        }
+    }
+
+    /// The dimension the function is defined in, if it's only defined in one.
+    pub fn dimension(&self) -> Option<usize> {
+        match self {
+            TestFunction::Himmelblau => Some(2),
+            TestFunction::GaussianMixture{centers, ..} => centers.first().map(|c| c.len()),
+            _ => None
+        }
+    }
+
+    /// The number of maxima in the interior of the domain in `dim` dimensions, if it's known.
+    pub fn num_maxima(&self, dim: usize) -> Option<usize> {
+        match self {
+            TestFunction::Sinusoid{periods} => Some((2 * periods).pow(dim as u32) / 2),
+            TestFunction::GaussianMixture{centers, ..} => Some(centers.len()),
+            _ => None
+        }
+    }
+
+    /// The number of minima in the interior of the domain in `dim` dimensions, if it's known.
+    pub fn num_minima(&self, dim: usize) -> Option<usize> {
+        match self {
+            TestFunction::Himmelblau => Some(4),
+            TestFunction::Ackley => Some(3usize.pow(dim as u32)),
+            TestFunction::Sinusoid{periods} => Some((2 * periods).pow(dim as u32) / 2),
+            TestFunction::GaussianMixture{..} => Some(0)
+        }
+    }
+}
+
+/// Samples `n` points uniformly from the unit cube of the given dimension, labeling each with the
+/// value of `field` there.
+///
+/// Points are given their index as their id.
+pub fn random_points<R: Rng, F: FnMut(&[f64]) -> f64>(n: usize, dim: usize, mut field: F, rng: &mut R)
+    -> Vec<LabeledPoint<Vec<f64>>> {
+    (0..n)
+        .map(|i| {
+            let point: Vec<f64> = (0..dim).map(|_| rng.gen_range(0., 1.)).collect();
+            LabeledPoint{id: i as i64, value: field(&point), point}
+        })
+        .collect()
+}
+
+/// Samples `n` points uniformly from the domain of `function` in `dim` dimensions, labeling each
+/// with the value of the function there.
+///
+/// Returns an error if the function isn't defined in `dim` dimensions.
+pub fn sample_test_function<R: Rng>(function: &TestFunction, n: usize, dim: usize, rng: &mut R)
+    -> Result<Vec<LabeledPoint<Vec<f64>>>, GraphError> {
+    if let Some(expected) = function.dimension() {
+        if expected != dim {
+            return Err(GraphError::WrongDimension{expected, found: dim});
+        }
+    }
+    let (low, high) = function.bounds();
+    let mut points = random_points(n, dim, |_| 0., rng);
+    for point in points.iter_mut() {
+        for x in point.point.iter_mut() {
+            *x = low + (high - low) * *x;
+        }
+        point.value = function.evaluate(&point.point);
+    }
+    Ok(points)
+}
+
+/// Constructs a random geometric graph: `n` points sampled uniformly from the unit cube of the
+/// given dimension, labeled with the value of `field` there, with every pair of points within
+/// `radius` of each other connected by an edge weighted by their distance.
+///
+/// ```
+/// use rand::prelude::*;
+/// use talus::synthetic::random_geometric_graph;
+///
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let mut noise = StdRng::seed_from_u64(1);
+/// let graph = random_geometric_graph(100, 2, 0.2, |_| noise.gen(), &mut rng).unwrap();
+/// assert_eq!(graph.node_count(), 100);
+/// ```
+pub fn random_geometric_graph<R: Rng, F: FnMut(&[f64]) -> f64>(n: usize, dim: usize, radius: f64, field: F, rng: &mut R)
+    -> Result<UnGraph<LabeledPoint<Vec<f64>>, f64>, GraphError> {
+    let points = random_points(n, dim, field, rng);
+    build_epsilon(&points, radius, EdgeWeighting::Distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_geometric_graph() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let graph = random_geometric_graph(200, 3, 0.25, |p| p.iter().sum(), &mut rng).unwrap();
+        assert_eq!(graph.node_count(), 200);
+        for node in graph.raw_nodes() {
+            assert!(node.weight.point.iter().all(|&x| (0. ..1.).contains(&x)));
+            assert_eq!(node.weight.value, node.weight.point.iter().sum::<f64>());
+        }
+        assert!(graph.raw_edges().iter().all(|e| e.weight <= 0.25));
+
+        // the same seed gives the same graph
+        let mut rng = StdRng::seed_from_u64(3);
+        let again = random_geometric_graph(200, 3, 0.25, |p| p.iter().sum(), &mut rng).unwrap();
+        assert_eq!(again.edge_count(), graph.edge_count());
+    }
+
+    // the number of extrema that persist for longer than the threshold
+    fn persistent(persistence: &std::collections::HashMap<petgraph::graph::NodeIndex, f64>, threshold: f64) -> usize {
+        persistence.values().filter(|&&l| l > threshold).count()
+    }
+
+    #[test]
+    fn test_known_extrema() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let functions = [
+            (TestFunction::Himmelblau, 2, 5.),
+            (TestFunction::Ackley, 2, 0.4),
+            (TestFunction::Sinusoid{periods: 1}, 2, 0.3),
+            (TestFunction::Sinusoid{periods: 1}, 3, 0.3),
+            (TestFunction::GaussianMixture{centers: vec![vec![0.25, 0.25], vec![0.75, 0.3], vec![0.5, 0.75]], width: 0.08}, 2, 0.1)
+        ];
+        for (function, dim, threshold) in functions.iter() {
+            let points = sample_test_function(function, 3000, *dim, &mut rng).unwrap();
+            let graph = crate::graph::build_knn(&points, 12).unwrap();
+            let complex = crate::morse::MorseSmaleComplex::from_graph(&graph).unwrap();
+            if let Some(maxima) = function.num_maxima(*dim) {
+                assert_eq!(persistent(&complex.descending_complex.get_persistence(), *threshold), maxima);
+            }
+            if let Some(minima) = function.num_minima(*dim) {
+                // the boundary of the domain can add minima to a mixture, but nothing else
+                if minima > 0 {
+                    assert_eq!(persistent(&complex.ascending_complex.get_persistence(), *threshold), minima);
+                }
+            }
+        }
+        assert!(sample_test_function(&TestFunction::Himmelblau, 10, 3, &mut rng).is_err());
+    }
+}
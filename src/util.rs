@@ -0,0 +1,145 @@
+//! Data structures shared by the algorithms in this crate, exposed for reuse in similar sweeps.
+
+/// A union-find (disjoint set) structure in which every set has a pinned representative.
+///
+/// A plain union-find picks representatives for its own convenience, so the representative of a
+/// set can change whenever it is merged with another. In a `PointedUnionFind`, each set instead
+/// has a designated representative that is always one of its members, and `union(x, y)` keeps
+/// the representative of `x`'s set. This is exactly what a sweep that merges components into the
+/// oldest one needs: the representative of a component is its oldest element, no matter how the
+/// merges were performed internally.
+///
+/// Internally this uses union by size and path compression, so all operations take effectively
+/// constant amortized time.
+///
+/// ```
+/// use talus::util::PointedUnionFind;
+///
+/// let mut sets = PointedUnionFind::new(4);
+/// sets.union(2, 0);
+/// sets.union(2, 1);
+/// sets.union(3, 2);
+/// assert_eq!(sets.find(0), 3);
+/// assert_eq!(sets.size(1), 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PointedUnionFind {
+    parent: Vec<usize>,
+    // only meaningful at internal roots
+    size: Vec<usize>,
+    representative: Vec<usize>
+}
+
+impl PointedUnionFind {
+    /// Creates `n` singleton sets, `{0}` through `{n - 1}`, each its own representative.
+    pub fn new(n: usize) -> Self {
+        PointedUnionFind{parent: (0..n).collect(), size: vec![1; n], representative: (0..n).collect()}
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parent.is_empty()
+    }
+
+    fn root(&self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn root_mut(&mut self, x: usize) -> usize {
+        let root = self.root(x);
+        let mut x = x;
+        while self.parent[x] != root {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    /// Returns the representative of the set containing `x`.
+    ///
+    /// The representative is always a member of the set, so `find(find(x)) == find(x)`.
+    ///
+    /// Panics if `x` is out of bounds.
+    pub fn find(&self, x: usize) -> usize {
+        self.representative[self.root(x)]
+    }
+
+    /// Like [find](#method.find), but also compresses the path from `x` so that later lookups
+    /// are faster.
+    pub fn find_mut(&mut self, x: usize) -> usize {
+        let root = self.root_mut(x);
+        self.representative[root]
+    }
+
+    /// Returns whether `x` and `y` are in the same set.
+    pub fn equiv(&self, x: usize, y: usize) -> bool {
+        self.root(x) == self.root(y)
+    }
+
+    /// Returns the number of elements in the set containing `x`.
+    pub fn size(&self, x: usize) -> usize {
+        self.size[self.root(x)]
+    }
+
+    /// Merges the sets containing `x` and `y`, keeping the representative of `x`'s set.
+    ///
+    /// Returns `false` if they were already the same set.
+    pub fn union(&mut self, x: usize, y: usize) -> bool {
+        let x_root = self.root_mut(x);
+        let y_root = self.root_mut(y);
+        if x_root == y_root {
+            return false;
+        }
+        let representative = self.representative[x_root];
+        let (big, small) = if self.size[x_root] >= self.size[y_root] { (x_root, y_root) } else { (y_root, x_root) };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+        self.representative[big] = representative;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_representative_is_pinned() {
+        let mut sets = PointedUnionFind::new(6);
+        // grow a big set under 0, then merge it into a small set: 5 must stay the representative
+        // even though the big set's root wins internally
+        assert!(sets.union(0, 1));
+        assert!(sets.union(0, 2));
+        assert!(sets.union(0, 3));
+        assert!(sets.union(5, 0));
+        for x in 0..4 {
+            assert_eq!(sets.find(x), 5);
+            assert_eq!(sets.find(sets.find(x)), 5);
+        }
+        assert_eq!(sets.find(4), 4);
+        assert!(!sets.union(1, 3));
+        assert_eq!(sets.find_mut(3), 5);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let mut sets = PointedUnionFind::new(5);
+        sets.union(1, 2);
+        sets.union(3, 4);
+        sets.union(4, 2);
+        assert_eq!(sets.size(1), 4);
+        assert_eq!(sets.size(0), 1);
+        assert!(sets.equiv(1, 3));
+        assert!(!sets.equiv(0, 3));
+        assert_eq!(sets.find(1), 3);
+        assert_eq!(sets.len(), 5);
+    }
+}
@@ -0,0 +1,168 @@
+//! [Vega-Lite](https://vega.github.io/vega-lite/) specifications for plotting persistence.
+//!
+//! Each function returns a complete specification as a JSON string, with the data inlined, which
+//! renders as is in Jupyter (e.g. with `altair.Chart.from_json`), the Vega editor, or any other
+//! Vega-Lite frontend.
+//!
+//! JSON has no infinities, so the points of a diagram that never die are drawn just past the
+//! finite values, on the side they would be, and marked as essential in the data.
+use std::f64;
+use std::fmt::Write;
+
+use crate::morse::MorseComplex;
+use crate::persistence::{Barcode, DiagramPoint, PersistenceDiagram};
+
+const SCHEMA: &str = "https://vega.github.io/schema/vega-lite/v5.json";
+
+/// Returns a specification for a scatter plot of `diagram`, with the diagonal drawn in.
+///
+/// Points are colored by whether they are essential, and show their birth, death, persistence
+/// and representative node when hovered.
+pub fn diagram_spec(diagram: &PersistenceDiagram) -> String {
+    let (low, high) = plot_range(diagram.points());
+    let values = point_values(diagram.points(), low, high, |_, _| ());
+    format!(concat!(
+        r#"{{"$schema":"{}","title":"Persistence diagram","#,
+        r#""layer":["#,
+        r#"{{"data":{{"values":[{{"birth":{low},"death":{low}}},{{"birth":{high},"death":{high}}}]}},"#,
+        r#""mark":{{"type":"line","color":"gray","strokeDash":[4,4]}},"#,
+        r#""encoding":{{"x":{{"field":"birth","type":"quantitative"}},"y":{{"field":"death","type":"quantitative"}}}}}},"#,
+        r#"{{"data":{{"values":[{values}]}},"#,
+        r#""mark":{{"type":"point","filled":true}},"#,
+        r#""encoding":{{"x":{{"field":"birth","type":"quantitative","title":"Birth"}},"#,
+        r#""y":{{"field":"death","type":"quantitative","title":"Death"}},"#,
+        r#""color":{{"field":"essential","type":"nominal","title":"Essential"}},"#,
+        r#""tooltip":[{{"field":"birth"}},{{"field":"death"}},{{"field":"persistence"}},{{"field":"node"}}]}}}}"#,
+        r#"]}}"#),
+        SCHEMA, low = number(low), high = number(high), values = values)
+}
+
+/// Returns a specification for a plot of `barcode`, with each bar as a horizontal interval from
+/// its birth to its death, the longest at the top.
+pub fn barcode_spec(barcode: &Barcode) -> String {
+    let (low, high) = plot_range(barcode.bars());
+    let values = point_values(barcode.bars(), low, high, |i, json| {
+        let _ = write!(json, r#","bar":{}"#, i);
+    });
+    format!(concat!(
+        r#"{{"$schema":"{}","title":"Barcode","#,
+        r#""data":{{"values":[{values}]}},"#,
+        r#""mark":{{"type":"rule","strokeWidth":2}},"#,
+        r#""encoding":{{"x":{{"field":"birth","type":"quantitative","title":"Value"}},"x2":{{"field":"death"}},"#,
+        r#""y":{{"field":"bar","type":"ordinal","title":null,"axis":null}},"#,
+        r#""color":{{"field":"essential","type":"nominal","title":"Essential"}},"#,
+        r#""tooltip":[{{"field":"birth"}},{{"field":"death"}},{{"field":"persistence"}},{{"field":"node"}}]}}}}"#),
+        SCHEMA, values = values)
+}
+
+/// Returns a specification for a step plot of the
+/// [persistence curve](../morse/struct.MorseComplex.html#method.persistence_curve) of `complex`,
+/// starting from every extremum at a threshold of 0.
+pub fn persistence_curve_spec(complex: &MorseComplex) -> String {
+    let mut values = format!(r#"{{"threshold":0,"cells":{}}}"#, complex.extrema().len());
+    for (threshold, count) in complex.persistence_curve() {
+        let _ = write!(values, r#",{{"threshold":{},"cells":{}}}"#, number(threshold), count);
+    }
+    format!(concat!(
+        r#"{{"$schema":"{}","title":"Persistence curve","#,
+        r#""data":{{"values":[{values}]}},"#,
+        r#""mark":{{"type":"line","interpolate":"step-after","point":true}},"#,
+        r#""encoding":{{"x":{{"field":"threshold","type":"quantitative","title":"Persistence threshold"}},"#,
+        r#""y":{{"field":"cells","type":"quantitative","title":"Cells"}}}}}}"#),
+        SCHEMA, values = values)
+}
+
+// The finite range of the births and deaths of `points`, widened by a margin on each side that
+// the infinite deaths are drawn in
+fn plot_range(points: &[DiagramPoint]) -> (f64, f64) {
+    let finite = points.iter()
+        .flat_map(|point| vec![point.birth, point.death])
+        .filter(|value| value.is_finite());
+    let (low, high) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| (low.min(value), high.max(value)));
+    if low > high {
+        return (0., 1.);
+    }
+    let margin = if high > low { 0.1 * (high - low) } else { 1. };
+    (low - margin, high + margin)
+}
+
+// The JSON objects for `points`, separated by commas, with `extra` adding fields to the object of
+// each point from its position
+fn point_values<F: Fn(usize, &mut String)>(points: &[DiagramPoint], low: f64, high: f64, extra: F) -> String {
+    let mut json = String::new();
+    for (i, point) in points.iter().enumerate() {
+        let death = match point.death {
+            death if death == f64::INFINITY => high,
+            death if death == f64::NEG_INFINITY => low,
+            death => death
+        };
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(json, r#"{{"birth":{},"death":{},"persistence":{},"essential":{},"node":{}"#,
+                       number(point.birth), number(death), number(point.persistence()), point.is_essential(),
+                       point.representative.index());
+        extra(i, &mut json);
+        json.push('}');
+    }
+    json
+}
+
+// A JSON number, or null for the values JSON can't represent
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::graph::NodeIndex;
+
+    fn diagram() -> PersistenceDiagram {
+        PersistenceDiagram::new(vec![
+            DiagramPoint{birth: 5., death: f64::NEG_INFINITY, representative: NodeIndex::new(4)},
+            DiagramPoint{birth: 4., death: 2., representative: NodeIndex::new(2)},
+            DiagramPoint{birth: 1., death: 0., representative: NodeIndex::new(0)}
+        ])
+    }
+
+    #[test]
+    fn test_diagram_spec() {
+        let spec = diagram_spec(&diagram());
+        assert!(spec.starts_with(r#"{"$schema":"https://vega.github.io/schema/vega-lite/v5.json""#));
+        // the essential point is drawn just below the lowest finite value
+        assert!(spec.contains(r#"{"birth":5,"death":-0.5,"persistence":null,"essential":true,"node":4}"#));
+        assert!(spec.contains(r#"{"birth":4,"death":2,"persistence":2,"essential":false,"node":2}"#));
+        assert!(spec.contains(r#"[{"birth":-0.5,"death":-0.5},{"birth":5.5,"death":5.5}]"#));
+        assert_eq!(spec.matches('{').count(), spec.matches('}').count());
+        assert_eq!(spec.matches('[').count(), spec.matches(']').count());
+
+        let empty = diagram_spec(&PersistenceDiagram::default());
+        assert!(empty.contains(r#""values":[]"#));
+    }
+
+    #[test]
+    fn test_barcode_spec() {
+        let spec = barcode_spec(&Barcode::from_diagram(&diagram()));
+        assert!(spec.contains(r#""essential":true,"node":4,"bar":0}"#));
+        assert!(spec.contains(r#""node":0,"bar":2}"#));
+        assert!(spec.contains(r#""x2":{"field":"death"}"#));
+        assert_eq!(spec.matches('{').count(), spec.matches('}').count());
+    }
+
+    #[test]
+    fn test_persistence_curve_spec() {
+        use crate::fixtures::path_graph;
+        use crate::morse::MorseKind;
+
+        let graph = path_graph(&[1., 0., 4., 2., 5.]);
+        let complex = MorseComplex::from_graph(MorseKind::Descending, &graph).unwrap();
+        let spec = persistence_curve_spec(&complex);
+        assert!(spec.contains(r#""values":[{"threshold":0,"cells":3},{"threshold":1,"cells":2},{"threshold":2,"cells":1}]"#));
+        assert_eq!(spec.matches('{').count(), spec.matches('}').count());
+    }
+}
@@ -0,0 +1,27 @@
+use rand::prelude::*;
+use petgraph::algo::connected_components;
+use talus::morse::MorseSmaleComplex;
+use talus::synthetic::random_geometric_graph;
+
+#[test]
+fn betti_curve_test() {
+    let mut rng = StdRng::seed_from_u64(2021);
+    for trial in 0..10 {
+        let mut noise = StdRng::seed_from_u64(trial as u64);
+        let graph = random_geometric_graph(150, 2, 0.12, |p| (6. * p[0]).sin() + p[1] + noise.gen::<f64>(), &mut rng).unwrap();
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        let thresholds: Vec<f64> = (0..=30).map(|i| -1. + i as f64 * 0.1).collect();
+        for (sign, morse) in [(1., &complex.descending_complex), (-1., &complex.ascending_complex)].iter() {
+            let counts = morse.component_counts(&graph, Some(&thresholds)).unwrap();
+            // counting the components of each level set directly gives the same curve
+            for (&threshold, &(at, count)) in thresholds.iter().zip(counts.iter()) {
+                assert_eq!(at, threshold);
+                let level_set = graph.filter_map(
+                    |_, point| if sign * point.value >= sign * threshold { Some(()) } else { None },
+                    |_, &weight| Some(weight)
+                );
+                assert_eq!(count, connected_components(&level_set), "at {}", threshold);
+            }
+        }
+    }
+}
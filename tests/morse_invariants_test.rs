@@ -0,0 +1,42 @@
+use std::f64;
+use std::collections::HashSet;
+use rand::prelude::*;
+use talus::graph::component_labels;
+use talus::morse::MorseSmaleComplex;
+use talus::synthetic::random_geometric_graph;
+
+#[test]
+fn morse_invariants_test() {
+    let mut rng = StdRng::seed_from_u64(2020);
+    for trial in 0..40 {
+        let dim = 1 + trial % 3;
+        let mut noise = StdRng::seed_from_u64(trial as u64);
+        let graph = random_geometric_graph(300, dim, 0.15, |p| p.iter().sum::<f64>() + noise.gen::<f64>(), &mut rng).unwrap();
+        let num_components = component_labels(&graph).into_iter().collect::<HashSet<_>>().len();
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        for (sign, morse) in [(1., &complex.descending_complex), (-1., &complex.ascending_complex)].iter() {
+            let cells = morse.get_complex();
+            let persistence = morse.get_persistence();
+            assert_eq!(cells.len(), graph.node_count());
+
+            // every node flows to an extremum at least as extreme as itself
+            for (&node, &extremum) in cells.iter() {
+                assert_eq!(cells[&extremum], extremum);
+                assert!(sign * graph[extremum].value >= sign * graph[node].value);
+            }
+            let extrema: HashSet<_> = cells.values().cloned().collect();
+            for (node, &lifetime) in persistence.iter() {
+                assert!(lifetime >= 0.);
+                if !extrema.contains(node) {
+                    assert_eq!(lifetime, 0.);
+                }
+            }
+
+            // each component keeps one extremum forever, and every other one is merged away once
+            let immortal = persistence.values().filter(|l| l.is_infinite()).count();
+            assert_eq!(immortal, num_components);
+            assert_eq!(morse.filtration.len(), extrema.len() - num_components);
+            assert!(morse.filtration.windows(2).all(|steps| steps[0].time <= steps[1].time));
+        }
+    }
+}
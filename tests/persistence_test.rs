@@ -1,4 +1,3 @@
-use talus;
 use std::f64;
 use std::path::PathBuf;
 use std::collections::HashMap;
@@ -0,0 +1,23 @@
+use rand::prelude::*;
+use petgraph::visit::EdgeRef;
+use talus::morse::{MorseSmaleComplex, MorseKind};
+use talus::persistence::{PersistenceDiagram, zero_dim_persistence_with};
+use talus::synthetic::random_geometric_graph;
+
+#[test]
+fn zero_dim_persistence_test() {
+    let mut rng = StdRng::seed_from_u64(2022);
+    for trial in 0..10 {
+        let mut noise = StdRng::seed_from_u64(trial as u64);
+        // rounded values, so that there are plenty of ties
+        let graph = random_geometric_graph(200, 2, 0.1, |p| ((6. * p[0]).sin() + p[1] + noise.gen::<f64>() * 10.).round(), &mut rng).unwrap();
+        let values: Vec<f64> = graph.node_indices().map(|node| graph[node].value).collect();
+        let edges: Vec<(usize, usize)> = graph.edge_references().map(|edge| (edge.source().index(), edge.target().index())).collect();
+        let complex = MorseSmaleComplex::from_graph(&graph).unwrap();
+        // the standalone sweep gives exactly the diagrams of the complexes
+        for (kind, morse) in [(MorseKind::Ascending, &complex.ascending_complex), (MorseKind::Descending, &complex.descending_complex)].iter() {
+            let expected = PersistenceDiagram::from_complex(morse, &graph).unwrap();
+            assert_eq!(zero_dim_persistence_with(&values, &edges, *kind).unwrap(), expected);
+        }
+    }
+}